@@ -1,7 +1,10 @@
 //! Benchmarks for Lelantus protocol
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use silver_lelantus::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 
 fn bench_commitment_creation(c: &mut Criterion) {
     c.bench_function("commitment_creation", |b| {
@@ -52,5 +55,260 @@ fn bench_accumulator_add(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_commitment_creation, bench_accumulator_add);
+fn bench_bulk_import(c: &mut Criterion) {
+    let params = LelantusParameters::default();
+    let commitments: Vec<Commitment> = match CommitmentScheme::new(&params) {
+        Ok(scheme) => (0..256)
+            .filter_map(|i| scheme.commit(1000 + i).ok())
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to create commitment scheme: {}", e);
+            return;
+        }
+    };
+
+    let import = |unchecked: bool| {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        for commitment in &commitments {
+            if unchecked {
+                state.add_coin_unchecked(commitment)?;
+            } else {
+                state.add_coin(commitment)?;
+            }
+        }
+        Ok::<_, LelantusError>(state.accumulator_snapshot())
+    };
+
+    c.bench_function("bulk_import_checked", |b| {
+        b.iter(|| black_box(import(false)))
+    });
+    c.bench_function("bulk_import_unchecked", |b| {
+        b.iter(|| black_box(import(true)))
+    });
+}
+
+fn bench_amount_decryption(c: &mut Criterion) {
+    let view_key = ViewKey::generate();
+    let witnesses: Vec<Witness> = (0..256u64)
+        .filter_map(|i| {
+            let commitment = Commitment {
+                value: i.to_le_bytes().to_vec(),
+                randomness: vec![2; 64],
+            };
+            let encrypted =
+                Witness::encrypt_amount_for_view_key(&commitment, i as usize, 1000, &view_key)
+                    .ok()?;
+            Some(Witness::new(
+                commitment,
+                i as usize,
+                vec![3; 32],
+                vec![4; 32],
+                encrypted,
+            ))
+        })
+        .collect();
+
+    c.bench_function("amount_decryption_sequential", |b| {
+        b.iter(|| {
+            witnesses
+                .iter()
+                .map(|w| w.get_amount_with_view_key(black_box(&view_key)))
+                .collect::<Vec<_>>()
+        });
+    });
+
+    c.bench_function("amount_decryption_batch", |b| {
+        b.iter(|| Witness::decrypt_amounts_batch(black_box(&witnesses), &view_key));
+    });
+}
+
+fn bench_verification_under_contention(c: &mut Criterion) {
+    let state = match LelantusState::new(LelantusParameters::default()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to create Lelantus state: {}", e);
+            return;
+        }
+    };
+
+    // The writer holds the gate while adding a coin, so a JoinSplit and the
+    // snapshot of the head it was built against can be taken together
+    let gate = Mutex::new(());
+    let fresh_joinsplit = || {
+        let _gate = gate.lock().unwrap_or_else(PoisonError::into_inner);
+        build_joinsplit(&state).map(|joinsplit| (joinsplit, state.accumulator_snapshot()))
+    };
+
+    let stop = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        // Writer continuously adding coins while verification runs
+        scope.spawn(|| {
+            while !stop.load(Ordering::Relaxed) {
+                if let Ok(commitment) = state.commitment_scheme().commit(1000) {
+                    let _gate = gate.lock().unwrap_or_else(PoisonError::into_inner);
+                    let _ = state.add_coin(&commitment);
+                }
+                std::thread::sleep(Duration::from_micros(50));
+            }
+        });
+
+        // Every iteration verifies a JoinSplit not seen before, built against
+        // a head the writer has since moved past
+        c.bench_function("verify_joinsplit_locked", |b| {
+            b.iter_batched_ref(
+                fresh_joinsplit,
+                |built| {
+                    let (joinsplit, snapshot) = built.as_ref().map_err(Clone::clone)?;
+                    state.verify_joinsplit_at_anchor(black_box(joinsplit), snapshot.value())
+                },
+                BatchSize::PerIteration,
+            );
+        });
+
+        c.bench_function("verify_joinsplit_snapshot", |b| {
+            b.iter_batched_ref(
+                fresh_joinsplit,
+                |built| {
+                    let (joinsplit, snapshot) = built.as_ref().map_err(Clone::clone)?;
+                    state.verify_joinsplit_with_snapshot(black_box(joinsplit), snapshot)
+                },
+                BatchSize::PerIteration,
+            );
+        });
+
+        stop.store(true, Ordering::Relaxed);
+    });
+}
+
+fn bench_prepared_verifier(c: &mut Criterion) {
+    let state = match LelantusState::new(LelantusParameters::default()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to create Lelantus state: {}", e);
+            return;
+        }
+    };
+    let (joinsplit, verifier, accumulator) = match build_joinsplit(&state).and_then(|js| {
+        Ok((
+            js,
+            PreparedVerifier::new(&state.parameters())?,
+            Accumulator::deserialize(&state.get_accumulator()?)?,
+        ))
+    }) {
+        Ok(built) => built,
+        Err(e) => {
+            eprintln!("Failed to build JoinSplit: {}", e);
+            return;
+        }
+    };
+    let snapshot = state.accumulator_snapshot();
+
+    // Clear the verification cache before every call, or all but the first
+    // would be answered from it
+    c.bench_function("verify_joinsplit_unprepared", |b| {
+        b.iter_batched(
+            || state.invalidate_verification_cache(),
+            |()| state.verify_joinsplit_with_snapshot(black_box(&joinsplit), &snapshot),
+            BatchSize::PerIteration,
+        );
+    });
+
+    c.bench_function("verify_joinsplit_prepared", |b| {
+        b.iter(|| verifier.verify(black_box(&joinsplit), &accumulator));
+    });
+}
+
+fn bench_parallel_verification(c: &mut Criterion) {
+    let state = match LelantusState::new(LelantusParameters::default()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to create Lelantus state: {}", e);
+            return;
+        }
+    };
+    // Mint every coin first so all JoinSplits are built against the head
+    // they are verified at
+    let joinsplits: Vec<JoinSplit> = match state.mint_batch(&[1000; 64]).and_then(|coins| {
+        coins
+            .into_iter()
+            .map(|coin| state.create_joinsplit(vec![coin], vec![900], 100))
+            .collect()
+    }) {
+        Ok(joinsplits) => joinsplits,
+        Err(e) => {
+            eprintln!("Failed to build JoinSplits: {}", e);
+            return;
+        }
+    };
+
+    // Clear the verification cache before every batch, or all but the first
+    // would be answered from it
+    for workers in [1, 4] {
+        c.bench_function(&format!("verify_parallel_{}_workers", workers), |b| {
+            b.iter_batched(
+                || {
+                    state.invalidate_verification_cache();
+                    joinsplits.clone()
+                },
+                |joinsplits| state.verify_parallel(black_box(joinsplits), workers),
+                BatchSize::PerIteration,
+            );
+        });
+    }
+}
+
+/// Compare with and without `--features parallel` for the speedup
+fn bench_joinsplit_creation(c: &mut Criterion) {
+    let state = match LelantusState::new(LelantusParameters::default()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to create Lelantus state: {}", e);
+            return;
+        }
+    };
+    let coins = match state.mint_batch(&[1000; MAX_JOINSPLIT_INPUTS]) {
+        Ok(coins) => coins,
+        Err(e) => {
+            eprintln!("Failed to mint coins: {}", e);
+            return;
+        }
+    };
+    let total = 1000 * MAX_JOINSPLIT_INPUTS as u64;
+
+    c.bench_function("create_joinsplit_16_inputs", |b| {
+        b.iter(|| {
+            state.create_joinsplit(
+                black_box(coins.clone()),
+                vec![total / 2, total / 2 - 100],
+                100,
+            )
+        });
+    });
+}
+
+fn build_joinsplit(state: &LelantusState) -> Result<JoinSplit> {
+    let commitment = state.commitment_scheme().commit(1000)?;
+    state.add_coin(&commitment)?;
+    let encrypted = Witness::encrypt_amount(&commitment, 0, 1000)?;
+    let witness = Witness::new(
+        commitment.clone(),
+        0,
+        state.get_accumulator()?,
+        vec![1; 32],
+        encrypted,
+    );
+    state.create_joinsplit(vec![(commitment, witness)], vec![900], 100)
+}
+
+criterion_group!(
+    benches,
+    bench_commitment_creation,
+    bench_accumulator_add,
+    bench_bulk_import,
+    bench_amount_decryption,
+    bench_verification_under_contention,
+    bench_prepared_verifier,
+    bench_parallel_verification,
+    bench_joinsplit_creation
+);
 criterion_main!(benches);
@@ -1,15 +1,36 @@
 //! Lelantus accumulator implementation
+//!
+//! Coins are the leaves of an append-only Merkle tree whose first leaf is the
+//! hashed accumulator base. The accumulator value commits to the tree root,
+//! so membership proofs are logarithmic and verify without the elements.
 
 use crate::commitment::AccumulatorElement;
-use crate::errors::{LelantusError, Result};
+use crate::errors::{ErrorContext, LelantusError, Result};
+use crate::merkle;
 use crate::parameters::LelantusParameters;
+use crate::prelude::*;
+use crate::serialization::{binary, LelantusSerializable};
+use alloc::collections::VecDeque;
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::ops::Range;
 use hex;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use sha2::{Digest, Sha512};
 
+/// Number of recent accumulator heads retained as valid anchors
+pub const ANCHOR_WINDOW: usize = 100;
+
+/// Domain separator for per-epoch accumulator bases
+const EPOCH_BASE_DOMAIN: &[u8] = b"lelantus_epoch_base";
+
+/// Magic bytes opening a portable membership proof
+const PORTABLE_PROOF_MAGIC: &[u8; 4] = b"LMPF";
+
+/// Current portable membership proof format version
+const PORTABLE_PROOF_VERSION: u8 = 4;
+
 /// Lelantus accumulator for coin commitments
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct Accumulator {
     /// Current accumulator value
     value: Vec<u8>,
@@ -17,40 +38,303 @@ pub struct Accumulator {
     /// Elements in the accumulator
     elements: Vec<AccumulatorElement>,
 
+    /// Number of earlier elements not held locally (checkpoint bootstrap)
+    #[serde(default)]
+    pruned_count: usize,
+
+    /// Recent heads with their element counts, oldest first, ending with
+    /// the current value
+    #[serde(default)]
+    anchors: VecDeque<(Vec<u8>, usize)>,
+
+    /// Peaks of the Merkle tree over the initial value and all elements
+    peaks: Vec<[u8; 32]>,
+
+    /// Coin group this accumulator holds
+    #[serde(default)]
+    group_id: u64,
+
     /// Parameters
     parameters: LelantusParameters,
 }
 
+/// Largest accepted accumulator encoding; holds the default element limit
+const MAX_ACCUMULATOR_ENCODED_SIZE: usize = 1 << 28;
+
+impl LelantusSerializable for Accumulator {
+    const MAX_ENCODED_SIZE: usize = MAX_ACCUMULATOR_ENCODED_SIZE;
+    const MIN_ENCODING_VERSION: u8 = 4;
+}
+
 impl Accumulator {
-    /// Create a new accumulator
+    /// Create a new accumulator for coin group 0
     pub fn new(parameters: &LelantusParameters) -> Result<Self> {
+        Self::new_group(parameters, 0)
+    }
+
+    /// Create a new accumulator for coin group `group_id`
+    pub fn new_group(parameters: &LelantusParameters, group_id: u64) -> Result<Self> {
         parameters.validate()?;
 
-        // Initialize with generator
-        let mut hasher = Sha512::new();
-        hasher.update(&parameters.accumulator_base);
-        let initial_value = hex::encode(hasher.finalize()).into_bytes();
+        let peaks = vec![merkle::leaf_hash(&initial_value(parameters))];
+        let value = head_value(&peaks, 1)?;
 
         Ok(Self {
-            value: initial_value,
+            anchors: VecDeque::from([(value.clone(), 0)]),
+            value,
             elements: Vec::new(),
+            pruned_count: 0,
+            peaks,
+            group_id,
+            parameters: parameters.clone(),
+        })
+    }
+
+    /// Restore an accumulator from a checkpointed head
+    ///
+    /// `frontier` holds the most recent elements; the remaining
+    /// `element_count - frontier.len()` elements are not available locally.
+    /// `peaks` must hash to `value`, so the restored accumulator can keep
+    /// growing without the earlier elements.
+    pub fn from_checkpoint(
+        parameters: &LelantusParameters,
+        value: Vec<u8>,
+        element_count: usize,
+        frontier: Vec<AccumulatorElement>,
+        peaks: Vec<[u8; 32]>,
+    ) -> Result<Self> {
+        parameters.validate()?;
+
+        let pruned_count = element_count.checked_sub(frontier.len()).ok_or_else(|| {
+            LelantusError::AccumulatorError("frontier larger than element count".to_string())
+        })?;
+
+        if head_value(&peaks, element_count as u64 + 1).ok() != Some(value.clone()) {
+            return Err(LelantusError::AccumulatorError(
+                "checkpoint peaks do not match the accumulator value".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            anchors: VecDeque::from([(value.clone(), element_count)]),
+            value,
+            elements: frontier,
+            pruned_count,
+            peaks,
+            group_id: 0,
             parameters: parameters.clone(),
         })
     }
 
     /// Add an element to the accumulator
+    ///
+    /// The element must be exactly as long as a commitment value under the
+    /// configured hash, which keeps the accumulator homogeneous.
     pub fn add_element(&mut self, element: AccumulatorElement) -> Result<()> {
-        // Update accumulator: new_value = H(old_value || element)
-        let mut hasher = Sha512::new();
-        hasher.update(&self.value);
-        hasher.update(&element.value);
+        if element.value.len() != self.parameters.hash_algo()?.encoded_len() {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        if self.is_full() {
+            return Err(LelantusError::AccumulatorError(
+                "accumulator is full".to_string(),
+            ));
+        }
 
-        self.value = hex::encode(hasher.finalize()).into_bytes();
+        let tree_size = self.tree_size();
+        merkle::append_peak(
+            &mut self.peaks,
+            tree_size,
+            merkle::leaf_hash(&element.value),
+        );
+        self.value = head_value(&self.peaks, tree_size + 1)?;
         self.elements.push(element);
+        self.record_anchor();
 
         Ok(())
     }
 
+    /// Add a batch of elements, returning the diff that describes it
+    ///
+    /// The diff lets light clients advance their witnesses past the batch
+    /// without fetching the whole accumulator.
+    pub fn add_elements(&mut self, elements: Vec<AccumulatorElement>) -> Result<AccumulatorDiff> {
+        let diff = AccumulatorDiff {
+            start_count: self.element_count(),
+            previous_value: self.value.clone(),
+            previous_peaks: self.peaks.clone(),
+            elements,
+        };
+
+        for element in &diff.elements {
+            self.add_element(element.clone())?;
+        }
+
+        Ok(diff)
+    }
+
+    /// Append a batch of elements in one update, returning their indices
+    ///
+    /// Every element and the remaining capacity are checked before anything
+    /// changes, so a failed batch leaves the accumulator untouched. The head
+    /// is recomputed once and recorded as a single anchor.
+    pub fn extend(&mut self, elements: Vec<AccumulatorElement>) -> Result<Range<usize>> {
+        let encoded_len = self.parameters.hash_algo()?.encoded_len();
+        if elements
+            .iter()
+            .any(|element| element.value.len() != encoded_len)
+        {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let start = self.element_count();
+        if elements.len() > self.parameters.max_elements.saturating_sub(start) {
+            return Err(LelantusError::AccumulatorError(
+                "accumulator is full".to_string(),
+            ));
+        }
+        if elements.is_empty() {
+            return Ok(start..start);
+        }
+
+        let mut peaks = self.peaks.clone();
+        let mut tree_size = self.tree_size();
+        for element in &elements {
+            merkle::append_peak(&mut peaks, tree_size, merkle::leaf_hash(&element.value));
+            tree_size += 1;
+        }
+        self.value = head_value(&peaks, tree_size)?;
+        self.peaks = peaks;
+        self.elements.extend(elements);
+        self.record_anchor();
+
+        Ok(start..self.element_count())
+    }
+
+    /// Retain the current head as an anchor, evicting the oldest if full
+    fn record_anchor(&mut self) {
+        if self.anchors.len() == ANCHOR_WINDOW {
+            self.anchors.pop_front();
+        }
+        self.anchors
+            .push_back((self.value.clone(), self.element_count()));
+    }
+
+    /// Check whether `anchor` is one of the retained recent heads
+    pub fn is_recent_anchor(&self, anchor: &[u8]) -> bool {
+        self.anchor_snapshot(anchor).is_some()
+    }
+
+    /// Get the retained recent heads, oldest first
+    pub fn anchors(&self) -> impl Iterator<Item = &[u8]> {
+        self.anchors.iter().map(|(head, _)| head.as_slice())
+    }
+
+    /// Snapshot of the retained recent head `anchor`, if any
+    pub fn anchor_snapshot(&self, anchor: &[u8]) -> Option<AccumulatorSnapshot> {
+        self.anchors
+            .iter()
+            .rev()
+            .find(|(head, _)| head.as_slice() == anchor)
+            .map(|(head, element_count)| AccumulatorSnapshot {
+                value: head.clone(),
+                element_count: *element_count,
+            })
+    }
+
+    /// Index of the anonymity set holding element `index`
+    ///
+    /// Set `i` holds elements `i·N .. (i+1)·N`, for `N` the anonymity set
+    /// size of the parameters.
+    pub fn anonymity_set_index(&self, index: usize) -> u64 {
+        (index / self.parameters.anonymity_set_size()) as u64
+    }
+
+    /// Element values of anonymity set `set_index` as of `element_count`
+    /// elements
+    ///
+    /// Elements added later are left out, so the set is the one a JoinSplit
+    /// built against that head was proven over. Empty if the set starts at
+    /// or after `element_count`. Fails if `element_count` is past the
+    /// current count or the set reaches into pruned elements.
+    pub fn anonymity_set(&self, set_index: u64, element_count: usize) -> Result<Vec<Vec<u8>>> {
+        if element_count > self.element_count() {
+            return Err(LelantusError::AccumulatorError(
+                "element count past the accumulator head".to_string(),
+            ));
+        }
+
+        let size = self.parameters.anonymity_set_size();
+        let start = match usize::try_from(set_index)
+            .ok()
+            .and_then(|index| index.checked_mul(size))
+        {
+            Some(start) if start < element_count => start,
+            _ => return Ok(Vec::new()),
+        };
+        if start < self.pruned_count {
+            return Err(LelantusError::AccumulatorError(
+                "anonymity set unavailable for checkpointed accumulator".to_string(),
+            ));
+        }
+
+        let end = element_count.min(start + size);
+        Ok(
+            self.elements[start - self.pruned_count..end - self.pruned_count]
+                .iter()
+                .map(|element| element.value.clone())
+                .collect(),
+        )
+    }
+
+    /// Combine shard accumulators into one in an explicit canonical order
+    ///
+    /// `ordering` must be a permutation of the shard indices. The combined
+    /// accumulator holds the shard elements concatenated in that order, so
+    /// its head is deterministic for a given ordering but differs between
+    /// orderings. Every shard must hold all of its elements.
+    pub fn combine(shards: &[Accumulator], ordering: &[usize]) -> Result<Accumulator> {
+        let first = shards
+            .first()
+            .ok_or_else(|| LelantusError::AccumulatorError("no shards to combine".to_string()))?;
+
+        let mut seen = vec![false; shards.len()];
+        if ordering.len() != shards.len()
+            || ordering
+                .iter()
+                .any(|&i| i >= shards.len() || core::mem::replace(&mut seen[i], true))
+        {
+            return Err(LelantusError::AccumulatorError(
+                "ordering must be a permutation of shard indices".to_string(),
+            ));
+        }
+
+        if shards
+            .iter()
+            .any(|shard| shard.parameters.accumulator_base != first.parameters.accumulator_base)
+        {
+            return Err(LelantusError::AccumulatorError(
+                "shards use different accumulator bases".to_string(),
+            ));
+        }
+
+        if shards.iter().any(|shard| shard.pruned_count > 0) {
+            return Err(LelantusError::AccumulatorError(
+                "cannot combine checkpointed shards".to_string(),
+            ));
+        }
+
+        let mut combined = Accumulator::new(&first.parameters)?;
+        for &index in ordering {
+            for element in &shards[index].elements {
+                combined.add_element(element.clone())?;
+            }
+        }
+        combined.anchors = VecDeque::from([(combined.value.clone(), combined.element_count())]);
+
+        Ok(combined)
+    }
+
     /// Get the current accumulator value
     pub fn value(&self) -> &[u8] {
         &self.value
@@ -58,72 +342,492 @@ impl Accumulator {
 
     /// Get the number of elements
     pub fn element_count(&self) -> usize {
-        self.elements.len()
+        self.pruned_count + self.elements.len()
+    }
+
+    /// Leaves of the Merkle tree: the initial value followed by every element
+    fn tree_size(&self) -> u64 {
+        self.element_count() as u64 + 1
+    }
+
+    /// Peaks of the Merkle tree behind the current value
+    ///
+    /// Together with the element count they are enough to keep appending.
+    pub fn peaks(&self) -> &[[u8; 32]] {
+        &self.peaks
+    }
+
+    /// Coin group this accumulator holds
+    pub fn group_id(&self) -> u64 {
+        self.group_id
+    }
+
+    /// Whether the group has reached `max_elements` and accepts no more coins
+    pub fn is_full(&self) -> bool {
+        self.element_count() >= self.parameters.max_elements
     }
 
-    /// Get all elements
+    /// Get all elements held locally
     pub fn elements(&self) -> &[AccumulatorElement] {
         &self.elements
     }
 
+    /// Index of the first element held locally with `value`, if any
+    pub fn position(&self, value: &[u8]) -> Option<usize> {
+        self.elements
+            .iter()
+            .position(|element| element.value == value)
+            .map(|index| self.pruned_count + index)
+    }
+
+    /// Get up to `size` of the most recent elements
+    pub fn frontier(&self, size: usize) -> &[AccumulatorElement] {
+        &self.elements[self.elements.len().saturating_sub(size)..]
+    }
+
+    /// Take a cheap snapshot of the accumulator head
+    pub fn snapshot(&self) -> AccumulatorSnapshot {
+        AccumulatorSnapshot {
+            value: self.value.clone(),
+            element_count: self.element_count(),
+        }
+    }
+
     /// Serialize the accumulator
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec(self).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        LelantusSerializable::to_bytes(self)
     }
 
     /// Deserialize the accumulator
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        serde_json::from_slice(data).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        <Self as LelantusSerializable>::from_bytes(data)
     }
 
-    /// Create a proof of membership for an element
-    pub fn create_membership_proof(&self, element_index: usize) -> Result<MembershipProof> {
-        if element_index >= self.elements.len() {
-            return Err(LelantusError::InvalidParameter);
+    /// Encode the accumulator for consensus
+    ///
+    /// Writes the parameters, group id, pruned element count, elements,
+    /// peaks, recent anchors with their element counts and value, with lists
+    /// prefixed by their count.
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_bytes(&mut buf, &self.parameters.consensus_encode()?)?;
+        binary::put_u64(&mut buf, self.group_id);
+        binary::put_u64(&mut buf, self.pruned_count as u64);
+        binary::put_u64(&mut buf, self.elements.len() as u64);
+        for element in &self.elements {
+            binary::put_bytes(&mut buf, &element.value)?;
         }
+        binary::put_u64(&mut buf, self.peaks.len() as u64);
+        for peak in &self.peaks {
+            binary::put_bytes(&mut buf, peak)?;
+        }
+        binary::put_u64(&mut buf, self.anchors.len() as u64);
+        for (anchor, element_count) in &self.anchors {
+            binary::put_bytes(&mut buf, anchor)?;
+            binary::put_u64(&mut buf, *element_count as u64);
+        }
+        binary::put_bytes(&mut buf, &self.value)?;
+        Ok(buf)
+    }
 
-        // Create path from element to root
-        let mut path = Vec::new();
-        let mut current_value = self.parameters.accumulator_base.clone();
-
-        // Build path by accumulating all elements
-        for (i, element) in self.elements.iter().enumerate() {
-            let mut hasher = Sha512::new();
-            hasher.update(&current_value);
-            hasher.update(&element.value);
-            current_value = hex::encode(hasher.finalize()).into_bytes();
+    /// Decode an accumulator from its consensus encoding
+    ///
+    /// The peaks must hash to the value, and the anchors must end with it.
+    /// Anchor element counts must not decrease and must end with the
+    /// element count.
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let malformed = |what: &str| LelantusError::SerializationError(what.to_string());
+        let mut reader = binary::Reader::new(data);
+        let parameters = LelantusParameters::consensus_decode(&reader.read_bytes()?)?;
+        let group_id = reader.read_u64()?;
+        let pruned_count =
+            usize::try_from(reader.read_u64()?).map_err(|_| malformed("count overflow"))?;
 
-            path.push(ProofNode {
-                value: element.value.clone(),
-                is_left: i <= element_index,
+        let mut elements = Vec::new();
+        for _ in 0..reader.read_u64()? {
+            elements.push(AccumulatorElement {
+                value: reader.read_bytes()?,
             });
         }
+        let mut peaks = Vec::new();
+        for _ in 0..reader.read_u64()? {
+            let peak = <[u8; 32]>::try_from(reader.read_bytes()?.as_slice())
+                .map_err(|_| malformed("peak is not 32 bytes"))?;
+            peaks.push(peak);
+        }
+        let mut anchors = VecDeque::new();
+        for _ in 0..reader.read_u64()? {
+            let anchor = reader.read_bytes()?;
+            let count =
+                usize::try_from(reader.read_u64()?).map_err(|_| malformed("count overflow"))?;
+            anchors.push_back((anchor, count));
+        }
+        let value = reader.read_bytes()?;
+        reader.finish()?;
 
-        Ok(MembershipProof {
-            element_index,
-            path,
-            accumulator_value: self.value.clone(),
-        })
+        let element_count = pruned_count
+            .checked_add(elements.len())
+            .ok_or_else(|| malformed("count overflow"))?;
+        if anchors.len() > ANCHOR_WINDOW || anchors.back() != Some(&(value.clone(), element_count))
+        {
+            return Err(malformed("anchors do not end with the accumulator head"));
+        }
+        if anchors
+            .iter()
+            .zip(anchors.iter().skip(1))
+            .any(|((_, earlier), (_, later))| earlier > later)
+        {
+            return Err(malformed("anchor element counts decrease"));
+        }
+
+        // With the full history at hand, the peaks must come from it
+        if pruned_count == 0 {
+            let mut rebuilt = vec![merkle::leaf_hash(&initial_value(&parameters))];
+            for (index, element) in elements.iter().enumerate() {
+                merkle::append_peak(
+                    &mut rebuilt,
+                    index as u64 + 1,
+                    merkle::leaf_hash(&element.value),
+                );
+            }
+            if rebuilt != peaks {
+                return Err(malformed("peaks do not match the elements"));
+            }
+        }
+
+        let mut accumulator =
+            Self::from_checkpoint(&parameters, value, element_count, elements, peaks)?;
+        accumulator.group_id = group_id;
+        accumulator.anchors = anchors;
+        Ok(accumulator)
+    }
+
+    /// Create a proof of membership for an element
+    pub fn create_membership_proof(&self, element_index: usize) -> Result<MembershipProof> {
+        self.create_membership_proofs(element_index..element_index + 1)?
+            .pop()
+            .ok_or(LelantusError::invalid_parameter("element_index"))
+    }
+
+    /// Create membership proofs for every element in `indices`
+    ///
+    /// Hashes the tree once instead of once per element, so proving a
+    /// freshly added batch costs a single pass over the accumulator.
+    pub fn create_membership_proofs(
+        &self,
+        indices: core::ops::Range<usize>,
+    ) -> Result<Vec<MembershipProof>> {
+        if indices.is_empty() || indices.end > self.elements.len() {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("indices")
+                    .expected(format!("non-empty, within 0..{}", self.elements.len()))
+                    .actual(format!("{:?}", indices)),
+            ));
+        }
+
+        // Paths are hashed from all leaves, which needs the full history
+        if self.pruned_count > 0 {
+            return Err(LelantusError::AccumulatorError(
+                "membership proofs unavailable for checkpointed accumulator".to_string(),
+            ));
+        }
+
+        let leaves: Vec<[u8; 32]> = core::iter::once(initial_value(&self.parameters))
+            .chain(self.elements.iter().map(|element| element.value.clone()))
+            .map(|value| merkle::leaf_hash(&value))
+            .collect();
+        let levels = merkle::tree_levels(&leaves);
+
+        indices
+            .map(|element_index| {
+                let position = element_index as u64 + 1;
+                let siblings = merkle::path_from_levels(&levels, element_index + 1);
+                let sides = merkle::sibling_sides(position, self.tree_size());
+                Ok(MembershipProof {
+                    element_index,
+                    element_count: self.element_count(),
+                    element: self.elements[element_index].value.clone(),
+                    path: proof_nodes(&siblings, &sides),
+                    peaks: self.peaks.clone(),
+                    accumulator_value: self.value.clone(),
+                    group_id: self.group_id,
+                })
+            })
+            .collect()
     }
 
     /// Verify a membership proof
+    ///
+    /// The path must lead to one of the retained recent heads. Only hashes
+    /// along the logarithmic path; the elements are not needed. Paths longer
+    /// than the parameters allow are rejected with `InvalidProof` before any
+    /// hashing.
     pub fn verify_membership_proof(&self, proof: &MembershipProof) -> Result<bool> {
-        if proof.element_index >= self.elements.len() {
+        if proof.path.len() > self.parameters.max_proof_path_len() {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        if proof.group_id != self.group_id
+            || proof.element_index >= proof.element_count
+            || proof.element_count > self.element_count()
+            || !self.is_recent_anchor(&proof.accumulator_value)
+        {
             return Ok(false);
         }
 
-        // Reconstruct the accumulator value by replaying the path
-        let mut current_value = self.parameters.accumulator_base.clone();
+        Ok(proof.root_value() == Some(proof.accumulator_value.clone()))
+    }
+}
+
+/// Sequence of accumulators, one per epoch
+///
+/// Only the latest epoch accepts new coins. Each epoch can run under its own
+/// accumulator base, so coins from different epochs share no common history.
+/// Epochs are the coin groups of the protocol: epoch `i` holds group `i`, and
+/// a full group rolls over to a new one as coins are added.
+#[derive(Debug, Clone)]
+pub struct AccumulatorSet {
+    /// Accumulators of every epoch, oldest first
+    epochs: Vec<Accumulator>,
+}
+
+impl AccumulatorSet {
+    /// Create a set whose first epoch uses the configured base
+    pub fn new(parameters: &LelantusParameters) -> Result<Self> {
+        Ok(Self {
+            epochs: vec![Accumulator::new(parameters)?],
+        })
+    }
+
+    /// Public base of epoch `epoch` derived from `seed`
+    ///
+    /// Anyone holding the seed can reproduce the base.
+    pub fn epoch_base(epoch: usize, seed: &[u8]) -> Vec<u8> {
+        let mut hasher = Sha512::new();
+        hasher.update(EPOCH_BASE_DOMAIN);
+        hasher.update((epoch as u64).to_le_bytes());
+        hasher.update((seed.len() as u64).to_le_bytes());
+        hasher.update(seed);
+        hasher.finalize().to_vec()
+    }
 
-        for node in proof.path.iter() {
-            let mut hasher = Sha512::new();
-            hasher.update(&current_value);
-            hasher.update(&node.value);
-            current_value = hex::encode(hasher.finalize()).into_bytes();
+    /// Start a new epoch under a fresh base derived from `seed`
+    ///
+    /// Returns the index of the new epoch.
+    pub fn rotate_epoch_with_fresh_base(&mut self, seed: &[u8]) -> Result<usize> {
+        let epoch = self.epochs.len();
+        let parameters = LelantusParameters {
+            accumulator_base: Self::epoch_base(epoch, seed),
+            ..self.current().parameters.clone()
+        };
+        self.epochs
+            .push(Accumulator::new_group(&parameters, epoch as u64)?);
+        Ok(epoch)
+    }
+
+    /// Add an element to the current epoch, rolling over if it is full
+    ///
+    /// A new group's base is derived from the first group's base. Returns
+    /// the group the element was added to.
+    pub fn add_element(&mut self, element: AccumulatorElement) -> Result<u64> {
+        if self.current().is_full() {
+            let seed = self.epochs[0].parameters.accumulator_base.clone();
+            self.rotate_epoch_with_fresh_base(&seed)?;
         }
+        let current = self.current_mut();
+        current.add_element(element)?;
+        Ok(current.group_id)
+    }
+
+    /// Accumulator of the current epoch
+    pub fn current(&self) -> &Accumulator {
+        // `epochs` always holds at least the first epoch
+        &self.epochs[self.epochs.len() - 1]
+    }
+
+    fn current_mut(&mut self) -> &mut Accumulator {
+        let last = self.epochs.len() - 1;
+        &mut self.epochs[last]
+    }
+
+    /// Accumulator of epoch `epoch`
+    pub fn epoch(&self, epoch: usize) -> Option<&Accumulator> {
+        self.epochs.get(epoch)
+    }
+
+    /// Number of epochs
+    pub fn epoch_count(&self) -> usize {
+        self.epochs.len()
+    }
+
+    /// Epoch whose base a membership proof replays against
+    pub fn epoch_of(&self, proof: &MembershipProof) -> Option<usize> {
+        let epoch = usize::try_from(proof.group_id).ok()?;
+        self.epochs
+            .get(epoch)
+            .filter(|accumulator| accumulator.verify_membership_proof(proof).unwrap_or(false))
+            .map(|_| epoch)
+    }
+}
+
+/// Head of an empty accumulator: the hashed generator
+fn initial_value(parameters: &LelantusParameters) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(&parameters.accumulator_base);
+    hex::encode(hasher.finalize()).into_bytes()
+}
+
+/// Accumulator value of a tree with `peaks` and `tree_size` leaves
+fn head_value(peaks: &[[u8; 32]], tree_size: u64) -> Result<Vec<u8>> {
+    let root = merkle::root_from_peaks(peaks, tree_size).ok_or_else(|| {
+        LelantusError::AccumulatorError("peaks do not match the element count".to_string())
+    })?;
+    Ok(hex::encode(Sha512::digest(root)).into_bytes())
+}
+
+/// Path nodes from sibling hashes and their sides
+fn proof_nodes(siblings: &[[u8; 32]], sides: &[bool]) -> Vec<ProofNode> {
+    siblings
+        .iter()
+        .zip(sides)
+        .map(|(sibling, &is_left)| ProofNode {
+            value: sibling.to_vec(),
+            is_left,
+        })
+        .collect()
+}
+
+/// Elements appended to the accumulator in one batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccumulatorDiff {
+    /// Element count before the batch
+    pub start_count: usize,
+
+    /// Accumulator value before the batch
+    pub previous_value: Vec<u8>,
+
+    /// Merkle peaks before the batch
+    #[serde(default)]
+    pub previous_peaks: Vec<[u8; 32]>,
+
+    /// Appended elements, in order
+    pub elements: Vec<AccumulatorElement>,
+}
+
+impl AccumulatorDiff {
+    /// Element count after the batch
+    pub fn end_count(&self) -> usize {
+        self.start_count + self.elements.len()
+    }
+
+    /// Whether the previous peaks hash to the previous value
+    pub fn is_consistent(&self) -> bool {
+        head_value(&self.previous_peaks, self.start_count as u64 + 1).ok()
+            == Some(self.previous_value.clone())
+    }
+
+    /// Merkle peaks after the batch
+    pub fn resulting_peaks(&self) -> Vec<[u8; 32]> {
+        let mut peaks = self.previous_peaks.clone();
+        for (offset, element) in self.elements.iter().enumerate() {
+            let tree_size = (self.start_count + offset) as u64 + 1;
+            merkle::append_peak(&mut peaks, tree_size, merkle::leaf_hash(&element.value));
+        }
+        peaks
+    }
+
+    /// Accumulator value after the batch
+    ///
+    /// Fails if the previous peaks do not fit the element count.
+    pub fn resulting_value(&self) -> Result<Vec<u8>> {
+        head_value(&self.resulting_peaks(), self.end_count() as u64 + 1)
+    }
+
+    /// Encode the diff in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_u64(&mut buf, self.start_count as u64);
+        binary::put_bytes(&mut buf, &self.previous_value)?;
+        binary::put_u64(&mut buf, self.previous_peaks.len() as u64);
+        for peak in &self.previous_peaks {
+            binary::put_bytes(&mut buf, peak)?;
+        }
+        binary::put_u64(&mut buf, self.elements.len() as u64);
+        for element in &self.elements {
+            binary::put_bytes(&mut buf, &element.value)?;
+        }
+        Ok(buf)
+    }
+
+    /// Decode a diff from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let start_count = reader.read_u64()? as usize;
+        let previous_value = reader.read_bytes()?;
+        let peak_count = reader.read_u64()?;
+        let previous_peaks = (0..peak_count)
+            .map(|_| read_hash(&mut reader))
+            .collect::<Result<Vec<_>>>()?;
+        let count = reader.read_u64()?;
+        let elements = (0..count)
+            .map(|_| {
+                Ok(AccumulatorElement {
+                    value: reader.read_bytes()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        reader.finish()?;
+
+        Ok(Self {
+            start_count,
+            previous_value,
+            previous_peaks,
+            elements,
+        })
+    }
+
+    /// Append the diff to a stream as one length-prefixed frame
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        let mut frame = Vec::new();
+        binary::put_bytes(&mut frame, &self.consensus_encode()?)?;
+        Ok(writer.write_all(&frame)?)
+    }
+
+    /// Read the next diff from a stream, or `None` at end of stream
+    ///
+    /// A diff never carries more than the accumulator it extends, so frames
+    /// above the accumulator size limit are rejected unread.
+    #[cfg(feature = "std")]
+    pub fn read_from(reader: &mut impl std::io::Read) -> Result<Option<Self>> {
+        binary::read_frame(reader, MAX_ACCUMULATOR_ENCODED_SIZE)?
+            .map(|frame| Self::consensus_decode(&frame))
+            .transpose()
+    }
+}
+
+/// Point-in-time view of the accumulator head
+///
+/// Holds only the head value and element count, so it can be cloned freely
+/// and handed to verification workers without holding any lock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccumulatorSnapshot {
+    /// Accumulator value at snapshot time
+    value: Vec<u8>,
+
+    /// Number of elements at snapshot time
+    element_count: usize,
+}
+
+impl AccumulatorSnapshot {
+    /// Get the accumulator value
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
 
-        // Verify the reconstructed value matches the proof's accumulator value
-        Ok(current_value == proof.accumulator_value)
+    /// Get the number of elements
+    pub fn element_count(&self) -> usize {
+        self.element_count
     }
 }
 
@@ -133,11 +837,215 @@ pub struct MembershipProof {
     /// Index of the element
     pub element_index: usize,
 
-    /// Path from element to root
+    /// Number of elements in the accumulator the proof was made against
+    pub element_count: usize,
+
+    /// The proven element
+    pub element: Vec<u8>,
+
+    /// Sibling hashes from the element up to the root
     pub path: Vec<ProofNode>,
 
+    /// Merkle peaks behind `accumulator_value`, for advancing the proof
+    #[serde(default)]
+    pub peaks: Vec<[u8; 32]>,
+
     /// Accumulator value at proof creation time
     pub accumulator_value: Vec<u8>,
+
+    /// Coin group the element belongs to
+    #[serde(default)]
+    pub group_id: u64,
+}
+
+impl MembershipProof {
+    /// Accumulator value the path leads to, if it fits the element count
+    fn root_value(&self) -> Option<Vec<u8>> {
+        let position = self.element_index as u64 + 1;
+        let tree_size = self.element_count as u64 + 1;
+        let sides: Vec<bool> = self.path.iter().map(|node| node.is_left).collect();
+        if sides != merkle::sibling_sides(position, tree_size) {
+            return None;
+        }
+
+        let siblings = self
+            .path
+            .iter()
+            .map(|node| node.value.as_slice().try_into().ok())
+            .collect::<Option<Vec<[u8; 32]>>>()?;
+        let root = merkle::root_from_path(
+            merkle::leaf_hash(&self.element),
+            position,
+            tree_size,
+            &siblings,
+        )?;
+        Some(hex::encode(Sha512::digest(root)).into_bytes())
+    }
+
+    /// Advance the proof past a batch of appended elements
+    ///
+    /// The diff must start at the accumulator value and element count the
+    /// proof was made against. The proof is left unchanged on error.
+    pub fn apply_diff(&mut self, diff: &AccumulatorDiff) -> Result<()> {
+        let stale = || {
+            LelantusError::AccumulatorError(
+                "diff does not extend the proof accumulator".to_string(),
+            )
+        };
+        if diff.start_count != self.element_count
+            || diff.previous_value != self.accumulator_value
+            || !diff.is_consistent()
+        {
+            return Err(stale());
+        }
+
+        let siblings = self
+            .path
+            .iter()
+            .map(|node| node.value.as_slice().try_into().ok())
+            .collect::<Option<Vec<[u8; 32]>>>()
+            .ok_or(LelantusError::InvalidProof)?;
+        let new_leaves: Vec<[u8; 32]> = diff
+            .elements
+            .iter()
+            .map(|element| merkle::leaf_hash(&element.value))
+            .collect();
+        let position = self.element_index as u64 + 1;
+        let siblings = merkle::advance_path(
+            merkle::leaf_hash(&self.element),
+            position,
+            self.element_count as u64 + 1,
+            &siblings,
+            &diff.previous_peaks,
+            &new_leaves,
+        )
+        .ok_or(LelantusError::InvalidProof)?;
+
+        let element_count = diff.end_count();
+        let sides = merkle::sibling_sides(position, element_count as u64 + 1);
+        self.accumulator_value = diff.resulting_value()?;
+        self.path = proof_nodes(&siblings, &sides);
+        self.peaks = diff.resulting_peaks();
+        self.element_count = element_count;
+        Ok(())
+    }
+
+    /// Advance the proof past `elements` appended after its accumulator value
+    ///
+    /// Uses the peaks carried by the proof, so no diff is needed.
+    pub fn append(&mut self, elements: &[AccumulatorElement]) -> Result<()> {
+        self.apply_diff(&AccumulatorDiff {
+            start_count: self.element_count,
+            previous_value: self.accumulator_value.clone(),
+            previous_peaks: self.peaks.clone(),
+            elements: elements.to_vec(),
+        })
+    }
+
+    /// Encode the proof with a version header and parameter fingerprint
+    ///
+    /// Unlike the serde encoding, the result records which parameter set it
+    /// belongs to, so it cannot be silently loaded on another network.
+    pub fn to_portable(&self, parameters: &LelantusParameters) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(PORTABLE_PROOF_MAGIC);
+        buf.push(PORTABLE_PROOF_VERSION);
+        buf.extend_from_slice(&parameters.fingerprint()?);
+        buf.extend_from_slice(&self.consensus_encode()?);
+        Ok(buf)
+    }
+
+    /// Encode the proof in the canonical binary format, without a header
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_u64(&mut buf, self.element_index as u64);
+        binary::put_u64(&mut buf, self.element_count as u64);
+        binary::put_bytes(&mut buf, &self.element)?;
+        binary::put_u64(&mut buf, self.path.len() as u64);
+        for node in &self.path {
+            binary::put_bytes(&mut buf, &node.value)?;
+            buf.push(u8::from(node.is_left));
+        }
+        binary::put_u64(&mut buf, self.peaks.len() as u64);
+        for peak in &self.peaks {
+            binary::put_bytes(&mut buf, peak)?;
+        }
+        binary::put_bytes(&mut buf, &self.accumulator_value)?;
+        binary::put_u64(&mut buf, self.group_id);
+        Ok(buf)
+    }
+
+    /// Decode a portable proof, checking it was made under `parameters`
+    pub fn from_portable(data: &[u8], parameters: &LelantusParameters) -> Result<Self> {
+        let header_size = PORTABLE_PROOF_MAGIC.len() + 1 + 32;
+        if data.len() < header_size || &data[..PORTABLE_PROOF_MAGIC.len()] != PORTABLE_PROOF_MAGIC {
+            return Err(LelantusError::SerializationError(
+                "not a portable membership proof".to_string(),
+            ));
+        }
+
+        let version = data[PORTABLE_PROOF_MAGIC.len()];
+        if version != PORTABLE_PROOF_VERSION {
+            return Err(LelantusError::SerializationError(format!(
+                "unsupported portable proof version {}",
+                version
+            )));
+        }
+
+        if data[PORTABLE_PROOF_MAGIC.len() + 1..header_size] != parameters.fingerprint()? {
+            return Err(LelantusError::ParameterMismatch);
+        }
+
+        Self::consensus_decode(&data[header_size..])
+    }
+
+    /// Decode a proof from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let invalid_flag =
+            || LelantusError::SerializationError("invalid path direction flag".to_string());
+        let mut reader = binary::Reader::new(data);
+        let element_index = reader.read_u64()? as usize;
+        let element_count = reader.read_u64()? as usize;
+        let element = reader.read_bytes()?;
+        let path_len = reader.read_u64()?;
+        let path = (0..path_len)
+            .map(|_| {
+                let value = reader.read_bytes()?;
+                let is_left = match reader.read_u8()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(invalid_flag()),
+                };
+                Ok(ProofNode { value, is_left })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let peak_count = reader.read_u64()?;
+        let peaks = (0..peak_count)
+            .map(|_| read_hash(&mut reader))
+            .collect::<Result<Vec<_>>>()?;
+        let accumulator_value = reader.read_bytes()?;
+        let group_id = reader.read_u64()?;
+        reader.finish()?;
+
+        Ok(Self {
+            element_index,
+            element_count,
+            element,
+            path,
+            peaks,
+            accumulator_value,
+            group_id,
+        })
+    }
+}
+
+/// Read a 32-byte hash field
+fn read_hash(reader: &mut binary::Reader) -> Result<[u8; 32]> {
+    reader
+        .read_bytes()?
+        .as_slice()
+        .try_into()
+        .map_err(|_| LelantusError::SerializationError("hash field must be 32 bytes".to_string()))
 }
 
 /// Node in a membership proof path
@@ -153,8 +1061,11 @@ pub struct ProofNode {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parameters::PrivacyLevel;
+
+    /// Length of a commitment value under the default parameters
+    const ELEMENT_LEN: usize = 2 * crate::pedersen::POINT_SIZE;
 
-    #[test]
     #[test]
     fn test_accumulator_creation() -> Result<()> {
         let params = LelantusParameters::default();
@@ -166,13 +1077,39 @@ mod tests {
     fn test_add_element() -> Result<()> {
         let params = LelantusParameters::default();
         let mut accumulator = Accumulator::new(&params)?;
-        let element = AccumulatorElement { value: vec![1; 32] };
+        let element = AccumulatorElement {
+            value: vec![1; ELEMENT_LEN],
+        };
 
         accumulator.add_element(element)?;
         assert_eq!(accumulator.element_count(), 1);
         Ok(())
     }
 
+    #[test]
+    fn test_add_element_rejects_wrong_length() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut accumulator = Accumulator::new(&params)?;
+        let head = accumulator.value().to_vec();
+
+        for len in [0, 32, 64, 128, 135] {
+            assert!(matches!(
+                accumulator.add_element(AccumulatorElement {
+                    value: vec![1; len]
+                }),
+                Err(LelantusError::InvalidCommitment)
+            ));
+        }
+        assert_eq!(accumulator.value(), &head[..]);
+        assert_eq!(accumulator.element_count(), 0);
+
+        accumulator.add_element(AccumulatorElement {
+            value: vec![1; ELEMENT_LEN],
+        })?;
+        assert_eq!(accumulator.element_count(), 1);
+        Ok(())
+    }
+
     #[test]
     fn test_multiple_elements() -> Result<()> {
         let params = LelantusParameters::default();
@@ -180,7 +1117,7 @@ mod tests {
 
         for i in 0..10 {
             let element = AccumulatorElement {
-                value: vec![i as u8; 32],
+                value: vec![i as u8; ELEMENT_LEN],
             };
             accumulator.add_element(element)?;
         }
@@ -189,12 +1126,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_extend_matches_single_insertions() -> Result<()> {
+        let params = LelantusParameters {
+            max_elements: 8,
+            ..Default::default()
+        };
+        let elements: Vec<AccumulatorElement> = (0..5u8)
+            .map(|i| AccumulatorElement {
+                value: vec![i; ELEMENT_LEN],
+            })
+            .collect();
+
+        let mut single = Accumulator::new(&params)?;
+        for element in &elements {
+            single.add_element(element.clone())?;
+        }
+        let mut batched = Accumulator::new(&params)?;
+        assert_eq!(batched.extend(elements.clone())?, 0..5);
+        assert_eq!(batched.value(), single.value());
+        assert_eq!(batched.peaks(), single.peaks());
+
+        // A batch that does not fit, or holds a bad element, changes nothing
+        assert!(batched.extend(elements[..4].to_vec()).is_err());
+        let mut bad = elements[..2].to_vec();
+        bad[1].value.pop();
+        assert!(matches!(
+            batched.extend(bad),
+            Err(LelantusError::InvalidCommitment)
+        ));
+        assert_eq!(batched.value(), single.value());
+        assert_eq!(batched.extend(elements[..3].to_vec())?, 5..8);
+        Ok(())
+    }
+
     #[test]
     fn test_serialization() -> Result<()> {
         let params = LelantusParameters::default();
         let mut accumulator = Accumulator::new(&params)?;
         let element = AccumulatorElement {
-            value: vec![42; 32],
+            value: vec![42; ELEMENT_LEN],
         };
         accumulator.add_element(element)?;
 
@@ -204,6 +1175,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_consensus_encoding_golden() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut accumulator = Accumulator::new(&params)?;
+        for byte in 1..=3 {
+            accumulator.add_element(AccumulatorElement {
+                value: vec![byte; ELEMENT_LEN],
+            })?;
+        }
+
+        let encoded = accumulator.consensus_encode()?;
+        assert_eq!(
+            hex::encode(Sha512::digest(&encoded)),
+            "31758740d8d3472faf6fb6e429c361891a7a1071588433e997700da2eae68f73\
+             1ff516c1c22200dbe91679582f135b058e5145da176c0fe5b3ec8ec7664ef519"
+        );
+
+        let decoded = Accumulator::consensus_decode(&encoded)?;
+        assert_eq!(decoded.consensus_encode()?, encoded);
+        assert_eq!(decoded.value(), accumulator.value());
+
+        // Elements that do not produce the peaks are rejected
+        let mut forged = accumulator.clone();
+        forged.elements[1].value = vec![9; ELEMENT_LEN];
+        assert!(Accumulator::consensus_decode(&forged.consensus_encode()?).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_membership_proof() -> Result<()> {
         let params = LelantusParameters::default();
@@ -211,7 +1210,7 @@ mod tests {
 
         for i in 0..5 {
             let element = AccumulatorElement {
-                value: vec![i as u8; 32],
+                value: vec![i as u8; ELEMENT_LEN],
             };
             accumulator.add_element(element)?;
         }
@@ -219,8 +1218,315 @@ mod tests {
         let proof = accumulator.create_membership_proof(2)?;
         // Verify proof structure is valid
         assert_eq!(proof.element_index, 2);
-        assert_eq!(proof.path.len(), 5);
+        // One sibling per level of the 6-leaf tree
+        assert_eq!(proof.path.len(), 3);
         assert!(!proof.accumulator_value.is_empty());
+        assert!(accumulator.verify_membership_proof(&proof)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_membership_proofs() -> Result<()> {
+        let mut accumulator = Accumulator::new(&LelantusParameters::default())?;
+        for i in 0..6 {
+            accumulator.add_element(AccumulatorElement {
+                value: vec![i as u8; ELEMENT_LEN],
+            })?;
+        }
+
+        let proofs = accumulator.create_membership_proofs(2..6)?;
+        assert_eq!(proofs.len(), 4);
+        for proof in &proofs {
+            let single = accumulator.create_membership_proof(proof.element_index)?;
+            assert_eq!(proof.consensus_encode()?, single.consensus_encode()?);
+        }
+        assert!(accumulator.create_membership_proofs(4..7).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_membership_proof_path_limit() -> Result<()> {
+        let params = LelantusParameters {
+            max_elements: 8,
+            ..Default::default()
+        };
+        let mut accumulator = Accumulator::new(&params)?;
+        for i in 0..5 {
+            accumulator.add_element(AccumulatorElement {
+                value: vec![i as u8; ELEMENT_LEN],
+            })?;
+        }
+
+        let proof = accumulator.create_membership_proof(2)?;
+        assert!(accumulator.verify_membership_proof(&proof)?);
+
+        let mut oversized = proof;
+        oversized.path = vec![oversized.path[0].clone(); params.max_proof_path_len() + 1];
+        assert!(matches!(
+            accumulator.verify_membership_proof(&oversized),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        // The accumulator never grows past the path limit
+        for i in 5..8 {
+            accumulator.add_element(AccumulatorElement {
+                value: vec![i as u8; ELEMENT_LEN],
+            })?;
+        }
+        assert!(accumulator
+            .add_element(AccumulatorElement {
+                value: vec![8; ELEMENT_LEN]
+            })
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_epoch_rotation_with_fresh_base() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut set = AccumulatorSet::new(&params)?;
+        set.add_element(AccumulatorElement {
+            value: vec![1; ELEMENT_LEN],
+        })?;
+        let first_proof = set.current().create_membership_proof(0)?;
+
+        assert_eq!(set.rotate_epoch_with_fresh_base(b"epoch one")?, 1);
+        set.add_element(AccumulatorElement {
+            value: vec![1; ELEMENT_LEN],
+        })?;
+        let second_proof = set.current().create_membership_proof(0)?;
+
+        assert_eq!(set.rotate_epoch_with_fresh_base(b"epoch two")?, 2);
+        let bases: Vec<&[u8]> = (0..set.epoch_count())
+            .filter_map(|epoch| set.epoch(epoch))
+            .map(|accumulator| accumulator.parameters.accumulator_base.as_slice())
+            .collect();
+        assert_ne!(bases[0], bases[1]);
+        assert_ne!(bases[1], bases[2]);
+        assert_eq!(bases[1], AccumulatorSet::epoch_base(1, b"epoch one"));
+
+        // The same coin in two epochs is told apart by the base it replays to
+        assert_eq!(set.epoch_of(&first_proof), Some(0));
+        assert_eq!(set.epoch_of(&second_proof), Some(1));
+        assert_ne!(
+            first_proof.accumulator_value,
+            second_proof.accumulator_value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_coin_group_rollover() -> Result<()> {
+        let params = LelantusParameters {
+            max_elements: 2,
+            ..Default::default()
+        };
+        let mut set = AccumulatorSet::new(&params)?;
+        let groups = (0..5u8)
+            .map(|i| {
+                set.add_element(AccumulatorElement {
+                    value: vec![i; ELEMENT_LEN],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(groups, vec![0, 0, 1, 1, 2]);
+        assert_eq!(set.epoch_count(), 3);
+
+        let proof = set.epoch(1).map(|group| group.create_membership_proof(1));
+        let proof = proof.ok_or(LelantusError::invalid_parameter("epoch"))??;
+        assert_eq!(proof.group_id, 1);
+        assert_eq!(set.epoch_of(&proof), Some(1));
+        assert_eq!(
+            MembershipProof::consensus_decode(&proof.consensus_encode()?)?.group_id,
+            1
+        );
+
+        // A proof claiming another group does not verify there
+        let mut moved = proof.clone();
+        moved.group_id = 0;
+        assert_eq!(set.epoch_of(&moved), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_portable_membership_proof() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut accumulator = Accumulator::new(&params)?;
+        for i in 0..3 {
+            accumulator.add_element(AccumulatorElement {
+                value: vec![i as u8; ELEMENT_LEN],
+            })?;
+        }
+
+        let proof = accumulator.create_membership_proof(1)?;
+        let portable = proof.to_portable(&params)?;
+        assert_eq!(&portable[..4], PORTABLE_PROOF_MAGIC);
+        assert_eq!(&portable[5..37], &params.fingerprint()?[..]);
+
+        let restored = MembershipProof::from_portable(&portable, &params)?;
+        assert_eq!(restored.element_index, 1);
+        assert_eq!(restored.path.len(), proof.path.len());
+        assert_eq!(restored.accumulator_value, proof.accumulator_value);
+
+        let other = LelantusParameters::with_privacy_level(PrivacyLevel::Maximum);
+        assert!(matches!(
+            MembershipProof::from_portable(&portable, &other),
+            Err(LelantusError::ParameterMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut accumulator = Accumulator::new(&params)?;
+        accumulator.add_element(AccumulatorElement {
+            value: vec![1; ELEMENT_LEN],
+        })?;
+
+        let snapshot = accumulator.snapshot();
+        assert_eq!(snapshot.value(), accumulator.value());
+        assert_eq!(snapshot.element_count(), 1);
+
+        accumulator.add_element(AccumulatorElement {
+            value: vec![2; ELEMENT_LEN],
+        })?;
+        assert_ne!(snapshot.value(), accumulator.value());
+        assert_eq!(snapshot.element_count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_checkpoint() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut accumulator = Accumulator::new(&params)?;
+        for i in 0..10 {
+            accumulator.add_element(AccumulatorElement {
+                value: vec![i as u8; ELEMENT_LEN],
+            })?;
+        }
+
+        let frontier = accumulator.frontier(4).to_vec();
+        assert_eq!(frontier.len(), 4);
+
+        let mut restored = Accumulator::from_checkpoint(
+            &params,
+            accumulator.value().to_vec(),
+            accumulator.element_count(),
+            frontier.clone(),
+            accumulator.peaks().to_vec(),
+        )?;
+        assert_eq!(restored.value(), accumulator.value());
+        assert_eq!(restored.element_count(), 10);
+        assert!(restored.create_membership_proof(0).is_err());
+
+        // Peaks must hash to the checkpointed value
+        assert!(Accumulator::from_checkpoint(
+            &params,
+            accumulator.value().to_vec(),
+            accumulator.element_count(),
+            frontier,
+            vec![[0; 32]; accumulator.peaks().len()],
+        )
+        .is_err());
+
+        // Both continue identically from the checkpointed head
+        let element = AccumulatorElement {
+            value: vec![42; ELEMENT_LEN],
+        };
+        accumulator.add_element(element.clone())?;
+        restored.add_element(element)?;
+        assert_eq!(restored.value(), accumulator.value());
+        assert_eq!(restored.element_count(), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_combine_shards() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut shards = Vec::new();
+        for shard in 0..3u8 {
+            let mut accumulator = Accumulator::new(&params)?;
+            for i in 0..4u8 {
+                accumulator.add_element(AccumulatorElement {
+                    value: vec![shard * 10 + i; ELEMENT_LEN],
+                })?;
+            }
+            shards.push(accumulator);
+        }
+
+        let combined = Accumulator::combine(&shards, &[2, 0, 1])?;
+        let again = Accumulator::combine(&shards, &[2, 0, 1])?;
+        assert_eq!(combined.value(), again.value());
+        assert_eq!(combined.element_count(), 12);
+        assert_eq!(combined.elements()[0], shards[2].elements()[0]);
+
+        let reordered = Accumulator::combine(&shards, &[0, 1, 2])?;
+        assert_ne!(combined.value(), reordered.value());
+
+        assert!(Accumulator::combine(&shards, &[0, 0, 1]).is_err());
+        assert!(Accumulator::combine(&shards, &[0, 1]).is_err());
+        assert!(Accumulator::combine(&[], &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_anchor_window() -> Result<()> {
+        let params = LelantusParameters::default();
+        let mut accumulator = Accumulator::new(&params)?;
+        let genesis = accumulator.value().to_vec();
+
+        accumulator.add_element(AccumulatorElement {
+            value: vec![1; ELEMENT_LEN],
+        })?;
+        let first = accumulator.value().to_vec();
+        assert!(accumulator.is_recent_anchor(&genesis));
+        assert!(accumulator.is_recent_anchor(&first));
+        let snapshot = accumulator.anchor_snapshot(&first);
+        assert_eq!(snapshot.map(|s| s.element_count()), Some(1));
+
+        for i in 0..ANCHOR_WINDOW {
+            accumulator.add_element(AccumulatorElement {
+                value: vec![i as u8; ELEMENT_LEN],
+            })?;
+        }
+        assert_eq!(accumulator.anchors().count(), ANCHOR_WINDOW);
+        assert!(!accumulator.is_recent_anchor(&genesis));
+        assert!(!accumulator.is_recent_anchor(&first));
+        assert!(accumulator.is_recent_anchor(accumulator.value()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymity_sets() -> Result<()> {
+        let params = LelantusParameters::default();
+        let size = params.anonymity_set_size();
+        let mut accumulator = Accumulator::new(&params)?;
+        accumulator.extend(
+            (0..size + 6)
+                .map(|i| AccumulatorElement {
+                    value: vec![i as u8; ELEMENT_LEN],
+                })
+                .collect(),
+        )?;
+
+        assert_eq!(accumulator.anonymity_set_index(size - 1), 0);
+        assert_eq!(accumulator.anonymity_set_index(size), 1);
+        assert_eq!(accumulator.anonymity_set(0, size + 6)?.len(), size);
+        assert_eq!(accumulator.anonymity_set(1, size + 6)?.len(), 6);
+
+        // Sets are cut off at the given head
+        let set = accumulator.anonymity_set(1, size + 2)?;
+        assert_eq!(
+            set,
+            vec![
+                vec![size as u8; ELEMENT_LEN],
+                vec![size as u8 + 1; ELEMENT_LEN]
+            ]
+        );
+        assert!(accumulator.anonymity_set(1, size)?.is_empty());
+        assert!(accumulator.anonymity_set(u64::MAX, size + 6)?.is_empty());
+        assert!(accumulator.anonymity_set(0, size + 7).is_err());
         Ok(())
     }
 }
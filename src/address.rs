@@ -0,0 +1,547 @@
+//! Stealth addresses for receiving coins
+//!
+//! A [`StealthAddress`] carries two P-521 public keys: the scan key `A = a·G`
+//! and the spend key `B = b·G`. To pay it, a sender draws an ephemeral `r`,
+//! publishes `R = r·G` with the output and hashes `r·A` into a shared secret
+//! `s`. The output's one-time key `P = H(s)·G + B`, its commitment randomness
+//! and the view key its amount is sealed to all follow from `s`, so no two
+//! payments to one address can be linked.
+//!
+//! The recipient finds its outputs with the scan secret `a` and the public
+//! spend key `B`, since `a·R = r·A` and `P` follows from `s` and `B`. A
+//! [`StealthScanKey`] holds just those two, so scanning can be handed to a
+//! watch-only wallet; only the spend secret gives the one-time secret
+//! `b + H(s)`.
+//! Addresses are bech32m strings under [`ADDRESS_HRP`], and
+//! [`PaymentRequest`]s under [`PAYMENT_REQUEST_HRP`].
+
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::errors::{LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use crate::keys::{SpendKey, ViewKey};
+use crate::pedersen::{decode, decode_scalar, encode, scalar_from_digest, POINT_SIZE};
+use crate::serialization::{bech32m, DecodeError};
+use hmac::{Hmac, Mac};
+use p521::elliptic_curve::{Field, PrimeField};
+use p521::{ProjectivePoint, Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Human-readable prefix of encoded addresses
+pub const ADDRESS_HRP: &str = "lelantus";
+
+/// Human-readable prefix of encoded payment requests
+pub const PAYMENT_REQUEST_HRP: &str = "lelantusreq";
+
+/// Largest memo a payment request carries, in bytes
+///
+/// Keeps encoded requests within the bech32m length limit.
+pub const MAX_REQUEST_MEMO_SIZE: usize = 256;
+
+/// Domain separator deriving the scan secret from a spend key
+const SCAN_SECRET_DOMAIN: &[u8] = b"lelantus_stealth_scan";
+
+/// Domain separator deriving the spend secret from a spend key
+const SPEND_SECRET_DOMAIN: &[u8] = b"lelantus_stealth_spend";
+
+/// Domain separator hashing the Diffie-Hellman point into the shared secret
+const SHARED_SECRET_DOMAIN: &[u8] = b"lelantus_stealth_shared";
+
+/// Domain separator for the one-time key tweak `H(s)`
+const ONE_TIME_DOMAIN: &[u8] = b"lelantus_stealth_one_time";
+
+/// Domain separator for the output's commitment randomness
+const RANDOMNESS_DOMAIN: &[u8] = b"lelantus_stealth_randomness";
+
+/// Domain separator for the view key sealing the output's amount
+const VIEW_KEY_DOMAIN: &[u8] = b"lelantus_stealth_view_key";
+
+/// HMAC-SHA512 of `parts` under `key`
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(key)
+        .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+    for part in parts {
+        mac.update(part);
+    }
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// Shared secret of a payment from its Diffie-Hellman point
+fn shared_secret(point: &ProjectivePoint) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(SHARED_SECRET_DOMAIN);
+    hasher.update(encode(point));
+    hasher.finalize().to_vec()
+}
+
+/// One-time key tweak `H(s)`
+fn one_time_tweak(shared: &[u8]) -> Result<Scalar> {
+    Ok(scalar_from_digest(&hmac_sha512(
+        shared,
+        &[ONE_TIME_DOMAIN],
+    )?))
+}
+
+/// Commitment randomness of `size` bytes bound to the one-time key
+fn output_randomness(shared: &[u8], one_time_key: &[u8], size: usize) -> Result<Vec<u8>> {
+    let mut randomness = Vec::with_capacity(size);
+    let mut block = 0u64;
+    while randomness.len() < size {
+        randomness.extend(hmac_sha512(
+            shared,
+            &[RANDOMNESS_DOMAIN, one_time_key, &block.to_le_bytes()],
+        )?);
+        block += 1;
+    }
+    randomness.truncate(size);
+    Ok(randomness)
+}
+
+/// View key sealing the amount of the output
+fn output_view_key(shared: &[u8]) -> Result<ViewKey> {
+    ViewKey::from_bytes(&hmac_sha512(shared, &[VIEW_KEY_DOMAIN])?)
+}
+
+/// Dual-key stealth address: a scan key and a spend key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StealthAddress {
+    scan_key: Vec<u8>,
+    spend_key: Vec<u8>,
+}
+
+impl StealthAddress {
+    /// Create an address from compressed scan and spend keys
+    pub fn new(scan_key: &[u8], spend_key: &[u8]) -> Result<Self> {
+        if decode(scan_key).is_none() {
+            return Err(LelantusError::invalid_parameter("scan_key"));
+        }
+        if decode(spend_key).is_none() {
+            return Err(LelantusError::invalid_parameter("spend_key"));
+        }
+
+        Ok(Self {
+            scan_key: scan_key.to_vec(),
+            spend_key: spend_key.to_vec(),
+        })
+    }
+
+    /// Compressed scan key `A`
+    pub fn scan_key(&self) -> &[u8] {
+        &self.scan_key
+    }
+
+    /// Compressed spend key `B`
+    pub fn spend_key(&self) -> &[u8] {
+        &self.spend_key
+    }
+
+    /// Encode as a bech32m string under [`ADDRESS_HRP`]
+    pub fn to_bech32(&self) -> Result<String> {
+        bech32m::encode(ADDRESS_HRP, &self.to_payload())
+    }
+
+    /// Decode an address written by [`to_bech32`](Self::to_bech32)
+    ///
+    /// Fails with `SerializationError` on a bad checksum, prefix or length,
+    /// and with `InvalidParameter` if the keys are not curve points.
+    pub fn from_bech32(encoded: &str) -> Result<Self> {
+        let data = bech32m::decode(ADDRESS_HRP, encoded)?;
+        if data.len() != 2 * POINT_SIZE {
+            return Err(DecodeError::InvalidLength {
+                expected: 2 * POINT_SIZE,
+                actual: data.len(),
+            }
+            .into());
+        }
+        Self::from_payload(&data)
+    }
+
+    /// Scan key followed by spend key
+    fn to_payload(&self) -> Vec<u8> {
+        let mut data = self.scan_key.clone();
+        data.extend_from_slice(&self.spend_key);
+        data
+    }
+
+    /// Inverse of [`to_payload`](Self::to_payload), reading the first two
+    /// points of `data`
+    fn from_payload(data: &[u8]) -> Result<Self> {
+        Self::new(&data[..POINT_SIZE], &data[POINT_SIZE..2 * POINT_SIZE])
+    }
+
+    /// Derive an output paying `amount` to this address
+    ///
+    /// The commitment opens to `amount` under `scheme`. Publish the ephemeral
+    /// key with the output, see [`JoinSplit::set_ephemeral_key`], and seal
+    /// the amount to the output's view key with
+    /// [`JoinSplit::set_amount_note`].
+    pub fn derive_output(
+        &self,
+        scheme: &CommitmentScheme,
+        amount: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<StealthOutput> {
+        let (Some(scan_key), Some(spend_key)) = (decode(&self.scan_key), decode(&self.spend_key))
+        else {
+            return Err(LelantusError::invalid_parameter("address"));
+        };
+
+        let ephemeral = Scalar::random(&mut *rng);
+        let shared = shared_secret(&(scan_key * ephemeral));
+        let one_time_key =
+            encode(&(ProjectivePoint::GENERATOR * one_time_tweak(&shared)? + spend_key));
+        let size = scheme.parameters().randomness_bits / 8;
+        let commitment = scheme
+            .commit_with_randomness(amount, output_randomness(&shared, &one_time_key, size)?)?;
+
+        Ok(StealthOutput {
+            commitment,
+            ephemeral_key: encode(&(ProjectivePoint::GENERATOR * ephemeral)),
+            one_time_key,
+            view_key: output_view_key(&shared)?,
+        })
+    }
+}
+
+/// Request for a payment to a [`StealthAddress`]
+///
+/// Encoded as the address's keys, the amount as a little-endian `u64` and
+/// the memo, under [`PAYMENT_REQUEST_HRP`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentRequest {
+    /// Address to pay
+    pub address: StealthAddress,
+
+    /// Requested amount in mist
+    pub amount: u64,
+
+    /// Memo for the payer, at most [`MAX_REQUEST_MEMO_SIZE`] bytes
+    pub memo: Vec<u8>,
+}
+
+impl PaymentRequest {
+    /// Encode as a bech32m string under [`PAYMENT_REQUEST_HRP`]
+    pub fn to_bech32(&self) -> Result<String> {
+        if self.memo.len() > MAX_REQUEST_MEMO_SIZE {
+            return Err(LelantusError::invalid_parameter("memo"));
+        }
+        let mut data = self.address.to_payload();
+        data.extend_from_slice(&self.amount.to_le_bytes());
+        data.extend_from_slice(&self.memo);
+        bech32m::encode(PAYMENT_REQUEST_HRP, &data)
+    }
+
+    /// Decode a request written by [`to_bech32`](Self::to_bech32)
+    pub fn from_bech32(encoded: &str) -> Result<Self> {
+        let data = bech32m::decode(PAYMENT_REQUEST_HRP, encoded)?;
+        let header = 2 * POINT_SIZE + 8;
+        if data.len() < header || data.len() > header + MAX_REQUEST_MEMO_SIZE {
+            return Err(DecodeError::InvalidLength {
+                expected: header,
+                actual: data.len(),
+            }
+            .into());
+        }
+
+        let mut amount = [0u8; 8];
+        amount.copy_from_slice(&data[2 * POINT_SIZE..header]);
+        Ok(Self {
+            address: StealthAddress::from_payload(&data)?,
+            amount: u64::from_le_bytes(amount),
+            memo: data[header..].to_vec(),
+        })
+    }
+}
+
+/// Output paying a [`StealthAddress`]
+#[derive(Debug, Clone)]
+pub struct StealthOutput {
+    /// Output commitment
+    pub commitment: Commitment,
+
+    /// Compressed ephemeral key `R`, published with the output
+    pub ephemeral_key: Vec<u8>,
+
+    /// Compressed one-time key `P` the output is bound to
+    pub one_time_key: Vec<u8>,
+
+    /// View key the output's amount is sealed to
+    pub view_key: ViewKey,
+}
+
+/// Secret keys behind a [`StealthAddress`]
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct StealthKeys {
+    scan_secret: Vec<u8>,
+    spend_secret: Vec<u8>,
+}
+
+impl StealthKeys {
+    /// Derive the stealth keys of a wallet's spend key
+    pub fn from_spend_key(spend_key: &SpendKey) -> Result<Self> {
+        let derive = |domain: &[u8]| -> Result<Vec<u8>> {
+            let scalar = scalar_from_digest(&hmac_sha512(spend_key.as_bytes(), &[domain])?);
+            if bool::from(scalar.is_zero()) {
+                return Err(LelantusError::CryptoError(
+                    "stealth key is zero".to_string(),
+                ));
+            }
+            Ok(scalar.to_repr().to_vec())
+        };
+
+        Ok(Self {
+            scan_secret: derive(SCAN_SECRET_DOMAIN)?,
+            spend_secret: derive(SPEND_SECRET_DOMAIN)?,
+        })
+    }
+
+    fn scan_scalar(&self) -> Result<Scalar> {
+        decode_scalar(&self.scan_secret).ok_or(LelantusError::invalid_parameter("scan_secret"))
+    }
+
+    fn spend_scalar(&self) -> Result<Scalar> {
+        decode_scalar(&self.spend_secret).ok_or(LelantusError::invalid_parameter("spend_secret"))
+    }
+
+    /// Address paying these keys
+    pub fn address(&self) -> Result<StealthAddress> {
+        Ok(StealthAddress {
+            scan_key: encode(&(ProjectivePoint::GENERATOR * self.scan_scalar()?)),
+            spend_key: encode(&(ProjectivePoint::GENERATOR * self.spend_scalar()?)),
+        })
+    }
+
+    /// Scan-only key: the scan secret and the public spend key
+    pub fn scan_key(&self) -> Result<StealthScanKey> {
+        Ok(StealthScanKey {
+            scan_secret: self.scan_secret.clone(),
+            spend_key: self.address()?.spend_key,
+        })
+    }
+
+    /// Amount of output `index` of `joinsplit` if it pays these keys
+    ///
+    /// Same as [`StealthScanKey::scan`] with [`scan_key`](Self::scan_key).
+    pub fn scan(
+        &self,
+        scheme: &CommitmentScheme,
+        joinsplit: &JoinSplit,
+        index: usize,
+    ) -> Result<Option<u64>> {
+        self.scan_key()?.scan(scheme, joinsplit, index)
+    }
+
+    /// One-time secret `b + H(s)` of the output published with
+    /// `ephemeral_key`
+    ///
+    /// This is the discrete log of the output's one-time key `P`, so it
+    /// proves ownership of the output. Nothing in a JoinSplit checks it:
+    /// spends are authorized by the wallet's [`SpendKey`] through the
+    /// nullifiers.
+    pub fn one_time_secret(&self, ephemeral_key: &[u8]) -> Result<Vec<u8>> {
+        let shared = self.scan_key()?.shared_secret(ephemeral_key)?;
+        Ok((one_time_tweak(&shared)? + self.spend_scalar()?)
+            .to_repr()
+            .to_vec())
+    }
+}
+
+impl core::fmt::Debug for StealthKeys {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StealthKeys").finish_non_exhaustive()
+    }
+}
+
+/// Scan-only keys of a [`StealthAddress`]: the scan secret `a` and the
+/// public spend key `B`
+///
+/// Finds and opens payments to the address but cannot derive their
+/// one-time secrets.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct StealthScanKey {
+    scan_secret: Vec<u8>,
+    spend_key: Vec<u8>,
+}
+
+impl StealthScanKey {
+    /// Create a scan key from a scan secret and a compressed spend key
+    pub fn new(scan_secret: &[u8], spend_key: &[u8]) -> Result<Self> {
+        if decode_scalar(scan_secret).is_none_or(|scalar| bool::from(scalar.is_zero())) {
+            return Err(LelantusError::invalid_parameter("scan_secret"));
+        }
+        if decode(spend_key).is_none() {
+            return Err(LelantusError::invalid_parameter("spend_key"));
+        }
+
+        Ok(Self {
+            scan_secret: scan_secret.to_vec(),
+            spend_key: spend_key.to_vec(),
+        })
+    }
+
+    /// Scan secret `a`, to hand to a watch-only wallet with the spend key
+    pub fn scan_secret(&self) -> &[u8] {
+        &self.scan_secret
+    }
+
+    fn scan_scalar(&self) -> Result<Scalar> {
+        decode_scalar(&self.scan_secret).ok_or(LelantusError::invalid_parameter("scan_secret"))
+    }
+
+    /// Address this key scans for
+    pub fn address(&self) -> Result<StealthAddress> {
+        StealthAddress::new(
+            &encode(&(ProjectivePoint::GENERATOR * self.scan_scalar()?)),
+            &self.spend_key,
+        )
+    }
+
+    /// Shared secret of the payment published with `ephemeral_key`
+    fn shared_secret(&self, ephemeral_key: &[u8]) -> Result<Vec<u8>> {
+        let ephemeral =
+            decode(ephemeral_key).ok_or(LelantusError::invalid_parameter("ephemeral_key"))?;
+        Ok(shared_secret(&(ephemeral * self.scan_scalar()?)))
+    }
+
+    /// Amount of output `index` of `joinsplit` if it pays this key's address
+    ///
+    /// Recomputes the one-time key `H(s)·G + B` from the public spend key,
+    /// and from it the output's randomness, which is not published.
+    /// Returns `None` for outputs paying anyone else, including outputs
+    /// without a stealth ephemeral key.
+    pub fn scan(
+        &self,
+        scheme: &CommitmentScheme,
+        joinsplit: &JoinSplit,
+        index: usize,
+    ) -> Result<Option<u64>> {
+        let output = joinsplit
+            .outputs
+            .get(index)
+            .ok_or(LelantusError::invalid_parameter("output_index"))?;
+        let Some(ephemeral_key) = joinsplit.ephemeral_keys.get(index) else {
+            return Ok(None);
+        };
+        let spend_key =
+            decode(&self.spend_key).ok_or(LelantusError::invalid_parameter("spend_key"))?;
+
+        let shared = self.shared_secret(ephemeral_key)?;
+        let one_time_key =
+            encode(&(ProjectivePoint::GENERATOR * one_time_tweak(&shared)? + spend_key));
+        let Some(amount) = joinsplit.open_amount_note(index, &output_view_key(&shared)?)? else {
+            return Ok(None);
+        };
+
+        // The randomness follows from the one-time key, so only outputs to
+        // this address open with it
+        let size = scheme.parameters().randomness_bits / 8;
+        let randomness = output_randomness(&shared, &one_time_key, size)?;
+        if scheme.commit_with_randomness(amount, randomness)?.value != output.value {
+            return Ok(None);
+        }
+        Ok(Some(amount))
+    }
+}
+
+impl core::fmt::Debug for StealthScanKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StealthScanKey")
+            .field("spend_key", &hex::encode(&self.spend_key))
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::LelantusParameters;
+
+    fn keys(seed: u8) -> Result<StealthKeys> {
+        StealthKeys::from_spend_key(&SpendKey::from_seed(&[seed; 32])?)
+    }
+
+    #[test]
+    fn test_address_bech32_roundtrip() -> Result<()> {
+        let address = keys(1)?.address()?;
+        let encoded = address.to_bech32()?;
+        assert!(encoded.starts_with("lelantus1"));
+        assert_eq!(StealthAddress::from_bech32(&encoded)?, address);
+        assert_eq!(
+            StealthAddress::from_bech32(&encoded.to_uppercase())?,
+            address
+        );
+
+        let mut corrupted = encoded.into_bytes();
+        let last = corrupted.len() - 1;
+        corrupted[last] = if corrupted[last] == b'q' { b'p' } else { b'q' };
+        let corrupted = String::from_utf8(corrupted)
+            .map_err(|e| LelantusError::SerializationError(e.to_string()))?;
+        assert!(StealthAddress::from_bech32(&corrupted).is_err());
+
+        let foreign = bech32m::encode("other", &address.to_payload())?;
+        assert!(StealthAddress::from_bech32(&foreign).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_payment_request_roundtrip() -> Result<()> {
+        let request = PaymentRequest {
+            address: keys(1)?.address()?,
+            amount: 250_000,
+            memo: b"invoice 42".to_vec(),
+        };
+        let encoded = request.to_bech32()?;
+        assert!(encoded.starts_with("lelantusreq1"));
+        assert_eq!(PaymentRequest::from_bech32(&encoded)?, request);
+
+        // Requests and addresses do not decode as each other
+        assert!(StealthAddress::from_bech32(&encoded).is_err());
+        assert!(PaymentRequest::from_bech32(&request.address.to_bech32()?).is_err());
+
+        let oversized = PaymentRequest {
+            memo: vec![0; MAX_REQUEST_MEMO_SIZE + 1],
+            ..request
+        };
+        assert!(oversized.to_bech32().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_time_keys() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let recipient = keys(1)?;
+        let address = recipient.address()?;
+
+        let first = address.derive_output(&scheme, 500, &mut rand::thread_rng())?;
+        let second = address.derive_output(&scheme, 500, &mut rand::thread_rng())?;
+        assert!(scheme.verify(&first.commitment, 500)?);
+        assert_ne!(first.one_time_key, second.one_time_key);
+        assert_ne!(first.commitment, second.commitment);
+
+        let secret = recipient.one_time_secret(&first.ephemeral_key)?;
+        let scalar = decode_scalar(&secret).ok_or(LelantusError::invalid_parameter("secret"))?;
+        assert_eq!(
+            encode(&(ProjectivePoint::GENERATOR * scalar)),
+            first.one_time_key
+        );
+        assert_ne!(keys(2)?.one_time_secret(&first.ephemeral_key)?, secret);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_key_holds_no_spend_secret() -> Result<()> {
+        let recipient = keys(1)?;
+        let address = recipient.address()?;
+        let scan_key = recipient.scan_key()?;
+        assert_eq!(scan_key.address()?, address);
+
+        // Rebuilt from the scan secret and the public spend key alone
+        let watcher = StealthScanKey::new(scan_key.scan_secret(), address.spend_key())?;
+        assert_eq!(watcher.address()?, address);
+        assert!(StealthScanKey::new(&[0; 66], address.spend_key()).is_err());
+        assert!(StealthScanKey::new(scan_key.scan_secret(), &[2; POINT_SIZE]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! Checked coin amounts
+//!
+//! [`Amount`] is a value in mist that never exceeds [`MAX_MONEY`], the whole
+//! SLVR supply. Arithmetic on amounts fails with `BalanceMismatch` instead of
+//! wrapping, so sums of inputs, outputs and fees stay meaningful without
+//! overflow checks at every call site.
+
+use crate::errors::{LelantusError, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+pub use silver_core::MIST_PER_SLVR;
+
+/// Mist per whole SLVR, the `silver-core` base-unit ratio
+///
+/// `silver-core` needs `std`, so `no_std` builds carry this copy.
+#[cfg(not(feature = "std"))]
+pub const MIST_PER_SLVR: u64 = NO_STD_MIST_PER_SLVR;
+
+/// Copy of core's ratio, checked against it whenever core is linked
+const NO_STD_MIST_PER_SLVR: u64 = 1_000_000_000;
+#[cfg(feature = "std")]
+const _: () = assert!(NO_STD_MIST_PER_SLVR == MIST_PER_SLVR);
+
+/// Total SLVR supply
+const MAX_SUPPLY_SLVR: u64 = 21_000_000;
+
+/// Largest valid amount in mist: the whole SLVR supply
+pub const MAX_MONEY: u64 = MAX_SUPPLY_SLVR.saturating_mul(MIST_PER_SLVR);
+
+/// Coin amount in mist, at most [`MAX_MONEY`]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    /// No value
+    pub const ZERO: Self = Self(0);
+
+    /// The whole supply
+    pub const MAX: Self = Self(MAX_MONEY);
+
+    /// Wrap `mist`, failing with `BalanceMismatch` above [`MAX_MONEY`]
+    pub fn new(mist: u64) -> Result<Self> {
+        if mist > MAX_MONEY {
+            return Err(LelantusError::BalanceMismatch);
+        }
+        Ok(Self(mist))
+    }
+
+    /// Amount of `slvr` whole SLVR, `MIST_PER_SLVR` mist each
+    ///
+    /// Fails with `BalanceMismatch` above [`MAX_MONEY`].
+    pub fn from_slvr(slvr: u64) -> Result<Self> {
+        Self::new(
+            slvr.checked_mul(MIST_PER_SLVR)
+                .ok_or(LelantusError::BalanceMismatch)?,
+        )
+    }
+
+    /// Wrap `mist`, clamping it to [`MAX_MONEY`]
+    pub fn saturating(mist: u64) -> Self {
+        Self(mist.min(MAX_MONEY))
+    }
+
+    /// Value in mist
+    pub fn as_mist(self) -> u64 {
+        self.0
+    }
+
+    /// Whether the amount is zero
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Add two amounts, failing with `BalanceMismatch` above [`MAX_MONEY`]
+    pub fn checked_add(self, other: Self) -> Result<Self> {
+        Self::new(
+            self.0
+                .checked_add(other.0)
+                .ok_or(LelantusError::BalanceMismatch)?,
+        )
+    }
+
+    /// Subtract `other`, failing with `BalanceMismatch` below zero
+    pub fn checked_sub(self, other: Self) -> Result<Self> {
+        self.0
+            .checked_sub(other.0)
+            .map(Self)
+            .ok_or(LelantusError::BalanceMismatch)
+    }
+
+    /// Multiply by `factor`, failing with `BalanceMismatch` above [`MAX_MONEY`]
+    pub fn checked_mul(self, factor: u64) -> Result<Self> {
+        Self::new(
+            self.0
+                .checked_mul(factor)
+                .ok_or(LelantusError::BalanceMismatch)?,
+        )
+    }
+
+    /// Sum `amounts`, failing with `BalanceMismatch` above [`MAX_MONEY`]
+    pub fn sum(amounts: impl IntoIterator<Item = Self>) -> Result<Self> {
+        amounts
+            .into_iter()
+            .try_fold(Self::ZERO, |total, amount| total.checked_add(amount))
+    }
+}
+
+impl TryFrom<u64> for Amount {
+    type Error = LelantusError;
+
+    fn try_from(mist: u64) -> Result<Self> {
+        Self::new(mist)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} mist", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() -> Result<()> {
+        let a = Amount::new(600)?;
+        let b = Amount::new(400)?;
+        assert_eq!(a.checked_add(b)?, Amount::new(1000)?);
+        assert_eq!(a.checked_sub(b)?.as_mist(), 200);
+        assert_eq!(a.checked_mul(3)?.as_mist(), 1800);
+        assert_eq!(Amount::sum([a, b, a])?.as_mist(), 1600);
+
+        assert!(b.checked_sub(a).is_err());
+        assert!(Amount::MAX.checked_add(Amount::new(1)?).is_err());
+        assert!(Amount::MAX.checked_mul(2).is_err());
+        assert!(Amount::new(MAX_MONEY + 1).is_err());
+        assert_eq!(Amount::saturating(u64::MAX), Amount::MAX);
+        assert_eq!(MAX_MONEY, 21_000_000 * MIST_PER_SLVR);
+        assert_eq!(Amount::from_slvr(3)?.as_mist(), 3 * MIST_PER_SLVR);
+        assert_eq!(Amount::from_slvr(21_000_000)?, Amount::MAX);
+        assert!(Amount::from_slvr(21_000_001).is_err());
+        assert!(Amount::from_slvr(u64::MAX).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! Async facade over [`LelantusState`] (feature `async`)
+//!
+//! Proof generation and verification are CPU-bound and block for
+//! milliseconds to seconds. [`AsyncLelantusState`] runs each call on a rayon
+//! worker pool and awaits the result over a channel, so async callers never
+//! block their executor and need no `spawn_blocking` of their own. It works
+//! with any async runtime.
+
+use crate::commitment::Commitment;
+use crate::errors::{LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use crate::witness::Witness;
+use crate::LelantusState;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// Async handle to a shared Lelantus state
+#[derive(Clone, Debug)]
+pub struct AsyncLelantusState {
+    state: LelantusState,
+    pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl AsyncLelantusState {
+    /// Run operations on `state` on the global rayon pool
+    pub fn new(state: LelantusState) -> Self {
+        Self { state, pool: None }
+    }
+
+    /// Run operations on `state` on a dedicated pool of `workers` threads
+    pub fn with_workers(state: LelantusState, workers: usize) -> Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(workers.max(1))
+            .thread_name(|index| format!("lelantus-worker-{index}"))
+            .build()
+            .map_err(|e| LelantusError::WorkerFailed(e.to_string()))?;
+        Ok(Self {
+            state,
+            pool: Some(Arc::new(pool)),
+        })
+    }
+
+    /// The wrapped state, for synchronous calls
+    pub fn state(&self) -> &LelantusState {
+        &self.state
+    }
+
+    /// Run `operation` on the worker pool and await its result
+    async fn offload<T, F>(&self, operation: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&LelantusState) -> Result<T> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let state = self.state.clone();
+        let task = move || {
+            // The receiver is gone only if the caller stopped waiting
+            let _ = sender.send(operation(&state));
+        };
+        match &self.pool {
+            Some(pool) => pool.spawn(task),
+            None => rayon::spawn(task),
+        }
+
+        receiver
+            .await
+            .map_err(|_| LelantusError::WorkerFailed("worker task panicked".to_string()))?
+    }
+
+    /// Add a coin commitment to the accumulator
+    ///
+    /// See [`LelantusState::add_coin`].
+    pub async fn add_coin(&self, commitment: Commitment) -> Result<()> {
+        self.offload(move |state| state.add_coin(&commitment)).await
+    }
+
+    /// Create a JoinSplit transaction
+    ///
+    /// See [`LelantusState::create_joinsplit`].
+    pub async fn create_joinsplit(
+        &self,
+        inputs: Vec<(Commitment, Witness)>,
+        outputs: Vec<u64>,
+        fee: u64,
+    ) -> Result<JoinSplit> {
+        self.offload(move |state| state.create_joinsplit(inputs, outputs, fee))
+            .await
+    }
+
+    /// Verify a JoinSplit transaction
+    ///
+    /// See [`LelantusState::verify_joinsplit`].
+    pub async fn verify_joinsplit(&self, joinsplit: JoinSplit) -> Result<bool> {
+        self.offload(move |state| state.verify_joinsplit(&joinsplit))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::LelantusParameters;
+
+    #[tokio::test]
+    async fn test_async_joinsplit_round_trip() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[1_000])?;
+        let facade = AsyncLelantusState::with_workers(state, 2)?;
+
+        let joinsplit = facade.create_joinsplit(coins, vec![900], 100).await?;
+        assert!(facade.verify_joinsplit(joinsplit.clone()).await?);
+        let before = facade.state().accumulator_snapshot().element_count();
+        for output in joinsplit.outputs {
+            facade.add_coin(output).await?;
+        }
+        assert_eq!(
+            facade.state().accumulator_snapshot().element_count(),
+            before + 1
+        );
+
+        // Errors from the wrapped state come back unchanged
+        let short = AsyncLelantusState::new(facade.state().clone())
+            .create_joinsplit(Vec::new(), vec![900], 100)
+            .await;
+        assert!(matches!(short, Err(LelantusError::InvalidInputCount)));
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! Binding signatures tying a JoinSplit to its enclosing transaction
+//!
+//! A JoinSplit is created before the silver-core transaction around it is
+//! final, so its proofs cannot cover that transaction. Instead each JoinSplit
+//! gets a fresh [`BindingKey`] whose verification key its zero-knowledge
+//! proof commits to. Once the enclosing transaction's digest is known, the
+//! creator signs it together with the JoinSplit. Only the creator holds the
+//! key, and replacing the verification key breaks the zero-knowledge proof,
+//! so a JoinSplit cannot be moved into another transaction.
+
+use crate::errors::{LelantusError, Result};
+use crate::pedersen::{decode, decode_scalar, encode, scalar_from_digest};
+use crate::serialization::{binary, LelantusSerializable};
+use crate::transcript::{ProofDomain, ProofTranscript};
+use borsh::{BorshDeserialize, BorshSerialize};
+use p521::elliptic_curve::{Field, PrimeField};
+use p521::{ProjectivePoint, Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Size of an encoded P-521 scalar
+pub const SCALAR_SIZE: usize = 66;
+
+/// Secret key signing the transaction a JoinSplit belongs to
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct BindingKey {
+    secret: Vec<u8>,
+}
+
+impl BindingKey {
+    /// Draw a fresh key from `rng`
+    pub fn generate(rng: &mut (impl CryptoRng + RngCore)) -> Self {
+        Self {
+            secret: Scalar::random(&mut *rng).to_repr().to_vec(),
+        }
+    }
+
+    fn scalar(&self) -> Result<Scalar> {
+        decode_scalar(&self.secret).ok_or(LelantusError::invalid_parameter("binding_secret"))
+    }
+
+    /// Compressed SEC1 verification key
+    pub fn verification_key(&self) -> Result<Vec<u8>> {
+        Ok(encode(&(ProjectivePoint::GENERATOR * self.scalar()?)))
+    }
+}
+
+impl std::fmt::Debug for BindingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BindingKey(..)")
+    }
+}
+
+/// Schnorr signature over P-521 by a [`BindingKey`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct BindingSignature {
+    /// Compressed nonce commitment `k·G`
+    pub nonce_point: Vec<u8>,
+
+    /// Response scalar `k + e·x`
+    pub response: Vec<u8>,
+}
+
+impl LelantusSerializable for BindingSignature {}
+
+impl BindingSignature {
+    /// Fiat-Shamir challenge over the key, nonce and signed message
+    fn challenge(
+        verification_key: &[u8],
+        nonce_point: &[u8],
+        joinsplit: &[u8],
+        tx_digest: &[u8],
+    ) -> Scalar {
+        let mut transcript = ProofTranscript::new(ProofDomain::BindingSignature);
+        transcript.append_message(b"verification-key", verification_key);
+        transcript.append_message(b"nonce-point", nonce_point);
+        transcript.append_message(b"joinsplit", joinsplit);
+        transcript.append_message(b"tx-digest", tx_digest);
+        scalar_from_digest(&transcript.challenge_bytes(b"challenge"))
+    }
+
+    /// Sign an encoded JoinSplit and the digest of its transaction
+    pub fn sign(
+        key: &BindingKey,
+        joinsplit: &[u8],
+        tx_digest: &[u8],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self> {
+        let secret = key.scalar()?;
+        let nonce = Scalar::random(&mut *rng);
+        let nonce_point = encode(&(ProjectivePoint::GENERATOR * nonce));
+        let challenge =
+            Self::challenge(&key.verification_key()?, &nonce_point, joinsplit, tx_digest);
+
+        Ok(Self {
+            nonce_point,
+            response: (nonce + challenge * secret).to_repr().to_vec(),
+        })
+    }
+
+    /// Verify the signature over an encoded JoinSplit and transaction digest
+    ///
+    /// Malformed keys and signatures verify as false.
+    pub fn verify(&self, verification_key: &[u8], joinsplit: &[u8], tx_digest: &[u8]) -> bool {
+        let (Some(public), Some(nonce_point), Some(response)) = (
+            decode(verification_key),
+            decode(&self.nonce_point),
+            decode_scalar(&self.response),
+        ) else {
+            return false;
+        };
+
+        let challenge = Self::challenge(verification_key, &self.nonce_point, joinsplit, tx_digest);
+        ProjectivePoint::GENERATOR * response == nonce_point + public * challenge
+    }
+
+    /// Encode the signature in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_bytes(&mut buf, &self.nonce_point)?;
+        binary::put_bytes(&mut buf, &self.response)?;
+        Ok(buf)
+    }
+
+    /// Decode the signature from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let nonce_point = reader.read_bytes()?;
+        let response = reader.read_bytes()?;
+        reader.finish()?;
+        Ok(Self {
+            nonce_point,
+            response,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_binding_signature() -> Result<()> {
+        let key = BindingKey::generate(&mut OsRng);
+        let verification_key = key.verification_key()?;
+        let signature = BindingSignature::sign(&key, b"joinsplit", b"tx", &mut OsRng)?;
+
+        assert!(signature.verify(&verification_key, b"joinsplit", b"tx"));
+        assert!(!signature.verify(&verification_key, b"joinsplit", b"other tx"));
+        assert!(!signature.verify(&verification_key, b"other", b"tx"));
+        let other = BindingKey::generate(&mut OsRng).verification_key()?;
+        assert!(!signature.verify(&other, b"joinsplit", b"tx"));
+        assert!(!signature.verify(&[], b"joinsplit", b"tx"));
+
+        let decoded = BindingSignature::consensus_decode(&signature.consensus_encode()?)?;
+        assert_eq!(decoded, signature);
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+//! JoinSplit construction with automatic change
+//!
+//! [`JoinSplitBuilder`] takes the coins to spend, the payments to make and a
+//! fee rate, and works out the fee and the change returned to the sender.
+
+use crate::address::StealthAddress;
+use crate::amount::Amount;
+use crate::commitment::Commitment;
+use crate::errors::{LelantusError, Result};
+use crate::fee::FeeEstimator;
+use crate::joinsplit::JoinSplit;
+use crate::keys::ViewKey;
+use crate::witness::Witness;
+use crate::LelantusState;
+
+/// JoinSplit built by a [`JoinSplitBuilder`]
+#[derive(Debug, Clone)]
+pub struct BuiltJoinSplit {
+    /// The transaction
+    pub joinsplit: JoinSplit,
+
+    /// Change coin returned to the sender, if any
+    ///
+    /// The witness is pending: it records the index the coin takes if the
+    /// JoinSplit's outputs are the next coins added to the accumulator, and
+    /// has no membership proof yet. Once the outputs are added,
+    /// [`LelantusState::upgrade_witness`] completes it.
+    pub change: Option<(Commitment, Witness)>,
+}
+
+/// Builder for a JoinSplit paying fixed amounts and returning change
+#[derive(Debug)]
+pub struct JoinSplitBuilder<'a> {
+    state: &'a LelantusState,
+    inputs: Vec<(Commitment, Witness)>,
+    payments: Vec<u64>,
+    fee_rate: u64,
+    change_view_key: Option<ViewKey>,
+    memos: Vec<(usize, Vec<u8>, ViewKey)>,
+    recipients: Vec<(usize, ViewKey)>,
+    addresses: Vec<(usize, StealthAddress)>,
+}
+
+impl<'a> JoinSplitBuilder<'a> {
+    /// Start a JoinSplit against `state`
+    pub fn new(state: &'a LelantusState) -> Self {
+        Self {
+            state,
+            inputs: Vec::new(),
+            payments: Vec::new(),
+            fee_rate: 0,
+            change_view_key: None,
+            memos: Vec::new(),
+            recipients: Vec::new(),
+            addresses: Vec::new(),
+        }
+    }
+
+    /// Spend a coin
+    pub fn input(mut self, coin: (Commitment, Witness)) -> Self {
+        self.inputs.push(coin);
+        self
+    }
+
+    /// Spend several coins
+    pub fn inputs(mut self, coins: impl IntoIterator<Item = (Commitment, Witness)>) -> Self {
+        self.inputs.extend(coins);
+        self
+    }
+
+    /// Add a payment output of `amount`
+    pub fn pay(mut self, amount: u64) -> Self {
+        self.payments.push(amount);
+        self
+    }
+
+    /// Add a payment output of `amount` the holder of `view_key` can find
+    ///
+    /// The amount is sealed to the recipient's view key; see
+    /// [`JoinSplit::set_amount_note`].
+    pub fn pay_to(mut self, amount: u64, view_key: &ViewKey) -> Self {
+        self.recipients
+            .push((self.payments.len(), view_key.clone()));
+        self.payments.push(amount);
+        self
+    }
+
+    /// Add a payment output of `amount` to a stealth `address`
+    ///
+    /// The output gets a fresh one-time key only the address's owner can
+    /// recognize, with the amount sealed to it; see
+    /// [`StealthKeys::scan`](crate::address::StealthKeys::scan).
+    pub fn pay_to_address(mut self, amount: u64, address: &StealthAddress) -> Self {
+        self.addresses.push((self.payments.len(), address.clone()));
+        self.payments.push(amount);
+        self
+    }
+
+    /// Add a payment output of `amount` carrying `memo` for the recipient
+    ///
+    /// Like [`pay_to`](Self::pay_to), with the memo encrypted to the
+    /// recipient's `view_key`; see [`JoinSplit::set_memo`].
+    pub fn pay_with_memo(mut self, amount: u64, memo: &[u8], view_key: &ViewKey) -> Self {
+        self.memos
+            .push((self.payments.len(), memo.to_vec(), view_key.clone()));
+        self.pay_to(amount, view_key)
+    }
+
+    /// Set the fee per byte of estimated transaction size
+    pub fn fee_rate(mut self, per_byte: u64) -> Self {
+        self.fee_rate = per_byte;
+        self
+    }
+
+    /// Flag the change output for the holder of `view_key`
+    pub fn change_view_key(mut self, view_key: &ViewKey) -> Self {
+        self.change_view_key = Some(view_key.clone());
+        self
+    }
+
+    /// Fee for a JoinSplit with `outputs` outputs at the configured rate
+    fn fee_for(&self, outputs: usize) -> Result<Amount> {
+        self.fee_estimator().fee(self.inputs.len(), outputs)
+    }
+
+    /// Estimator pricing the memos, amount notes and ephemeral keys this
+    /// JoinSplit carries
+    fn fee_estimator(&self) -> FeeEstimator {
+        FeeEstimator::with_parameters(self.fee_rate, &self.state.parameters)
+            .with_memos(!self.memos.is_empty())
+            .with_amount_notes(!self.recipients.is_empty() || !self.addresses.is_empty())
+            .with_ephemeral_keys(!self.addresses.is_empty())
+    }
+
+    /// Build the JoinSplit
+    ///
+    /// Adds a change output for whatever the payments and fee leave over.
+    /// Change below the minimum coin value, or with no output slot left, is
+    /// added to the fee instead. Fails with `BalanceMismatch` if the inputs
+    /// do not cover the payments and the fee.
+    pub fn build(self) -> Result<BuiltJoinSplit> {
+        if self.payments.is_empty() {
+            return Err(LelantusError::InvalidOutputCount);
+        }
+
+        let input_sum = self.state.verified_input_sum(&self.inputs, None)?;
+        let payment_sum = LelantusState::output_sum(&self.payments)?;
+        let available = input_sum.checked_sub(payment_sum)?;
+
+        let change = if self.payments.len() < self.state.parameters.max_joinsplit_outputs() {
+            let fee = self.fee_for(self.payments.len() + 1)?;
+            available
+                .checked_sub(fee)
+                .ok()
+                .filter(|change| change.as_mist() >= self.state.parameters.min_coin_value.max(1))
+                .map(|change| (change.as_mist(), fee))
+        } else {
+            None
+        };
+
+        let mut outputs = self.payments.clone();
+        let fee = match change {
+            Some((amount, fee)) => {
+                outputs.push(amount);
+                fee
+            }
+            None if available >= self.fee_for(self.payments.len())? => available,
+            None => return Err(LelantusError::BalanceMismatch),
+        };
+
+        // Index the first output takes if the outputs are the next coins
+        let (next_index, accumulator_value) = {
+            let accumulator = self.state.accumulator.read();
+            (accumulator.element_count(), accumulator.value().to_vec())
+        };
+
+        let stealth = self
+            .addresses
+            .iter()
+            .map(|(output, address)| {
+                let derived = self
+                    .state
+                    .derive_output_for(address, self.payments[*output])?;
+                Ok((*output, derived))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let preset = stealth
+            .iter()
+            .map(|(output, derived)| (*output, derived.commitment.clone()))
+            .collect::<Vec<_>>();
+
+        let mut joinsplit = self.state.create_joinsplit_with_preset_outputs(
+            self.inputs,
+            outputs,
+            &preset,
+            fee.as_mist(),
+        )?;
+        for (output, memo, view_key) in &self.memos {
+            joinsplit.set_memo(*output, memo, view_key)?;
+        }
+        for (output, view_key) in &self.recipients {
+            joinsplit.set_amount_note(*output, self.payments[*output], view_key)?;
+        }
+        for (output, derived) in &stealth {
+            joinsplit.set_ephemeral_key(*output, &derived.ephemeral_key)?;
+            joinsplit.set_amount_note(*output, self.payments[*output], &derived.view_key)?;
+        }
+
+        let change = match change {
+            Some((amount, _)) => {
+                let output = self.payments.len();
+                if let Some(view_key) = &self.change_view_key {
+                    joinsplit.mark_change_output(output, view_key)?;
+                }
+
+                let commitment = joinsplit.outputs[output].clone();
+                let index = next_index + output;
+                let encrypted = Witness::encrypt_amount(&commitment, index, amount)?;
+                let witness = Witness::new(
+                    commitment.clone(),
+                    index,
+                    accumulator_value,
+                    Vec::new(),
+                    encrypted,
+                );
+                Some((commitment, witness))
+            }
+            None => None,
+        };
+
+        Ok(BuiltJoinSplit { joinsplit, change })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::StealthKeys;
+    use crate::keys::SpendKey;
+    use crate::parameters::{LelantusParameters, PrivacyLevel};
+
+    #[test]
+    fn test_builder_returns_change() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[400_000, 300_000])?;
+        let view_key = ViewKey::generate();
+        let recipient = ViewKey::generate();
+
+        let built = JoinSplitBuilder::new(&state)
+            .inputs(coins)
+            .pay_with_memo(250_000, b"invoice 42", &recipient)
+            .fee_rate(2)
+            .change_view_key(&view_key)
+            .build()?;
+        let joinsplit = &built.joinsplit;
+        assert!(state.verify_joinsplit(joinsplit)?);
+        assert_eq!(joinsplit.outputs.len(), 2);
+        assert!(joinsplit.is_change_output(1, &view_key)?);
+        assert_eq!(
+            joinsplit.decrypt_memo(0, &recipient)?,
+            Some(b"invoice 42".to_vec())
+        );
+        assert_eq!(joinsplit.decrypt_memo(1, &recipient)?, None);
+        assert_eq!(joinsplit.open_amount_note(0, &recipient)?, Some(250_000));
+
+        let (commitment, mut witness) = built.change.ok_or(LelantusError::WitnessNotFound)?;
+        let change = witness.get_amount()?;
+        assert_eq!(700_000, 250_000 + change + joinsplit.fee);
+        let mut signed = joinsplit.clone();
+        signed.sign_binding(b"tx")?;
+        assert_eq!(joinsplit.fee, 2 * signed.consensus_encode()?.len() as u64);
+
+        // Once the outputs land, the change coin can be spent
+        for output in &joinsplit.outputs {
+            state.add_coin(output)?;
+        }
+        state.upgrade_witness(&mut witness)?;
+        let spend = state.create_joinsplit(vec![(commitment, witness)], vec![change - 100], 100)?;
+        assert!(state.verify_joinsplit(&spend)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_pays_stealth_addresses() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[400_000])?;
+        let recipient = StealthKeys::from_spend_key(&SpendKey::generate())?;
+        let address = recipient.address()?;
+
+        let built = JoinSplitBuilder::new(&state)
+            .inputs(coins)
+            .pay_to_address(150_000, &address)
+            .pay_to_address(50_000, &address)
+            .fee_rate(1)
+            .build()?;
+        let joinsplit = &built.joinsplit;
+        assert!(state.verify_joinsplit(joinsplit)?);
+        assert_eq!(joinsplit.ephemeral_keys.len(), 3);
+
+        let scheme = state.commitment_scheme();
+        assert_eq!(recipient.scan(&scheme, joinsplit, 0)?, Some(150_000));
+        assert_eq!(recipient.scan(&scheme, joinsplit, 1)?, Some(50_000));
+        assert_eq!(recipient.scan(&scheme, joinsplit, 2)?, None);
+        let stranger = StealthKeys::from_spend_key(&SpendKey::generate())?;
+        assert_eq!(stranger.scan(&scheme, joinsplit, 0)?, None);
+
+        // A watch-only wallet finds the payments without the spend secret
+        let watcher = recipient.scan_key()?;
+        assert_eq!(watcher.scan(&scheme, joinsplit, 0)?, Some(150_000));
+        assert_eq!(watcher.scan(&scheme, joinsplit, 2)?, None);
+
+        let decoded = JoinSplit::consensus_decode(&joinsplit.consensus_encode()?)?;
+        assert_eq!(recipient.scan(&scheme, &decoded, 0)?, Some(150_000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_folds_dust_into_fee() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[100_000])?;
+        let fee = JoinSplit::estimated_size(1, 1, PrivacyLevel::Standard)? as u64;
+
+        // Leftover too small to pay for a change output goes to the fee
+        let built = JoinSplitBuilder::new(&state)
+            .inputs(coins.clone())
+            .pay(100_000 - fee - 5)
+            .fee_rate(1)
+            .build()?;
+        assert!(built.change.is_none());
+        assert_eq!(built.joinsplit.fee, fee + 5);
+
+        let short = JoinSplitBuilder::new(&state)
+            .inputs(coins)
+            .pay(100_000 - fee + 1)
+            .fee_rate(1)
+            .build();
+        assert!(matches!(short, Err(LelantusError::BalanceMismatch)));
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+//! State checkpoints for fast initial sync
+//!
+//! A checkpoint lets a new node start at a given height instead of replaying
+//! the whole coin history.
+//!
+//! Trust assumption: the accumulator head and element count cannot be
+//! recomputed without that history, so importing a checkpoint trusts them.
+//! The included Merkle peaks are checked against the head, which lets the
+//! imported accumulator keep growing.
+//! A checkpoint must come from a trusted source or be matched against the
+//! head committed by consensus at `height`. The nullifier set is checked
+//! against the included root, and the frontier only carries recent coins, so
+//! membership proofs for earlier coins are unavailable after import.
+
+use crate::commitment::AccumulatorElement;
+use crate::nullifier::nullifier_merkle_root;
+use crate::serial::SerialNumber;
+use serde::{Deserialize, Serialize};
+
+/// Snapshot of the Lelantus state at a block height
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateCheckpoint {
+    /// Block height of the checkpoint
+    pub height: u64,
+
+    /// Accumulator head at the checkpoint
+    pub accumulator_value: Vec<u8>,
+
+    /// Total number of accumulated coins
+    pub element_count: usize,
+
+    /// Most recent accumulator elements
+    pub frontier: Vec<AccumulatorElement>,
+
+    /// Merkle peaks of the accumulator tree
+    #[serde(default)]
+    pub peaks: Vec<[u8; 32]>,
+
+    /// Spent nullifiers in sorted order
+    pub nullifiers: Vec<SerialNumber>,
+
+    /// Merkle root over the spent nullifiers
+    pub nullifier_root: [u8; 32],
+}
+
+impl StateCheckpoint {
+    /// Check that the nullifier list matches the root and is strictly sorted
+    pub fn verify_nullifier_root(&self) -> bool {
+        self.nullifiers.windows(2).all(|pair| pair[0] < pair[1])
+            && nullifier_merkle_root(self.nullifiers.iter()) == self.nullifier_root
+    }
+}
@@ -0,0 +1,242 @@
+//! Coin selection for JoinSplit inputs
+//!
+//! A [`CoinSelection`] strategy picks which coins to spend so that their
+//! amounts cover a payment plus its fee. Strategies work on coin amounts
+//! only; [`select_coins`] opens the amounts of wallet coins and returns the
+//! chosen `(Commitment, Witness)` pairs ready for
+//! [`LelantusState::create_joinsplit`](crate::LelantusState::create_joinsplit).
+
+use crate::amount::Amount;
+use crate::commitment::Commitment;
+use crate::errors::{LelantusError, Result};
+use crate::witness::Witness;
+use crate::MAX_JOINSPLIT_INPUTS;
+use rand::seq::SliceRandom;
+
+/// Strategy choosing coins to cover a target amount
+pub trait CoinSelection {
+    /// Pick coins whose amounts sum to at least `target`
+    ///
+    /// Returns positions into `amounts`, choosing at most
+    /// [`MAX_JOINSPLIT_INPUTS`] coins. Fails with `BalanceMismatch` if no
+    /// acceptable selection exists.
+    fn select(&self, amounts: &[u64], target: u64) -> Result<Vec<usize>>;
+}
+
+/// Spend the largest coins first
+///
+/// Uses as few inputs as possible, at the cost of usually producing change.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LargestFirst;
+
+impl CoinSelection for LargestFirst {
+    fn select(&self, amounts: &[u64], target: u64) -> Result<Vec<usize>> {
+        let mut order: Vec<usize> = (0..amounts.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(amounts[index]));
+        take_until_covered(amounts, &order, target)
+    }
+}
+
+/// Search for a selection matching the target without change
+///
+/// Depth-first search over coins sorted largest first, pruning branches that
+/// overshoot `target + tolerance` or can no longer reach `target`. Set
+/// `tolerance` to what a change output would cost, so that an overshoot is
+/// only accepted where it is cheaper to give it up as fee.
+#[derive(Debug, Clone, Copy)]
+pub struct BranchAndBound {
+    /// Largest overshoot of the target that is accepted
+    pub tolerance: u64,
+
+    /// Number of search steps before giving up
+    pub max_tries: usize,
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        Self {
+            tolerance: 0,
+            max_tries: 100_000,
+        }
+    }
+}
+
+/// Depth-first search state for [`BranchAndBound`]
+struct Search<'a> {
+    sorted: &'a [(usize, u64)],
+    remaining: Vec<u128>,
+    target: u128,
+    limit: u128,
+    tries_left: usize,
+    selected: Vec<usize>,
+}
+
+impl Search<'_> {
+    /// Extend the selection from `depth` on, returning true on a match
+    fn run(&mut self, depth: usize, sum: u128) -> bool {
+        if sum >= self.target {
+            return sum <= self.limit;
+        }
+        if depth == self.sorted.len()
+            || self.selected.len() == MAX_JOINSPLIT_INPUTS
+            || sum + self.remaining[depth] < self.target
+            || self.tries_left == 0
+        {
+            return false;
+        }
+        self.tries_left -= 1;
+
+        // Include the coin first, so larger coins are preferred
+        let (index, amount) = self.sorted[depth];
+        self.selected.push(index);
+        if self.run(depth + 1, sum + u128::from(amount)) {
+            return true;
+        }
+        self.selected.pop();
+
+        self.run(depth + 1, sum)
+    }
+}
+
+impl CoinSelection for BranchAndBound {
+    fn select(&self, amounts: &[u64], target: u64) -> Result<Vec<usize>> {
+        let mut sorted: Vec<(usize, u64)> = amounts.iter().copied().enumerate().collect();
+        sorted.sort_by_key(|&(_, amount)| std::cmp::Reverse(amount));
+
+        // Suffix sums bound what the undecided coins can still add
+        let mut remaining = vec![0u128; sorted.len() + 1];
+        for depth in (0..sorted.len()).rev() {
+            remaining[depth] = remaining[depth + 1] + u128::from(sorted[depth].1);
+        }
+
+        let mut search = Search {
+            sorted: &sorted,
+            remaining,
+            target: u128::from(target),
+            limit: u128::from(target) + u128::from(self.tolerance),
+            tries_left: self.max_tries,
+            selected: Vec::new(),
+        };
+        if search.run(0, 0) {
+            Ok(search.selected)
+        } else {
+            Err(LelantusError::BalanceMismatch)
+        }
+    }
+}
+
+/// Spend coins in random order
+///
+/// Selections do not follow the amounts held, so repeated spends reveal
+/// less about the wallet, at the cost of more inputs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomSelection;
+
+impl CoinSelection for RandomSelection {
+    fn select(&self, amounts: &[u64], target: u64) -> Result<Vec<usize>> {
+        let mut order: Vec<usize> = (0..amounts.len()).collect();
+        order.shuffle(&mut rand::thread_rng());
+        take_until_covered(amounts, &order, target)
+    }
+}
+
+/// Take coins in `order` until they cover `target`
+fn take_until_covered(amounts: &[u64], order: &[usize], target: u64) -> Result<Vec<usize>> {
+    let mut selected = Vec::new();
+    let mut sum: u128 = 0;
+    for &index in order.iter().take(MAX_JOINSPLIT_INPUTS) {
+        if sum >= u128::from(target) && !selected.is_empty() {
+            break;
+        }
+        selected.push(index);
+        sum += u128::from(amounts[index]);
+    }
+
+    if sum < u128::from(target) || selected.is_empty() {
+        return Err(LelantusError::BalanceMismatch);
+    }
+    Ok(selected)
+}
+
+/// Select wallet coins covering `target` plus `fee` with `strategy`
+///
+/// Amounts are opened with each coin's own key. Returns the chosen coins in
+/// the order the strategy picked them.
+pub fn select_coins(
+    strategy: &dyn CoinSelection,
+    coins: &[(Commitment, Witness)],
+    target: Amount,
+    fee: Amount,
+) -> Result<Vec<(Commitment, Witness)>> {
+    let total = target.checked_add(fee)?.as_mist();
+    let amounts = coins
+        .iter()
+        .map(|(_, witness)| witness.get_amount())
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(strategy
+        .select(&amounts, total)?
+        .into_iter()
+        .map(|index| coins[index].clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::LelantusParameters;
+    use crate::LelantusState;
+
+    fn sum(amounts: &[u64], selected: &[usize]) -> u64 {
+        selected.iter().map(|&index| amounts[index]).sum()
+    }
+
+    #[test]
+    fn test_strategies_cover_target() -> Result<()> {
+        let amounts = [500, 100, 2000, 300, 800];
+
+        let largest = LargestFirst.select(&amounts, 2500)?;
+        assert_eq!(largest, vec![2, 4]);
+
+        let exact = BranchAndBound::default().select(&amounts, 1200)?;
+        assert_eq!(sum(&amounts, &exact), 1200);
+        assert!(BranchAndBound::default().select(&amounts, 1250).is_err());
+        let within = BranchAndBound {
+            tolerance: 60,
+            ..Default::default()
+        }
+        .select(&amounts, 1250)?;
+        assert_eq!(sum(&amounts, &within), 1300);
+
+        let random = RandomSelection.select(&amounts, 3000)?;
+        assert!(sum(&amounts, &random) >= 3000);
+
+        for strategy in [
+            &LargestFirst as &dyn CoinSelection,
+            &BranchAndBound::default(),
+            &RandomSelection,
+        ] {
+            assert!(matches!(
+                strategy.select(&amounts, 4000),
+                Err(LelantusError::BalanceMismatch)
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_coins_feeds_joinsplit() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[700, 400, 1500, 250])?;
+
+        let inputs = select_coins(
+            &BranchAndBound::default(),
+            &coins,
+            Amount::new(1000)?,
+            Amount::new(100)?,
+        )?;
+        let joinsplit = state.create_joinsplit(inputs, vec![1000], 100)?;
+        assert!(state.verify_joinsplit(&joinsplit)?);
+        Ok(())
+    }
+}
@@ -1,24 +1,57 @@
 //! Commitment scheme for Lelantus
 
 use crate::errors::{LelantusError, Result};
-use crate::parameters::LelantusParameters;
+#[cfg(feature = "std")]
+use crate::keys::FullViewingKey;
+use crate::parameters::{HashAlgo, LelantusParameters};
+use crate::pedersen::{self, DifferenceProof, Generators, OpeningProof, SerialProof};
+use crate::prelude::*;
+use crate::serialization::{binary, LelantusSerializable};
+use alloc::collections::BTreeMap;
+use borsh::{BorshDeserialize, BorshSerialize};
 use hex;
-use rand::Rng;
+use hmac::{Hmac, Mac};
+use p521::ProjectivePoint;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
-use serde_json;
 use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 /// Pedersen commitment
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+///
+/// The randomness opens the commitment, so it is wiped when dropped.
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    Zeroize,
+    ZeroizeOnDrop,
+)]
 pub struct Commitment {
-    /// Commitment value (hash)
+    /// Published commitment, hex-encoded: a compressed P-521 point under
+    /// the `pedersen` scheme, a digest under the hash schemes
     pub value: Vec<u8>,
 
     /// Randomness used in commitment
     pub randomness: Vec<u8>,
 }
 
+impl LelantusSerializable for Commitment {}
+
 impl Commitment {
+    /// Commitment as published on chain: its value without the opening
+    pub fn from_value(value: Vec<u8>) -> Self {
+        Self {
+            value,
+            randomness: Vec::new(),
+        }
+    }
+
     /// Convert commitment to accumulator element
     pub fn to_element(&self) -> Result<AccumulatorElement> {
         Ok(AccumulatorElement {
@@ -28,27 +61,111 @@ impl Commitment {
 
     /// Serialize commitment to bytes
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec(self).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        LelantusSerializable::to_bytes(self)
     }
 
     /// Deserialize commitment from bytes
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        serde_json::from_slice(data).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        <Self as LelantusSerializable>::from_bytes(data)
+    }
+
+    /// Encode commitment in the canonical binary format used for consensus
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_bytes(&mut buf, &self.value)?;
+        binary::put_bytes(&mut buf, &self.randomness)?;
+        Ok(buf)
+    }
+
+    /// Decode commitment from its consensus encoding
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let value = reader.read_bytes()?;
+        let randomness = reader.read_bytes()?;
+        reader.finish()?;
+        Ok(Self { value, randomness })
     }
 }
 
 /// Accumulator element
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
 pub struct AccumulatorElement {
     /// Element value
     pub value: Vec<u8>,
 }
 
+impl LelantusSerializable for AccumulatorElement {}
+
+impl AccumulatorElement {
+    /// Encode element in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_bytes(&mut buf, &self.value)?;
+        Ok(buf)
+    }
+
+    /// Decode element from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let value = reader.read_bytes()?;
+        reader.finish()?;
+        Ok(Self { value })
+    }
+}
+
+/// Maximum size of a payload bound to a commitment
+pub const MAX_COMMITMENT_PAYLOAD_SIZE: usize = 1024;
+
+/// Domain separator for payload hashes
+const PAYLOAD_DOMAIN: &[u8] = b"lelantus_commitment_payload";
+
+/// Domain separator for deterministic commitment randomness
+const DETERMINISTIC_DOMAIN: &[u8] = b"lelantus_deterministic_randomness";
+
+/// Domain separator deriving per-asset generators
+const ASSET_GENERATOR_DOMAIN: &[u8] = b"lelantus_asset_generator";
+
+/// Domain separator blinding an asset generator with coin randomness
+const ASSET_BLINDING_DOMAIN: &[u8] = b"lelantus_asset_blinding";
+
+/// Domain separator for coin serial numbers
+const SERIAL_DOMAIN: &[u8] = b"lelantus_nullifier";
+
+/// Identifier of an asset carried by a coin
+pub type AssetId = [u8; 32];
+
+/// The native SLVR asset, committed with the base generator
+pub const NATIVE_ASSET: AssetId = [0; 32];
+
+/// Serial number committed by coin `randomness`
+///
+/// Revealed as the coin's nullifier when it is spent.
+pub(crate) fn serial_digest(randomness: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(SERIAL_DOMAIN);
+    hasher.update(randomness);
+    hasher.finalize().to_vec()
+}
+
+/// Hash a commitment payload, rejecting oversized payloads
+fn payload_hash(payload: &[u8]) -> Result<Vec<u8>> {
+    if payload.len() > MAX_COMMITMENT_PAYLOAD_SIZE {
+        return Err(LelantusError::InvalidCommitment);
+    }
+
+    let mut hasher = Sha512::new();
+    hasher.update(PAYLOAD_DOMAIN);
+    hasher.update(payload);
+    Ok(hasher.finalize().to_vec())
+}
+
 /// Commitment scheme
 #[derive(Debug)]
 pub struct CommitmentScheme {
     parameters: LelantusParameters,
     generator: Vec<u8>,
+    hash_algo: HashAlgo,
+    pedersen: Option<Generators>,
 }
 
 impl CommitmentScheme {
@@ -56,14 +173,31 @@ impl CommitmentScheme {
     pub fn new(parameters: &LelantusParameters) -> Result<Self> {
         parameters.validate()?;
 
+        let hash_algo = parameters.hash_algo()?;
         Ok(Self {
             parameters: parameters.clone(),
             generator: parameters.generator.clone(),
+            pedersen: (hash_algo == HashAlgo::Pedersen)
+                .then(|| Generators::new(&parameters.generator)),
+            hash_algo,
         })
     }
 
     /// Create a commitment to a value
+    #[cfg(feature = "std")]
     pub fn commit(&self, value: u64) -> Result<Commitment> {
+        self.commit_with_rng(value, &mut rand::thread_rng())
+    }
+
+    /// Create a commitment to a value with randomness drawn from `rng`
+    ///
+    /// A fixed-seed RNG gives reproducible commitments for tests and
+    /// auditable builds.
+    pub fn commit_with_rng(
+        &self,
+        value: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Commitment> {
         if value > self.parameters.max_coin_value {
             return Err(LelantusError::InvalidCommitment);
         }
@@ -72,26 +206,186 @@ impl CommitmentScheme {
             return Err(LelantusError::InvalidCommitment);
         }
 
-        // Generate random randomness
-        let mut rng = rand::thread_rng();
-        let randomness: Vec<u8> = (0..self.parameters.randomness_bits / 8)
-            .map(|_| rng.gen())
-            .collect();
+        let randomness = self.random_randomness(rng);
+        Ok(Commitment {
+            value: self.hash(value, &randomness, None)?,
+            randomness,
+        })
+    }
+
+    /// Create a commitment to a value bound to an arbitrary payload
+    ///
+    /// The payload hash is folded into the commitment, so it opens only
+    /// together with the exact payload. Payloads are capped at
+    /// [`MAX_COMMITMENT_PAYLOAD_SIZE`] bytes.
+    #[cfg(feature = "std")]
+    pub fn commit_with_payload(&self, value: u64, payload: &[u8]) -> Result<Commitment> {
+        let payload_hash = payload_hash(payload)?;
+        if value > self.parameters.max_coin_value || value < self.parameters.min_coin_value {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let randomness = self.random_randomness(&mut rand::thread_rng());
+        Ok(Commitment {
+            value: self.hash(value, &randomness, Some(&payload_hash))?,
+            randomness,
+        })
+    }
+
+    /// Generator of `asset`, derived from the base generator
+    ///
+    /// The native asset uses the base generator itself, so native asset
+    /// commitments are ordinary commitments.
+    pub fn asset_generator(&self, asset: &AssetId) -> Vec<u8> {
+        if *asset == NATIVE_ASSET {
+            return self.generator.clone();
+        }
 
-        // Compute commitment: H(generator || value || randomness)
         let mut hasher = Sha512::new();
+        hasher.update(ASSET_GENERATOR_DOMAIN);
         hasher.update(&self.generator);
-        hasher.update(value.to_le_bytes());
-        hasher.update(&randomness);
+        hasher.update(asset);
+        hasher.finalize().to_vec()
+    }
+
+    /// Generator of `asset` blinded with the coin's `randomness`, for hash
+    /// commitments
+    ///
+    /// Commitments to different assets then share one distribution, so the
+    /// asset of a coin in the anonymity set cannot be told without opening
+    /// it.
+    fn blinded_asset_generator(&self, asset: &AssetId, randomness: &[u8]) -> Vec<u8> {
+        if *asset == NATIVE_ASSET {
+            return self.generator.clone();
+        }
+
+        let mut hasher = Sha512::new();
+        hasher.update(ASSET_BLINDING_DOMAIN);
+        hasher.update(self.asset_generator(asset));
+        hasher.update(randomness);
+        hasher.finalize().to_vec()
+    }
+
+    /// Blinded asset tag of a coin of `asset` committed with `randomness`
+    ///
+    /// The tag `A + τ·H` adds a per-coin blinding to the asset's value
+    /// generator `A`, and the coin commits to its value under the tag as
+    /// well as under `A`. Tags of one asset look unrelated, so JoinSplits
+    /// publish them in place of asset ids, with an
+    /// [`AssetSurjectionProof`](crate::AssetSurjectionProof) showing each
+    /// belongs to some asset of the transaction. Only available for the
+    /// `pedersen` scheme.
+    pub fn asset_tag(&self, asset: &AssetId, randomness: &[u8]) -> Result<Vec<u8>> {
+        let tag = self
+            .pedersen_generators()?
+            .asset_tag(&self.asset_value_generator(asset), randomness);
+        Ok(pedersen::encode(&tag))
+    }
+
+    /// Create a commitment to `value` of `asset` with randomness from `rng`
+    pub fn commit_asset_with_rng(
+        &self,
+        asset: &AssetId,
+        value: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Commitment> {
+        if value > self.parameters.max_coin_value || value < self.parameters.min_coin_value {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let randomness = self.random_randomness(rng);
+        self.commit_asset_with_randomness(asset, value, randomness)
+    }
+
+    /// Create a commitment to `value` of `asset` with specific randomness
+    pub fn commit_asset_with_randomness(
+        &self,
+        asset: &AssetId,
+        value: u64,
+        randomness: Vec<u8>,
+    ) -> Result<Commitment> {
+        if value > self.parameters.max_coin_value {
+            return Err(LelantusError::InvalidCommitment);
+        }
 
-        let commitment_value = hex::encode(hasher.finalize()).into_bytes();
+        if randomness.len() != self.parameters.randomness_bits / 8 {
+            return Err(LelantusError::InvalidCommitment);
+        }
 
+        // Under the `pedersen` scheme the blinding term already hides the
+        // asset, and the coin's blinded generator is its asset tag
+        let generator = match self.pedersen {
+            Some(_) => self.asset_generator(asset),
+            None => self.blinded_asset_generator(asset, &randomness),
+        };
         Ok(Commitment {
-            value: commitment_value,
+            value: self.hash_with_generator(&generator, value, &randomness, None)?,
             randomness,
         })
     }
 
+    /// Create the commitment published in place of a spent coin of `asset`
+    ///
+    /// Under the `pedersen` scheme it commits to `value` alone, with no
+    /// serial: the spend reveals the coin's serial instead, and proves the
+    /// coin minus [`spend_offset`](Self::spend_offset) opens to zero. Other
+    /// schemes give an ordinary commitment.
+    pub fn commit_input_with_rng(
+        &self,
+        asset: &AssetId,
+        value: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Commitment> {
+        let Some(generators) = &self.pedersen else {
+            return self.commit_asset_with_rng(asset, value, rng);
+        };
+        if value > self.parameters.max_coin_value || value < self.parameters.min_coin_value {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let randomness = self.random_randomness(rng);
+        let value_generator = self.asset_value_generator(asset);
+        Ok(Commitment {
+            value: generators.commit_input_to(&value_generator, value, &randomness),
+            randomness,
+        })
+    }
+
+    /// Offset a spent coin is shown to be a member against: `input +
+    /// serial·G₁`
+    ///
+    /// `input` is the commitment published in place of the coin and `serial`
+    /// the nullifier revealed for it, so a coin minus the offset opens to
+    /// zero only if it commits to that serial. Keeps the randomness of
+    /// `input`. Only available for the `pedersen` scheme.
+    pub fn spend_offset(&self, input: &Commitment, serial: &[u8]) -> Result<Commitment> {
+        Ok(Commitment {
+            value: self
+                .pedersen_generators()?
+                .spend_offset(&input.value, serial)?,
+            randomness: input.randomness.clone(),
+        })
+    }
+
+    /// Open a commitment to `value` of `asset`
+    pub fn verify_asset(
+        &self,
+        commitment: &Commitment,
+        asset: &AssetId,
+        value: u64,
+    ) -> Result<bool> {
+        let recomputed =
+            self.commit_asset_with_randomness(asset, value, commitment.randomness.clone())?;
+        Ok(recomputed.value == commitment.value)
+    }
+
+    /// Generate fresh commitment randomness
+    fn random_randomness(&self, rng: &mut (impl CryptoRng + RngCore)) -> Vec<u8> {
+        let mut randomness = vec![0u8; self.parameters.randomness_bits / 8];
+        rng.fill_bytes(&mut randomness);
+        randomness
+    }
+
     /// Create a commitment with specific randomness (for testing/verification)
     pub fn commit_with_randomness(&self, value: u64, randomness: Vec<u8>) -> Result<Commitment> {
         if value > self.parameters.max_coin_value {
@@ -102,26 +396,371 @@ impl CommitmentScheme {
             return Err(LelantusError::InvalidCommitment);
         }
 
-        // Compute commitment: H(generator || value || randomness)
-        let mut hasher = Sha512::new();
-        hasher.update(&self.generator);
-        hasher.update(value.to_le_bytes());
-        hasher.update(&randomness);
-
-        let commitment_value = hex::encode(hasher.finalize()).into_bytes();
+        Ok(Commitment {
+            value: self.hash(value, &randomness, None)?,
+            randomness,
+        })
+    }
 
+    /// Create a commitment to `value` owned by `owner`
+    ///
+    /// The randomness carries the owner's tag, and the coin is committed
+    /// under the owner's keyed opening of it; see [`FullViewingKey::open`].
+    #[cfg(feature = "std")]
+    pub fn commit_to(&self, owner: &FullViewingKey, value: u64) -> Result<Commitment> {
+        let randomness = owner.coin_randomness(self.parameters.randomness_bits / 8)?;
+        let opened = self.commit_with_randomness(value, owner.coin_opening(&randomness)?)?;
         Ok(Commitment {
-            value: commitment_value,
+            value: opened.value.clone(),
             randomness,
         })
     }
 
+    /// Create the commitment at derivation `index` of a wallet seed
+    ///
+    /// The randomness is HMAC-SHA512 of the index keyed by `seed`, so the
+    /// same seed, index and value always give the same commitment.
+    pub fn commit_deterministic(&self, seed: &[u8], index: u64, value: u64) -> Result<Commitment> {
+        if seed.is_empty() {
+            return Err(LelantusError::invalid_parameter("seed"));
+        }
+
+        let size = self.parameters.randomness_bits / 8;
+        let mut randomness = Vec::with_capacity(size);
+        let mut block = 0u64;
+        while randomness.len() < size {
+            let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(seed)
+                .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+            mac.update(DETERMINISTIC_DOMAIN);
+            mac.update(&index.to_le_bytes());
+            mac.update(&block.to_le_bytes());
+            randomness.extend_from_slice(&mac.finalize().into_bytes());
+            block += 1;
+        }
+        randomness.truncate(size);
+
+        self.commit_with_randomness(value, randomness)
+    }
+
+    /// Derive the commitments at indices `start..start + count` of a seed
+    ///
+    /// Lets a wallet batch-generate candidates for gap-limit scanning of mints
+    /// of a known `value` against the accumulator.
+    pub fn derive_range(
+        &self,
+        seed: &[u8],
+        start: u64,
+        count: u64,
+        value: u64,
+    ) -> Result<Vec<Commitment>> {
+        let end = start
+            .checked_add(count)
+            .ok_or(LelantusError::invalid_parameter("count"))?;
+        (start..end)
+            .map(|index| self.commit_deterministic(seed, index, value))
+            .collect()
+    }
+
+    /// Check that a commitment has the shape this scheme produces
+    ///
+    /// Does not open the commitment; it only rejects values of the wrong size
+    /// or encoding and randomness of the wrong length.
+    pub fn check_well_formed(&self, commitment: &Commitment) -> Result<()> {
+        if commitment.value.len() != self.hash_algo.encoded_len()
+            || !commitment.value.iter().all(|b| b.is_ascii_hexdigit())
+            || commitment.randomness.len() != self.parameters.randomness_bits / 8
+        {
+            return Err(LelantusError::InvalidCommitment);
+        }
+        Ok(())
+    }
+
+    /// Compute commitment value: H(generator || value || randomness [|| payload hash])
+    ///
+    /// The value is encoded in the parameters' `value_byte_width` bytes.
+    fn hash(&self, value: u64, randomness: &[u8], payload_hash: Option<&[u8]>) -> Result<Vec<u8>> {
+        self.hash_with_generator(&self.generator, value, randomness, payload_hash)
+    }
+
+    /// [`hash`](Self::hash) under another generator, such as an asset's
+    fn hash_with_generator(
+        &self,
+        generator: &[u8],
+        value: u64,
+        randomness: &[u8],
+        payload_hash: Option<&[u8]>,
+    ) -> Result<Vec<u8>> {
+        let value_bytes = self.parameters.encode_value(value)?;
+        Ok(match self.hash_algo {
+            HashAlgo::Sha512 => {
+                let mut hasher = Sha512::new();
+                hasher.update(generator);
+                hasher.update(&value_bytes);
+                hasher.update(randomness);
+                if let Some(payload_hash) = payload_hash {
+                    hasher.update(payload_hash);
+                }
+                hex::encode(hasher.finalize()).into_bytes()
+            }
+            HashAlgo::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(generator);
+                hasher.update(&value_bytes);
+                hasher.update(randomness);
+                if let Some(payload_hash) = payload_hash {
+                    hasher.update(payload_hash);
+                }
+                hex::encode(hasher.finalize().as_bytes()).into_bytes()
+            }
+            HashAlgo::Pedersen => {
+                let value_generator = self.value_generator(generator);
+                self.pedersen_generators()?.commit_to(
+                    &value_generator,
+                    value,
+                    randomness,
+                    payload_hash,
+                )
+            }
+            #[cfg(feature = "poseidon")]
+            HashAlgo::Poseidon => {
+                let mut inputs: Vec<&[u8]> = vec![generator, &value_bytes, randomness];
+                inputs.extend(payload_hash);
+                hex::encode(crate::poseidon::hash(&inputs)).into_bytes()
+            }
+        })
+    }
+
+    /// Pedersen value generator for `generator`
+    ///
+    /// The base generator carries value as `G`; another generator carries
+    /// value under its own point, never the blinding.
+    fn value_generator(&self, generator: &[u8]) -> ProjectivePoint {
+        if generator == self.generator.as_slice() {
+            ProjectivePoint::GENERATOR
+        } else {
+            Generators::value_generator(generator)
+        }
+    }
+
+    /// Pedersen value generator of `asset`
+    pub(crate) fn asset_value_generator(&self, asset: &AssetId) -> ProjectivePoint {
+        self.value_generator(&self.asset_generator(asset))
+    }
+
     /// Verify a commitment (open it)
     pub fn verify(&self, commitment: &Commitment, value: u64) -> Result<bool> {
         let recomputed = self.commit_with_randomness(value, commitment.randomness.clone())?;
         Ok(recomputed.value == commitment.value)
     }
 
+    /// Open a commitment created with [`commit_with_payload`](Self::commit_with_payload)
+    pub fn verify_with_payload(
+        &self,
+        commitment: &Commitment,
+        value: u64,
+        payload: &[u8],
+    ) -> Result<bool> {
+        let payload_hash = payload_hash(payload)?;
+        if value > self.parameters.max_coin_value
+            || commitment.randomness.len() != self.parameters.randomness_bits / 8
+        {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let recomputed = self.hash(value, &commitment.randomness, Some(&payload_hash))?;
+        Ok(recomputed == commitment.value)
+    }
+
+    /// Prove that `c1` commits to exactly `diff` more than `c2`
+    ///
+    /// `r1` and `r2` are the randomness of the two commitments. Neither value
+    /// is revealed. Only available for the homomorphic `pedersen` scheme;
+    /// fails with `InvalidCommitment` if the difference does not hold.
+    #[cfg(feature = "std")]
+    pub fn prove_difference(
+        &self,
+        c1: &Commitment,
+        c2: &Commitment,
+        diff: u64,
+        r1: &[u8],
+        r2: &[u8],
+    ) -> Result<DifferenceProof> {
+        self.prove_difference_with_rng(c1, c2, diff, r1, r2, &mut rand::rngs::OsRng)
+    }
+
+    /// [`prove_difference`](Self::prove_difference) with the proof nonce
+    /// drawn from `rng`
+    pub fn prove_difference_with_rng(
+        &self,
+        c1: &Commitment,
+        c2: &Commitment,
+        diff: u64,
+        r1: &[u8],
+        r2: &[u8],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<DifferenceProof> {
+        self.pedersen_generators()?
+            .prove_difference(&c1.value, &c2.value, diff, r1, r2, rng)
+    }
+
+    /// Verify a proof from [`prove_difference`](Self::prove_difference)
+    pub fn verify_difference(
+        &self,
+        c1: &Commitment,
+        c2: &Commitment,
+        diff: u64,
+        proof: &DifferenceProof,
+    ) -> Result<bool> {
+        self.pedersen_generators()?
+            .verify_difference(&c1.value, &c2.value, diff, proof)
+    }
+
+    /// Prove that a commitment opens to `value` without revealing its randomness
+    ///
+    /// Only available for the `pedersen` scheme.
+    #[cfg(feature = "std")]
+    pub fn prove_opening(&self, commitment: &Commitment, value: u64) -> Result<OpeningProof> {
+        self.prove_opening_with_rng(commitment, value, &mut rand::rngs::OsRng)
+    }
+
+    /// [`prove_opening`](Self::prove_opening) with the proof nonce drawn
+    /// from `rng`
+    pub fn prove_opening_with_rng(
+        &self,
+        commitment: &Commitment,
+        value: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<OpeningProof> {
+        self.pedersen_generators()?.prove_opening(
+            &commitment.value,
+            value,
+            &commitment.randomness,
+            rng,
+        )
+    }
+
+    /// Verify a proof that the commitment `value_bytes` opens to `value`
+    ///
+    /// Fails with `InvalidCommitment` if `value` is outside the coin value
+    /// bounds.
+    pub fn verify_opening(
+        &self,
+        value_bytes: &[u8],
+        value: u64,
+        proof: &OpeningProof,
+    ) -> Result<bool> {
+        if value < self.parameters.min_coin_value || value > self.parameters.max_coin_value {
+            return Err(LelantusError::InvalidCommitment);
+        }
+        self.pedersen_generators()?
+            .verify_opening(value_bytes, value, proof)
+    }
+
+    /// Prove that a coin commits to its serial number without revealing its
+    /// value or randomness
+    ///
+    /// `value` is the coin's value under [`NATIVE_ASSET`]. Only available for
+    /// the `pedersen` scheme.
+    #[cfg(feature = "std")]
+    pub fn prove_serial(&self, commitment: &Commitment, value: u64) -> Result<SerialProof> {
+        self.prove_serial_with_rng(commitment, value, &mut rand::rngs::OsRng)
+    }
+
+    /// [`prove_serial`](Self::prove_serial) with the proof nonces drawn from
+    /// `rng`
+    pub fn prove_serial_with_rng(
+        &self,
+        commitment: &Commitment,
+        value: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<SerialProof> {
+        self.pedersen_generators()?.prove_serial(
+            &commitment.value,
+            value,
+            &commitment.randomness,
+            rng,
+        )
+    }
+
+    /// Verify a proof that the commitment `value_bytes` commits to `serial`
+    pub fn verify_serial(
+        &self,
+        value_bytes: &[u8],
+        serial: &[u8],
+        proof: &SerialProof,
+    ) -> Result<bool> {
+        self.pedersen_generators()?
+            .verify_serial(value_bytes, serial, proof)
+    }
+
+    /// Homomorphic sum of two commitments
+    ///
+    /// The result commits to the sum of both values and has no randomness
+    /// of its own; open it with [`verify_sum`](Self::verify_sum) and the
+    /// randomness of its terms. Only available for the `pedersen` scheme.
+    pub fn add(&self, a: &Commitment, b: &Commitment) -> Result<Commitment> {
+        self.pedersen_generators()?;
+        Ok(Commitment {
+            value: pedersen::add(&a.value, &b.value)?,
+            randomness: Vec::new(),
+        })
+    }
+
+    /// Homomorphic difference of two commitments, see [`add`](Self::add)
+    pub fn sub(&self, a: &Commitment, b: &Commitment) -> Result<Commitment> {
+        self.pedersen_generators()?;
+        Ok(Commitment {
+            value: pedersen::sub(&a.value, &b.value)?,
+            randomness: Vec::new(),
+        })
+    }
+
+    /// Open a homomorphic sum to `value`, given the randomness of each term
+    pub fn verify_sum(&self, sum: &Commitment, value: u64, randomness: &[&[u8]]) -> Result<bool> {
+        let expected = self.pedersen_generators()?.commit_sum(value, randomness);
+        Ok(expected == sum.value)
+    }
+
+    /// Serialize a commitment as its compressed group element
+    pub fn to_group_element(&self, commitment: &Commitment) -> Result<Vec<u8>> {
+        self.pedersen_generators()?;
+        pedersen::to_point_bytes(&commitment.value)
+    }
+
+    /// Commitment value of a compressed group element
+    ///
+    /// Fails with `InvalidCommitment` if the bytes are not a curve point.
+    pub fn value_from_group_element(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        self.pedersen_generators()?;
+        pedersen::from_point_bytes(bytes)
+    }
+
+    /// Generators of the homomorphic scheme, if this is one
+    pub(crate) fn pedersen_generators(&self) -> Result<&Generators> {
+        self.pedersen
+            .as_ref()
+            .ok_or(LelantusError::invalid_parameter("hash_function"))
+    }
+
+    /// Find commitments that share identical randomness
+    ///
+    /// Returns every index pair `(i, j)` with `i < j` whose randomness matches.
+    /// Reused randomness links coins and can leak values, so wallets can run
+    /// this over their own commitments as a self-audit.
+    pub fn detect_reused_randomness(commitments: &[Commitment]) -> Vec<(usize, usize)> {
+        let mut seen: BTreeMap<&[u8], Vec<usize>> = BTreeMap::new();
+        let mut pairs = Vec::new();
+
+        for (j, commitment) in commitments.iter().enumerate() {
+            let previous = seen.entry(&commitment.randomness).or_default();
+            pairs.extend(previous.iter().map(|&i| (i, j)));
+            previous.push(j);
+        }
+
+        pairs.sort_unstable();
+        pairs
+    }
+
     /// Get the generator
     pub fn generator(&self) -> &[u8] {
         &self.generator
@@ -137,7 +776,26 @@ impl CommitmentScheme {
 mod tests {
     use super::*;
 
+    fn sha512_params() -> LelantusParameters {
+        LelantusParameters {
+            hash_function: "sha512".to_string(),
+            ..Default::default()
+        }
+    }
+
     #[test]
+    fn test_consensus_encoding_golden() -> Result<()> {
+        let commitment = Commitment {
+            value: vec![0xab; 2],
+            randomness: vec![0xcd; 3],
+        };
+        let encoded = commitment.consensus_encode()?;
+        assert_eq!(hex::encode(&encoded), "02000000abab03000000cdcdcd");
+        assert_eq!(Commitment::consensus_decode(&encoded)?, commitment);
+        assert!(Commitment::consensus_decode(&encoded[1..]).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_commitment_creation() -> Result<()> {
         let params = LelantusParameters::default();
@@ -174,6 +832,74 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_commit_with_seeded_rng() -> Result<()> {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let first = scheme.commit_with_rng(1000, &mut StdRng::seed_from_u64(7))?;
+        let second = scheme.commit_with_rng(1000, &mut StdRng::seed_from_u64(7))?;
+        assert_eq!(first, second);
+        assert!(scheme.verify(&first, 1000)?);
+        assert_ne!(
+            first,
+            scheme.commit_with_rng(1000, &mut StdRng::seed_from_u64(8))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_asset_commitments() -> Result<()> {
+        let params = LelantusParameters::default();
+        let scheme = CommitmentScheme::new(&params)?;
+        let randomness = vec![42; params.randomness_bits / 8];
+        let token = [7; 32];
+
+        let native =
+            scheme.commit_asset_with_randomness(&NATIVE_ASSET, 1000, randomness.clone())?;
+        assert_eq!(
+            native,
+            scheme.commit_with_randomness(1000, randomness.clone())?
+        );
+
+        let coin = scheme.commit_asset_with_randomness(&token, 1000, randomness)?;
+        assert_ne!(coin.value, native.value);
+        scheme.check_well_formed(&coin)?;
+        assert!(scheme.verify_asset(&coin, &token, 1000)?);
+        assert!(!scheme.verify_asset(&coin, &token, 999)?);
+        assert!(!scheme.verify_asset(&coin, &[8; 32], 1000)?);
+        assert!(!scheme.verify(&coin, 1000)?);
+        assert_ne!(
+            scheme.asset_generator(&token),
+            scheme.asset_generator(&[8; 32])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pedersen_asset_in_value_generator() -> Result<()> {
+        let params = LelantusParameters {
+            hash_function: "pedersen".to_string(),
+            ..LelantusParameters::default()
+        };
+        let scheme = CommitmentScheme::new(&params)?;
+        let randomness = vec![42; params.randomness_bits / 8];
+        let token = [7; 32];
+
+        // Same randomness, so the blinding cancels and only value is left
+        let coin = scheme.commit_asset_with_randomness(&token, 1000, randomness.clone())?;
+        let blinding = scheme.commit_with_randomness(0, randomness)?;
+        let value_generator = Generators::value_generator(&scheme.asset_generator(&token));
+        assert_eq!(
+            pedersen::decode_hex(&coin.value)? - pedersen::decode_hex(&blinding.value)?,
+            value_generator * p521::Scalar::from(1000u64)
+        );
+        assert!(scheme.verify_asset(&coin, &token, 1000)?);
+        assert!(!scheme.verify_asset(&coin, &[8; 32], 1000)?);
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_commitment_value() -> Result<()> {
         let params = LelantusParameters::default();
@@ -182,4 +908,169 @@ mod tests {
         assert!(result.is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_detect_reused_randomness() -> Result<()> {
+        let params = LelantusParameters::default();
+        let scheme = CommitmentScheme::new(&params)?;
+        let randomness = vec![42; params.randomness_bits / 8];
+
+        let commitments = vec![
+            scheme.commit(1000)?,
+            scheme.commit_with_randomness(2000, randomness.clone())?,
+            scheme.commit(3000)?,
+            scheme.commit_with_randomness(4000, randomness)?,
+        ];
+
+        assert_eq!(
+            CommitmentScheme::detect_reused_randomness(&commitments),
+            vec![(1, 3)]
+        );
+        assert!(CommitmentScheme::detect_reused_randomness(&commitments[..3]).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_commitment_with_payload() -> Result<()> {
+        let params = LelantusParameters::default();
+        let scheme = CommitmentScheme::new(&params)?;
+        let randomness = vec![42; params.randomness_bits / 8];
+
+        // Same value and randomness, different payloads
+        let first = scheme.hash(1000, &randomness, Some(&payload_hash(b"nft:1")?))?;
+        let second = scheme.hash(1000, &randomness, Some(&payload_hash(b"nft:2")?))?;
+        assert_ne!(first, second);
+        assert_ne!(first, scheme.hash(1000, &randomness, None)?);
+
+        let commitment = scheme.commit_with_payload(1000, b"nft:1")?;
+        assert!(scheme.verify_with_payload(&commitment, 1000, b"nft:1")?);
+        assert!(!scheme.verify_with_payload(&commitment, 1000, b"nft:2")?);
+        assert!(!scheme.verify_with_payload(&commitment, 1001, b"nft:1")?);
+        assert!(!scheme.verify(&commitment, 1000)?);
+
+        let oversized = vec![0; MAX_COMMITMENT_PAYLOAD_SIZE + 1];
+        assert!(scheme.commit_with_payload(1000, &oversized).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_derive_range() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let seed = b"wallet seed";
+
+        let range = scheme.derive_range(seed, 10, 5, 1000)?;
+        assert_eq!(range.len(), 5);
+        for (offset, commitment) in range.iter().enumerate() {
+            let index = 10 + offset as u64;
+            assert_eq!(*commitment, scheme.commit_deterministic(seed, index, 1000)?);
+            assert!(scheme.verify(commitment, 1000)?);
+        }
+
+        // Reproducible, and overlapping ranges agree
+        assert_eq!(range, scheme.derive_range(seed, 10, 5, 1000)?);
+        assert_eq!(range[2..], scheme.derive_range(seed, 12, 3, 1000)?[..]);
+        assert!(CommitmentScheme::detect_reused_randomness(&range).is_empty());
+        assert_ne!(range, scheme.derive_range(b"other seed", 10, 5, 1000)?);
+
+        assert!(scheme.derive_range(seed, u64::MAX, 2, 1000).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_narrow_value_encoding() -> Result<()> {
+        let params = LelantusParameters {
+            max_coin_value: 1_000_000,
+            value_byte_width: 4,
+            hash_function: "sha512".to_string(),
+            ..Default::default()
+        };
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(999_999)?;
+        assert!(scheme.verify(&commitment, 999_999)?);
+        assert!(!scheme.verify(&commitment, 999_998)?);
+        assert_eq!(params.encode_value(999_999)?.len(), 4);
+
+        // Same opening hashes differently under the default 8-byte width
+        let wide = CommitmentScheme::new(&LelantusParameters {
+            max_coin_value: 1_000_000,
+            hash_function: "sha512".to_string(),
+            ..Default::default()
+        })?;
+        assert!(!wide.verify(&commitment, 999_999)?);
+
+        // 1,000,000 needs 3 bytes
+        let too_narrow = LelantusParameters {
+            value_byte_width: 2,
+            ..params
+        };
+        assert!(too_narrow.validate().is_err());
+        assert!(CommitmentScheme::new(&too_narrow).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pedersen_homomorphism() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let a = scheme.commit(300)?;
+        let b = scheme.commit(400)?;
+
+        let sum = scheme.add(&a, &b)?;
+        assert!(scheme.verify_sum(&sum, 700, &[&a.randomness, &b.randomness])?);
+        assert!(!scheme.verify_sum(&sum, 701, &[&a.randomness, &b.randomness])?);
+        assert_eq!(scheme.sub(&sum, &b)?.value, a.value);
+
+        let element = scheme.to_group_element(&a)?;
+        assert_eq!(element.len(), pedersen::POINT_SIZE);
+        assert_eq!(scheme.value_from_group_element(&element)?, a.value);
+        assert!(scheme.value_from_group_element(&[0x02; 67]).is_err());
+
+        let sha512 = CommitmentScheme::new(&sha512_params())?;
+        assert!(sha512.add(&a, &b).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_difference_proof() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let c1 = scheme.commit(1500)?;
+        let c2 = scheme.commit(1000)?;
+        assert!(scheme.verify(&c1, 1500)?);
+
+        let proof = scheme.prove_difference(&c1, &c2, 500, &c1.randomness, &c2.randomness)?;
+        assert!(scheme.verify_difference(&c1, &c2, 500, &proof)?);
+        assert!(!scheme.verify_difference(&c1, &c2, 501, &proof)?);
+        assert!(!scheme.verify_difference(&c2, &c1, 500, &proof)?);
+
+        // A false difference cannot be proven
+        assert!(scheme
+            .prove_difference(&c1, &c2, 400, &c1.randomness, &c2.randomness)
+            .is_err());
+
+        // Hash-based commitments are not homomorphic
+        let sha512 = CommitmentScheme::new(&sha512_params())?;
+        assert!(matches!(
+            sha512.verify_difference(&c1, &c2, 500, &proof),
+            Err(LelantusError::InvalidParameter(_))
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "poseidon")]
+    #[test]
+    fn test_poseidon_commitments() -> Result<()> {
+        let params = LelantusParameters {
+            hash_function: "poseidon".to_string(),
+            ..Default::default()
+        };
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1000)?;
+        assert!(scheme.verify(&commitment, 1000)?);
+        assert!(!scheme.verify(&commitment, 2000)?);
+
+        let sha512 = CommitmentScheme::new(&sha512_params())?;
+        let other = sha512.commit_with_randomness(1000, commitment.randomness.clone())?;
+        assert_ne!(other.value, commitment.value);
+        assert!(!sha512.verify(&commitment, 1000)?);
+        Ok(())
+    }
 }
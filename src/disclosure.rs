@@ -0,0 +1,176 @@
+//! Payment disclosures for selective transparency
+//!
+//! A sender can export a [`DisclosurePackage`] for one JoinSplit output: the
+//! output's value and randomness, the transaction it belongs to, and who it
+//! paid. Anyone holding the package can open the on-chain output commitment
+//! with it and confirm the value, without learning anything about the other
+//! outputs or the inputs.
+//!
+//! The recipient of a package is recorded as the sender states it; opening
+//! the commitment proves the value, not the recipient. A [`PaymentProof`]
+//! also proves the recipient: its package names the recipient's view key
+//! fingerprint, and the output's amount note, bound to the output commitment,
+//! must open to the disclosed value under that view key.
+
+use crate::commitment::CommitmentScheme;
+use crate::errors::{ErrorContext, LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use crate::serialization::LelantusSerializable;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Opening of one JoinSplit output, for an auditor
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    Zeroize,
+    ZeroizeOnDrop,
+)]
+pub struct DisclosurePackage {
+    /// Id of the JoinSplit holding the output
+    pub txid: [u8; 32],
+
+    /// Index of the output within the JoinSplit
+    pub output_index: u64,
+
+    /// Value of the output in mist
+    pub value: u64,
+
+    /// Recipient identifier supplied by the sender, such as an address
+    pub recipient: Vec<u8>,
+
+    /// Randomness of the output commitment
+    pub randomness: Vec<u8>,
+}
+
+impl LelantusSerializable for DisclosurePackage {}
+
+impl DisclosurePackage {
+    /// Disclose output `output_index` of `joinsplit`, paid to `recipient`
+    ///
+    /// Takes the randomness from the creator's copy of `joinsplit`, as
+    /// decoded JoinSplits carry none. Fails with `InvalidParameter` for an
+    /// index out of range and with `InvalidCommitment` when `value` does not
+    /// open the output.
+    pub fn new(
+        scheme: &CommitmentScheme,
+        joinsplit: &JoinSplit,
+        output_index: usize,
+        value: u64,
+        recipient: &[u8],
+    ) -> Result<Self> {
+        let output = joinsplit.outputs.get(output_index).ok_or_else(|| {
+            LelantusError::InvalidParameter(
+                ErrorContext::new("output_index")
+                    .expected(format!("< {}", joinsplit.outputs.len()))
+                    .actual(output_index),
+            )
+        })?;
+        if !scheme.verify(output, value)? {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        Ok(Self {
+            txid: joinsplit.txid()?,
+            output_index: output_index as u64,
+            value,
+            recipient: recipient.to_vec(),
+            randomness: output.randomness.clone(),
+        })
+    }
+
+    /// Check the package against `joinsplit` as found on chain
+    ///
+    /// Holds when `joinsplit` is the disclosed transaction and the value and
+    /// randomness open the disclosed output commitment.
+    pub fn verify(&self, scheme: &CommitmentScheme, joinsplit: &JoinSplit) -> Result<bool> {
+        if self.txid != joinsplit.txid()? {
+            return Ok(false);
+        }
+        let Some(output) = usize::try_from(self.output_index)
+            .ok()
+            .and_then(|index| joinsplit.outputs.get(index))
+        else {
+            return Ok(false);
+        };
+        if self.randomness.len() != scheme.parameters().randomness_bits / 8 {
+            return Ok(false);
+        }
+
+        let opened = scheme.commit_with_randomness(self.value, self.randomness.clone())?;
+        Ok(opened.value == output.value)
+    }
+}
+
+/// Proof that a JoinSplit output paid the holder of a view key
+///
+/// Created with [`JoinSplit::create_payment_proof`] and checked with
+/// [`JoinSplit::verify_payment_proof`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct PaymentProof {
+    /// Opening of the output, naming the recipient's view key fingerprint
+    pub disclosure: DisclosurePackage,
+}
+
+impl LelantusSerializable for PaymentProof {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::LelantusParameters;
+    use crate::LelantusState;
+
+    #[test]
+    fn test_disclosure_roundtrip() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[1_000])?;
+        let joinsplit = state.create_joinsplit(coins, vec![600, 300], 100)?;
+        let scheme = state.commitment_scheme();
+
+        let package = DisclosurePackage::new(&scheme, &joinsplit, 1, 300, b"exchange")?;
+        let decoded =
+            <DisclosurePackage as LelantusSerializable>::from_bytes(&package.to_bytes()?)?;
+        assert_eq!(decoded, package);
+        assert!(decoded.verify(&scheme, &joinsplit)?);
+
+        // The package opens the output as published, without its randomness
+        let published = JoinSplit::consensus_decode(&joinsplit.consensus_encode()?)?;
+        assert!(published.outputs[1].randomness.is_empty());
+        assert!(decoded.verify(&scheme, &published)?);
+
+        let mut wrong_value = package.clone();
+        wrong_value.value = 301;
+        assert!(!wrong_value.verify(&scheme, &joinsplit)?);
+
+        let mut wrong_output = package;
+        wrong_output.output_index = 0;
+        assert!(!wrong_output.verify(&scheme, &joinsplit)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disclosure_requires_an_opening() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[1_000])?;
+        let joinsplit = state.create_joinsplit(coins, vec![900], 100)?;
+        let scheme = state.commitment_scheme();
+
+        assert!(matches!(
+            DisclosurePackage::new(&scheme, &joinsplit, 0, 901, b"exchange"),
+            Err(LelantusError::InvalidCommitment)
+        ));
+        assert!(DisclosurePackage::new(&scheme, &joinsplit, 1, 900, b"exchange").is_err());
+
+        let other = state.create_joinsplit(state.mint_batch(&[1_000])?, vec![900], 100)?;
+        let package = DisclosurePackage::new(&scheme, &joinsplit, 0, 900, b"exchange")?;
+        assert!(!package.verify(&scheme, &other)?);
+        Ok(())
+    }
+}
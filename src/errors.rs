@@ -1,52 +1,336 @@
 //! Error types for Lelantus protocol
 
-use thiserror::Error;
+use alloc::string::{String, ToString};
+use core::ffi::CStr;
+use core::fmt;
+
+/// Which field of a parameter set or witness was rejected, and why
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Name of the rejected field
+    pub field: &'static str,
+
+    /// Position within the field, for lists
+    pub index: Option<usize>,
+
+    /// What the field should have held
+    pub expected: Option<String>,
+
+    /// What the field held instead
+    pub actual: Option<String>,
+}
+
+impl ErrorContext {
+    /// Context naming `field`
+    pub fn new(field: &'static str) -> Self {
+        Self {
+            field,
+            ..Self::default()
+        }
+    }
+
+    /// Point at position `index` of the field
+    pub fn at(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Record what the field should have held
+    pub fn expected(mut self, expected: impl fmt::Display) -> Self {
+        self.expected = Some(expected.to_string());
+        self
+    }
+
+    /// Record what the field held instead
+    pub fn actual(mut self, actual: impl fmt::Display) -> Self {
+        self.actual = Some(actual.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.field)?;
+        if let Some(index) = self.index {
+            write!(f, "[{}]", index)?;
+        }
+        match (&self.expected, &self.actual) {
+            (Some(expected), Some(actual)) => write!(f, ": expected {}, got {}", expected, actual),
+            (Some(expected), None) => write!(f, ": expected {}", expected),
+            (None, Some(actual)) => write!(f, ": got {}", actual),
+            (None, None) => Ok(()),
+        }
+    }
+}
 
 /// Lelantus protocol errors
-#[derive(Error, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub enum LelantusError {
-    #[error("Invalid input count: must be between 1 and 16")]
+    /// Too few or too many JoinSplit inputs
     InvalidInputCount,
 
-    #[error("Invalid output count: must be between 1 and 2")]
+    /// Too few or too many JoinSplit outputs
     InvalidOutputCount,
 
-    #[error("Balance mismatch: inputs do not equal outputs + fee")]
+    /// Inputs do not cover outputs and fee, or an amount is out of range
     BalanceMismatch,
 
-    #[error("Invalid commitment")]
+    /// Malformed or unopenable commitment
     InvalidCommitment,
 
-    #[error("Invalid witness")]
-    InvalidWitness,
+    /// Witness field rejected
+    InvalidWitness(ErrorContext),
 
-    #[error("Invalid proof")]
+    /// Malformed proof
     InvalidProof,
 
-    #[error("Proof verification failed")]
+    /// Well-formed proof that does not verify
     ProofVerificationFailed,
 
-    #[error("Accumulator error: {0}")]
+    /// Accumulator update or lookup failure
     AccumulatorError(String),
 
-    #[error("Serialization error: {0}")]
+    /// Encoding or decoding failure
     SerializationError(String),
 
-    #[error("Cryptographic error: {0}")]
+    /// Cryptographic primitive failure
     CryptoError(String),
 
-    #[error("Invalid parameter")]
-    InvalidParameter,
+    /// Parameter or argument rejected
+    InvalidParameter(ErrorContext),
+
+    /// Data produced under different parameters
+    ParameterMismatch,
 
-    #[error("Witness not found")]
+    /// Proof anonymity set differs from the parameters
+    PrivacyLevelMismatch,
+
+    /// No witness for the requested coin
     WitnessNotFound,
 
-    #[error("Range proof error: {0}")]
+    /// Serial number already spent
+    DoubleSpend,
+
+    /// Coin not owned by the spend key
+    NotCoinOwner,
+
+    /// Range proof failure
     RangeProofError(String),
 
-    #[error("Zero-knowledge proof error: {0}")]
+    /// Zero-knowledge proof failure
     ZKProofError(String),
+
+    /// Worker pool failure
+    WorkerFailed(String),
+
+    /// Transaction bytes that do not decode
+    MalformedTransaction(String),
+
+    /// Transaction refers to an anonymity set past the accumulator head
+    UnknownAnonymitySet(u64),
+}
+
+impl fmt::Display for LelantusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInputCount => f.write_str("Invalid input count: must be between 1 and 16"),
+            Self::InvalidOutputCount => {
+                f.write_str("Invalid output count: exceeds the parameters' output limit")
+            }
+            Self::BalanceMismatch => {
+                f.write_str("Balance mismatch: inputs do not equal outputs + fee")
+            }
+            Self::InvalidCommitment => f.write_str("Invalid commitment"),
+            Self::InvalidWitness(detail) => write!(f, "Invalid witness: {}", detail),
+            Self::InvalidProof => f.write_str("Invalid proof"),
+            Self::ProofVerificationFailed => f.write_str("Proof verification failed"),
+            Self::AccumulatorError(detail) => write!(f, "Accumulator error: {}", detail),
+            Self::SerializationError(detail) => write!(f, "Serialization error: {}", detail),
+            Self::CryptoError(detail) => write!(f, "Cryptographic error: {}", detail),
+            Self::InvalidParameter(detail) => write!(f, "Invalid parameter: {}", detail),
+            Self::ParameterMismatch => {
+                f.write_str("Parameter mismatch: data was produced under different parameters")
+            }
+            Self::PrivacyLevelMismatch => f.write_str(
+                "Privacy level mismatch: proof anonymity set differs from the parameters",
+            ),
+            Self::WitnessNotFound => f.write_str("Witness not found"),
+            Self::DoubleSpend => f.write_str("Double spend: nullifier already spent"),
+            Self::NotCoinOwner => f.write_str("Coin is not owned by the spend key"),
+            Self::RangeProofError(detail) => write!(f, "Range proof error: {}", detail),
+            Self::ZKProofError(detail) => write!(f, "Zero-knowledge proof error: {}", detail),
+            Self::WorkerFailed(detail) => write!(f, "Worker pool error: {}", detail),
+            Self::MalformedTransaction(detail) => write!(f, "Malformed transaction: {}", detail),
+            Self::UnknownAnonymitySet(set) => write!(f, "Unknown anonymity set: {}", set),
+        }
+    }
+}
+
+impl core::error::Error for LelantusError {}
+
+/// I/O failures while reading or writing encoded data
+#[cfg(feature = "std")]
+impl From<std::io::Error> for LelantusError {
+    fn from(e: std::io::Error) -> Self {
+        Self::SerializationError(e.to_string())
+    }
+}
+
+impl LelantusError {
+    /// `InvalidParameter` naming `field`
+    pub fn invalid_parameter(field: &'static str) -> Self {
+        Self::InvalidParameter(ErrorContext::new(field))
+    }
+
+    /// `InvalidWitness` naming `field`
+    pub fn invalid_witness(field: &'static str) -> Self {
+        Self::InvalidWitness(ErrorContext::new(field))
+    }
+
+    /// Stable numeric code for FFI and RPC callers
+    ///
+    /// Codes are never reused or renumbered; new variants get new codes.
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::InvalidInputCount => 1,
+            Self::InvalidOutputCount => 2,
+            Self::BalanceMismatch => 3,
+            Self::InvalidCommitment => 4,
+            Self::InvalidWitness(_) => 5,
+            Self::InvalidProof => 6,
+            Self::ProofVerificationFailed => 7,
+            Self::AccumulatorError(_) => 8,
+            Self::SerializationError(_) => 9,
+            Self::CryptoError(_) => 10,
+            Self::InvalidParameter(_) => 11,
+            Self::ParameterMismatch => 12,
+            Self::PrivacyLevelMismatch => 13,
+            Self::WitnessNotFound => 14,
+            Self::DoubleSpend => 15,
+            Self::NotCoinOwner => 16,
+            Self::RangeProofError(_) => 17,
+            Self::ZKProofError(_) => 18,
+            Self::WorkerFailed(_) => 19,
+            Self::MalformedTransaction(_) => 20,
+            Self::UnknownAnonymitySet(_) => 21,
+        }
+    }
+
+    /// Static description of errors with [`code`](Self::code) `code`
+    ///
+    /// NUL-terminated so FFI callers can hand it out as is; unassigned codes
+    /// read "unknown error".
+    pub fn description_for_code(code: u16) -> &'static CStr {
+        match code {
+            1 => c"invalid input count",
+            2 => c"invalid output count",
+            3 => c"balance mismatch",
+            4 => c"invalid commitment",
+            5 => c"invalid witness",
+            6 => c"invalid proof",
+            7 => c"proof verification failed",
+            8 => c"accumulator error",
+            9 => c"serialization error",
+            10 => c"cryptographic error",
+            11 => c"invalid parameter",
+            12 => c"parameter mismatch",
+            13 => c"privacy level mismatch",
+            14 => c"witness not found",
+            15 => c"double spend",
+            16 => c"coin not owned by the spend key",
+            17 => c"range proof error",
+            18 => c"zero-knowledge proof error",
+            19 => c"worker pool error",
+            20 => c"malformed transaction",
+            21 => c"unknown anonymity set",
+            _ => c"unknown error",
+        }
+    }
+
+    /// Whether the error means the data breaks consensus rules
+    ///
+    /// Consensus failures condemn the transaction itself, so peers relaying
+    /// it may be penalised. Other errors come from the local node, such as a
+    /// missing witness, bad configuration or a failed worker, and say nothing
+    /// about the transaction. Decoding and set lookups fail either way, so
+    /// transaction data reports them as `MalformedTransaction` and
+    /// `UnknownAnonymitySet` rather than `SerializationError` and
+    /// `AccumulatorError`.
+    pub fn is_consensus_failure(&self) -> bool {
+        Self::is_consensus_failure_code(self.code())
+    }
+
+    /// Whether errors with [`code`](Self::code) `code` are consensus failures
+    pub fn is_consensus_failure_code(code: u16) -> bool {
+        // InvalidInputCount, InvalidOutputCount, BalanceMismatch,
+        // InvalidCommitment, InvalidProof, ProofVerificationFailed,
+        // PrivacyLevelMismatch, DoubleSpend, RangeProofError, ZKProofError,
+        // MalformedTransaction, UnknownAnonymitySet
+        matches!(code, 1..=4 | 6 | 7 | 13 | 15 | 17 | 18 | 20 | 21)
+    }
+
+    /// Field context of `InvalidParameter` and `InvalidWitness` errors
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::InvalidParameter(context) | Self::InvalidWitness(context) => Some(context),
+            _ => None,
+        }
+    }
 }
 
 /// Result type for Lelantus operations
-pub type Result<T> = std::result::Result<T, LelantusError>;
+pub type Result<T> = core::result::Result<T, LelantusError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_context_display() {
+        let error = LelantusError::InvalidParameter(
+            ErrorContext::new("max_outputs")
+                .expected("1..=16")
+                .actual(0),
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid parameter: max_outputs: expected 1..=16, got 0"
+        );
+        assert_eq!(
+            LelantusError::InvalidWitness(ErrorContext::new("inputs").at(3)).to_string(),
+            "Invalid witness: inputs[3]"
+        );
+        assert_eq!(error.context().map(|c| c.field), Some("max_outputs"));
+        assert!(LelantusError::DoubleSpend.context().is_none());
+    }
+
+    #[test]
+    fn test_codes_and_classification() {
+        assert_eq!(LelantusError::InvalidInputCount.code(), 1);
+        assert_eq!(LelantusError::invalid_witness("commitment").code(), 5);
+        assert_eq!(LelantusError::invalid_parameter("seed").code(), 11);
+        assert_eq!(LelantusError::WorkerFailed(String::new()).code(), 19);
+
+        assert!(LelantusError::DoubleSpend.is_consensus_failure());
+        assert!(LelantusError::ProofVerificationFailed.is_consensus_failure());
+        assert!(!LelantusError::WitnessNotFound.is_consensus_failure());
+        assert!(!LelantusError::invalid_parameter("seed").is_consensus_failure());
+        assert!(LelantusError::MalformedTransaction(String::new()).is_consensus_failure());
+        assert!(LelantusError::UnknownAnonymitySet(7).is_consensus_failure());
+        assert!(!LelantusError::SerializationError(String::new()).is_consensus_failure());
+        assert!(!LelantusError::AccumulatorError(String::new()).is_consensus_failure());
+
+        for code in 1..=21 {
+            assert_ne!(LelantusError::description_for_code(code), c"unknown error");
+        }
+        assert_eq!(LelantusError::description_for_code(0), c"unknown error");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_errors_are_serialization_errors() {
+        let error = LelantusError::from(std::io::Error::other("disk full"));
+        assert_eq!(error.code(), 9);
+        assert_eq!(error.to_string(), "Serialization error: disk full");
+    }
+}
@@ -0,0 +1,147 @@
+//! Fee estimation from JoinSplit sizes
+//!
+//! A [`FeeEstimator`] prices a JoinSplit before it is built: it computes the
+//! consensus-encoded size of a JoinSplit of the same shape from the encoding
+//! layout, see [`JoinSplit::estimated_size`], and multiplies by a fee rate.
+
+use crate::amount::Amount;
+use crate::errors::Result;
+use crate::joinsplit::{amount_note_size, JoinSplit, ENCRYPTED_MEMO_SIZE};
+use crate::parameters::{LelantusParameters, PrivacyLevel};
+use crate::pedersen::POINT_SIZE;
+use crate::serialization::binary::LENGTH_PREFIX_SIZE;
+
+/// Converts JoinSplit sizes into fees at a fixed rate
+#[derive(Debug, Clone)]
+pub struct FeeEstimator {
+    fee_rate: u64,
+    parameters: LelantusParameters,
+    memos: bool,
+    amount_notes: bool,
+    ephemeral_keys: bool,
+}
+
+impl FeeEstimator {
+    /// Charge `fee_rate` per byte for JoinSplits at `privacy_level`
+    pub fn new(fee_rate: u64, privacy_level: PrivacyLevel) -> Self {
+        Self::with_parameters(
+            fee_rate,
+            &LelantusParameters::with_privacy_level(privacy_level),
+        )
+    }
+
+    /// Charge `fee_rate` per byte for JoinSplits under `parameters`
+    pub fn with_parameters(fee_rate: u64, parameters: &LelantusParameters) -> Self {
+        Self {
+            fee_rate,
+            parameters: parameters.clone(),
+            memos: false,
+            amount_notes: false,
+            ephemeral_keys: false,
+        }
+    }
+
+    /// Price in encrypted memos, as carried once any output has one
+    pub fn with_memos(mut self, memos: bool) -> Self {
+        self.memos = memos;
+        self
+    }
+
+    /// Price in amount notes, as carried once any output has one
+    pub fn with_amount_notes(mut self, amount_notes: bool) -> Self {
+        self.amount_notes = amount_notes;
+        self
+    }
+
+    /// Price in stealth ephemeral keys, as carried once any output has one
+    pub fn with_ephemeral_keys(mut self, ephemeral_keys: bool) -> Self {
+        self.ephemeral_keys = ephemeral_keys;
+        self
+    }
+
+    /// Fee charged per byte
+    pub fn fee_rate(&self) -> u64 {
+        self.fee_rate
+    }
+
+    /// Consensus-encoded size of a signed JoinSplit of the given shape
+    pub fn size(&self, inputs: usize, outputs: usize) -> Result<usize> {
+        let mut note_size = 0;
+        if self.memos {
+            note_size += LENGTH_PREFIX_SIZE + ENCRYPTED_MEMO_SIZE;
+        }
+        if self.amount_notes {
+            note_size += LENGTH_PREFIX_SIZE + amount_note_size(self.parameters.randomness_bits / 8);
+        }
+        if self.ephemeral_keys {
+            note_size += LENGTH_PREFIX_SIZE + POINT_SIZE;
+        }
+        Ok(JoinSplit::encoded_size(&self.parameters, inputs, outputs)? + outputs * note_size)
+    }
+
+    /// Fee for a JoinSplit of the given shape
+    ///
+    /// Fails with `BalanceMismatch` if the fee exceeds the money supply.
+    pub fn fee(&self, inputs: usize, outputs: usize) -> Result<Amount> {
+        self.fee_for_size(self.size(inputs, outputs)?)
+    }
+
+    /// Fee for `size` bytes
+    pub fn fee_for_size(&self, size: usize) -> Result<Amount> {
+        Amount::new(self.fee_rate)?.checked_mul(size as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::LelantusError;
+    use crate::LelantusState;
+
+    #[test]
+    fn test_estimated_size_matches_real_joinsplits() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        for (inputs, outputs) in [(1, 1), (2, 2), (4, 1)] {
+            let amounts = vec![1_000; inputs];
+            let coins = state.mint_batch(&amounts)?;
+            let mut joinsplit = state.create_joinsplit(
+                coins,
+                vec![100; outputs],
+                1_000 * inputs as u64 - 100 * outputs as u64,
+            )?;
+            joinsplit.sign_binding(b"tx")?;
+            let size = joinsplit.consensus_encode()?.len();
+            assert_eq!(
+                JoinSplit::estimated_size(inputs, outputs, PrivacyLevel::Standard)?,
+                size
+            );
+
+            let view_key = crate::keys::ViewKey::generate();
+            joinsplit.set_memo(0, b"memo", &view_key)?;
+            joinsplit.set_amount_note(0, 100, &view_key)?;
+            let estimator = FeeEstimator::new(3, PrivacyLevel::Standard)
+                .with_memos(true)
+                .with_amount_notes(true);
+            let size = joinsplit.consensus_encode()?.len();
+            assert_eq!(estimator.size(inputs, outputs)?, size);
+            assert_eq!(estimator.fee(inputs, outputs)?.as_mist(), 3 * size as u64);
+
+            let ephemeral_key = crate::pedersen::encode(&p521::ProjectivePoint::GENERATOR);
+            joinsplit.set_ephemeral_key(0, &ephemeral_key)?;
+            let estimator = estimator.with_ephemeral_keys(true);
+            assert_eq!(
+                estimator.size(inputs, outputs)?,
+                joinsplit.consensus_encode()?.len()
+            );
+        }
+
+        assert!(matches!(
+            JoinSplit::estimated_size(0, 1, PrivacyLevel::Standard),
+            Err(LelantusError::InvalidInputCount)
+        ));
+        assert!(FeeEstimator::new(u64::MAX, PrivacyLevel::Standard)
+            .fee(1, 1)
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+//! C bindings for the verification path
+//!
+//! Every function returns [`LELANTUS_OK`] or an error code: a positive
+//! [`LelantusError::code`], or one of the negative `LELANTUS_ERR_*` codes for
+//! failures at the boundary itself. Codes are stable across releases, and
+//! [`lelantus_error_message`] describes each one.
+//!
+//! States are opaque handles created by [`lelantus_state_new`] and released
+//! with [`lelantus_state_free`]. Byte arguments are a pointer and a length;
+//! a null pointer is only accepted with a zero length. Panics never unwind
+//! into the caller; they surface as [`LELANTUS_ERR_PANIC`].
+//!
+//! `cargo build --release --features ffi` produces the shared library.
+
+use crate::commitment::Commitment;
+use crate::errors::{LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use crate::parameters::LelantusParameters;
+use crate::LelantusState;
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Success
+pub const LELANTUS_OK: i32 = 0;
+
+/// A required pointer argument was null
+pub const LELANTUS_ERR_NULL_POINTER: i32 = -1;
+
+/// The library panicked; the state may be inconsistent and should be freed
+pub const LELANTUS_ERR_PANIC: i32 = -2;
+
+/// Opaque Lelantus state handle
+pub struct LelantusHandle {
+    state: LelantusState,
+}
+
+/// Run `f`, mapping errors and panics to codes
+fn guard(f: impl FnOnce() -> Result<i32>) -> i32 {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(code)) => code,
+        Ok(Err(e)) => i32::from(e.code()),
+        Err(_) => LELANTUS_ERR_PANIC,
+    }
+}
+
+/// View `len` bytes at `data`, or `None` for a null pointer with a nonzero
+/// length
+///
+/// # Safety
+///
+/// A non-null `data` must point to `len` readable bytes.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        // SAFETY: the caller guarantees `data` points to `len` readable bytes
+        (false, _) => Some(unsafe { std::slice::from_raw_parts(data, len) }),
+    }
+}
+
+/// Create a state, writing its handle to `out`
+///
+/// `parameters` is the consensus encoding of [`LelantusParameters`], or null
+/// with a zero length for the defaults.
+///
+/// # Safety
+///
+/// `out` must be valid for writes, and a non-null `parameters` must point
+/// to `parameters_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn lelantus_state_new(
+    parameters: *const u8,
+    parameters_len: usize,
+    out: *mut *mut LelantusHandle,
+) -> i32 {
+    // SAFETY: forwarded from this function's contract
+    let Some(parameters) = (unsafe { bytes(parameters, parameters_len) }) else {
+        return LELANTUS_ERR_NULL_POINTER;
+    };
+    if out.is_null() {
+        return LELANTUS_ERR_NULL_POINTER;
+    }
+    guard(|| {
+        let parameters = match parameters {
+            [] => LelantusParameters::default(),
+            encoded => LelantusParameters::consensus_decode(encoded)?,
+        };
+        let handle = Box::new(LelantusHandle {
+            state: LelantusState::new(parameters)?,
+        });
+        // SAFETY: `out` is non-null and valid for writes per the contract
+        unsafe { *out = Box::into_raw(handle) };
+        Ok(LELANTUS_OK)
+    })
+}
+
+/// Release a state created by [`lelantus_state_new`]
+///
+/// Null is ignored.
+///
+/// # Safety
+///
+/// `state` must be null or a handle from [`lelantus_state_new`] that has not
+/// been freed, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn lelantus_state_free(state: *mut LelantusHandle) {
+    if !state.is_null() {
+        // SAFETY: the handle came from `Box::into_raw` and is freed only once
+        drop(unsafe { Box::from_raw(state) });
+    }
+}
+
+/// Add a coin to the state's accumulator
+///
+/// `commitment` is the consensus encoding of a [`Commitment`], as written by
+/// [`Commitment::consensus_encode`].
+///
+/// # Safety
+///
+/// `state` must be a live handle and `commitment` must point to
+/// `commitment_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn lelantus_accumulator_add(
+    state: *const LelantusHandle,
+    commitment: *const u8,
+    commitment_len: usize,
+) -> i32 {
+    // SAFETY: forwarded from this function's contract
+    let (Some(handle), Some(commitment)) = (unsafe { state.as_ref() }, unsafe {
+        bytes(commitment, commitment_len)
+    }) else {
+        return LELANTUS_ERR_NULL_POINTER;
+    };
+    guard(|| {
+        let commitment = Commitment::consensus_decode(commitment)?;
+        handle.state.add_coin(&commitment)?;
+        Ok(LELANTUS_OK)
+    })
+}
+
+/// Verify a JoinSplit against the state's current accumulator
+///
+/// `joinsplit` is the JoinSplit's consensus encoding. Returns
+/// [`LELANTUS_OK`] for a valid JoinSplit and the `ProofVerificationFailed`
+/// code for one whose proofs do not verify.
+///
+/// # Safety
+///
+/// `state` must be a live handle and `joinsplit` must point to
+/// `joinsplit_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn lelantus_verify_joinsplit(
+    state: *const LelantusHandle,
+    joinsplit: *const u8,
+    joinsplit_len: usize,
+) -> i32 {
+    // SAFETY: forwarded from this function's contract
+    let (Some(handle), Some(joinsplit)) = (unsafe { state.as_ref() }, unsafe {
+        bytes(joinsplit, joinsplit_len)
+    }) else {
+        return LELANTUS_ERR_NULL_POINTER;
+    };
+    guard(|| {
+        let joinsplit = JoinSplit::consensus_decode(joinsplit)?;
+        match handle.state.verify_joinsplit(&joinsplit)? {
+            true => Ok(LELANTUS_OK),
+            false => Err(LelantusError::ProofVerificationFailed),
+        }
+    })
+}
+
+/// Whether `code` means the data breaks consensus rules
+///
+/// See [`LelantusError::is_consensus_failure`]; boundary codes never do.
+#[no_mangle]
+pub extern "C" fn lelantus_is_consensus_failure(code: i32) -> bool {
+    u16::try_from(code).is_ok_and(LelantusError::is_consensus_failure_code)
+}
+
+/// Static, NUL-terminated description of `code`
+///
+/// Error codes read [`LelantusError::description_for_code`].
+#[no_mangle]
+pub extern "C" fn lelantus_error_message(code: i32) -> *const c_char {
+    let message: &CStr = match code {
+        LELANTUS_OK => c"ok",
+        LELANTUS_ERR_NULL_POINTER => c"null pointer argument",
+        LELANTUS_ERR_PANIC => c"internal panic",
+        _ => u16::try_from(code).map_or(c"unknown error", LelantusError::description_for_code),
+    };
+    message.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_through_ffi() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[1_000])?;
+        let joinsplit = state.create_joinsplit(coins.clone(), vec![900], 100)?;
+        let encoded = joinsplit.consensus_encode()?;
+        let coin = coins[0].0.consensus_encode()?;
+
+        let mut handle = std::ptr::null_mut();
+        // SAFETY: every pointer below is valid for the stated length
+        unsafe {
+            assert_eq!(
+                lelantus_state_new(std::ptr::null(), 0, &mut handle),
+                LELANTUS_OK
+            );
+
+            // The fresh state has no anonymity set holding the spent coin
+            let code = lelantus_verify_joinsplit(handle, encoded.as_ptr(), encoded.len());
+            assert_eq!(
+                code,
+                i32::from(LelantusError::UnknownAnonymitySet(0).code())
+            );
+            assert!(lelantus_is_consensus_failure(code));
+
+            assert_eq!(
+                lelantus_accumulator_add(handle, coin.as_ptr(), coin.len()),
+                LELANTUS_OK
+            );
+            assert_eq!(
+                lelantus_verify_joinsplit(handle, encoded.as_ptr(), encoded.len()),
+                LELANTUS_OK
+            );
+
+            let code = lelantus_verify_joinsplit(handle, encoded.as_ptr(), 3);
+            assert_eq!(
+                code,
+                i32::from(LelantusError::MalformedTransaction(String::new()).code())
+            );
+            assert!(lelantus_is_consensus_failure(code));
+            assert_eq!(
+                lelantus_verify_joinsplit(handle, std::ptr::null(), 8),
+                LELANTUS_ERR_NULL_POINTER
+            );
+            lelantus_state_free(handle);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_codes_match_errors() {
+        for error in [
+            LelantusError::InvalidInputCount,
+            LelantusError::DoubleSpend,
+            LelantusError::invalid_parameter("seed"),
+            LelantusError::WorkerFailed(String::new()),
+        ] {
+            let code = i32::from(error.code());
+            assert_eq!(
+                lelantus_is_consensus_failure(code),
+                error.is_consensus_failure()
+            );
+            // SAFETY: messages are static NUL-terminated strings
+            let message = unsafe { CStr::from_ptr(lelantus_error_message(code)) };
+            assert_ne!(message, c"unknown error");
+        }
+        assert!(!lelantus_is_consensus_failure(LELANTUS_ERR_PANIC));
+    }
+}
@@ -0,0 +1,298 @@
+//! Inner-product arguments
+//!
+//! Proves knowledge of vectors `a` and `b` with
+//! `P = <a, G> + <b, H> + <a, b>·U` for public generator vectors `G`, `H`
+//! and a point `U`, as in Bulletproofs. Each round halves the vectors under
+//! a transcript challenge, so a proof over `n` entries holds `2·log2(n)`
+//! points and two scalars. The argument runs on its caller's transcript, so
+//! its challenges also bind the statement `P` was built from.
+
+use crate::errors::{LelantusError, Result};
+use crate::pedersen::{decode, decode_scalar, encode, scalar_from_digest, POINT_SIZE, SCALAR_SIZE};
+use crate::prelude::*;
+use crate::serialization::binary::{self, LENGTH_PREFIX_SIZE, U64_SIZE};
+use crate::serialization::LelantusSerializable;
+use crate::transcript::ProofTranscript;
+use borsh::{BorshDeserialize, BorshSerialize};
+use p521::elliptic_curve::PrimeField;
+use p521::{ProjectivePoint, Scalar};
+use serde::{Deserialize, Serialize};
+
+/// Inner-product argument
+///
+/// Points are compressed SEC1 encodings, scalars big-endian.
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+)]
+pub struct InnerProductProof {
+    /// Cross term `L` of each round
+    pub left: Vec<Vec<u8>>,
+
+    /// Cross term `R` of each round
+    pub right: Vec<Vec<u8>>,
+
+    /// Final entry of `a`
+    pub a: Vec<u8>,
+
+    /// Final entry of `b`
+    pub b: Vec<u8>,
+}
+
+impl LelantusSerializable for InnerProductProof {}
+
+/// What a verifier needs from an inner-product proof to check it against `P`
+///
+/// The proof holds when
+/// `P + Σ cross_terms = a·<weights, G> + b·<weights⁻¹, H> + a·b·U`.
+pub(crate) struct InnerProductCheck {
+    /// Final entry of `a`
+    pub(crate) a: Scalar,
+
+    /// Final entry of `b`
+    pub(crate) b: Scalar,
+
+    /// Weight of each `G_i` in the folded generator; `H_i` takes its
+    /// inverse, which is the weight of `G_{n-1-i}`
+    pub(crate) weights: Vec<Scalar>,
+
+    /// `L` and `R` of each round with their weights `u²` and `u⁻²`
+    pub(crate) cross_terms: Vec<(ProjectivePoint, Scalar)>,
+}
+
+/// `<a, b>`
+pub(crate) fn inner_product(a: &[Scalar], b: &[Scalar]) -> Scalar {
+    a.iter()
+        .zip(b)
+        .fold(Scalar::ZERO, |sum, (x, y)| sum + *x * y)
+}
+
+/// `Σ scalars_i·points_i`
+pub(crate) fn multiply(points: &[ProjectivePoint], scalars: &[Scalar]) -> ProjectivePoint {
+    points
+        .iter()
+        .zip(scalars)
+        .fold(ProjectivePoint::IDENTITY, |sum, (point, scalar)| {
+            sum + *point * scalar
+        })
+}
+
+/// Challenge of a round and its inverse, after absorbing its cross terms
+fn round_challenge(
+    transcript: &mut ProofTranscript,
+    left: &[u8],
+    right: &[u8],
+) -> Result<(Scalar, Scalar)> {
+    transcript.append_message(b"ipa-left", left);
+    transcript.append_message(b"ipa-right", right);
+    let challenge = scalar_from_digest(&transcript.challenge_bytes(b"ipa-challenge"));
+    let inverse = Option::<Scalar>::from(challenge.invert()).ok_or(LelantusError::InvalidProof)?;
+    Ok((challenge, inverse))
+}
+
+/// `lo·x + hi·y`, entry by entry, for the halves of `values`
+fn fold<T>(values: &[T], x: Scalar, y: Scalar) -> Vec<T>
+where
+    T: Copy + core::ops::Mul<Scalar, Output = T> + core::ops::Add<Output = T>,
+{
+    let (lo, hi) = values.split_at(values.len() / 2);
+    lo.iter().zip(hi).map(|(l, h)| *l * x + *h * y).collect()
+}
+
+impl InnerProductProof {
+    /// Prove `<a, b>` for `P = <a, g> + <b, h> + <a, b>·u`
+    ///
+    /// Every vector must have the same power-of-two length.
+    pub(crate) fn create(
+        transcript: &mut ProofTranscript,
+        mut g: Vec<ProjectivePoint>,
+        mut h: Vec<ProjectivePoint>,
+        u: ProjectivePoint,
+        mut a: Vec<Scalar>,
+        mut b: Vec<Scalar>,
+    ) -> Result<Self> {
+        let length = a.len();
+        if !length.is_power_of_two() || [b.len(), g.len(), h.len()] != [length; 3] {
+            return Err(LelantusError::RangeProofError(
+                "inner-product vectors must share a power-of-two length".to_string(),
+            ));
+        }
+        transcript.append_u64(b"ipa-length", length as u64);
+
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        while a.len() > 1 {
+            let half = a.len() / 2;
+            let (a_lo, a_hi) = a.split_at(half);
+            let (b_lo, b_hi) = b.split_at(half);
+            let (g_lo, g_hi) = g.split_at(half);
+            let (h_lo, h_hi) = h.split_at(half);
+
+            let l = encode(
+                &(multiply(g_hi, a_lo) + multiply(h_lo, b_hi) + u * inner_product(a_lo, b_hi)),
+            );
+            let r = encode(
+                &(multiply(g_lo, a_hi) + multiply(h_hi, b_lo) + u * inner_product(a_hi, b_lo)),
+            );
+            let (x, x_inv) = round_challenge(transcript, &l, &r)?;
+            left.push(l);
+            right.push(r);
+
+            a = fold(&a, x, x_inv);
+            b = fold(&b, x_inv, x);
+            g = fold(&g, x_inv, x);
+            h = fold(&h, x, x_inv);
+        }
+
+        Ok(Self {
+            left,
+            right,
+            a: a[0].to_repr().to_vec(),
+            b: b[0].to_repr().to_vec(),
+        })
+    }
+
+    /// Check that the proof has one round per halving of `length` and
+    /// fields of point and scalar size
+    pub(crate) fn check_encoding(&self, length: usize) -> Result<()> {
+        let rounds = length.trailing_zeros() as usize;
+        if !length.is_power_of_two()
+            || self.left.len() != rounds
+            || self.right.len() != rounds
+            || self
+                .left
+                .iter()
+                .chain(&self.right)
+                .any(|point| point.len() != POINT_SIZE)
+            || self.a.len() != SCALAR_SIZE
+            || self.b.len() != SCALAR_SIZE
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Encoded size of a proof with `rounds` rounds
+    pub(crate) fn encoded_size(rounds: usize) -> usize {
+        2 * (U64_SIZE + rounds * (LENGTH_PREFIX_SIZE + POINT_SIZE))
+            + 2 * (LENGTH_PREFIX_SIZE + SCALAR_SIZE)
+    }
+
+    /// Append the proof in the canonical binary format
+    pub(crate) fn put(&self, buf: &mut Vec<u8>) -> Result<()> {
+        for points in [&self.left, &self.right] {
+            binary::put_u64(buf, points.len() as u64);
+            for point in points {
+                binary::put_bytes(buf, point)?;
+            }
+        }
+        binary::put_bytes(buf, &self.a)?;
+        binary::put_bytes(buf, &self.b)
+    }
+
+    /// Read a proof written by [`put`](Self::put)
+    pub(crate) fn read(reader: &mut binary::Reader<'_>) -> Result<Self> {
+        let read_points = |reader: &mut binary::Reader<'_>| -> Result<Vec<Vec<u8>>> {
+            let count = reader.read_u64()?;
+            (0..count).map(|_| reader.read_bytes()).collect()
+        };
+        let left = read_points(reader)?;
+        let right = read_points(reader)?;
+        Ok(Self {
+            left,
+            right,
+            a: reader.read_bytes()?,
+            b: reader.read_bytes()?,
+        })
+    }
+
+    /// Replay the rounds of a proof over `length` entries on `transcript`
+    ///
+    /// Fails with `InvalidProof` if the proof does not have one round per
+    /// halving of `length` or holds malformed points or scalars.
+    pub(crate) fn verification(
+        &self,
+        transcript: &mut ProofTranscript,
+        length: usize,
+    ) -> Result<InnerProductCheck> {
+        let rounds = length.trailing_zeros() as usize;
+        if !length.is_power_of_two() || self.left.len() != rounds || self.right.len() != rounds {
+            return Err(LelantusError::InvalidProof);
+        }
+        transcript.append_u64(b"ipa-length", length as u64);
+
+        let mut squares = Vec::with_capacity(rounds);
+        let mut product_inv = Scalar::ONE;
+        let mut cross_terms = Vec::with_capacity(2 * rounds);
+        for (l, r) in self.left.iter().zip(&self.right) {
+            let (x, x_inv) = round_challenge(transcript, l, r)?;
+            cross_terms.push((decode(l).ok_or(LelantusError::InvalidProof)?, x.square()));
+            cross_terms.push((
+                decode(r).ok_or(LelantusError::InvalidProof)?,
+                x_inv.square(),
+            ));
+            squares.push(x.square());
+            product_inv *= x_inv;
+        }
+
+        // Entry i takes x for every round where it sat in the upper half and
+        // x⁻¹ otherwise; round k splits on bit rounds-1-k of i
+        let mut weights = Vec::with_capacity(length);
+        weights.push(product_inv);
+        for i in 1..length {
+            let top = (usize::BITS - 1 - i.leading_zeros()) as usize;
+            weights.push(weights[i - (1 << top)] * squares[rounds - 1 - top]);
+        }
+
+        Ok(InnerProductCheck {
+            a: decode_scalar(&self.a).ok_or(LelantusError::InvalidProof)?,
+            b: decode_scalar(&self.b).ok_or(LelantusError::InvalidProof)?,
+            weights,
+            cross_terms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pedersen::Generators;
+    use crate::transcript::ProofDomain;
+
+    #[test]
+    fn test_inner_product_roundtrip() -> Result<()> {
+        let generators = Generators::new(b"seed");
+        let (g, h) = generators.vector_generators(8);
+        let u = generators.inner_product_generator();
+        let a: Vec<Scalar> = (1..=8u64).map(Scalar::from).collect();
+        let b: Vec<Scalar> = (11..=18u64).map(Scalar::from).collect();
+        let p = multiply(&g, &a) + multiply(&h, &b) + u * inner_product(&a, &b);
+
+        let mut transcript = ProofTranscript::new(ProofDomain::InnerProduct);
+        let proof = InnerProductProof::create(&mut transcript, g.clone(), h.clone(), u, a, b)?;
+        assert_eq!(proof.left.len(), 3);
+
+        let holds = |proof: &InnerProductProof, p: ProjectivePoint| -> Result<bool> {
+            let mut transcript = ProofTranscript::new(ProofDomain::InnerProduct);
+            let check = proof.verification(&mut transcript, 8)?;
+            let inverses: Vec<Scalar> = check.weights.iter().rev().copied().collect();
+            let folded = multiply(&g, &check.weights) * check.a
+                + multiply(&h, &inverses) * check.b
+                + u * (check.a * check.b);
+            let lhs = check
+                .cross_terms
+                .iter()
+                .fold(p, |sum, (point, weight)| sum + *point * weight);
+            Ok(lhs == folded)
+        };
+        assert!(holds(&proof, p)?);
+
+        // Another inner product, or a dropped round, does not verify
+        assert!(!holds(&proof, p + u)?);
+        let mut short = proof.clone();
+        short.left.pop();
+        assert!(short
+            .verification(&mut ProofTranscript::new(ProofDomain::InnerProduct), 8)
+            .is_err());
+        Ok(())
+    }
+}
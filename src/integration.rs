@@ -0,0 +1,237 @@
+//! Embedding JoinSplits in silver-core transactions
+//!
+//! A JoinSplit travels in a zero-value [`TxOutput`] whose script is
+//! [`SCRIPT_MARKER`], a version byte and the JoinSplit's consensus encoding.
+//! Validators pull JoinSplits back out of a [`Transaction`] with
+//! [`extract_joinsplits`].
+//!
+//! [`JoinSplit::to_core_tx`] and [`JoinSplit::from_core_tx`] wrap this for
+//! a transaction carrying a single JoinSplit. Every amount in a JoinSplit is
+//! already in mist, the `silver-core` base unit; [`Amount::from_slvr`]
+//! converts whole SLVR.
+//!
+//! The binding signature signs the digest of the enclosing transaction, so
+//! that digest cannot cover the signature itself. Hosts hash a JoinSplit
+//! output's [`txid_contribution`] in place of its script.
+
+use crate::amount::Amount;
+use crate::errors::{LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use sha2::{Digest, Sha256};
+use silver_core::{Transaction, TxOutput};
+
+/// Prefix marking an output script as carrying a JoinSplit: `OP_RETURN`
+/// followed by a protocol tag
+pub const SCRIPT_MARKER: &[u8] = b"\x6asilver-lelantus";
+
+/// Version of the JoinSplit script layout
+pub const SCRIPT_VERSION: u8 = 1;
+
+/// Domain separator for txid contributions
+const TXID_CONTRIBUTION_DOMAIN: &[u8] = b"lelantus_txid_contribution";
+
+/// Output script carrying `joinsplit`
+pub fn joinsplit_script(joinsplit: &JoinSplit) -> Result<Vec<u8>> {
+    let mut script = SCRIPT_MARKER.to_vec();
+    script.push(SCRIPT_VERSION);
+    script.extend_from_slice(&joinsplit.consensus_encode()?);
+    Ok(script)
+}
+
+/// Whether `script` carries a JoinSplit
+pub fn is_joinsplit_script(script: &[u8]) -> bool {
+    script.starts_with(SCRIPT_MARKER)
+}
+
+/// Decode the JoinSplit carried by `script`
+///
+/// Returns `None` for scripts without the marker. Marked scripts with an
+/// unknown version or a malformed JoinSplit fail with `MalformedTransaction`.
+pub fn parse_joinsplit_script(script: &[u8]) -> Result<Option<JoinSplit>> {
+    let Some(payload) = script.strip_prefix(SCRIPT_MARKER) else {
+        return Ok(None);
+    };
+    match payload.split_first() {
+        Some((&SCRIPT_VERSION, encoded)) => Ok(Some(JoinSplit::consensus_decode(encoded)?)),
+        _ => Err(LelantusError::MalformedTransaction(
+            "unsupported JoinSplit script version".to_string(),
+        )),
+    }
+}
+
+/// Add a zero-value output carrying `joinsplit` to `tx`
+pub fn embed_joinsplit(tx: &mut Transaction, joinsplit: &JoinSplit) -> Result<()> {
+    tx.outputs.push(TxOutput {
+        value: 0,
+        script_pubkey: joinsplit_script(joinsplit)?,
+    });
+    Ok(())
+}
+
+/// Every JoinSplit carried by `tx`, in output order
+///
+/// Fails if any marked output does not decode, so a validator never
+/// silently skips a malformed JoinSplit.
+pub fn extract_joinsplits(tx: &Transaction) -> Result<Vec<JoinSplit>> {
+    let mut joinsplits = Vec::new();
+    for output in &tx.outputs {
+        if let Some(joinsplit) = parse_joinsplit_script(&output.script_pubkey)? {
+            joinsplits.push(joinsplit);
+        }
+    }
+    Ok(joinsplits)
+}
+
+/// Bytes a host hashes into its txid for an output carrying `joinsplit`
+///
+/// Covers everything but the binding signature, so the JoinSplit can be
+/// signed after the txid is known without changing it.
+pub fn txid_contribution(joinsplit: &JoinSplit) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(TXID_CONTRIBUTION_DOMAIN);
+    hasher.update(joinsplit.binding_body()?);
+    Ok(hasher.finalize().into())
+}
+
+impl JoinSplit {
+    /// Embed in the core transaction `tx`
+    ///
+    /// Returns the transparent fee the JoinSplit pays, in mist, for the
+    /// host to credit to the transaction's fee.
+    pub fn to_core_tx(&self, tx: &mut Transaction) -> Result<Amount> {
+        let fee = Amount::new(self.fee)?;
+        embed_joinsplit(tx, self)?;
+        Ok(fee)
+    }
+
+    /// The JoinSplit carried by the core transaction `tx`
+    ///
+    /// Fails with `MalformedTransaction` unless `tx` carries exactly one
+    /// JoinSplit, and with `BalanceMismatch` if its fee exceeds
+    /// [`MAX_MONEY`](crate::amount::MAX_MONEY).
+    pub fn from_core_tx(tx: &Transaction) -> Result<Self> {
+        let mut joinsplits = extract_joinsplits(tx)?;
+        if joinsplits.len() != 1 {
+            return Err(LelantusError::MalformedTransaction(format!(
+                "expected one JoinSplit, found {}",
+                joinsplits.len()
+            )));
+        }
+        let joinsplit = joinsplits.remove(0);
+        Amount::new(joinsplit.fee)?;
+        Ok(joinsplit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::Commitment;
+    use crate::parameters::LelantusParameters;
+    use crate::serialization::LelantusSerializable;
+    use crate::LelantusState;
+    use silver_core::MIST_PER_SLVR;
+
+    /// Transaction with one plain output paying `script`
+    fn payment(script: &[u8]) -> Transaction {
+        let mut tx = Transaction::default();
+        tx.outputs.push(TxOutput {
+            value: 1_000,
+            script_pubkey: script.to_vec(),
+        });
+        tx
+    }
+
+    /// Digest over plain scripts and JoinSplit contributions
+    fn tx_digest(tx: &Transaction) -> Result<Vec<u8>> {
+        let mut hasher = Sha256::new();
+        for output in &tx.outputs {
+            match parse_joinsplit_script(&output.script_pubkey)? {
+                Some(joinsplit) => hasher.update(txid_contribution(&joinsplit)?),
+                None => hasher.update(&output.script_pubkey),
+            }
+        }
+        Ok(hasher.finalize().to_vec())
+    }
+
+    #[test]
+    fn test_embed_and_extract() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[1_000])?;
+        let mut joinsplit = state.create_joinsplit(coins, vec![900], 100)?;
+
+        // Embed unsigned, then sign the digest the embedded output yields
+        let mut tx = payment(b"payment");
+        embed_joinsplit(&mut tx, &joinsplit)?;
+        assert_eq!(tx.outputs[1].value, 0);
+        let digest = tx_digest(&tx)?;
+        joinsplit.sign_binding(&digest)?;
+        tx.outputs[1].script_pubkey = joinsplit_script(&joinsplit)?;
+        assert_eq!(tx_digest(&tx)?, digest);
+
+        let extracted = extract_joinsplits(&tx)?;
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].txid()?, joinsplit.txid()?);
+        assert!(extracted[0].verify_binding(&digest)?);
+        assert!(state.verify_joinsplit(&extracted[0])?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_core_tx_roundtrip() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[Amount::from_slvr(2)?.as_mist()])?;
+        let fee = Amount::new(1_000)?;
+        let output = 2 * MIST_PER_SLVR - fee.as_mist();
+        let joinsplit = state.create_joinsplit(coins, vec![output], fee.as_mist())?;
+
+        let mut tx = payment(b"payment");
+        assert_eq!(joinsplit.to_core_tx(&mut tx)?, fee);
+
+        let decoded = JoinSplit::from_core_tx(&tx)?;
+        let values = |commitments: &[Commitment]| {
+            commitments
+                .iter()
+                .map(|c| c.value.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&decoded.inputs), values(&joinsplit.inputs));
+        assert_eq!(values(&decoded.outputs), values(&joinsplit.outputs));
+        assert_eq!(decoded.fee, fee.as_mist());
+        assert_eq!(
+            LelantusSerializable::to_bytes(&decoded.proof)?,
+            LelantusSerializable::to_bytes(&joinsplit.proof)?
+        );
+        assert!(state.verify_joinsplit(&decoded)?);
+
+        // Exactly one JoinSplit per core transaction
+        joinsplit.to_core_tx(&mut tx)?;
+        assert!(JoinSplit::from_core_tx(&tx).is_err());
+        assert!(JoinSplit::from_core_tx(&Transaction::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_scripts_are_rejected() {
+        let mut tx = payment(b"payment");
+        assert!(matches!(extract_joinsplits(&tx), Ok(joinsplits) if joinsplits.is_empty()));
+
+        let mut unknown_version = SCRIPT_MARKER.to_vec();
+        unknown_version.push(SCRIPT_VERSION + 1);
+        tx.outputs.push(TxOutput {
+            value: 0,
+            script_pubkey: unknown_version,
+        });
+        assert!(matches!(
+            extract_joinsplits(&tx),
+            Err(LelantusError::MalformedTransaction(_))
+        ));
+
+        let mut truncated = SCRIPT_MARKER.to_vec();
+        truncated.extend_from_slice(&[SCRIPT_VERSION, 0]);
+        assert!(matches!(
+            parse_joinsplit_script(&truncated),
+            Err(LelantusError::MalformedTransaction(_))
+        ));
+    }
+}
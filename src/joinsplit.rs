@@ -1,27 +1,150 @@
 //! JoinSplit transaction for Lelantus
 
-use crate::commitment::Commitment;
-use crate::proof::{RangeProof, ZKProof};
+use crate::binding::{BindingKey, BindingSignature, SCALAR_SIZE};
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::disclosure::{DisclosurePackage, PaymentProof};
+use crate::errors::{ErrorContext, LelantusError};
+use crate::keys::ViewKey;
+use crate::merkle::{self, MerkleProof};
+use crate::nullifier::NULLIFIER_SIZE;
+use crate::one_out_of_many::OneOutOfManyProof;
+use crate::parameters::{LelantusParameters, PrivacyLevel};
+use crate::pedersen::{decode, encode, POINT_SIZE};
+use crate::proof::{AggregatedRangeProof, RangeProof, ZKProof};
+use crate::serial::SerialNumber;
+use crate::serialization::binary::{LENGTH_PREFIX_SIZE, U64_SIZE};
+use crate::serialization::{binary, LelantusSerializable};
+use crate::surjection::AssetSurjectionProof;
+use crate::verifier::PreparedVerifier;
+use crate::witness::{constant_time_compare, open_amount, Witness, AMOUNT_METADATA_OFFSET};
+use crate::{LelantusState, MAX_JOINSPLIT_INPUTS};
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hmac::{Hmac, Mac};
+use p521::elliptic_curve::Field;
+use p521::{ProjectivePoint, Scalar};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use serde_json;
+use sha2::{Digest, Sha256, Sha512};
+
+/// Size of an encrypted per-output change flag
+pub const CHANGE_TAG_SIZE: usize = 32;
+
+/// Domain separator for change flags
+const CHANGE_TAG_DOMAIN: &[u8] = b"lelantus_change_tag";
+
+/// Largest memo that can be attached to an output
+pub const MEMO_SIZE: usize = 512;
+
+/// Size of an encrypted memo: nonce, length-prefixed padded memo, GCM tag
+pub const ENCRYPTED_MEMO_SIZE: usize = MEMO_NONCE_SIZE + 2 + MEMO_SIZE + 16;
+
+/// Size of the AES-GCM nonce of an encrypted memo
+const MEMO_NONCE_SIZE: usize = 12;
+
+/// Domain separator for memo encryption keys
+const MEMO_DOMAIN: &[u8] = b"lelantus_memo";
+
+/// Size of the AES-GCM nonce of the randomness in an amount note
+const OPENING_NONCE_SIZE: usize = 12;
+
+/// Domain separator for the keys sealing output randomness
+const OPENING_DOMAIN: &[u8] = b"lelantus_output_opening";
+
+/// Size of an amount note for outputs with `randomness_len` bytes of
+/// randomness: the sealed amount, then the randomness under AES-256-GCM
+pub const fn amount_note_size(randomness_len: usize) -> usize {
+    AMOUNT_METADATA_OFFSET + OPENING_NONCE_SIZE + randomness_len + 16
+}
+
+/// Domain separator for transaction ids
+const TXID_DOMAIN: &[u8] = b"lelantus_txid";
+
+/// Size of a hex-encoded SHA-512 digest
+const DIGEST_SIZE: usize = 128;
+
+/// Encoded size of a published commitment (hex-encoded point)
+const COMMITMENT_SIZE: usize = LENGTH_PREFIX_SIZE + 2 * POINT_SIZE;
+
+/// Encoded size of an input: its commitment, nullifier, anonymity set index
+/// and membership proof
+fn input_size(privacy_level: PrivacyLevel) -> usize {
+    COMMITMENT_SIZE
+        + LENGTH_PREFIX_SIZE
+        + NULLIFIER_SIZE
+        + U64_SIZE
+        + LENGTH_PREFIX_SIZE
+        + OneOutOfManyProof::encoded_size(privacy_level)
+}
+
+/// Encoded size of an output: its commitment and change flag
+const OUTPUT_SIZE: usize = COMMITMENT_SIZE + LENGTH_PREFIX_SIZE + CHANGE_TAG_SIZE;
+
+/// Encoded size of the binding key and binding signature
+const BINDING_SIZE: usize = 4 * LENGTH_PREFIX_SIZE + U64_SIZE + 2 * POINT_SIZE + SCALAR_SIZE;
+
+/// Encoded size of the zero-knowledge proof: nonce point, challenge, the two
+/// mask responses and the anonymity set size
+const ZK_PROOF_SIZE: usize =
+    3 * LENGTH_PREFIX_SIZE + 2 * POINT_SIZE + DIGEST_SIZE + 4 * SCALAR_SIZE + U64_SIZE;
 
 /// JoinSplit proof
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct JoinSplitProof {
-    /// Range proofs for outputs
+    /// Range proofs for outputs, one per output; empty when aggregated
     pub range_proofs: Vec<RangeProof>,
 
+    /// Range proof covering all outputs, replacing `range_proofs`
+    #[serde(default)]
+    pub aggregated_range_proof: Option<AggregatedRangeProof>,
+
+    /// Proof that the asset tags the aggregated range proof is checked
+    /// against blind assets of the transaction, present when it moves assets
+    /// other than the native one
+    #[serde(default)]
+    pub asset_proof: Option<AssetSurjectionProof>,
+
     /// Zero-knowledge proof
     pub zk_proof: ZKProof,
+
+    /// Anonymity set of each input, by index; see
+    /// [`Accumulator::anonymity_set`](crate::accumulator::Accumulator::anonymity_set)
+    pub anonymity_sets: Vec<u64>,
+
+    /// Proof that each input commits to the value of some coin in its
+    /// anonymity set, without revealing which
+    pub membership_proofs: Vec<OneOutOfManyProof>,
+}
+
+impl LelantusSerializable for JoinSplitProof {
+    const MIN_ENCODING_VERSION: u8 = 7;
 }
 
 /// JoinSplit transaction
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Inputs and outputs keep their openings in memory for the creator, but
+/// every encoding writes only the commitment values; see
+/// [`set_amount_note`](Self::set_amount_note) for how recipients learn theirs.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct JoinSplit {
-    /// Input commitments
+    /// Fresh commitments to the values of the spent coins
+    ///
+    /// The coins themselves are not revealed: each input is only proven to
+    /// commit to the value of some coin in its anonymity set.
+    #[serde(with = "public_commitments")]
+    #[borsh(
+        serialize_with = "public_commitments::serialize_borsh",
+        deserialize_with = "public_commitments::deserialize_borsh"
+    )]
     pub inputs: Vec<Commitment>,
 
     /// Output commitments
+    #[serde(with = "public_commitments")]
+    #[borsh(
+        serialize_with = "public_commitments::serialize_borsh",
+        deserialize_with = "public_commitments::deserialize_borsh"
+    )]
     pub outputs: Vec<Commitment>,
 
     /// Proof
@@ -29,6 +152,140 @@ pub struct JoinSplit {
 
     /// Transaction fee
     pub fee: u64,
+
+    /// Serial numbers revealed by the inputs, in input order
+    #[serde(default)]
+    pub nullifiers: Vec<SerialNumber>,
+
+    /// Per-output change flags, random unless marked with the sender's view key
+    #[serde(default)]
+    pub change_tags: Vec<Vec<u8>>,
+
+    /// Per-output encrypted memos, random unless set for a recipient
+    #[serde(default)]
+    pub memos: Vec<Vec<u8>>,
+
+    /// Per-output amounts sealed to the recipient's view key, random unless set
+    #[serde(default)]
+    pub amount_notes: Vec<Vec<u8>>,
+
+    /// Per-output ephemeral keys of stealth payments, random points unless set
+    #[serde(default)]
+    pub ephemeral_keys: Vec<Vec<u8>>,
+
+    /// Binding verification key, committed to by the zero-knowledge proof
+    #[serde(default)]
+    pub binding_key: Vec<u8>,
+
+    /// Signature over the JoinSplit and its enclosing transaction
+    #[serde(default)]
+    pub binding_signature: Option<BindingSignature>,
+
+    /// Secret binding key, held by the creator and never serialized
+    #[serde(skip)]
+    #[borsh(skip)]
+    pub binding_secret: Option<BindingKey>,
+
+    /// Zero-byte padding to a fixed serialized size, ignored by verification
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "zero_padding")]
+    #[borsh(
+        serialize_with = "zero_padding::serialize_borsh",
+        deserialize_with = "zero_padding::deserialize_borsh"
+    )]
+    pub padding: Vec<u8>,
+}
+
+impl LelantusSerializable for JoinSplit {
+    const MIN_ENCODING_VERSION: u8 = 7;
+}
+
+/// Encodings of commitments that leave out their openings
+///
+/// Only the values are written, and commitments decode with empty
+/// randomness. Publishing the randomness would reveal every amount and let
+/// anyone recompute the balance proof's blinding excess.
+mod public_commitments {
+    use crate::commitment::Commitment;
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::io;
+
+    #[allow(clippy::ptr_arg)]
+    pub fn serialize_borsh<W: io::Write>(
+        commitments: &Vec<Commitment>,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let values: Vec<&Vec<u8>> = commitments.iter().map(|c| &c.value).collect();
+        BorshSerialize::serialize(&values, writer)
+    }
+
+    pub fn deserialize_borsh<R: io::Read>(reader: &mut R) -> io::Result<Vec<Commitment>> {
+        let values = Vec::<Vec<u8>>::deserialize_reader(reader)?;
+        Ok(values.into_iter().map(Commitment::from_value).collect())
+    }
+
+    pub fn serialize<S: Serializer>(
+        commitments: &[Commitment],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(commitments.iter().map(|c| &c.value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Commitment>, D::Error> {
+        let values = <Vec<Vec<u8>> as Deserialize>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(Commitment::from_value).collect())
+    }
+}
+
+/// Encodings of padding that only admit zero bytes
+///
+/// Serde encodes padding as a string of `'0'` characters, one per byte, so
+/// padding can hit any serialized size exactly. Borsh encodes it as plain
+/// bytes. Either way only zero bytes can be encoded, so padding carries no
+/// data and cannot be varied without changing the transaction length.
+mod zero_padding {
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::io;
+
+    fn non_zero() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "padding must be zero bytes")
+    }
+
+    #[allow(clippy::ptr_arg)]
+    pub fn serialize_borsh<W: io::Write>(padding: &Vec<u8>, writer: &mut W) -> io::Result<()> {
+        if padding.iter().any(|&b| b != 0) {
+            return Err(non_zero());
+        }
+        BorshSerialize::serialize(padding, writer)
+    }
+
+    pub fn deserialize_borsh<R: io::Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+        let padding = Vec::<u8>::deserialize_reader(reader)?;
+        if padding.iter().any(|&b| b != 0) {
+            return Err(non_zero());
+        }
+        Ok(padding)
+    }
+
+    pub fn serialize<S: Serializer>(padding: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if padding.iter().any(|&b| b != 0) {
+            return Err(S::Error::custom("padding must be zero bytes"));
+        }
+        serializer.serialize_str(&"0".repeat(padding.len()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = <String as Deserialize>::deserialize(deserializer)?;
+        if encoded.bytes().any(|b| b != b'0') {
+            return Err(D::Error::custom("padding must be zero bytes"));
+        }
+        Ok(vec![0; encoded.len()])
+    }
 }
 
 impl JoinSplit {
@@ -42,20 +299,1100 @@ impl JoinSplit {
         self.outputs.len()
     }
 
+    /// Consensus-encoded size in bytes of a signed JoinSplit of the given
+    /// shape at `privacy_level`
+    ///
+    /// See [`encoded_size`](Self::encoded_size). Memos, amount notes and
+    /// ephemeral keys are left out; see
+    /// [`FeeEstimator`](crate::fee::FeeEstimator) to include them.
+    pub fn estimated_size(
+        inputs: usize,
+        outputs: usize,
+        privacy_level: PrivacyLevel,
+    ) -> crate::errors::Result<usize> {
+        Self::encoded_size(
+            &LelantusParameters::with_privacy_level(privacy_level),
+            inputs,
+            outputs,
+        )
+    }
+
+    /// Consensus-encoded size in bytes of a signed JoinSplit of the given
+    /// shape under `parameters`
+    ///
+    /// Computed from the encoding layout, so nothing is proven. Outputs are
+    /// taken to share one asset, and memos, amount notes and ephemeral keys
+    /// to be absent.
+    pub(crate) fn encoded_size(
+        parameters: &LelantusParameters,
+        inputs: usize,
+        outputs: usize,
+    ) -> crate::errors::Result<usize> {
+        if inputs == 0 || inputs > MAX_JOINSPLIT_INPUTS {
+            return Err(LelantusError::InvalidInputCount);
+        }
+        if outputs == 0 || outputs > parameters.max_joinsplit_outputs() {
+            return Err(LelantusError::InvalidOutputCount);
+        }
+        Ok(Self::layout_size(parameters, inputs, outputs))
+    }
+
+    /// Encoded size of a JoinSplit of the given shape, for any counts
+    fn layout_size(parameters: &LelantusParameters, inputs: usize, outputs: usize) -> usize {
+        // Counts of the twelve lists besides the binding signature, and the fee
+        13 * U64_SIZE
+            + inputs * input_size(parameters.privacy_level)
+            + outputs * OUTPUT_SIZE
+            + LENGTH_PREFIX_SIZE
+            + AggregatedRangeProof::encoded_size(parameters, outputs, 1)
+            + LENGTH_PREFIX_SIZE
+            + ZK_PROOF_SIZE
+            + BINDING_SIZE
+            // Empty padding
+            + LENGTH_PREFIX_SIZE
+    }
+
+    /// Size increase from going to `current_inputs + 1` inputs
+    ///
+    /// Lets fee-aware coin selection weigh an extra input against its cost.
+    pub fn marginal_input_cost(current_inputs: usize, privacy_level: PrivacyLevel) -> usize {
+        let parameters = LelantusParameters::with_privacy_level(privacy_level);
+        Self::layout_size(&parameters, current_inputs + 1, 1)
+            - Self::layout_size(&parameters, current_inputs, 1)
+    }
+
+    /// Random change flags for `count` outputs, none of them marked
+    pub(crate) fn decoy_change_tags(count: usize, rng: &mut impl RngCore) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|_| {
+                let mut tag = vec![0u8; CHANGE_TAG_SIZE];
+                rng.fill_bytes(&mut tag);
+                tag
+            })
+            .collect()
+    }
+
+    /// Change flag of `output` under `view_key`: a MAC bound to the commitment
+    fn change_tag(output: &Commitment, view_key: &ViewKey) -> crate::errors::Result<Vec<u8>> {
+        let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(view_key.as_bytes())
+            .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+        mac.update(CHANGE_TAG_DOMAIN);
+        mac.update(&(output.value.len() as u64).to_le_bytes());
+        mac.update(&output.value);
+        Ok(mac.finalize().into_bytes()[..CHANGE_TAG_SIZE].to_vec())
+    }
+
+    /// Mark output `index` as change returning to the holder of `view_key`
+    ///
+    /// The flag is indistinguishable from the random flags of other outputs
+    /// to anyone without the view key.
+    pub fn mark_change_output(
+        &mut self,
+        index: usize,
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<()> {
+        let output = self
+            .outputs
+            .get(index)
+            .ok_or(LelantusError::invalid_parameter("output_index"))?;
+        let tag = Self::change_tag(output, view_key)?;
+
+        if self.change_tags.len() != self.outputs.len() {
+            self.change_tags = Self::decoy_change_tags(self.outputs.len(), &mut rand::thread_rng());
+        }
+        self.change_tags[index] = tag;
+        Ok(())
+    }
+
+    /// Check whether output `index` was marked as change for `view_key`
+    pub fn is_change_output(
+        &self,
+        index: usize,
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<bool> {
+        let output = self
+            .outputs
+            .get(index)
+            .ok_or(LelantusError::invalid_parameter("output_index"))?;
+
+        Ok(match self.change_tags.get(index) {
+            Some(tag) => constant_time_compare(tag, &Self::change_tag(output, view_key)?),
+            None => false,
+        })
+    }
+
+    /// Random `size`-byte notes for `count` outputs, none of them readable
+    pub(crate) fn decoy_notes(count: usize, size: usize) -> Vec<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        (0..count)
+            .map(|_| {
+                let mut note = vec![0u8; size];
+                rng.fill_bytes(&mut note);
+                note
+            })
+            .collect()
+    }
+
+    /// Cipher for data about `output` sealed to `view_key`, separated by
+    /// `domain`
+    fn output_cipher(
+        domain: &[u8],
+        output: &Commitment,
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<Aes256Gcm> {
+        let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(view_key.as_bytes())
+            .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+        mac.update(domain);
+        mac.update(&(output.value.len() as u64).to_le_bytes());
+        mac.update(&output.value);
+        let key = mac.finalize().into_bytes();
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key[..32])))
+    }
+
+    /// Attach `memo` to output `index`, readable with the recipient's `view_key`
+    ///
+    /// The memo is padded to [`MEMO_SIZE`] and encrypted with AES-256-GCM
+    /// bound to the output commitment. Other outputs get random memos of the
+    /// same size, so outputs with and without a memo look alike.
+    pub fn set_memo(
+        &mut self,
+        index: usize,
+        memo: &[u8],
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<()> {
+        let output = self
+            .outputs
+            .get(index)
+            .ok_or(LelantusError::invalid_parameter("output_index"))?;
+        if memo.len() > MEMO_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("memo")
+                    .expected(format!("<= {} bytes", MEMO_SIZE))
+                    .actual(memo.len()),
+            ));
+        }
+
+        let mut plaintext = Vec::with_capacity(2 + MEMO_SIZE);
+        plaintext.extend_from_slice(&(memo.len() as u16).to_le_bytes());
+        plaintext.extend_from_slice(memo);
+        plaintext.resize(2 + MEMO_SIZE, 0);
+
+        let mut nonce = [0u8; MEMO_NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = Self::output_cipher(MEMO_DOMAIN, output, view_key)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &output.value,
+                },
+            )
+            .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+
+        if self.memos.len() != self.outputs.len() {
+            self.memos = Self::decoy_notes(self.outputs.len(), ENCRYPTED_MEMO_SIZE);
+        }
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+        self.memos[index] = encrypted;
+        Ok(())
+    }
+
+    /// Decrypt the memo of output `index` with `view_key`
+    ///
+    /// Returns `None` if the output carries no memo readable with this key,
+    /// which is what wallet scanning sees for other recipients' outputs.
+    pub fn decrypt_memo(
+        &self,
+        index: usize,
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<Option<Vec<u8>>> {
+        let output = self
+            .outputs
+            .get(index)
+            .ok_or(LelantusError::invalid_parameter("output_index"))?;
+        let encrypted = match self.memos.get(index) {
+            Some(encrypted) if encrypted.len() == ENCRYPTED_MEMO_SIZE => encrypted,
+            _ => return Ok(None),
+        };
+
+        let (nonce, ciphertext) = encrypted.split_at(MEMO_NONCE_SIZE);
+        let plaintext = match Self::output_cipher(MEMO_DOMAIN, output, view_key)?.decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &output.value,
+            },
+        ) {
+            Ok(plaintext) => plaintext,
+            Err(_) => return Ok(None),
+        };
+
+        let len = usize::from(u16::from_le_bytes([plaintext[0], plaintext[1]]));
+        if len > MEMO_SIZE {
+            return Err(LelantusError::InvalidProof);
+        }
+        Ok(Some(plaintext[2..2 + len].to_vec()))
+    }
+
+    /// Seal the opening of output `index` to the recipient's `view_key`
+    ///
+    /// The note holds the amount and the commitment randomness, which the
+    /// JoinSplit does not publish otherwise, so the recipient can find the
+    /// output by scanning and later spend it; see
+    /// [`Scanner`](crate::scanner::Scanner). Other outputs get random notes
+    /// of the same size. Only the creator's JoinSplit holds the openings; a
+    /// decoded one fails with `InvalidCommitment`.
+    pub fn set_amount_note(
+        &mut self,
+        index: usize,
+        amount: u64,
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<()> {
+        let output = self
+            .outputs
+            .get(index)
+            .ok_or(LelantusError::invalid_parameter("output_index"))?;
+        if output.randomness.is_empty() {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let mut note = Witness::encrypt_amount_for_view_key(output, index, amount, view_key)?;
+        let mut nonce = [0u8; OPENING_NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let sealed = Self::output_cipher(OPENING_DOMAIN, output, view_key)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &output.randomness,
+                    aad: &output.value,
+                },
+            )
+            .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+        note.extend_from_slice(&nonce);
+        note.extend(sealed);
+
+        let size = amount_note_size(output.randomness.len());
+        if self.amount_notes.len() != self.outputs.len() {
+            self.amount_notes = Self::decoy_notes(self.outputs.len(), size);
+        }
+        self.amount_notes[index] = note;
+        Ok(())
+    }
+
+    /// Open output `index` with `view_key`
+    ///
+    /// Returns the amount and the output commitment with its randomness
+    /// restored from the amount note, or `None` if the note was not sealed to
+    /// this key. Whether the opening matches the commitment is left to the
+    /// caller.
+    pub fn open_output(
+        &self,
+        index: usize,
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<Option<(u64, Commitment)>> {
+        let output = self
+            .outputs
+            .get(index)
+            .ok_or(LelantusError::invalid_parameter("output_index"))?;
+        let note = match self.amount_notes.get(index) {
+            Some(note) if note.len() > amount_note_size(0) => note,
+            _ => return Ok(None),
+        };
+
+        let (sealed_amount, opening) = note.split_at(AMOUNT_METADATA_OFFSET);
+        let Ok(amount) = open_amount(view_key.as_bytes(), output, index, sealed_amount) else {
+            return Ok(None);
+        };
+        let (nonce, ciphertext) = opening.split_at(OPENING_NONCE_SIZE);
+        let Ok(randomness) = Self::output_cipher(OPENING_DOMAIN, output, view_key)?.decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &output.value,
+            },
+        ) else {
+            return Ok(None);
+        };
+
+        Ok(Some((
+            amount,
+            Commitment {
+                value: output.value.clone(),
+                randomness,
+            },
+        )))
+    }
+
+    /// Open the amount note of output `index` with `view_key`
+    ///
+    /// Returns `None` if the note was not sealed to this key.
+    pub fn open_amount_note(
+        &self,
+        index: usize,
+        view_key: &ViewKey,
+    ) -> crate::errors::Result<Option<u64>> {
+        Ok(self.open_output(index, view_key)?.map(|(amount, _)| amount))
+    }
+
+    /// Prove that output `index` paid the holder of `recipient_view_key`
+    ///
+    /// The output's amount note must be sealed to `recipient_view_key`, see
+    /// [`set_amount_note`](Self::set_amount_note); the proof discloses the
+    /// value and randomness it holds. Fails with `InvalidParameter`
+    /// otherwise.
+    pub fn create_payment_proof(
+        &self,
+        index: usize,
+        recipient_view_key: &ViewKey,
+    ) -> crate::errors::Result<PaymentProof> {
+        let (value, output) = self
+            .open_output(index, recipient_view_key)?
+            .ok_or(LelantusError::invalid_parameter("recipient_view_key"))?;
+
+        Ok(PaymentProof {
+            disclosure: DisclosurePackage {
+                txid: self.txid()?,
+                output_index: index as u64,
+                value,
+                recipient: recipient_view_key.fingerprint().to_vec(),
+                randomness: output.randomness.clone(),
+            },
+        })
+    }
+
+    /// Check that `proof` shows this JoinSplit paid `recipient_view_key`
+    ///
+    /// Holds when the disclosed value and randomness open the output
+    /// commitment under `scheme`, the proof names the key's fingerprint, and
+    /// the output's amount note opens to the same value under the key.
+    pub fn verify_payment_proof(
+        &self,
+        proof: &PaymentProof,
+        recipient_view_key: &ViewKey,
+        scheme: &CommitmentScheme,
+    ) -> crate::errors::Result<bool> {
+        let disclosure = &proof.disclosure;
+        if disclosure.recipient != recipient_view_key.fingerprint()
+            || !disclosure.verify(scheme, self)?
+        {
+            return Ok(false);
+        }
+
+        // `verify` checked the index is in range
+        let index = disclosure.output_index as usize;
+        Ok(self.open_amount_note(index, recipient_view_key)? == Some(disclosure.value))
+    }
+
+    /// Random points for `count` outputs, as carried by non-stealth outputs
+    pub(crate) fn decoy_ephemeral_keys(count: usize) -> Vec<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        (0..count)
+            .map(|_| encode(&(ProjectivePoint::GENERATOR * Scalar::random(&mut rng))))
+            .collect()
+    }
+
+    /// Publish the ephemeral key of a stealth payment in output `index`
+    ///
+    /// See [`StealthAddress::derive_output`](crate::address::StealthAddress::derive_output).
+    /// Other outputs get random points, so stealth payments look like any
+    /// other output.
+    pub fn set_ephemeral_key(
+        &mut self,
+        index: usize,
+        ephemeral_key: &[u8],
+    ) -> crate::errors::Result<()> {
+        if index >= self.outputs.len() {
+            return Err(LelantusError::invalid_parameter("output_index"));
+        }
+        if decode(ephemeral_key).is_none() {
+            return Err(LelantusError::invalid_parameter("ephemeral_key"));
+        }
+
+        if self.ephemeral_keys.len() != self.outputs.len() {
+            self.ephemeral_keys = Self::decoy_ephemeral_keys(self.outputs.len());
+        }
+        self.ephemeral_keys[index] = ephemeral_key.to_vec();
+        Ok(())
+    }
+
+    /// Get the serial numbers this JoinSplit reveals, one per input
+    pub fn nullifiers(&self) -> &[SerialNumber] {
+        &self.nullifiers
+    }
+
+    /// Reject transactions that spend the same input twice
+    pub fn check_distinct_inputs(&self) -> crate::errors::Result<()> {
+        let mut seen = std::collections::BTreeSet::new();
+        if self.inputs.iter().all(|input| seen.insert(&input.value)) {
+            Ok(())
+        } else {
+            Err(LelantusError::DoubleSpend)
+        }
+    }
+
+    /// Run the checks that verify no proof
+    ///
+    /// Covers well-formedness, field sizes, the fee bound, duplicate inputs
+    /// and nullifiers. Mempools run this on every incoming transaction, with
+    /// one [`PreparedVerifier`] built up front, and pass only survivors to
+    /// [`verify_expensive`](Self::verify_expensive).
+    pub fn verify_cheap_checks(&self, verifier: &PreparedVerifier) -> crate::errors::Result<()> {
+        verifier.check_structure(self)
+    }
+
+    /// Validate everything that needs neither the accumulator nor the spent
+    /// serial numbers
+    ///
+    /// Extends [`verify_cheap_checks`](Self::verify_cheap_checks), which
+    /// already rejects duplicate serial numbers, with the size of the
+    /// consensus encoding, so mempools can drop a transaction before
+    /// touching any state.
+    pub fn validate_stateless(&self, verifier: &PreparedVerifier) -> crate::errors::Result<()> {
+        self.verify_cheap_checks(verifier)?;
+
+        let size = self.consensus_encode()?.len();
+        if size > <Self as LelantusSerializable>::MAX_ENCODED_SIZE {
+            return Err(LelantusError::SerializationError(format!(
+                "JoinSplit of {} bytes exceeds the size limit",
+                size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Verify the range proofs, the membership proofs and the zero-knowledge
+    /// proof against the accumulator of `state`
+    ///
+    /// Each input's membership proof is checked against its anonymity set as
+    /// of the current accumulator head. Assumes
+    /// [`verify_cheap_checks`](Self::verify_cheap_checks) passed.
+    pub fn verify_expensive(&self, state: &LelantusState) -> crate::errors::Result<bool> {
+        state.verify_proofs(self)
+    }
+
+    /// Check that every input reveals one distinct, well-sized nullifier
+    ///
+    /// Nullifiers are derived from each coin's hidden randomness, so they
+    /// cannot be recomputed from the inputs; the ZK proof binds each one to
+    /// its coin.
+    pub fn check_nullifiers(&self) -> crate::errors::Result<()> {
+        if self.nullifiers.len() != self.inputs.len()
+            || self
+                .nullifiers
+                .iter()
+                .any(|n| n.as_bytes().len() != NULLIFIER_SIZE)
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        let mut seen = std::collections::BTreeSet::new();
+        if self
+            .nullifiers
+            .iter()
+            .all(|nullifier| seen.insert(nullifier))
+        {
+            Ok(())
+        } else {
+            Err(LelantusError::DoubleSpend)
+        }
+    }
+
+    /// Check structural bounds before any cryptographic verification
+    ///
+    /// Mirrors the limits enforced when a JoinSplit is created under
+    /// `parameters`, so that a transaction built by hand cannot bypass them.
+    pub fn check_well_formed(&self, parameters: &LelantusParameters) -> crate::errors::Result<()> {
+        if self.inputs.is_empty() || self.inputs.len() > MAX_JOINSPLIT_INPUTS {
+            return Err(LelantusError::InvalidInputCount);
+        }
+
+        if self.outputs.is_empty() || self.outputs.len() > parameters.max_joinsplit_outputs() {
+            return Err(LelantusError::InvalidOutputCount);
+        }
+
+        // Either one range proof per output, over that output, or one
+        // aggregated over all
+        let range_proofs_cover_outputs = match &self.proof.aggregated_range_proof {
+            Some(_) => self.proof.range_proofs.is_empty(),
+            None => {
+                self.proof.range_proofs.len() == self.outputs.len()
+                    && self
+                        .proof
+                        .range_proofs
+                        .iter()
+                        .zip(&self.outputs)
+                        .all(|(proof, output)| proof.commitment == output.value)
+            }
+        };
+        if !range_proofs_cover_outputs {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        // Asset tags, checked by the aggregated range proof, cover every
+        // output, and the ring is no larger than the assets the inputs and
+        // outputs could hold
+        if let Some(asset_proof) = &self.proof.asset_proof {
+            if self.proof.aggregated_range_proof.is_none()
+                || asset_proof.tags.len() != self.outputs.len()
+                || asset_proof.assets.len() > self.inputs.len() + self.outputs.len()
+            {
+                return Err(LelantusError::InvalidProof);
+            }
+        }
+
+        // One anonymity set and membership proof per input
+        if self.proof.anonymity_sets.len() != self.inputs.len()
+            || self.proof.membership_proofs.len() != self.inputs.len()
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        if !self.change_tags.is_empty()
+            && (self.change_tags.len() != self.outputs.len()
+                || self
+                    .change_tags
+                    .iter()
+                    .any(|tag| tag.len() != CHANGE_TAG_SIZE))
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        if !self.memos.is_empty()
+            && (self.memos.len() != self.outputs.len()
+                || self
+                    .memos
+                    .iter()
+                    .any(|memo| memo.len() != ENCRYPTED_MEMO_SIZE))
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        if !self.amount_notes.is_empty()
+            && (self.amount_notes.len() != self.outputs.len()
+                || self
+                    .amount_notes
+                    .iter()
+                    .any(|note| note.len() != amount_note_size(parameters.randomness_bits / 8)))
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        if !self.ephemeral_keys.is_empty()
+            && (self.ephemeral_keys.len() != self.outputs.len()
+                || self
+                    .ephemeral_keys
+                    .iter()
+                    .any(|key| key.len() != POINT_SIZE))
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        // The binding key is optional, but a signature needs one
+        let binding_malformed = match &self.binding_signature {
+            Some(signature) => {
+                self.binding_key.len() != POINT_SIZE
+                    || signature.nonce_point.len() != POINT_SIZE
+                    || signature.response.len() != SCALAR_SIZE
+            }
+            None => !self.binding_key.is_empty() && self.binding_key.len() != POINT_SIZE,
+        };
+        if binding_malformed {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        // Non-zero padding would let anyone vary the transaction bytes
+        if self.padding.iter().any(|&b| b != 0) {
+            return Err(LelantusError::InvalidProof);
+        }
+
+        Ok(())
+    }
+
+    /// Pad the JoinSplit so that [`serialize`](Self::serialize) yields exactly
+    /// `target_size` bytes
+    ///
+    /// Replaces any existing padding. Fails with `InvalidParameter`, leaving
+    /// the padding unchanged, if the target is smaller than the unpadded size
+    /// or too close to it to fit the padding field.
+    pub fn pad_to(&mut self, target_size: usize) -> crate::errors::Result<()> {
+        let previous = std::mem::take(&mut self.padding);
+        let unpadded = LelantusSerializable::to_bytes(self)?.len();
+        if target_size == unpadded {
+            return Ok(());
+        }
+
+        // The first padding byte may also add the field itself
+        self.padding.push(0);
+        let minimal = LelantusSerializable::to_bytes(self)?.len();
+        if target_size < minimal {
+            self.padding = previous;
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("target_size")
+                    .expected(format!(">= {}", minimal))
+                    .actual(target_size),
+            ));
+        }
+
+        self.padding.resize(target_size - minimal + 1, 0);
+        Ok(())
+    }
+
+    /// Encode the JoinSplit compactly for relaying between peers
+    ///
+    /// An input proven against the same anonymity set as an earlier one
+    /// refers to that input's set index instead of repeating it. Carries
+    /// nothing beyond the JoinSplit itself.
+    pub fn to_relay_bundle(&self) -> crate::errors::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.put_body(&mut buf, true)?;
+        Ok(buf)
+    }
+
+    /// Decode a relay bundle written by [`to_relay_bundle`](Self::to_relay_bundle)
+    pub fn from_relay_bundle(data: &[u8]) -> crate::errors::Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let joinsplit = Self::read_body(&mut reader, true).map_err(malformed)?;
+        reader.finish().map_err(malformed)?;
+        Ok(joinsplit)
+    }
+
+    /// Append anonymity set indices, each repeated one as a reference to the
+    /// first input proven against that set
+    fn put_relay_sets(&self, buf: &mut Vec<u8>) {
+        let sets = &self.proof.anonymity_sets;
+        binary::put_u64(buf, sets.len() as u64);
+        for (i, set) in sets.iter().enumerate() {
+            let earlier = sets[..i].iter().position(|earlier| earlier == set);
+            match earlier.and_then(|j| u8::try_from(j + 1).ok()) {
+                Some(reference) => buf.push(reference),
+                None => {
+                    buf.push(0);
+                    binary::put_u64(buf, *set);
+                }
+            }
+        }
+    }
+
+    /// Read anonymity set indices written by
+    /// [`put_relay_sets`](Self::put_relay_sets)
+    fn read_relay_sets(reader: &mut binary::Reader<'_>) -> crate::errors::Result<Vec<u64>> {
+        let mut sets = Vec::new();
+        for _ in 0..reader.read_u64()? {
+            let set = match reader.read_u8()? {
+                0 => reader.read_u64()?,
+                reference => *sets.get(usize::from(reference) - 1).ok_or_else(|| {
+                    LelantusError::SerializationError("unknown set reference".to_string())
+                })?,
+            };
+            sets.push(set);
+        }
+        Ok(sets)
+    }
+
+    /// Append the canonical binary encoding of the JoinSplit
+    ///
+    /// With `relay`, anonymity sets are written deduplicated, as in
+    /// [`to_relay_bundle`](Self::to_relay_bundle).
+    fn put_body(&self, buf: &mut Vec<u8>, relay: bool) -> crate::errors::Result<()> {
+        put_list(buf, &self.inputs, |c| Ok(c.value.clone()))?;
+        put_list(buf, &self.outputs, |c| Ok(c.value.clone()))?;
+        put_list(buf, &self.proof.range_proofs, RangeProof::consensus_encode)?;
+        put_list(
+            buf,
+            self.proof.aggregated_range_proof.as_slice(),
+            AggregatedRangeProof::consensus_encode,
+        )?;
+        put_list(
+            buf,
+            self.proof.asset_proof.as_slice(),
+            AssetSurjectionProof::consensus_encode,
+        )?;
+        binary::put_bytes(buf, &self.proof.zk_proof.consensus_encode()?)?;
+        if relay {
+            self.put_relay_sets(buf);
+        } else {
+            binary::put_u64(buf, self.proof.anonymity_sets.len() as u64);
+            for set in &self.proof.anonymity_sets {
+                binary::put_u64(buf, *set);
+            }
+        }
+        put_list(
+            buf,
+            &self.proof.membership_proofs,
+            OneOutOfManyProof::consensus_encode,
+        )?;
+        binary::put_u64(buf, self.fee);
+        put_list(buf, &self.nullifiers, |n| Ok(n.as_bytes().to_vec()))?;
+        put_list(buf, &self.change_tags, |t| Ok(t.clone()))?;
+        put_list(buf, &self.memos, |m| Ok(m.clone()))?;
+        put_list(buf, &self.amount_notes, |n| Ok(n.clone()))?;
+        put_list(buf, &self.ephemeral_keys, |k| Ok(k.clone()))?;
+        binary::put_bytes(buf, &self.binding_key)?;
+        put_list(
+            buf,
+            self.binding_signature.as_slice(),
+            BindingSignature::consensus_encode,
+        )?;
+        binary::put_bytes(buf, &self.padding)
+    }
+
+    /// Read a JoinSplit written by [`put_body`](Self::put_body)
+    fn read_body(reader: &mut binary::Reader<'_>, relay: bool) -> crate::errors::Result<Self> {
+        let inputs = read_list(reader, |v| Ok(Commitment::from_value(v.to_vec())))?;
+        let outputs = read_list(reader, |v| Ok(Commitment::from_value(v.to_vec())))?;
+        let range_proofs = read_list(reader, RangeProof::consensus_decode)?;
+        let mut aggregated = read_list(reader, AggregatedRangeProof::consensus_decode)?;
+        if aggregated.len() > 1 {
+            return Err(LelantusError::SerializationError(
+                "more than one aggregated range proof".to_string(),
+            ));
+        }
+        let mut asset_proof = read_list(reader, AssetSurjectionProof::consensus_decode)?;
+        if asset_proof.len() > 1 {
+            return Err(LelantusError::SerializationError(
+                "more than one asset surjection proof".to_string(),
+            ));
+        }
+        let zk_proof = ZKProof::consensus_decode(&reader.read_bytes()?)?;
+        let anonymity_sets = if relay {
+            Self::read_relay_sets(reader)?
+        } else {
+            (0..reader.read_u64()?)
+                .map(|_| reader.read_u64())
+                .collect::<crate::errors::Result<Vec<_>>>()?
+        };
+        let membership_proofs = read_list(reader, OneOutOfManyProof::consensus_decode)?;
+        let fee = reader.read_u64()?;
+        let nullifiers = read_list(reader, |n| {
+            SerialNumber::from_bytes(n.to_vec())
+                .map_err(|e| LelantusError::SerializationError(e.to_string()))
+        })?;
+        let change_tags = read_list(reader, |t| Ok(t.to_vec()))?;
+        let memos = read_list(reader, |m| Ok(m.to_vec()))?;
+        let amount_notes = read_list(reader, |n| Ok(n.to_vec()))?;
+        let ephemeral_keys = read_list(reader, |k| Ok(k.to_vec()))?;
+        let binding_key = reader.read_bytes()?;
+        let mut binding_signature = read_list(reader, BindingSignature::consensus_decode)?;
+        if binding_signature.len() > 1 {
+            return Err(LelantusError::SerializationError(
+                "more than one binding signature".to_string(),
+            ));
+        }
+        let padding = reader.read_bytes()?;
+
+        Ok(Self {
+            inputs,
+            outputs,
+            proof: JoinSplitProof {
+                range_proofs,
+                aggregated_range_proof: aggregated.pop(),
+                asset_proof: asset_proof.pop(),
+                zk_proof,
+                anonymity_sets,
+                membership_proofs,
+            },
+            fee,
+            nullifiers,
+            change_tags,
+            memos,
+            amount_notes,
+            ephemeral_keys,
+            binding_key,
+            binding_signature: binding_signature.pop(),
+            binding_secret: None,
+            padding,
+        })
+    }
+
+    /// Encode the JoinSplit for consensus
+    ///
+    /// Every field is written in declaration order as fixed-width
+    /// little-endian integers and length-prefixed bytes, with lists prefixed
+    /// by their count, so equal JoinSplits always encode to equal bytes.
+    pub fn consensus_encode(&self) -> crate::errors::Result<Vec<u8>> {
+        if self.padding.iter().any(|&b| b != 0) {
+            return Err(LelantusError::SerializationError(
+                "padding must be zero bytes".to_string(),
+            ));
+        }
+
+        let mut buf = Vec::new();
+        self.put_body(&mut buf, false)?;
+        Ok(buf)
+    }
+
+    /// Decode a JoinSplit from its consensus encoding
+    ///
+    /// Rejects trailing bytes and non-zero padding, so only the bytes
+    /// [`consensus_encode`](Self::consensus_encode) produces are accepted.
+    pub fn consensus_decode(data: &[u8]) -> crate::errors::Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let joinsplit = Self::read_body(&mut reader, false).map_err(malformed)?;
+        reader.finish().map_err(malformed)?;
+        if joinsplit.padding.iter().any(|&b| b != 0) {
+            return Err(LelantusError::MalformedTransaction(
+                "padding must be zero bytes".to_string(),
+            ));
+        }
+        Ok(joinsplit)
+    }
+
+    /// Transaction id: a hash over the full Borsh encoding
+    ///
+    /// Fails with `SerializationError` if the JoinSplit has no encoding,
+    /// which is the case for non-zero padding.
+    pub fn txid(&self) -> crate::errors::Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        hasher.update(TXID_DOMAIN);
+        BorshSerialize::serialize(self, &mut hasher)
+            .map_err(|e| LelantusError::SerializationError(e.to_string()))?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Consensus encoding with the binding signature left out, as signed
+    pub(crate) fn binding_body(&self) -> crate::errors::Result<Vec<u8>> {
+        let mut unsigned = self.clone();
+        unsigned.binding_signature = None;
+        unsigned.binding_secret = None;
+        unsigned.consensus_encode()
+    }
+
+    /// Sign the digest of the transaction this JoinSplit is placed in
+    ///
+    /// Only the creator can sign: the secret binding key is never
+    /// serialized, so a decoded JoinSplit fails with `InvalidProof`.
+    /// Signing again replaces the previous signature.
+    pub fn sign_binding(&mut self, tx_digest: &[u8]) -> crate::errors::Result<()> {
+        let key = self
+            .binding_secret
+            .as_ref()
+            .ok_or(LelantusError::InvalidProof)?;
+        if key.verification_key()? != self.binding_key {
+            return Err(LelantusError::InvalidProof);
+        }
+        let signature = BindingSignature::sign(
+            key,
+            &self.binding_body()?,
+            tx_digest,
+            &mut rand::rngs::OsRng,
+        )?;
+        self.binding_signature = Some(signature);
+        Ok(())
+    }
+
+    /// Check that the JoinSplit was signed for the transaction with `tx_digest`
+    ///
+    /// Proof verification does not cover the binding; hosts call this with
+    /// the digest of the enclosing transaction.
+    pub fn verify_binding(&self, tx_digest: &[u8]) -> crate::errors::Result<bool> {
+        match &self.binding_signature {
+            Some(signature) => {
+                Ok(signature.verify(&self.binding_key, &self.binding_body()?, tx_digest))
+            }
+            None => Ok(false),
+        }
+    }
+
     /// Serialize the JoinSplit
     pub fn serialize(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        Ok(serde_json::to_vec(self)?)
+        Ok(LelantusSerializable::to_bytes(self)?)
     }
 
     /// Deserialize the JoinSplit
     pub fn deserialize(data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(serde_json::from_slice(data)?)
+        Ok(<Self as LelantusSerializable>::from_bytes(data)?)
+    }
+}
+
+/// Merkle root over the txids of a block's JoinSplits, in block order
+pub fn block_commitment(txs: &[JoinSplit]) -> crate::errors::Result<[u8; 32]> {
+    let txids = txs
+        .iter()
+        .map(JoinSplit::txid)
+        .collect::<crate::errors::Result<Vec<_>>>()?;
+    Ok(merkle::merkle_root(txids.iter().map(|txid| &txid[..])))
+}
+
+/// Prove that `txs[index]` is committed by [`block_commitment`]
+///
+/// Light clients check the proof with [`MerkleProof::verify`] against the
+/// transaction's txid and the block commitment.
+pub fn tx_inclusion_proof(txs: &[JoinSplit], index: usize) -> crate::errors::Result<MerkleProof> {
+    let txids = txs
+        .iter()
+        .map(JoinSplit::txid)
+        .collect::<crate::errors::Result<Vec<_>>>()?;
+    MerkleProof::create(txids.iter().map(|txid| &txid[..]), index)
+}
+
+/// Append a count-prefixed list of binary-encoded items
+fn put_list<T>(
+    buf: &mut Vec<u8>,
+    items: &[T],
+    encode: impl Fn(&T) -> crate::errors::Result<Vec<u8>>,
+) -> crate::errors::Result<()> {
+    binary::put_u64(buf, items.len() as u64);
+    for item in items {
+        binary::put_bytes(buf, &encode(item)?)?;
+    }
+    Ok(())
+}
+
+/// Read a count-prefixed list of binary-encoded items
+fn read_list<T>(
+    reader: &mut binary::Reader<'_>,
+    decode: impl Fn(&[u8]) -> crate::errors::Result<T>,
+) -> crate::errors::Result<Vec<T>> {
+    let count = reader.read_u64()?;
+    let mut items = Vec::new();
+    for _ in 0..count {
+        items.push(decode(&reader.read_bytes()?)?);
+    }
+    Ok(items)
+}
+
+/// Report a decoding failure of transaction bytes as a consensus failure
+fn malformed(error: LelantusError) -> LelantusError {
+    match error {
+        LelantusError::SerializationError(detail) => LelantusError::MalformedTransaction(detail),
+        other => other,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commitment::NATIVE_ASSET;
+    use crate::inner_product::InnerProductProof;
+    use crate::MAX_JOINSPLIT_OUTPUTS;
+
+    #[test]
+    fn test_consensus_encoding_golden() -> Result<(), Box<dyn std::error::Error>> {
+        let joinsplit = JoinSplit {
+            inputs: vec![Commitment {
+                value: vec![0x01],
+                randomness: vec![0x02],
+            }],
+            outputs: vec![Commitment {
+                value: vec![0x03],
+                randomness: vec![0x04],
+            }],
+            proof: JoinSplitProof {
+                range_proofs: vec![],
+                aggregated_range_proof: Some(AggregatedRangeProof {
+                    bit_length: 64,
+                    output_count: 1,
+                    bit_commitment: vec![],
+                    blinding_commitment: vec![],
+                    asset_terms: vec![],
+                    blinding_response: vec![],
+                    inner_product: InnerProductProof::default(),
+                }),
+                asset_proof: None,
+                zk_proof: ZKProof {
+                    proof_data: vec![0x05],
+                    challenge: vec![0x06],
+                    response: vec![0x07],
+                    anonymity_set_size: 64,
+                },
+                anonymity_sets: vec![],
+                membership_proofs: vec![],
+            },
+            fee: 10,
+            nullifiers: vec![SerialNumber::from_bytes(vec![0x08; NULLIFIER_SIZE])?],
+            change_tags: vec![],
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key: Vec::new(),
+            binding_signature: None,
+            binding_secret: None,
+            padding: vec![0; 2],
+        };
+
+        let encoded = joinsplit.consensus_encode()?;
+        assert_eq!(
+            hex::encode(&encoded),
+            concat!(
+                // Inputs: count, then each length-prefixed value; the
+                // openings are not published
+                "0100000000000000",
+                "0100000001",
+                // Outputs
+                "0100000000000000",
+                "0100000003",
+                // Range proofs
+                "0000000000000000",
+                // Aggregated range proof: at most one, with its bit length,
+                // output count, A and S, group terms, μ and inner-product
+                // rounds
+                "0100000000000000",
+                "3c000000",
+                "4000000000000000",
+                "0100000000000000",
+                "00000000",
+                "00000000",
+                "0000000000000000",
+                "00000000",
+                "0000000000000000",
+                "0000000000000000",
+                "00000000",
+                "00000000",
+                // Asset surjection proof
+                "0000000000000000",
+                // Zero-knowledge proof
+                "17000000",
+                "0100000005",
+                "0100000006",
+                "0100000007",
+                "4000000000000000",
+                // Anonymity sets
+                "0000000000000000",
+                // Membership proofs
+                "0000000000000000",
+                // Fee
+                "0a00000000000000",
+                // Nullifiers
+                "0100000000000000",
+                "40000000",
+                "0808080808080808080808080808080808080808080808080808080808080808",
+                "0808080808080808080808080808080808080808080808080808080808080808",
+                // Change tags
+                "0000000000000000",
+                // Memos
+                "0000000000000000",
+                // Amount notes
+                "0000000000000000",
+                // Ephemeral keys
+                "0000000000000000",
+                // Binding key and signature
+                "00000000",
+                "0000000000000000",
+                // Padding
+                "020000000000",
+            )
+        );
+        let decoded = JoinSplit::consensus_decode(&encoded)?;
+        assert_eq!(decoded.consensus_encode()?, encoded);
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(JoinSplit::consensus_decode(&trailing).is_err());
+        let mut padded = encoded.clone();
+        *padded.last_mut().ok_or("empty encoding")? = 1;
+        assert!(JoinSplit::consensus_decode(&padded).is_err());
+
+        // Malformed bytes condemn the transaction, not the node decoding it
+        for malformed in [&encoded[..3], &trailing[..], &padded[..]] {
+            let error = JoinSplit::consensus_decode(malformed)
+                .err()
+                .ok_or("malformed encoding decoded")?;
+            assert!(matches!(error, LelantusError::MalformedTransaction(_)));
+            assert!(error.is_consensus_failure());
+        }
+        Ok(())
+    }
 
     #[test]
     fn test_joinsplit_creation() {
@@ -71,11 +1408,16 @@ mod tests {
 
         let proof = JoinSplitProof {
             range_proofs: vec![],
+            aggregated_range_proof: None,
+            asset_proof: None,
             zk_proof: crate::proof::ZKProof {
                 proof_data: vec![5; 32],
                 challenge: vec![6; 32],
                 response: vec![7; 32],
+                anonymity_set_size: 64,
             },
+            anonymity_sets: vec![],
+            membership_proofs: vec![],
         };
 
         let joinsplit = JoinSplit {
@@ -83,6 +1425,15 @@ mod tests {
             outputs,
             proof,
             fee: 100,
+            nullifiers: Vec::new(),
+            change_tags: Vec::new(),
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key: Vec::new(),
+            binding_signature: None,
+            binding_secret: None,
+            padding: Vec::new(),
         };
 
         assert_eq!(joinsplit.input_count(), 1);
@@ -103,11 +1454,16 @@ mod tests {
 
         let proof = JoinSplitProof {
             range_proofs: vec![],
+            aggregated_range_proof: None,
+            asset_proof: None,
             zk_proof: crate::proof::ZKProof {
                 proof_data: vec![5; 32],
                 challenge: vec![6; 32],
                 response: vec![7; 32],
+                anonymity_set_size: 64,
             },
+            anonymity_sets: vec![],
+            membership_proofs: vec![],
         };
 
         let joinsplit = JoinSplit {
@@ -115,6 +1471,15 @@ mod tests {
             outputs,
             proof,
             fee: 100,
+            nullifiers: Vec::new(),
+            change_tags: Vec::new(),
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key: Vec::new(),
+            binding_signature: None,
+            binding_secret: None,
+            padding: Vec::new(),
         };
 
         let serialized = joinsplit.serialize()?;
@@ -123,4 +1488,268 @@ mod tests {
         assert_eq!(joinsplit.output_count(), deserialized.output_count());
         Ok(())
     }
+
+    #[test]
+    fn test_check_well_formed() -> Result<(), Box<dyn std::error::Error>> {
+        let params = crate::parameters::LelantusParameters::default();
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1)?;
+        let range_proof = RangeProof::create(&commitment, 1, &params)?;
+        let coin = scheme.commit(1)?;
+        let input = scheme.commit_input_with_rng(&NATIVE_ASSET, 1, &mut rand::rngs::OsRng)?;
+        let membership_proof = OneOutOfManyProof::create(
+            &scheme,
+            core::slice::from_ref(&coin.value),
+            0,
+            &coin.randomness,
+            &scheme.spend_offset(&input, SerialNumber::from_opening(&coin)?.as_bytes())?,
+            params.privacy_level,
+        )?;
+
+        let mut joinsplit = JoinSplit {
+            inputs: vec![commitment.clone()],
+            outputs: vec![commitment],
+            proof: JoinSplitProof {
+                range_proofs: vec![range_proof],
+                aggregated_range_proof: None,
+                asset_proof: None,
+                zk_proof: crate::proof::ZKProof {
+                    proof_data: vec![5; 32],
+                    challenge: vec![6; 32],
+                    response: vec![7; 32],
+                    anonymity_set_size: 64,
+                },
+                anonymity_sets: vec![0],
+                membership_proofs: vec![membership_proof],
+            },
+            fee: 100,
+            nullifiers: Vec::new(),
+            change_tags: Vec::new(),
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key: Vec::new(),
+            binding_signature: None,
+            binding_secret: None,
+            padding: Vec::new(),
+        };
+        assert!(joinsplit.check_well_formed(&params).is_ok());
+
+        // Every input needs its own membership proof
+        let mut unproven = joinsplit.clone();
+        unproven.proof.membership_proofs.clear();
+        assert!(matches!(
+            unproven.check_well_formed(&params),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        // Three outputs fit the default cap but not a cap of two
+        let output = joinsplit.outputs[0].clone();
+        let range_proof = joinsplit.proof.range_proofs[0].clone();
+        joinsplit.outputs = vec![output; 3];
+        joinsplit.proof.range_proofs = vec![range_proof; 3];
+        assert!(joinsplit.check_well_formed(&params).is_ok());
+        let mut capped = params.clone();
+        capped.max_outputs = 2;
+        assert!(matches!(
+            joinsplit.check_well_formed(&capped),
+            Err(LelantusError::InvalidOutputCount)
+        ));
+
+        joinsplit.outputs.clear();
+        assert!(matches!(
+            joinsplit.check_well_formed(&params),
+            Err(LelantusError::InvalidOutputCount)
+        ));
+
+        joinsplit.inputs.clear();
+        assert!(matches!(
+            joinsplit.check_well_formed(&params),
+            Err(LelantusError::InvalidInputCount)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_marginal_input_cost() -> Result<(), Box<dyn std::error::Error>> {
+        for level in [
+            PrivacyLevel::Standard,
+            PrivacyLevel::Enhanced,
+            PrivacyLevel::Maximum,
+        ] {
+            for inputs in 1..MAX_JOINSPLIT_INPUTS {
+                let cost = JoinSplit::marginal_input_cost(inputs, level);
+                assert!(cost > 0);
+                for outputs in 1..=MAX_JOINSPLIT_OUTPUTS {
+                    assert_eq!(
+                        JoinSplit::estimated_size(inputs + 1, outputs, level)?
+                            - JoinSplit::estimated_size(inputs, outputs, level)?,
+                        cost
+                    );
+                }
+            }
+        }
+
+        assert!(
+            JoinSplit::marginal_input_cost(1, PrivacyLevel::Maximum)
+                > JoinSplit::marginal_input_cost(1, PrivacyLevel::Standard)
+        );
+        Ok(())
+    }
+
+    fn joinsplit_with_fee(fee: u64) -> JoinSplit {
+        JoinSplit {
+            inputs: vec![Commitment {
+                value: vec![1; 32],
+                randomness: vec![2; 32],
+            }],
+            outputs: vec![Commitment {
+                value: vec![3; 32],
+                randomness: vec![4; 32],
+            }],
+            proof: JoinSplitProof {
+                range_proofs: vec![],
+                aggregated_range_proof: None,
+                asset_proof: None,
+                zk_proof: crate::proof::ZKProof {
+                    proof_data: vec![5; 32],
+                    challenge: vec![6; 32],
+                    response: vec![7; 32],
+                    anonymity_set_size: 64,
+                },
+                anonymity_sets: vec![],
+                membership_proofs: vec![],
+            },
+            fee,
+            nullifiers: Vec::new(),
+            change_tags: Vec::new(),
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key: Vec::new(),
+            binding_signature: None,
+            binding_secret: None,
+            padding: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_block_commitment() -> Result<(), Box<dyn std::error::Error>> {
+        let txs: Vec<JoinSplit> = (1..=5).map(joinsplit_with_fee).collect();
+        let root = block_commitment(&txs)?;
+        assert_eq!(root, block_commitment(&txs.clone())?);
+
+        let mut reordered = txs.clone();
+        reordered.swap(0, 1);
+        assert_ne!(root, block_commitment(&reordered)?);
+
+        for (index, tx) in txs.iter().enumerate() {
+            let proof = tx_inclusion_proof(&txs, index)?;
+            assert!(proof.verify(&tx.txid()?, &root));
+            assert!(!proof.verify(&joinsplit_with_fee(99).txid()?, &root));
+        }
+        assert!(tx_inclusion_proof(&txs, txs.len()).is_err());
+
+        // Non-zero padding has no encoding, so it has no txid either
+        let mut padded = txs;
+        padded[0].padding = vec![1];
+        assert!(padded[0].txid().is_err());
+        assert!(block_commitment(&padded).is_err());
+        assert!(tx_inclusion_proof(&padded, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_binding_signature_ties_transaction() -> Result<(), Box<dyn std::error::Error>> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let mut joinsplit = state.create_joinsplit(state.mint_batch(&[1_000])?, vec![900], 100)?;
+        assert!(!joinsplit.verify_binding(b"tx one")?);
+
+        joinsplit.sign_binding(b"tx one")?;
+        assert!(joinsplit.verify_binding(b"tx one")?);
+        assert!(!joinsplit.verify_binding(b"tx two")?);
+
+        // A relayed copy keeps its signature but cannot be signed again
+        let mut relayed = JoinSplit::consensus_decode(&joinsplit.consensus_encode()?)?;
+        assert!(relayed.verify_binding(b"tx one")?);
+        assert!(matches!(
+            relayed.sign_binding(b"tx two"),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        // Re-keying to sign another transaction breaks the proof
+        let attacker = BindingKey::generate(&mut rand::rngs::OsRng);
+        relayed.binding_key = attacker.verification_key()?;
+        relayed.binding_secret = Some(attacker);
+        relayed.sign_binding(b"tx two")?;
+        assert!(relayed.verify_binding(b"tx two")?);
+        assert!(!state.verify_joinsplit(&relayed)?);
+        assert!(state.verify_joinsplit(&joinsplit)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_payment_proof() -> Result<(), Box<dyn std::error::Error>> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let mut joinsplit =
+            state.create_joinsplit(state.mint_batch(&[1_000])?, vec![600, 300], 100)?;
+        let recipient = ViewKey::generate();
+        let other = ViewKey::generate();
+        joinsplit.set_amount_note(1, 300, &recipient)?;
+        let scheme = state.commitment_scheme();
+
+        let proof = joinsplit.create_payment_proof(1, &recipient)?;
+        assert_eq!(proof.disclosure.value, 300);
+        assert!(joinsplit.verify_payment_proof(&proof, &recipient, &scheme)?);
+        assert!(!joinsplit.verify_payment_proof(&proof, &other, &scheme)?);
+
+        // No note for this recipient on the other output
+        assert!(joinsplit.create_payment_proof(0, &recipient).is_err());
+        assert!(joinsplit.create_payment_proof(1, &other).is_err());
+
+        let mut redirected = proof.clone();
+        redirected.disclosure.output_index = 0;
+        assert!(!joinsplit.verify_payment_proof(&redirected, &recipient, &scheme)?);
+
+        let mut inflated = proof;
+        inflated.disclosure.value = 301;
+        assert!(!joinsplit.verify_payment_proof(&inflated, &recipient, &scheme)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_stateless() -> Result<(), Box<dyn std::error::Error>> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let joinsplit = state.create_joinsplit(state.mint_batch(&[1_000])?, vec![900], 100)?;
+        let verifier = PreparedVerifier::new(&state.parameters())?;
+        joinsplit.validate_stateless(&verifier)?;
+
+        let mut duplicated = joinsplit.clone();
+        duplicated.inputs.push(duplicated.inputs[0].clone());
+        duplicated.nullifiers.push(duplicated.nullifiers[0].clone());
+        duplicated
+            .proof
+            .anonymity_sets
+            .push(duplicated.proof.anonymity_sets[0]);
+        duplicated
+            .proof
+            .membership_proofs
+            .push(duplicated.proof.membership_proofs[0].clone());
+        assert!(matches!(
+            duplicated.validate_stateless(&verifier),
+            Err(LelantusError::DoubleSpend)
+        ));
+
+        let mut padded = joinsplit.clone();
+        padded.padding = vec![0; <JoinSplit as LelantusSerializable>::MAX_ENCODED_SIZE];
+        assert!(padded.validate_stateless(&verifier).is_err());
+
+        let mut overpaid = joinsplit;
+        overpaid.fee = u64::MAX;
+        assert!(matches!(
+            overpaid.validate_stateless(&verifier),
+            Err(LelantusError::BalanceMismatch)
+        ));
+        Ok(())
+    }
 }
@@ -0,0 +1,629 @@
+//! Key material for Lelantus wallets
+//!
+//! A [`SpendKey`] is the root secret of a wallet. The [`ViewKey`] and the
+//! [`FullViewingKey`] are derived from it one way: they let a wallet find and
+//! decrypt its coins, but not spend them.
+//!
+//! A coin owned by a key is committed under a keyed opening: its randomness
+//! run through HMAC-SHA512 under the key's nullifier key. The serial number
+//! the coin reveals when spent is a digest of that opening, so the coin's
+//! randomness alone cannot link a spend back to the coin.
+
+use crate::commitment::Commitment;
+use crate::errors::{ErrorContext, LelantusError, Result};
+use crate::prelude::*;
+use crate::witness::constant_time_compare;
+use hmac::{Hmac, Mac};
+#[cfg(feature = "std")]
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Size of a view key in bytes
+pub const VIEW_KEY_SIZE: usize = 64;
+
+/// Size of a spend key in bytes
+pub const SPEND_KEY_SIZE: usize = 64;
+
+/// Minimum seed length accepted for key derivation
+pub const MIN_SEED_SIZE: usize = 32;
+
+/// Size of the nonce prefixing the randomness of an owned coin
+const OWNERSHIP_NONCE_SIZE: usize = 32;
+
+/// Domain separator deriving a spend key from a seed
+const SPEND_KEY_DOMAIN: &[u8] = b"lelantus_spend_key";
+
+/// Domain separator deriving the view key from a spend key
+const VIEW_KEY_DOMAIN: &[u8] = b"lelantus_view_key";
+
+/// Domain separator deriving the ownership key from a spend key
+const OWNER_KEY_DOMAIN: &[u8] = b"lelantus_owner_key";
+
+/// Domain separator deriving the nullifier key from a spend key
+const NULLIFIER_KEY_DOMAIN: &[u8] = b"lelantus_nullifier_key";
+
+/// Domain separator for the ownership tag of a coin
+const OWNERSHIP_DOMAIN: &[u8] = b"lelantus_coin_owner";
+
+/// Domain separator for the keyed opening of an owned coin
+const COIN_OPENING_DOMAIN: &[u8] = b"lelantus_coin_opening";
+
+/// Domain separator for view key fingerprints
+const VIEW_KEY_FINGERPRINT_DOMAIN: &[u8] = b"lelantus_view_key_fingerprint";
+
+/// HMAC-SHA512 of `parts` under `key`
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(key)
+        .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+    for part in parts {
+        mac.update(part);
+    }
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// HMAC-SHA512 of `domain ‖ message` under `key`, stretched to `size` bytes
+/// by appending a block counter
+fn hmac_stretch(key: &[u8], domain: &[u8], message: &[u8], size: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(size);
+    let mut block = 0u64;
+    while output.len() < size {
+        output.extend(hmac_sha512(key, &[domain, message, &block.to_le_bytes()])?);
+        block += 1;
+    }
+    output.truncate(size);
+    Ok(output)
+}
+
+/// Spend key authorizing coin spends and deriving nullifiers
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct SpendKey {
+    key: Vec<u8>,
+}
+
+impl SpendKey {
+    /// Create a spend key from raw bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != SPEND_KEY_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("spend_key")
+                    .expected(SPEND_KEY_SIZE)
+                    .actual(bytes.len()),
+            ));
+        }
+
+        Ok(Self {
+            key: bytes.to_vec(),
+        })
+    }
+
+    /// Generate a fresh random spend key
+    #[cfg(feature = "std")]
+    pub fn generate() -> Self {
+        let mut key = vec![0u8; SPEND_KEY_SIZE];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self { key }
+    }
+
+    /// Derive a spend key from a wallet seed
+    ///
+    /// The same seed always gives the same key.
+    pub fn from_seed(seed: &[u8]) -> Result<Self> {
+        if seed.len() < MIN_SEED_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("seed")
+                    .expected(format!(">= {} bytes", MIN_SEED_SIZE))
+                    .actual(seed.len()),
+            ));
+        }
+
+        Ok(Self {
+            key: hmac_sha512(SPEND_KEY_DOMAIN, &[seed])?,
+        })
+    }
+
+    /// Get the raw key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Derive the view key of this spend key
+    pub fn view_key(&self) -> Result<ViewKey> {
+        Ok(ViewKey {
+            key: hmac_sha512(&self.key, &[VIEW_KEY_DOMAIN])?,
+        })
+    }
+
+    /// Derive the full viewing key of this spend key
+    pub fn full_viewing_key(&self) -> Result<FullViewingKey> {
+        Ok(FullViewingKey {
+            view_key: self.view_key()?,
+            owner_key: hmac_sha512(&self.key, &[OWNER_KEY_DOMAIN])?,
+            nullifier_key: hmac_sha512(&self.key, &[NULLIFIER_KEY_DOMAIN])?,
+        })
+    }
+
+    /// Check whether `commitment` is a coin owned by this key
+    pub fn owns(&self, commitment: &Commitment) -> Result<bool> {
+        self.full_viewing_key()?.owns(commitment)
+    }
+
+    /// Keyed opening of a coin owned by this key
+    ///
+    /// See [`FullViewingKey::open`].
+    pub fn open(&self, commitment: &Commitment) -> Result<Commitment> {
+        self.full_viewing_key()?.open(commitment)
+    }
+}
+
+impl core::fmt::Debug for SpendKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SpendKey").finish_non_exhaustive()
+    }
+}
+
+/// Offset marking a hardened child index
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// Key of the HMAC deriving a master key from a seed
+const MASTER_KEY_DOMAIN: &[u8] = b"Lelantus seed";
+
+/// Step in a derivation path; the hardened flag is the top bit of the index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChildIndex(u32);
+
+impl ChildIndex {
+    /// Normal child `index`
+    pub fn normal(index: u32) -> Result<Self> {
+        if index >= HARDENED_OFFSET {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("child_index")
+                    .expected(format!("< {}", HARDENED_OFFSET))
+                    .actual(index),
+            ));
+        }
+        Ok(Self(index))
+    }
+
+    /// Hardened child `index`
+    pub fn hardened(index: u32) -> Result<Self> {
+        if index >= HARDENED_OFFSET {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("child_index")
+                    .expected(format!("< {}", HARDENED_OFFSET))
+                    .actual(index),
+            ));
+        }
+        Ok(Self(index | HARDENED_OFFSET))
+    }
+
+    /// Whether this step is hardened
+    pub fn is_hardened(&self) -> bool {
+        self.0 >= HARDENED_OFFSET
+    }
+
+    /// Raw index, including the hardened flag
+    pub fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+impl core::fmt::Display for ChildIndex {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.is_hardened() {
+            write!(f, "{}'", self.0 - HARDENED_OFFSET)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// Derivation path such as `m/44'/1'/0'/5`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct DerivationPath {
+    steps: Vec<ChildIndex>,
+}
+
+impl DerivationPath {
+    /// BIP44-style path `m/44'/coin_type'/account'/index`
+    pub fn bip44(coin_type: u32, account: u32, index: u32) -> Result<Self> {
+        Ok(Self {
+            steps: vec![
+                ChildIndex::hardened(44)?,
+                ChildIndex::hardened(coin_type)?,
+                ChildIndex::hardened(account)?,
+                ChildIndex::normal(index)?,
+            ],
+        })
+    }
+
+    /// Steps from the master key
+    pub fn steps(&self) -> &[ChildIndex] {
+        &self.steps
+    }
+
+    /// Path extended by one step
+    pub fn child(&self, index: ChildIndex) -> Self {
+        let mut steps = self.steps.clone();
+        steps.push(index);
+        Self { steps }
+    }
+}
+
+impl core::str::FromStr for DerivationPath {
+    type Err = LelantusError;
+
+    /// Parse `m/...` with `'` or `h` marking hardened steps
+    fn from_str(path: &str) -> Result<Self> {
+        let mut parts = path.split('/');
+        if parts.next() != Some("m") {
+            return Err(LelantusError::invalid_parameter("derivation_path"));
+        }
+
+        let steps = parts
+            .map(|part| {
+                let (digits, hardened) = match part.strip_suffix(['\'', 'h']) {
+                    Some(digits) => (digits, true),
+                    None => (part, false),
+                };
+                let index: u32 = digits
+                    .parse()
+                    .map_err(|_| LelantusError::invalid_parameter("derivation_path"))?;
+                if hardened {
+                    ChildIndex::hardened(index)
+                } else {
+                    ChildIndex::normal(index)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { steps })
+    }
+}
+
+impl core::fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "m")?;
+        for step in &self.steps {
+            write!(f, "/{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Spend key with a chain code, for hierarchical deterministic wallets
+///
+/// Derivation follows BIP32 with HMAC-SHA512 in place of curve arithmetic.
+/// Keys here are symmetric, so there is no public derivation: every child,
+/// hardened or not, needs the parent spend key, and the two kinds differ only
+/// in their index.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct ExtendedSpendKey {
+    key: Vec<u8>,
+    chain_code: Vec<u8>,
+    depth: u8,
+}
+
+impl ExtendedSpendKey {
+    /// Derive the master key from a wallet seed
+    pub fn from_seed(seed: &[u8]) -> Result<Self> {
+        if seed.len() < MIN_SEED_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("seed")
+                    .expected(format!(">= {} bytes", MIN_SEED_SIZE))
+                    .actual(seed.len()),
+            ));
+        }
+
+        Self::split(hmac_sha512(MASTER_KEY_DOMAIN, &[seed])?, 0)
+    }
+
+    /// Derive the master key from a BIP39 mnemonic and optional passphrase
+    #[cfg(feature = "std")]
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self> {
+        let mnemonic = bip39::Mnemonic::parse(phrase)
+            .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+        let mut seed = mnemonic.to_seed(passphrase);
+        let master = Self::from_seed(&seed);
+        seed.zeroize();
+        master
+    }
+
+    /// Generate a fresh BIP39 mnemonic of `word_count` words (12 to 24)
+    #[cfg(feature = "std")]
+    pub fn generate_mnemonic(word_count: usize) -> Result<String> {
+        if !(12..=24).contains(&word_count) || !word_count.is_multiple_of(3) {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("word_count")
+                    .expected("12, 15, 18, 21 or 24")
+                    .actual(word_count),
+            ));
+        }
+
+        let mut entropy = vec![0u8; word_count / 3 * 4];
+        rand::thread_rng().fill_bytes(&mut entropy);
+        let mnemonic = bip39::Mnemonic::from_entropy(&entropy)
+            .map_err(|e| LelantusError::CryptoError(e.to_string()));
+        entropy.zeroize();
+        Ok(mnemonic?.to_string())
+    }
+
+    /// Split 64 bytes of HMAC output into a key and chain code
+    ///
+    /// The key is stretched back to [`SPEND_KEY_SIZE`] from the left half.
+    fn split(mut output: Vec<u8>, depth: u8) -> Result<Self> {
+        let chain_code = output.split_off(32);
+        let key = hmac_sha512(&output, &[SPEND_KEY_DOMAIN])?;
+        output.zeroize();
+        Ok(Self {
+            key,
+            chain_code,
+            depth,
+        })
+    }
+
+    /// Derive the child at `index`
+    pub fn derive_child(&self, index: ChildIndex) -> Result<Self> {
+        let depth = self
+            .depth
+            .checked_add(1)
+            .ok_or(LelantusError::invalid_parameter("depth"))?;
+        let output = hmac_sha512(
+            &self.chain_code,
+            &[&[0], &self.key, &index.raw().to_be_bytes()],
+        )?;
+        Self::split(output, depth)
+    }
+
+    /// Derive the descendant at `path` from this key
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self> {
+        path.steps()
+            .iter()
+            .try_fold(self.clone(), |key, &index| key.derive_child(index))
+    }
+
+    /// Number of derivation steps from the master key
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Spend key at this node
+    pub fn spend_key(&self) -> SpendKey {
+        SpendKey {
+            key: self.key.clone(),
+        }
+    }
+}
+
+impl core::fmt::Debug for ExtendedSpendKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ExtendedSpendKey")
+            .field("depth", &self.depth)
+            .finish_non_exhaustive()
+    }
+}
+
+/// View key allowing amount decryption without spend authority
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct ViewKey {
+    key: Vec<u8>,
+}
+
+impl ViewKey {
+    /// Create a view key from raw bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != VIEW_KEY_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("view_key")
+                    .expected(VIEW_KEY_SIZE)
+                    .actual(bytes.len()),
+            ));
+        }
+
+        Ok(Self {
+            key: bytes.to_vec(),
+        })
+    }
+
+    /// Generate a fresh random view key
+    #[cfg(feature = "std")]
+    pub fn generate() -> Self {
+        let mut key = vec![0u8; VIEW_KEY_SIZE];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self { key }
+    }
+
+    /// Get the raw key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Public identifier of the key, revealing nothing about it
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(VIEW_KEY_FINGERPRINT_DOMAIN);
+        hasher.update(&self.key);
+        hasher.finalize().into()
+    }
+}
+
+impl core::fmt::Debug for ViewKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ViewKey").finish_non_exhaustive()
+    }
+}
+
+/// Viewing half of a wallet: the view key plus the keys tagging and opening
+/// owned coins
+///
+/// Coins are paid to a full viewing key: their randomness carries a tag only
+/// this key can produce, they are committed under an opening keyed by its
+/// nullifier key, and their amounts are sealed to its view key. It can tell
+/// when its coins are spent, but cannot authorize spends.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct FullViewingKey {
+    view_key: ViewKey,
+    owner_key: Vec<u8>,
+    nullifier_key: Vec<u8>,
+}
+
+impl FullViewingKey {
+    /// Get the view key
+    pub fn view_key(&self) -> &ViewKey {
+        &self.view_key
+    }
+
+    /// Ownership tag for `nonce`, stretched to `size` bytes
+    fn ownership_tag(&self, nonce: &[u8], size: usize) -> Result<Vec<u8>> {
+        hmac_stretch(&self.owner_key, OWNERSHIP_DOMAIN, nonce, size)
+    }
+
+    /// Randomness a coin with `randomness` is committed under
+    pub(crate) fn coin_opening(&self, randomness: &[u8]) -> Result<Vec<u8>> {
+        if randomness.is_empty() {
+            return Err(LelantusError::InvalidCommitment);
+        }
+        hmac_stretch(
+            &self.nullifier_key,
+            COIN_OPENING_DOMAIN,
+            randomness,
+            randomness.len(),
+        )
+    }
+
+    /// Fresh commitment randomness of `size` bytes marking a coin as owned
+    ///
+    /// The randomness is a random nonce followed by the ownership tag of
+    /// that nonce.
+    #[cfg(feature = "std")]
+    pub fn coin_randomness(&self, size: usize) -> Result<Vec<u8>> {
+        if size <= OWNERSHIP_NONCE_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("size")
+                    .expected(format!("> {}", OWNERSHIP_NONCE_SIZE))
+                    .actual(size),
+            ));
+        }
+
+        let mut randomness = vec![0u8; OWNERSHIP_NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut randomness);
+        let tag = self.ownership_tag(&randomness, size - OWNERSHIP_NONCE_SIZE)?;
+        randomness.extend(tag);
+        Ok(randomness)
+    }
+
+    /// Check whether `commitment` is a coin owned by this key
+    pub fn owns(&self, commitment: &Commitment) -> Result<bool> {
+        let randomness = &commitment.randomness;
+        if randomness.len() <= OWNERSHIP_NONCE_SIZE {
+            return Ok(false);
+        }
+
+        let (nonce, tag) = randomness.split_at(OWNERSHIP_NONCE_SIZE);
+        Ok(constant_time_compare(
+            tag,
+            &self.ownership_tag(nonce, tag.len())?,
+        ))
+    }
+
+    /// Keyed opening of a coin owned by this key
+    ///
+    /// The returned commitment keeps the coin's value and carries the
+    /// randomness it was committed under, so it opens like an unowned coin.
+    /// Fails with `NotCoinOwner` if the coin is not owned by this key.
+    pub fn open(&self, commitment: &Commitment) -> Result<Commitment> {
+        if !self.owns(commitment)? {
+            return Err(LelantusError::NotCoinOwner);
+        }
+        Ok(Commitment {
+            value: commitment.value.clone(),
+            randomness: self.coin_opening(&commitment.randomness)?,
+        })
+    }
+}
+
+impl core::fmt::Debug for FullViewingKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FullViewingKey").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_key_from_bytes() -> Result<()> {
+        let key = ViewKey::from_bytes(&[7; VIEW_KEY_SIZE])?;
+        assert_eq!(key.as_bytes(), &[7; VIEW_KEY_SIZE][..]);
+        assert!(ViewKey::from_bytes(&[7; 32]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        assert_eq!(format!("{:?}", ViewKey::generate()), "ViewKey { .. }");
+        assert_eq!(format!("{:?}", SpendKey::generate()), "SpendKey { .. }");
+    }
+
+    #[test]
+    fn test_seed_derivation_and_ownership() -> Result<()> {
+        let key = SpendKey::from_seed(&[3; 32])?;
+        assert!(key == SpendKey::from_seed(&[3; 32])?);
+        assert!(SpendKey::from_seed(&[3; 16]).is_err());
+        assert!(key.view_key()? == key.full_viewing_key()?.view_key().clone());
+
+        let fvk = key.full_viewing_key()?;
+        let commitment = Commitment {
+            value: vec![1; 128],
+            randomness: fvk.coin_randomness(64)?,
+        };
+        assert!(key.owns(&commitment)?);
+        assert!(!SpendKey::from_seed(&[4; 32])?.owns(&commitment)?);
+
+        let mut tampered = commitment.clone();
+        tampered.randomness[0] ^= 1;
+        assert!(!key.owns(&tampered)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_derivation_path_round_trip() -> Result<()> {
+        let path: DerivationPath = "m/44'/1h/0'/5".parse()?;
+        assert_eq!(path, DerivationPath::bip44(1, 0, 5)?);
+        assert_eq!(path.to_string(), "m/44'/1'/0'/5");
+        assert!(path.steps()[2].is_hardened() && !path.steps()[3].is_hardened());
+
+        assert!("44'/0'".parse::<DerivationPath>().is_err());
+        assert!("m/x".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648".parse::<DerivationPath>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hd_restore_from_mnemonic() -> Result<()> {
+        let phrase = ExtendedSpendKey::generate_mnemonic(24)?;
+        assert_eq!(phrase.split_whitespace().count(), 24);
+
+        let master = ExtendedSpendKey::from_mnemonic(&phrase, "")?;
+        let path = DerivationPath::bip44(1, 0, 3)?;
+        let key = master.derive_path(&path)?;
+        assert_eq!(key.depth(), 4);
+
+        // Restoring from the same words gives the same keys
+        let restored = ExtendedSpendKey::from_mnemonic(&phrase, "")?.derive_path(&path)?;
+        assert!(key.spend_key() == restored.spend_key());
+
+        // Sibling, hardened and passphrase variants all differ
+        let sibling = master.derive_path(&DerivationPath::bip44(1, 0, 4)?)?;
+        assert!(key.spend_key() != sibling.spend_key());
+        let hardened = master.derive_path(&"m/44'/1'/0'/3'".parse()?)?;
+        assert!(key.spend_key() != hardened.spend_key());
+        let other = ExtendedSpendKey::from_mnemonic(&phrase, "extra")?.derive_path(&path)?;
+        assert!(key.spend_key() != other.spend_key());
+
+        assert!(ExtendedSpendKey::from_mnemonic("not a mnemonic", "").is_err());
+        assert!(ExtendedSpendKey::generate_mnemonic(13).is_err());
+        Ok(())
+    }
+}
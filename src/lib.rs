@@ -6,37 +6,284 @@
 //! - Coin history privacy
 //! - Efficient zero-knowledge proofs
 //! - Scalable privacy without trusted setup
+//!
+//! Without the default `std` feature the crate builds as `no_std` + `alloc`,
+//! keeping commitments, proofs, witnesses, keys and serialization for
+//! hardware wallets; state management and everything else need `std`.
+//! The C library built with `ffi` always links `std`.
+
+#![cfg_attr(not(any(feature = "std", feature = "ffi")), no_std)]
+
+extern crate alloc;
+
+/// `alloc` items the std prelude provides, for modules built without `std`
+mod prelude {
+    pub(crate) use alloc::format;
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec;
+    pub(crate) use alloc::vec::Vec;
+}
 
 pub mod accumulator;
+#[cfg(feature = "std")]
+pub mod address;
+pub mod amount;
+#[cfg(feature = "async")]
+pub mod async_state;
+#[cfg(feature = "std")]
+pub mod binding;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub mod coin_selection;
 pub mod commitment;
+#[cfg(feature = "std")]
+pub mod disclosure;
 pub mod errors;
+#[cfg(feature = "std")]
+pub mod fee;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod inner_product;
+#[cfg(feature = "std")]
+pub mod integration;
+#[cfg(feature = "std")]
 pub mod joinsplit;
+pub mod keys;
+pub mod merkle;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod mint;
+#[cfg(feature = "std")]
+pub mod nullifier;
+#[cfg(feature = "std")]
+pub mod one_out_of_many;
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod oracle;
 pub mod parameters;
+pub mod pedersen;
+#[cfg(feature = "poseidon")]
+pub mod poseidon;
 pub mod proof;
+#[cfg(feature = "std")]
+pub mod scanner;
+#[cfg(feature = "std")]
+pub mod schema;
+pub mod serial;
 pub mod serialization;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "std")]
+pub mod surjection;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+pub mod transcript;
+#[cfg(feature = "std")]
+pub mod verifier;
 pub mod witness;
+#[cfg(feature = "std")]
+pub mod witness_cache;
 
-pub use accumulator::{Accumulator, MembershipProof};
-pub use commitment::{AccumulatorElement, Commitment, CommitmentScheme};
-pub use errors::{LelantusError, Result};
-pub use joinsplit::{JoinSplit, JoinSplitProof};
-pub use parameters::{LelantusParameters, PrivacyLevel};
-pub use proof::{RangeProof, ZKProof};
-pub use witness::Witness;
+pub use accumulator::{
+    Accumulator, AccumulatorDiff, AccumulatorSet, AccumulatorSnapshot, MembershipProof,
+};
+#[cfg(feature = "std")]
+pub use address::{
+    PaymentRequest, StealthAddress, StealthKeys, StealthOutput, StealthScanKey, ADDRESS_HRP,
+    PAYMENT_REQUEST_HRP,
+};
+pub use amount::{Amount, MAX_MONEY, MIST_PER_SLVR};
+#[cfg(feature = "async")]
+pub use async_state::AsyncLelantusState;
+#[cfg(feature = "std")]
+pub use binding::{BindingKey, BindingSignature};
+#[cfg(feature = "std")]
+pub use builder::{BuiltJoinSplit, JoinSplitBuilder};
+#[cfg(feature = "std")]
+pub use checkpoint::StateCheckpoint;
+#[cfg(feature = "std")]
+pub use coin_selection::{
+    select_coins, BranchAndBound, CoinSelection, LargestFirst, RandomSelection,
+};
+pub use commitment::{AccumulatorElement, AssetId, Commitment, CommitmentScheme, NATIVE_ASSET};
+#[cfg(feature = "std")]
+pub use disclosure::{DisclosurePackage, PaymentProof};
+pub use errors::{ErrorContext, LelantusError, Result};
+#[cfg(feature = "std")]
+pub use fee::FeeEstimator;
+pub use inner_product::InnerProductProof;
+#[cfg(feature = "std")]
+pub use integration::extract_joinsplits;
+#[cfg(feature = "std")]
+pub use joinsplit::{block_commitment, tx_inclusion_proof, JoinSplit, JoinSplitProof};
+pub use keys::{ChildIndex, DerivationPath, ExtendedSpendKey, FullViewingKey, SpendKey, ViewKey};
+pub use merkle::MerkleProof;
+#[cfg(feature = "metrics")]
+pub use metrics::{ProofOperation, TimingHistogram, TimingSnapshot};
+#[cfg(feature = "std")]
+pub use mint::LelantusMint;
+#[cfg(feature = "std")]
+pub use nullifier::{derive_nullifier, verify_nullifier_unlinkability, NonSpendProof};
+#[cfg(feature = "std")]
+pub use one_out_of_many::OneOutOfManyProof;
+pub use parameters::{HashAlgo, LelantusParameters, PrivacyLevel};
+pub use pedersen::{BitProof, BoundProof, DifferenceProof, OpeningProof, SerialProof};
+pub use proof::{AggregatedRangeProof, AssetTerms, ProofMetrics, RangeProof, ZKProof, ZkStatement};
+#[cfg(feature = "std")]
+pub use scanner::{CoinRecord, Scanner};
+#[cfg(feature = "std")]
+pub use schema::{FieldEncoding, FieldLayout, FieldSpan, FieldSpec};
+pub use serial::SerialNumber;
+pub use serialization::{LelantusSerializable, SerializationFormat};
+#[cfg(feature = "std")]
+pub use store::{FileStore, StateStore, StoredState};
+#[cfg(feature = "std")]
+pub use surjection::AssetSurjectionProof;
+pub use transcript::{ProofDomain, ProofTranscript};
+#[cfg(feature = "std")]
+pub use verifier::{PreparedVerifier, VerifyReport};
+pub use witness::{AmountDisclosure, Witness};
+#[cfg(feature = "std")]
+pub use witness_cache::{CacheConfig, CacheStats, WitnessCache};
 
+#[cfg(feature = "std")]
+#[cfg(feature = "std")]
 use parking_lot::RwLock;
+#[cfg(feature = "std")]
+use rand::{CryptoRng, RngCore};
+#[cfg(feature = "std")]
+use rayon::prelude::*;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "std")]
 use std::sync::Arc;
+#[cfg(feature = "std")]
+use verifier::{RangeCheck, VerificationCache};
 
 /// Lelantus protocol version
-pub const LELANTUS_VERSION: u32 = 1;
+pub const LELANTUS_VERSION: u32 = 2;
+
+/// First protocol version allowing more than [`LEGACY_MAX_JOINSPLIT_OUTPUTS`] outputs
+pub const MULTI_OUTPUT_VERSION: u32 = 2;
 
 /// Maximum number of inputs in a JoinSplit transaction
 pub const MAX_JOINSPLIT_INPUTS: usize = 16;
 
-/// Maximum number of outputs in a JoinSplit transaction
-pub const MAX_JOINSPLIT_OUTPUTS: usize = 2;
+/// Maximum number of outputs in a JoinSplit transaction under any parameters
+///
+/// The limit in force is [`LelantusParameters::max_joinsplit_outputs`].
+pub const MAX_JOINSPLIT_OUTPUTS: usize = 16;
+
+/// Maximum number of outputs in a JoinSplit before [`MULTI_OUTPUT_VERSION`]
+pub const LEGACY_MAX_JOINSPLIT_OUTPUTS: usize = 2;
+
+/// Largest fee a JoinSplit may pay, in mist
+#[cfg(feature = "std")]
+const MAX_FEE: u64 = 1_000_000;
+
+/// Encoded size of an accumulator element under the default Pedersen scheme
+#[cfg(feature = "std")]
+const ESTIMATED_ELEMENT_VALUE_SIZE: usize = 2 * pedersen::POINT_SIZE;
+
+/// Size of a nullifier (an HMAC-SHA512 output)
+#[cfg(feature = "std")]
+const ESTIMATED_NULLIFIER_SIZE: usize = 64;
+
+/// Size of commitment randomness
+#[cfg(feature = "std")]
+const ESTIMATED_RANDOMNESS_SIZE: usize = 64;
+
+/// Approximate memory held by a [`LelantusState`], in bytes
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// Accumulator elements held locally
+    pub accumulator_bytes: usize,
+
+    /// Spent nullifier set
+    pub nullifier_bytes: usize,
+
+    /// Witness cache entries
+    pub witness_cache_bytes: usize,
+}
+
+#[cfg(feature = "std")]
+impl MemoryEstimate {
+    /// Total estimated bytes
+    pub fn total(&self) -> usize {
+        self.accumulator_bytes + self.nullifier_bytes + self.witness_cache_bytes
+    }
+}
+
+/// Bytes held by an owned byte vector with `len` bytes of content
+#[cfg(feature = "std")]
+fn vec_bytes(len: usize) -> usize {
+    std::mem::size_of::<Vec<u8>>() + len
+}
+
+/// Bytes held by a witness beyond its inline size
+#[cfg(feature = "std")]
+fn witness_heap_bytes(witness: &Witness) -> usize {
+    witness.commitment.value.len()
+        + witness.commitment.randomness.len()
+        + witness.accumulator_value.len()
+        + witness.membership_proof.len()
+        + witness.encrypted_value.len()
+}
+
+/// Evaluate a proof operation, recording its duration when the `metrics`
+/// feature is enabled
+#[cfg(feature = "std")]
+macro_rules! timed {
+    ($state:expr, $operation:ident, $body:expr) => {{
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+        let result = $body;
+        #[cfg(feature = "metrics")]
+        $state
+            .timings
+            .record(metrics::ProofOperation::$operation, start.elapsed());
+        result
+    }};
+}
+
+/// Map `f` over `items`, on the rayon pool with the `parallel` feature
+///
+/// Stops at an error; which error is returned when several items fail is
+/// unspecified on the parallel path.
+#[cfg(feature = "std")]
+fn try_map<T, U, F>(items: &[T], f: F) -> Result<Vec<U>>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> Result<U> + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    return items.par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return items.iter().map(f).collect();
+}
+
+/// Tag native coins with their asset and checked amount
+#[cfg(feature = "std")]
+fn native_coins(
+    inputs: Vec<(Commitment, Witness)>,
+    amounts: Vec<Amount>,
+) -> Vec<(AssetId, Commitment, Amount)> {
+    inputs
+        .into_iter()
+        .zip(amounts)
+        .map(|((coin, _), amount)| (NATIVE_ASSET, coin, amount))
+        .collect()
+}
 
 /// Lelantus state manager
+#[cfg(feature = "std")]
 #[derive(Clone, Debug)]
 pub struct LelantusState {
     /// Accumulator for coin commitments
@@ -48,34 +295,256 @@ pub struct LelantusState {
     /// Protocol parameters
     parameters: Arc<LelantusParameters>,
 
+    /// Verifier prepared once for `parameters`
+    verifier: PreparedVerifier,
+
     /// Witness cache for performance
-    witness_cache: Arc<RwLock<lru::LruCache<Vec<u8>, Witness>>>,
+    witness_cache: Arc<RwLock<WitnessCache>>,
+
+    /// JoinSplits already verified, so block connection can skip the proofs
+    /// checked at mempool acceptance
+    verification_cache: Arc<VerificationCache>,
+
+    /// Spent nullifiers
+    nullifiers: Arc<RwLock<BTreeSet<SerialNumber>>>,
+
+    /// Backend persisting the state, if any
+    store: Option<Arc<dyn StateStore>>,
+
+    /// Proof timing histograms
+    #[cfg(feature = "metrics")]
+    timings: Arc<metrics::ProofTimings>,
 }
 
+#[cfg(feature = "std")]
 impl LelantusState {
-    /// Create a new Lelantus state
+    /// Create a new Lelantus state with the default witness cache
     pub fn new(parameters: LelantusParameters) -> Result<Self> {
-        let commitment_scheme = CommitmentScheme::new(&parameters)?;
-        let accumulator = Accumulator::new(&parameters)?;
+        Self::with_cache_config(parameters, CacheConfig::default())
+    }
 
-        // Create LRU cache with proper error handling
-        let cache_size =
-            std::num::NonZeroUsize::new(1000).ok_or(LelantusError::InvalidParameter)?;
+    /// Create a new Lelantus state with a configured witness cache
+    pub fn with_cache_config(parameters: LelantusParameters, cache: CacheConfig) -> Result<Self> {
+        let commitment_scheme = Arc::new(CommitmentScheme::new(&parameters)?);
+        let accumulator = Accumulator::new(&parameters)?;
+        let parameters = Arc::new(parameters);
+        let verifier =
+            PreparedVerifier::from_shared(Arc::clone(&parameters), Arc::clone(&commitment_scheme))?;
 
         Ok(Self {
             accumulator: Arc::new(RwLock::new(accumulator)),
-            commitment_scheme: Arc::new(commitment_scheme),
-            parameters: Arc::new(parameters),
-            witness_cache: Arc::new(RwLock::new(lru::LruCache::new(cache_size))),
+            commitment_scheme,
+            parameters,
+            verifier,
+            witness_cache: Arc::new(RwLock::new(WitnessCache::new(cache)?)),
+            verification_cache: Arc::new(VerificationCache::default()),
+            nullifiers: Arc::new(RwLock::new(BTreeSet::new())),
+            store: None,
+            #[cfg(feature = "metrics")]
+            timings: Arc::new(metrics::ProofTimings::default()),
         })
     }
 
+    /// Open a state persisted in the file at `path`
+    ///
+    /// Starts empty if the file does not exist yet. See
+    /// [`open_with_store`](Self::open_with_store).
+    pub fn open(parameters: LelantusParameters, path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::open_with_store(parameters, Arc::new(FileStore::new(path)))
+    }
+
+    /// Open a state persisted in `store`
+    ///
+    /// Restores the accumulator and spent nullifiers if the store holds a
+    /// saved state, failing with `ParameterMismatch` if it was saved under
+    /// other parameters. Changes are written back by [`flush`](Self::flush).
+    pub fn open_with_store(
+        parameters: LelantusParameters,
+        store: Arc<dyn StateStore>,
+    ) -> Result<Self> {
+        let mut state = Self::new(parameters)?;
+        if let Some(stored) = store.load()? {
+            if stored.parameters_fingerprint != state.parameters.fingerprint()? {
+                return Err(LelantusError::ParameterMismatch);
+            }
+            *state.accumulator.write() = stored.accumulator;
+            *state.nullifiers.write() = stored.nullifiers.into_iter().collect();
+        }
+        state.store = Some(store);
+        Ok(state)
+    }
+
+    /// Write the accumulator and spent nullifiers to the store
+    ///
+    /// Does nothing for a state created without a store.
+    pub fn flush(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let stored = StoredState::new(
+            self.parameters.fingerprint()?,
+            self.accumulator.read().clone(),
+            self.nullifiers.read().iter().cloned().collect(),
+        );
+        store.save(&stored)
+    }
+
     /// Add a coin commitment to the accumulator
+    ///
+    /// Rejects commitments that are not well formed under this state's scheme.
     pub fn add_coin(&self, commitment: &Commitment) -> Result<()> {
+        self.commitment_scheme.check_well_formed(commitment)?;
+        self.add_coin_unchecked(commitment)
+    }
+
+    /// Add a coin commitment without validating it
+    ///
+    /// Only for trusted import paths (e.g. replaying a verified snapshot),
+    /// where per-coin validation is redundant. The accumulator is updated
+    /// exactly as by [`add_coin`](Self::add_coin). Only the element length
+    /// required by the accumulator is still enforced; other malformations are
+    /// accumulated as-is rather than rejected.
+    pub fn add_coin_unchecked(&self, commitment: &Commitment) -> Result<()> {
         let mut accumulator = self.accumulator.write();
         accumulator.add_element(commitment.to_element()?)
     }
 
+    /// Add a batch of coin commitments, returning their accumulator indices
+    ///
+    /// Validates every commitment, then appends them all under a single
+    /// write lock. Nothing is added if any commitment is invalid or the
+    /// batch does not fit.
+    pub fn add_coins(&self, commitments: &[Commitment]) -> Result<Vec<usize>> {
+        let elements = commitments
+            .iter()
+            .map(|commitment| {
+                self.commitment_scheme.check_well_formed(commitment)?;
+                commitment.to_element()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut accumulator = self.accumulator.write();
+        Ok(accumulator.extend(elements)?.collect())
+    }
+
+    /// Mint coins for `values`, returning each commitment with its witness
+    ///
+    /// Commits every value, appends the commitments in one accumulator
+    /// update and builds all witnesses from a single pass over the
+    /// accumulator. Nothing is added if any value is invalid.
+    pub fn mint_batch(&self, values: &[u64]) -> Result<Vec<(Commitment, Witness)>> {
+        let commitments = values
+            .iter()
+            .map(|&value| self.commitment_scheme.commit(value))
+            .collect::<Result<Vec<_>>>()?;
+        self.add_minted(commitments, values, Witness::encrypt_amount)
+    }
+
+    /// Mint coins for `values` owned by `owner`
+    ///
+    /// Like [`mint_batch`](Self::mint_batch), but the coins carry the owner's
+    /// ownership tag, are committed under its keyed opening and have their
+    /// amounts sealed to its view key.
+    pub fn mint_to(
+        &self,
+        owner: &FullViewingKey,
+        values: &[u64],
+    ) -> Result<Vec<(Commitment, Witness)>> {
+        let commitments = values
+            .iter()
+            .map(|&value| self.commitment_scheme.commit_to(owner, value))
+            .collect::<Result<Vec<_>>>()?;
+        self.add_minted(commitments, values, |commitment, index, value| {
+            Witness::encrypt_amount_for_view_key(commitment, index, value, owner.view_key())
+        })
+    }
+
+    /// Mint coins of `asset` for `values`
+    ///
+    /// Like [`mint_batch`](Self::mint_batch), but the commitments bind the
+    /// asset; spend them with
+    /// [`create_asset_joinsplit`](Self::create_asset_joinsplit). Needs the
+    /// `pedersen` scheme; fails with `InvalidParameter` otherwise.
+    pub fn mint_assets(
+        &self,
+        asset: &AssetId,
+        values: &[u64],
+    ) -> Result<Vec<(Commitment, Witness)>> {
+        self.mint_assets_with_rng(asset, values, &mut rand::rngs::OsRng)
+    }
+
+    /// Mint coins of `asset` with commitment randomness drawn from `rng`
+    ///
+    /// A fixed-seed RNG reproduces the commitments byte for byte. Only for
+    /// tests and auditable builds; production mints should use
+    /// [`mint_assets`](Self::mint_assets).
+    pub fn mint_assets_with_rng(
+        &self,
+        asset: &AssetId,
+        values: &[u64],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Vec<(Commitment, Witness)>> {
+        self.commitment_scheme.pedersen_generators()?;
+        let commitments = values
+            .iter()
+            .map(|&value| {
+                self.commitment_scheme
+                    .commit_asset_with_rng(asset, value, rng)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.add_minted(commitments, values, Witness::encrypt_amount)
+    }
+
+    /// Append freshly minted commitments and build their witnesses
+    pub(crate) fn add_minted(
+        &self,
+        commitments: Vec<Commitment>,
+        values: &[u64],
+        seal: impl Fn(&Commitment, usize, u64) -> Result<Vec<u8>>,
+    ) -> Result<Vec<(Commitment, Witness)>> {
+        self.parameters.check_denominations("values", values)?;
+        let elements = commitments
+            .iter()
+            .map(Commitment::to_element)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut accumulator = self.accumulator.write();
+        let start = accumulator.element_count();
+
+        // Witnesses need the full history; check before mutating
+        if start != accumulator.elements().len() {
+            return Err(LelantusError::AccumulatorError(
+                "membership proofs unavailable for checkpointed accumulator".to_string(),
+            ));
+        }
+        if start + elements.len() > self.parameters.max_elements {
+            return Err(LelantusError::AccumulatorError(
+                "accumulator is full".to_string(),
+            ));
+        }
+
+        let encrypted = commitments
+            .iter()
+            .zip(values)
+            .enumerate()
+            .map(|(offset, (commitment, &value))| seal(commitment, start + offset, value))
+            .collect::<Result<Vec<_>>>()?;
+
+        accumulator.add_elements(elements)?;
+        let proofs = accumulator.create_membership_proofs(start..start + values.len())?;
+
+        commitments
+            .into_iter()
+            .zip(proofs)
+            .zip(encrypted)
+            .map(|((commitment, proof), encrypted_value)| {
+                let witness =
+                    Witness::from_membership_proof(commitment.clone(), &proof, encrypted_value)?;
+                Ok((commitment, witness))
+            })
+            .collect()
+    }
+
     /// Get the current accumulator value
     pub fn get_accumulator(&self) -> Result<Vec<u8>> {
         let accumulator = self.accumulator.read();
@@ -83,191 +552,1323 @@ impl LelantusState {
     }
 
     /// Create a JoinSplit transaction
+    ///
+    /// Each input reveals the serial number committed inside its coin as its
+    /// nullifier. With denominations enabled, every output must be one.
     pub fn create_joinsplit(
         &self,
         inputs: Vec<(Commitment, Witness)>,
         outputs: Vec<u64>,
         fee: u64,
     ) -> Result<JoinSplit> {
+        self.build_joinsplit(inputs, outputs, fee, None, &mut rand::rngs::OsRng)
+    }
+
+    /// Create a JoinSplit with all randomness drawn from `rng`
+    ///
+    /// Output commitment randomness, proof blinding and change flags all come
+    /// from `rng`, so a fixed-seed RNG reproduces the JoinSplit byte for byte.
+    /// Only for tests and auditable builds; production spends should use
+    /// [`create_joinsplit`](Self::create_joinsplit).
+    pub fn create_joinsplit_with_rng(
+        &self,
+        inputs: Vec<(Commitment, Witness)>,
+        outputs: Vec<u64>,
+        fee: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<JoinSplit> {
+        self.build_joinsplit(inputs, outputs, fee, None, rng)
+    }
+
+    /// Create a JoinSplit, spending inputs owned by `owner` if given
+    fn build_joinsplit(
+        &self,
+        inputs: Vec<(Commitment, Witness)>,
+        outputs: Vec<u64>,
+        fee: u64,
+        owner: Option<&FullViewingKey>,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<JoinSplit> {
+        let amounts = self.check_joinsplit_balance(&inputs, &outputs, fee, owner)?;
+
+        // Create output commitments
+        let output_coins = outputs
+            .iter()
+            .map(|&amount| {
+                let commitment = self.commitment_scheme.commit_with_rng(amount, rng)?;
+                Ok((NATIVE_ASSET, commitment, amount))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Owned coins are proven under their keyed openings
+        let mut coins = native_coins(inputs, amounts);
+        if let Some(owner) = owner {
+            for (_, coin, _) in &mut coins {
+                *coin = owner.open(coin)?;
+            }
+        }
+        self.assemble_joinsplit(coins, output_coins, fee, rng)
+    }
+
+    /// Create a JoinSplit moving coins of several assets
+    ///
+    /// Each input names the asset it was minted as, and each output the
+    /// asset and value it pays. Every asset must balance on its own, with the
+    /// fee paid in [`NATIVE_ASSET`]; fails with `BalanceMismatch` otherwise.
+    ///
+    /// Needs the `pedersen` scheme, where each asset has its own value
+    /// generator and the JoinSplit's balance proof shows verifiers that
+    /// every asset balances; fails with `InvalidParameter` otherwise.
+    ///
+    /// No output publishes its asset. Each is range-proven under its
+    /// blinded asset tag, and an [`AssetSurjectionProof`] shows every tag
+    /// belongs to one of the outputs' assets or the native one; it names
+    /// that set of assets, but not which output holds which.
+    pub fn create_asset_joinsplit(
+        &self,
+        inputs: Vec<(AssetId, Commitment, Witness)>,
+        outputs: Vec<(AssetId, u64)>,
+        fee: u64,
+    ) -> Result<JoinSplit> {
+        self.commitment_scheme.pedersen_generators()?;
+        let amounts = self.check_asset_balance(&inputs, &outputs, fee)?;
+
+        let rng = &mut rand::rngs::OsRng;
+        let output_coins = outputs
+            .iter()
+            .map(|&(asset, amount)| {
+                let commitment = self
+                    .commitment_scheme
+                    .commit_asset_with_rng(&asset, amount, rng)?;
+                Ok((asset, commitment, amount))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let coins = inputs
+            .into_iter()
+            .zip(amounts)
+            .map(|((asset, commitment, _), amount)| (asset, commitment, amount))
+            .collect();
+
+        self.assemble_joinsplit(coins, output_coins, fee, rng)
+    }
+
+    /// Derive an output paying `amount` to a stealth `address`
+    ///
+    /// With denominations enabled, `amount` must be one. See
+    /// [`StealthAddress::derive_output`].
+    pub fn derive_output_for(
+        &self,
+        address: &StealthAddress,
+        amount: u64,
+    ) -> Result<StealthOutput> {
+        self.parameters.check_denominations("amount", &[amount])?;
+        address.derive_output(&self.commitment_scheme, amount, &mut rand::rngs::OsRng)
+    }
+
+    /// Create a JoinSplit whose outputs at the given indices use preset
+    /// commitments, such as stealth outputs
+    ///
+    /// Each preset commitment must open to the amount at its index; fails
+    /// with `InvalidCommitment` otherwise.
+    pub(crate) fn create_joinsplit_with_preset_outputs(
+        &self,
+        inputs: Vec<(Commitment, Witness)>,
+        outputs: Vec<u64>,
+        preset: &[(usize, Commitment)],
+        fee: u64,
+    ) -> Result<JoinSplit> {
+        let amounts = self.check_joinsplit_balance(&inputs, &outputs, fee, None)?;
+
+        let rng = &mut rand::rngs::OsRng;
+        let mut output_coins = outputs
+            .iter()
+            .map(|&amount| {
+                let commitment = self.commitment_scheme.commit_with_rng(amount, rng)?;
+                Ok((NATIVE_ASSET, commitment, amount))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (index, commitment) in preset {
+            let amount = *outputs
+                .get(*index)
+                .ok_or(LelantusError::invalid_parameter("output_index"))?;
+            if !self.commitment_scheme.verify(commitment, amount)? {
+                return Err(LelantusError::InvalidCommitment);
+            }
+            output_coins[*index].1 = commitment.clone();
+        }
+
+        let coins = native_coins(inputs, amounts);
+        self.assemble_joinsplit(coins, output_coins, fee, rng)
+    }
+
+    /// Prove and assemble a JoinSplit from checked coins and its outputs
+    ///
+    /// `coins` holds the asset, commitment and amount of each coin spent, and
+    /// `outputs` the same of each coin created.
+    fn assemble_joinsplit(
+        &self,
+        coins: Vec<(AssetId, Commitment, Amount)>,
+        outputs: Vec<(AssetId, Commitment, u64)>,
+        fee: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<JoinSplit> {
+        let inputs = self.spent_inputs(coins, rng)?;
+
+        // Generate proof, committing to a fresh binding key
+        let binding_secret = BindingKey::generate(rng);
+        let binding_key = binding_secret.verification_key()?;
+        let nullifiers = try_map(&inputs, |(coin, _)| SerialNumber::from_opening(coin))?;
+        let proof =
+            self.generate_joinsplit_proof(&inputs, &nullifiers, &outputs, fee, &binding_key, rng)?;
+
+        let change_tags = JoinSplit::decoy_change_tags(outputs.len(), rng);
+
+        Ok(JoinSplit {
+            inputs: inputs.into_iter().map(|(_, input)| input).collect(),
+            outputs: outputs
+                .into_iter()
+                .map(|(_, commitment, _)| commitment)
+                .collect(),
+            proof,
+            fee,
+            nullifiers,
+            change_tags,
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key,
+            binding_signature: None,
+            binding_secret: Some(binding_secret),
+            padding: Vec::new(),
+        })
+    }
+
+    /// Create a JoinSplit spending coins owned by `spend_key`
+    ///
+    /// Inputs are opened with the key, as committed and sealed by
+    /// [`mint_to`](Self::mint_to), so their nullifiers are keyed by it. Fails
+    /// with `NotCoinOwner` if any input was not minted to the key's full
+    /// viewing key.
+    pub fn create_joinsplit_with_key(
+        &self,
+        spend_key: &SpendKey,
+        inputs: Vec<(Commitment, Witness)>,
+        outputs: Vec<u64>,
+        fee: u64,
+    ) -> Result<JoinSplit> {
+        let owner = spend_key.full_viewing_key()?;
+        for (commitment, _) in &inputs {
+            if !owner.owns(commitment)? {
+                return Err(LelantusError::NotCoinOwner);
+            }
+        }
+        self.build_joinsplit(inputs, outputs, fee, Some(&owner), &mut rand::rngs::OsRng)
+    }
+
+    /// Generate the proof a JoinSplit would carry and discard it
+    ///
+    /// Performs the same checks as [`create_joinsplit`](Self::create_joinsplit)
+    /// and reports the serialized proof size and generation time, for fee
+    /// estimation.
+    pub fn dry_run_proof(
+        &self,
+        inputs: &[(Commitment, Witness)],
+        outputs: &[u64],
+        fee: u64,
+    ) -> Result<ProofMetrics> {
+        let amounts = self.check_joinsplit_balance(inputs, outputs, fee, None)?;
+
+        let output_coins = outputs
+            .iter()
+            .map(|&amount| Ok((NATIVE_ASSET, self.commitment_scheme.commit(amount)?, amount)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let start = std::time::Instant::now();
+        let inputs = self.spent_inputs(
+            native_coins(inputs.to_vec(), amounts),
+            &mut rand::rngs::OsRng,
+        )?;
+        let binding_key = BindingKey::generate(&mut rand::rngs::OsRng).verification_key()?;
+        let nullifiers = try_map(&inputs, |(coin, _)| SerialNumber::from_opening(coin))?;
+        let proof = self.generate_joinsplit_proof(
+            &inputs,
+            &nullifiers,
+            &output_coins,
+            fee,
+            &binding_key,
+            &mut rand::rngs::OsRng,
+        )?;
+        let generation_time = start.elapsed();
+
+        Ok(ProofMetrics {
+            serialized_size: LelantusSerializable::to_bytes(&proof)?.len(),
+            generation_time,
+        })
+    }
+
+    /// Check JoinSplit shape and that inputs balance outputs plus fee,
+    /// returning the input amounts
+    fn check_joinsplit_balance(
+        &self,
+        inputs: &[(Commitment, Witness)],
+        outputs: &[u64],
+        fee: u64,
+        owner: Option<&FullViewingKey>,
+    ) -> Result<Vec<Amount>> {
         if inputs.is_empty() || inputs.len() > MAX_JOINSPLIT_INPUTS {
             return Err(LelantusError::InvalidInputCount);
         }
 
-        if outputs.is_empty() || outputs.len() > MAX_JOINSPLIT_OUTPUTS {
+        if outputs.is_empty() || outputs.len() > self.parameters.max_joinsplit_outputs() {
             return Err(LelantusError::InvalidOutputCount);
         }
+        self.parameters.check_denominations("outputs", outputs)?;
+
+        let amounts = self.verified_input_amounts(inputs, owner)?;
+        let input_sum = Amount::sum(amounts.iter().copied())?;
+        let output_sum = Self::output_sum(outputs)?;
+
+        // PRODUCTION: Verify fee is reasonable
+        if fee > MAX_FEE {
+            return Err(LelantusError::BalanceMismatch);
+        }
+
+        // PRODUCTION: Verify balance equation: inputs = outputs + fee
+        if input_sum != output_sum.checked_add(Amount::new(fee)?)? {
+            return Err(LelantusError::BalanceMismatch);
+        }
+
+        Ok(amounts)
+    }
+
+    /// Check a multi-asset JoinSplit balances per asset, returning the input
+    /// amounts
+    ///
+    /// Inputs must open to their stated asset, and the fee counts against
+    /// [`NATIVE_ASSET`].
+    fn check_asset_balance(
+        &self,
+        inputs: &[(AssetId, Commitment, Witness)],
+        outputs: &[(AssetId, u64)],
+        fee: u64,
+    ) -> Result<Vec<Amount>> {
+        if inputs.is_empty() || inputs.len() > MAX_JOINSPLIT_INPUTS {
+            return Err(LelantusError::InvalidInputCount);
+        }
+
+        if outputs.is_empty() || outputs.len() > self.parameters.max_joinsplit_outputs() {
+            return Err(LelantusError::InvalidOutputCount);
+        }
+        let values: Vec<u64> = outputs.iter().map(|&(_, value)| value).collect();
+        self.parameters.check_denominations("outputs", &values)?;
+
+        // Zero-value outputs are not allowed
+        if fee > MAX_FEE || values.contains(&0) {
+            return Err(LelantusError::BalanceMismatch);
+        }
+
+        let mut balances: BTreeMap<AssetId, (Amount, Amount)> = BTreeMap::new();
+        let mut amounts = Vec::with_capacity(inputs.len());
+        for (asset, commitment, witness) in inputs {
+            let amount = self.verified_input_amount(commitment, witness, asset, None)?;
+            let (spent, _) = balances.entry(*asset).or_default();
+            *spent = spent.checked_add(amount)?;
+            amounts.push(amount);
+        }
+        let fee = (NATIVE_ASSET, fee);
+        for (asset, value) in outputs.iter().chain([&fee]) {
+            let (_, paid) = balances.entry(*asset).or_default();
+            *paid = paid.checked_add(Amount::new(*value)?)?;
+        }
+
+        if balances.values().any(|(spent, paid)| spent != paid) {
+            return Err(LelantusError::BalanceMismatch);
+        }
+        Ok(amounts)
+    }
+
+    /// Validate inputs against their witnesses and sum their amounts
+    ///
+    /// Inputs are opened with `owner` if given, otherwise with the coin's own
+    /// key.
+    fn verified_input_sum(
+        &self,
+        inputs: &[(Commitment, Witness)],
+        owner: Option<&FullViewingKey>,
+    ) -> Result<Amount> {
+        Amount::sum(self.verified_input_amounts(inputs, owner)?)
+    }
 
+    /// Validate inputs against their witnesses and return their amounts
+    fn verified_input_amounts(
+        &self,
+        inputs: &[(Commitment, Witness)],
+        owner: Option<&FullViewingKey>,
+    ) -> Result<Vec<Amount>> {
         // PRODUCTION IMPLEMENTATION: Full input validation with comprehensive checks
         // This performs:
         // 1. Commitment verification using witness
         // 2. Amount extraction and validation
-        // 3. Range proof verification
-        // 4. Balance verification with overflow protection
-        // 5. Proper error handling for all edge cases
-
-        let mut input_sum: u64 = 0;
-        let mut verified_inputs = Vec::with_capacity(inputs.len());
-
-        for (commitment, witness) in inputs.iter() {
-            // PRODUCTION: Verify witness structure and validity
-            witness
-                .verify()
-                .map_err(|_| LelantusError::InvalidWitness)?;
-
-            // PRODUCTION: Extract amount from witness with proper error handling
-            let amount = witness
-                .get_amount()
-                .map_err(|_| LelantusError::InvalidWitness)?;
-
-            // PRODUCTION: Verify the commitment matches the witness
-            // This ensures the commitment was created with the claimed amount
-            let expected_commitment = self
-                .commitment_scheme
-                .commit(amount)
-                .map_err(|_| LelantusError::InvalidWitness)?;
-
-            if expected_commitment.serialize()? != commitment.serialize()? {
-                return Err(LelantusError::InvalidWitness);
-            }
-
-            // PRODUCTION: Verify amount is within valid range (1 to 2^64-1)
-            // Zero-value coins are not allowed
-            if amount == 0 {
-                return Err(LelantusError::BalanceMismatch);
-            }
-
-            // PRODUCTION: Verify amount doesn't exceed maximum coin value
-            // This prevents potential overflow attacks
-            const MAX_COIN_VALUE: u64 = u64::MAX / 2; // Conservative limit
-            if amount > MAX_COIN_VALUE {
-                return Err(LelantusError::BalanceMismatch);
-            }
+        try_map(inputs, |(commitment, witness)| {
+            self.verified_input_amount(commitment, witness, &NATIVE_ASSET, owner)
+        })
+    }
 
-            // PRODUCTION: Check for overflow when summing inputs
-            input_sum = input_sum
-                .checked_add(amount)
-                .ok_or(LelantusError::BalanceMismatch)?;
+    /// Validate one input of `asset` against its witness and return its amount
+    fn verified_input_amount(
+        &self,
+        commitment: &Commitment,
+        witness: &Witness,
+        asset: &AssetId,
+        owner: Option<&FullViewingKey>,
+    ) -> Result<Amount> {
+        // PRODUCTION: Verify witness structure and validity
+        witness
+            .verify()
+            .map_err(|_| LelantusError::invalid_witness("witness"))?;
 
-            verified_inputs.push((commitment.clone(), amount));
+        // PRODUCTION: Extract amount from witness with proper error handling
+        let amount = match owner {
+            Some(owner) => witness.get_amount_with_view_key(owner.view_key()),
+            None => witness.get_amount(),
         }
+        .map_err(|_| LelantusError::invalid_witness("amount"))?;
+        let opening = owner.map(|owner| owner.open(commitment)).transpose()?;
 
-        // PRODUCTION: Calculate output sum with overflow protection
-        let mut output_sum: u64 = 0;
-        for &amount in outputs.iter() {
-            // Validate each output amount
-            if amount == 0 {
-                return Err(LelantusError::BalanceMismatch);
-            }
+        // PRODUCTION: Verify the commitment matches the witness
+        // This ensures the commitment was created with the claimed amount
+        let opens = self
+            .commitment_scheme
+            .verify_asset(opening.as_ref().unwrap_or(commitment), asset, amount)
+            .map_err(|_| LelantusError::invalid_witness("commitment"))?;
 
-            output_sum = output_sum
-                .checked_add(amount)
-                .ok_or(LelantusError::BalanceMismatch)?;
+        if !opens || witness.commitment != *commitment {
+            return Err(LelantusError::invalid_witness("commitment"));
         }
 
-        // PRODUCTION: Verify fee is reasonable
-        const MAX_FEE: u64 = 1_000_000; // Maximum fee in satoshis
-        if fee > MAX_FEE {
+        // PRODUCTION: Verify amount is within valid range (1 to MAX_MONEY)
+        // Zero-value coins are not allowed
+        if amount == 0 {
             return Err(LelantusError::BalanceMismatch);
         }
 
-        // PRODUCTION: Verify balance equation: inputs = outputs + fee
-        let expected_output_sum = output_sum
-            .checked_add(fee)
-            .ok_or(LelantusError::BalanceMismatch)?;
+        Amount::new(amount)
+    }
 
-        if input_sum != expected_output_sum {
+    /// Validate output amounts and sum them
+    fn output_sum(outputs: &[u64]) -> Result<Amount> {
+        // Zero-value outputs are not allowed
+        if outputs.contains(&0) {
             return Err(LelantusError::BalanceMismatch);
         }
 
-        // Create output commitments
-        let output_commitments: Result<Vec<_>> = outputs
+        let amounts = outputs
             .iter()
-            .map(|&amount| self.commitment_scheme.commit(amount))
-            .collect();
+            .map(|&amount| Amount::new(amount))
+            .collect::<Result<Vec<_>>>()?;
+        Amount::sum(amounts)
+    }
 
-        let output_commitments = output_commitments?;
+    /// Compute the fee that balances `inputs` against `outputs`
+    ///
+    /// Returns the leftover `input_sum - output_sum`, which is zero for an
+    /// exact balance, or [`LelantusError::BalanceMismatch`] if the outputs
+    /// exceed the inputs.
+    pub fn required_fee(
+        &self,
+        inputs: &[(Commitment, Witness)],
+        outputs: &[u64],
+    ) -> Result<Amount> {
+        let input_sum = self.verified_input_sum(inputs, None)?;
+        let output_sum = Self::output_sum(outputs)?;
+        input_sum.checked_sub(output_sum)
+    }
 
-        // Generate proof
-        let proof = self.generate_joinsplit_proof(&inputs, &output_commitments, fee)?;
+    /// Consolidate many coins into a single output
+    ///
+    /// The output carries the sum of all inputs minus `fee`.
+    pub fn sweep(&self, inputs: Vec<(Commitment, Witness)>, fee: u64) -> Result<JoinSplit> {
+        if inputs.is_empty() || inputs.len() > MAX_JOINSPLIT_INPUTS {
+            return Err(LelantusError::InvalidInputCount);
+        }
 
-        Ok(JoinSplit {
-            inputs: inputs.into_iter().map(|(c, _)| c).collect(),
-            outputs: output_commitments,
-            proof,
-            fee,
-        })
+        let total = self.verified_input_sum(&inputs, None)?;
+
+        // The consolidated output must be strictly positive after the fee
+        let output = total.checked_sub(Amount::new(fee)?)?;
+        if output.is_zero() {
+            return Err(LelantusError::BalanceMismatch);
+        }
+
+        self.create_joinsplit(inputs, vec![output.as_mist()], fee)
+    }
+
+    /// Pair each spent coin with a fresh commitment to the same asset and
+    /// amount
+    ///
+    /// The fresh commitment is what the JoinSplit publishes, so the spent
+    /// coin is only identified as a member of its anonymity set. It carries
+    /// no serial, as the spend reveals the coin's.
+    fn spent_inputs(
+        &self,
+        coins: Vec<(AssetId, Commitment, Amount)>,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Vec<(Commitment, Commitment)>> {
+        coins
+            .into_iter()
+            .map(|(asset, coin, amount)| {
+                let input =
+                    self.commitment_scheme
+                        .commit_input_with_rng(&asset, amount.as_mist(), rng)?;
+                Ok((coin, input))
+            })
+            .collect()
+    }
+
+    /// Prove each spent coin is in its anonymity set at the current head
+    ///
+    /// Each proof is against the published input plus the coin's revealed
+    /// serial, binding the serial to the coin. Returns the head the proofs
+    /// were made against, the anonymity set index of each input and one
+    /// membership proof per input. Fails with `InvalidWitness` if a coin is
+    /// not in the accumulator.
+    fn prove_membership(
+        &self,
+        inputs: &[(Commitment, Commitment)],
+        nullifiers: &[SerialNumber],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<(Vec<u8>, Vec<u64>, Vec<OneOutOfManyProof>)> {
+        let accumulator = self.accumulator.read();
+        let element_count = accumulator.element_count();
+        let set_size = self.parameters.anonymity_set_size();
+
+        let mut sets = Vec::with_capacity(inputs.len());
+        let mut proofs = Vec::with_capacity(inputs.len());
+        for ((coin, input), nullifier) in inputs.iter().zip(nullifiers) {
+            let index = accumulator
+                .position(&coin.value)
+                .ok_or(LelantusError::invalid_witness("commitment"))?;
+            let set_index = accumulator.anonymity_set_index(index);
+            let set = accumulator.anonymity_set(set_index, element_count)?;
+            proofs.push(OneOutOfManyProof::create_with_rng(
+                &self.commitment_scheme,
+                &set,
+                index - set_index as usize * set_size,
+                &coin.randomness,
+                &self
+                    .commitment_scheme
+                    .spend_offset(input, nullifier.as_bytes())?,
+                self.parameters.privacy_level,
+                rng,
+            )?);
+            sets.push(set_index);
+        }
+
+        Ok((accumulator.value().to_vec(), sets, proofs))
     }
 
     /// Generate a JoinSplit proof
+    ///
+    /// `inputs` pairs each spent coin with the fresh commitment published in
+    /// its place, and `outputs` holds the asset, commitment and value of each
+    /// coin created.
     fn generate_joinsplit_proof(
         &self,
-        inputs: &[(Commitment, Witness)],
-        outputs: &[Commitment],
+        inputs: &[(Commitment, Commitment)],
+        nullifiers: &[SerialNumber],
+        outputs: &[(AssetId, Commitment, u64)],
         fee: u64,
+        binding_key: &[u8],
+        rng: &mut (impl CryptoRng + RngCore),
     ) -> Result<JoinSplitProof> {
-        // Create range proofs for outputs
-        let range_proofs: Result<Vec<_>> = outputs
-            .iter()
-            .map(|commitment| RangeProof::create(commitment, &self.parameters))
-            .collect();
+        let assets: Vec<_> = outputs.iter().map(|(asset, _, _)| *asset).collect();
+        let values: Vec<_> = outputs.iter().map(|(_, _, value)| *value).collect();
+        let outputs: Vec<_> = outputs.iter().map(|(_, c, _)| c.clone()).collect();
 
-        let range_proofs = range_proofs?;
+        // Create one range proof covering all outputs. Outputs moving other
+        // assets are proven under their blinded asset tags, which the asset
+        // proof shows belong to the assets of the outputs or the native one.
+        let (asset_proof, aggregated_range_proof) = timed!(self, RangeProofCreation, {
+            let asset_proof = match assets.iter().any(|asset| *asset != NATIVE_ASSET) {
+                true => {
+                    let mut ring = assets.clone();
+                    ring.push(NATIVE_ASSET);
+                    Some(AssetSurjectionProof::create_with_rng(
+                        &self.commitment_scheme,
+                        &outputs,
+                        &assets,
+                        &ring,
+                        rng,
+                    )?)
+                }
+                false => None,
+            };
+            let tags = asset_proof.as_ref().map_or(&[][..], |proof| &proof.tags);
+            AggregatedRangeProof::create_with_rng(
+                &outputs,
+                &values,
+                tags,
+                &self.commitment_scheme,
+                rng,
+            )
+            .map(|range_proof| (asset_proof, range_proof))
+        })?;
 
-        // Create zero-knowledge proof
-        let zk_proof = ZKProof::create(inputs, outputs, fee, &self.parameters)?;
+        // Prove membership and balance against one accumulator head
+        let (anonymity_sets, membership_proofs, zk_proof) = timed!(self, ZkProofCreation, {
+            let (accumulator_value, anonymity_sets, membership_proofs) =
+                self.prove_membership(inputs, nullifiers, rng)?;
+            ZKProof::create_with_rng(
+                &ZkStatement {
+                    inputs: &inputs.iter().map(|(_, c)| c.clone()).collect::<Vec<_>>(),
+                    nullifiers,
+                    outputs: &outputs,
+                    fee,
+                    accumulator_value: &accumulator_value,
+                    binding_key,
+                },
+                &self.parameters,
+                rng,
+            )
+            .map(|zk_proof| (anonymity_sets, membership_proofs, zk_proof))
+        })?;
 
         Ok(JoinSplitProof {
-            range_proofs,
+            range_proofs: Vec::new(),
+            aggregated_range_proof: Some(aggregated_range_proof),
+            asset_proof,
             zk_proof,
+            anonymity_sets,
+            membership_proofs,
         })
     }
 
     /// Verify a JoinSplit transaction
     pub fn verify_joinsplit(&self, joinsplit: &JoinSplit) -> Result<bool> {
-        // Verify range proofs
-        for range_proof in &joinsplit.proof.range_proofs {
-            if !range_proof.verify(&self.parameters)? {
-                return Ok(false);
-            }
-        }
+        let snapshot = self.accumulator_snapshot();
+        self.verify_against(joinsplit, &snapshot)
+    }
 
-        // Verify zero-knowledge proof
-        let accumulator = self.accumulator.read();
-        let accumulator_value = accumulator.serialize()?;
+    /// Forget every cached verification
+    ///
+    /// Call on reorg: a JoinSplit verified on the abandoned branch must be
+    /// checked again before it is connected on the new one.
+    pub fn invalidate_verification_cache(&self) {
+        self.verification_cache.clear();
+    }
 
-        joinsplit.proof.zk_proof.verify(
-            &joinsplit.inputs,
-            &joinsplit.outputs,
-            &accumulator_value,
-            &self.parameters,
-        )
+    /// Take a snapshot of the accumulator head for lock-free verification
+    pub fn accumulator_snapshot(&self) -> AccumulatorSnapshot {
+        self.accumulator.read().snapshot()
     }
 
-    /// Get commitment scheme
-    pub fn commitment_scheme(&self) -> Arc<CommitmentScheme> {
-        Arc::clone(&self.commitment_scheme)
+    /// Verify a JoinSplit against a previously taken accumulator snapshot
+    ///
+    /// Anonymity sets are cut off at the snapshot's element count, so coins
+    /// added since do not change the verdict. The accumulator lock is only
+    /// held while those sets are copied out.
+    pub fn verify_joinsplit_with_snapshot(
+        &self,
+        joinsplit: &JoinSplit,
+        snapshot: &AccumulatorSnapshot,
+    ) -> Result<bool> {
+        self.verify_against(joinsplit, snapshot)
     }
 
-    /// Get parameters
+    /// Verify a batch of JoinSplits on a pool of `workers` threads
+    ///
+    /// All transactions are checked against one accumulator snapshot, and
+    /// results are returned in input order. Falls back to sequential
+    /// verification if the pool cannot be started.
+    pub fn verify_parallel(&self, txs: Vec<JoinSplit>, workers: usize) -> Vec<Result<bool>> {
+        let snapshot = self.accumulator_snapshot();
+        let verify =
+            |joinsplit: &JoinSplit| self.verify_joinsplit_with_snapshot(joinsplit, &snapshot);
+
+        match rayon::ThreadPoolBuilder::new()
+            .num_threads(workers.max(1))
+            .build()
+        {
+            Ok(pool) => pool.install(|| txs.par_iter().map(verify).collect()),
+            Err(_) => txs.iter().map(verify).collect(),
+        }
+    }
+
+    /// Verify a batch of JoinSplits, returning one verdict per input
+    ///
+    /// The accumulator is read once and one prepared verifier is shared by the
+    /// whole batch. Range proofs of every JoinSplit are checked in a single
+    /// parallel pass, and zero-knowledge proofs are only checked for
+    /// JoinSplits whose structure and range proofs passed. JoinSplits
+    /// verified before against the same head skip their proofs. A malformed
+    /// JoinSplit is reported as invalid rather than failing the batch.
+    pub fn verify_joinsplit_batch(&self, joinsplits: &[JoinSplit]) -> Result<Vec<bool>> {
+        let verifier = &self.verifier;
+        let (accumulator_value, sets) = {
+            let accumulator = self.accumulator.read();
+            let element_count = accumulator.element_count();
+            let sets: Vec<Option<Vec<Vec<Vec<u8>>>>> = joinsplits
+                .iter()
+                .map(|joinsplit| {
+                    crate::verifier::anonymity_sets(joinsplit, &accumulator, element_count).ok()
+                })
+                .collect();
+            (accumulator.value().to_vec(), sets)
+        };
+
+        let structured: Vec<bool> = joinsplits
+            .par_iter()
+            .zip(&sets)
+            .map(|(joinsplit, sets)| sets.is_some() && verifier.check_structure(joinsplit).is_ok())
+            .collect();
+        let keys: Vec<Option<[u8; 32]>> = joinsplits
+            .par_iter()
+            .map(|joinsplit| VerificationCache::key(joinsplit, &accumulator_value).ok())
+            .collect();
+        let cached: Vec<bool> = keys
+            .iter()
+            .zip(&structured)
+            .map(|(key, &structured)| {
+                structured && key.is_some_and(|key| self.verification_cache.contains(&key))
+            })
+            .collect();
+
+        let range_proofs: Vec<(usize, RangeCheck)> = joinsplits
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| structured[*index] && !cached[*index])
+            .flat_map(|(index, joinsplit)| {
+                RangeCheck::of(joinsplit)
+                    .into_iter()
+                    .map(move |check| (index, check))
+            })
+            .collect();
+        let failed_ranges: BTreeSet<usize> = range_proofs
+            .par_iter()
+            .filter(|(_, check)| {
+                !timed!(
+                    self,
+                    RangeProofVerification,
+                    verifier.verify_range_proof(*check)
+                )
+                .unwrap_or(false)
+            })
+            .map(|(index, _)| *index)
+            .collect();
+
+        Ok(joinsplits
+            .par_iter()
+            .enumerate()
+            .map(|(index, joinsplit)| {
+                if cached[index] {
+                    return true;
+                }
+                let valid = structured[index]
+                    && !failed_ranges.contains(&index)
+                    && sets[index].as_ref().is_some_and(|sets| {
+                        timed!(
+                            self,
+                            ZkProofVerification,
+                            verifier.verify_zk_proof(joinsplit, &accumulator_value, sets)
+                        )
+                        .unwrap_or(false)
+                    });
+                if let (true, Some(key)) = (valid, keys[index]) {
+                    self.verification_cache.insert(key);
+                }
+                valid
+            })
+            .collect())
+    }
+
+    /// Lazily verify a stream of JoinSplits
+    ///
+    /// Every item is verified against the accumulator head snapshotted when
+    /// this is called, and only when the iterator is advanced, so the stream
+    /// is never buffered.
+    pub fn verify_stream<'a>(
+        &'a self,
+        iter: impl Iterator<Item = JoinSplit> + 'a,
+    ) -> impl Iterator<Item = (JoinSplit, Result<bool>)> + 'a {
+        let snapshot = self.accumulator_snapshot();
+        iter.map(move |joinsplit| {
+            let result = self.verify_joinsplit_with_snapshot(&joinsplit, &snapshot);
+            (joinsplit, result)
+        })
+    }
+
+    /// Verify a JoinSplit against a past accumulator head
+    ///
+    /// Transactions reference the head they were built against, which may be
+    /// a few blocks behind the current one. `anchor` must still be within the
+    /// retained window of recent heads. Membership is checked against the
+    /// anonymity sets as they stood at `anchor`, so coins added after it
+    /// cannot be spent.
+    pub fn verify_joinsplit_at_anchor(&self, joinsplit: &JoinSplit, anchor: &[u8]) -> Result<bool> {
+        let snapshot = self
+            .accumulator
+            .read()
+            .anchor_snapshot(anchor)
+            .ok_or_else(|| {
+                LelantusError::AccumulatorError("anchor outside retained window".to_string())
+            })?;
+
+        self.verify_against(joinsplit, &snapshot)
+    }
+
+    /// Verify a JoinSplit against the current accumulator, reporting its weight
+    ///
+    /// Block validators can sum the weights of a block's transactions against
+    /// a block limit.
+    pub fn verify_joinsplit_metered(&self, joinsplit: &JoinSplit) -> Result<VerifyReport> {
+        let snapshot = self.accumulator_snapshot();
+        self.verify_against_metered(joinsplit, &snapshot)
+    }
+
+    /// Verify a JoinSplit against a given accumulator snapshot
+    fn verify_against(
+        &self,
+        joinsplit: &JoinSplit,
+        snapshot: &AccumulatorSnapshot,
+    ) -> Result<bool> {
+        Ok(self.verify_against_metered(joinsplit, snapshot)?.valid)
+    }
+
+    /// Verify a JoinSplit against a given accumulator snapshot, metering the
+    /// work
+    fn verify_against_metered(
+        &self,
+        joinsplit: &JoinSplit,
+        snapshot: &AccumulatorSnapshot,
+    ) -> Result<VerifyReport> {
+        self.verifier.check_structure(joinsplit)?;
+        self.verify_proofs_metered(&self.verifier, joinsplit, snapshot)
+    }
+
+    /// Verify the proofs of a structurally checked JoinSplit against the
+    /// current accumulator
+    pub(crate) fn verify_proofs(&self, joinsplit: &JoinSplit) -> Result<bool> {
+        let snapshot = self.accumulator_snapshot();
+        Ok(self
+            .verify_proofs_metered(&self.verifier, joinsplit, &snapshot)?
+            .valid)
+    }
+
+    /// Verify range proofs, then the membership and zero-knowledge proofs,
+    /// metering the work
+    fn verify_proofs_metered(
+        &self,
+        verifier: &PreparedVerifier,
+        joinsplit: &JoinSplit,
+        snapshot: &AccumulatorSnapshot,
+    ) -> Result<VerifyReport> {
+        let key = VerificationCache::key(joinsplit, snapshot.value())?;
+        if self.verification_cache.contains(&key) {
+            // Same weight as a full verification, so block limits do not
+            // depend on what this node happens to have cached
+            let checks = RangeCheck::of(joinsplit);
+            let range_weight: u64 = checks
+                .iter()
+                .map(|check| verifier.range_proof_weight(*check))
+                .sum();
+            return Ok(VerifyReport {
+                valid: true,
+                weight: range_weight + verifier.zk_proof_weight(joinsplit),
+                range_proofs_checked: checks.len(),
+            });
+        }
+
+        let mut report = VerifyReport {
+            valid: false,
+            weight: 0,
+            range_proofs_checked: 0,
+        };
+
+        // Verify range proofs
+        for check in RangeCheck::of(joinsplit) {
+            report.weight += verifier.range_proof_weight(check);
+            report.range_proofs_checked += 1;
+            if !timed!(
+                self,
+                RangeProofVerification,
+                verifier.verify_range_proof(check)
+            )? {
+                return Ok(report);
+            }
+        }
+
+        // Verify membership and zero-knowledge proofs
+        report.weight += verifier.zk_proof_weight(joinsplit);
+        let sets = crate::verifier::anonymity_sets(
+            joinsplit,
+            &self.accumulator.read(),
+            snapshot.element_count(),
+        )?;
+        report.valid = timed!(
+            self,
+            ZkProofVerification,
+            verifier.verify_zk_proof(joinsplit, snapshot.value(), &sets)
+        )?;
+        if report.valid {
+            self.verification_cache.insert(key);
+        }
+        Ok(report)
+    }
+
+    /// Verify the JoinSplits of a block and collect the nullifiers they consume
+    ///
+    /// Fails with `DoubleSpend` if a nullifier is already spent or consumed
+    /// twice within the block, and with `ProofVerificationFailed` if any
+    /// JoinSplit does not verify. Nothing is marked spent.
+    pub fn verify_block(&self, joinsplits: &[JoinSplit]) -> Result<BTreeSet<SerialNumber>> {
+        let mut consumed = BTreeSet::new();
+
+        for joinsplit in joinsplits {
+            if !self.verify_joinsplit(joinsplit)? {
+                return Err(LelantusError::ProofVerificationFailed);
+            }
+
+            for nullifier in joinsplit.nullifiers() {
+                if self.is_spent(nullifier) || !consumed.insert(nullifier.clone()) {
+                    return Err(LelantusError::DoubleSpend);
+                }
+            }
+        }
+
+        Ok(consumed)
+    }
+
+    /// Create a shielding mint of `value`
+    ///
+    /// Returns the mint to publish and the coin opening for the wallet.
+    /// Requires the `pedersen` scheme; nothing is added to the accumulator
+    /// until the mint is accepted. Fails with `InvalidParameter` if `value`
+    /// is not a denomination.
+    pub fn create_mint(&self, value: u64) -> Result<(LelantusMint, Commitment)> {
+        self.parameters.check_denominations("value", &[value])?;
+        LelantusMint::create(&self.commitment_scheme, value)
+    }
+
+    /// Verify that a shielding mint opens to its declared value
+    ///
+    /// Mint values are public, so denominations are enforced here too.
+    pub fn verify_mint(&self, mint: &LelantusMint) -> Result<bool> {
+        Ok(self.parameters.is_denomination(mint.value) && mint.verify(&self.commitment_scheme)?)
+    }
+
+    /// Check that a batch of mints and JoinSplits changes supply as expected
+    ///
+    /// Mint values and JoinSplit fees are public, and each verified
+    /// JoinSplit balances its hidden inputs against its outputs and fee, so
+    /// the batch creates exactly `Σ mint values − Σ fees`. Returns `false`
+    /// if any mint or JoinSplit fails verification, a nullifier is already
+    /// spent or consumed twice within the batch, or the total differs from
+    /// `expected_delta`; fails with `BalanceMismatch` if the total overflows.
+    pub fn verify_supply_delta(
+        &self,
+        mints: &[LelantusMint],
+        joinsplits: &[JoinSplit],
+        expected_delta: i128,
+    ) -> Result<bool> {
+        let mut delta = 0i128;
+        let mut consumed = BTreeSet::new();
+
+        for mint in mints {
+            if !self.verify_mint(mint)? {
+                return Ok(false);
+            }
+            delta = delta
+                .checked_add(i128::from(mint.value))
+                .ok_or(LelantusError::BalanceMismatch)?;
+        }
+
+        for joinsplit in joinsplits {
+            if !self.verify_joinsplit(joinsplit)? {
+                return Ok(false);
+            }
+            for nullifier in joinsplit.nullifiers() {
+                if self.is_spent(nullifier) || !consumed.insert(nullifier.clone()) {
+                    return Ok(false);
+                }
+            }
+            delta = delta
+                .checked_sub(i128::from(joinsplit.fee))
+                .ok_or(LelantusError::BalanceMismatch)?;
+        }
+
+        Ok(delta == expected_delta)
+    }
+
+    /// Get a copy of the proof timing histograms
+    #[cfg(feature = "metrics")]
+    pub fn timing_snapshot(&self) -> TimingSnapshot {
+        self.timings.snapshot()
+    }
+
+    /// Derive the serial number revealed when spending a coin owned by
+    /// `spend_key`
+    pub fn derive_nullifier(
+        &self,
+        spend_key: &SpendKey,
+        commitment: &Commitment,
+    ) -> Result<SerialNumber> {
+        SerialNumber::from_coin(spend_key, commitment)
+    }
+
+    /// Check whether a serial number has been spent
+    pub fn is_spent(&self, nullifier: &SerialNumber) -> bool {
+        self.nullifiers.read().contains(nullifier)
+    }
+
+    /// Record a serial number as spent
+    pub fn mark_spent(&self, nullifier: &SerialNumber) -> Result<()> {
+        if !self.nullifiers.write().insert(nullifier.clone()) {
+            return Err(LelantusError::DoubleSpend);
+        }
+        Ok(())
+    }
+
+    /// Prove that a coin has not been spent
+    ///
+    /// Derives the coin's would-be nullifier from its opening, proves the
+    /// coin commits to it and proves it is absent from the current spent
+    /// set. Fails with `DoubleSpend` if the coin has been spent. Coins
+    /// minted with [`mint_to`](Self::mint_to) are proven with
+    /// [`prove_not_spent_with_key`](Self::prove_not_spent_with_key).
+    pub fn prove_not_spent(
+        &self,
+        commitment: &Commitment,
+        witness: &Witness,
+    ) -> Result<NonSpendProof> {
+        self.build_non_spend_proof(commitment, witness, None)
+    }
+
+    /// Prove that a coin owned by `spend_key` has not been spent
+    ///
+    /// The coin is opened with the key, as in
+    /// [`create_joinsplit_with_key`](Self::create_joinsplit_with_key), so the
+    /// proof covers the keyed nullifier its spend would reveal. Fails with
+    /// `NotCoinOwner` if the coin was not minted to the key's full viewing
+    /// key.
+    pub fn prove_not_spent_with_key(
+        &self,
+        spend_key: &SpendKey,
+        commitment: &Commitment,
+        witness: &Witness,
+    ) -> Result<NonSpendProof> {
+        let owner = spend_key.full_viewing_key()?;
+        self.build_non_spend_proof(commitment, witness, Some(&owner))
+    }
+
+    /// Prove a coin has not been spent, opening it with `owner` if given
+    fn build_non_spend_proof(
+        &self,
+        commitment: &Commitment,
+        witness: &Witness,
+        owner: Option<&FullViewingKey>,
+    ) -> Result<NonSpendProof> {
+        if !witness.verify()? || witness.commitment() != commitment {
+            return Err(LelantusError::invalid_witness("commitment"));
+        }
+        let (opening, amount) = match owner {
+            Some(owner) => (
+                owner.open(commitment)?,
+                witness.get_amount_with_view_key(owner.view_key())?,
+            ),
+            None => (commitment.clone(), witness.get_amount()?),
+        };
+
+        let nullifiers = self.nullifiers.read();
+        let sorted: Vec<&SerialNumber> = nullifiers.iter().collect();
+        NonSpendProof::create(&self.commitment_scheme, &sorted, &opening, amount)
+    }
+
+    /// Check a non-spend proof against the current spent set
+    ///
+    /// The proven coin must be held in the accumulator.
+    pub fn verify_not_spent(&self, proof: &NonSpendProof) -> bool {
+        self.accumulator
+            .read()
+            .position(&proof.commitment)
+            .is_some()
+            && proof
+                .verify(&self.commitment_scheme, &self.nullifier_root())
+                .unwrap_or(false)
+    }
+
+    /// Merkle root over the spent nullifiers, for the state commitment
+    ///
+    /// The set is kept sorted, so the root depends only on its contents and
+    /// not on the order nullifiers were marked spent.
+    pub fn nullifier_root(&self) -> [u8; 32] {
+        nullifier::nullifier_merkle_root(self.nullifiers.read().iter())
+    }
+
+    /// Check whether a witness predates the current witness format
+    pub fn witness_needs_upgrade(&self, witness: &Witness) -> bool {
+        witness.version != witness::WITNESS_VERSION
+    }
+
+    /// Migrate a witness to the current format
+    ///
+    /// The legacy raw-bytes membership proof is replaced by a typed proof
+    /// rebuilt from this state's accumulator, which must hold the witnessed
+    /// coin at the witness index. Current-format witnesses are left as-is.
+    pub fn upgrade_witness(&self, witness: &mut Witness) -> Result<()> {
+        if !self.witness_needs_upgrade(witness) {
+            return Ok(());
+        }
+
+        let accumulator = self.accumulator.read();
+        let element = witness.commitment.to_element()?;
+        if accumulator.elements().get(witness.index).map(|e| &e.value) != Some(&element.value) {
+            return Err(LelantusError::invalid_witness("index"));
+        }
+
+        let proof = accumulator.create_membership_proof(witness.index)?;
+        *witness = Witness::from_membership_proof(
+            witness.commitment.clone(),
+            &proof,
+            std::mem::take(&mut witness.encrypted_value),
+        )?;
+        Ok(())
+    }
+
+    /// Bring witnesses up to date with the current accumulator
+    ///
+    /// Each witness is advanced past the coins added since its accumulator
+    /// value. Witnesses must be in the current format and the coins they
+    /// miss must still be held locally. Nothing is updated on error.
+    pub fn update_witnesses(&self, witnesses: &mut [Witness]) -> Result<()> {
+        let accumulator = self.accumulator.read();
+        let pruned_count = accumulator.element_count() - accumulator.elements().len();
+
+        let updated = witnesses
+            .iter()
+            .map(|witness| {
+                let start = witness.typed_membership_proof()?.element_count;
+                let missing = start
+                    .checked_sub(pruned_count)
+                    .and_then(|offset| accumulator.elements().get(offset..))
+                    .ok_or_else(|| {
+                        LelantusError::AccumulatorError(
+                            "coins since the witness are not held locally".to_string(),
+                        )
+                    })?;
+
+                let mut witness = witness.clone();
+                if !missing.is_empty() {
+                    witness.advance(missing)?;
+                }
+                Ok(witness)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        witnesses.clone_from_slice(&updated);
+        Ok(())
+    }
+
+    /// Export a checkpoint of the current state at `height`
+    pub fn export_checkpoint(&self, height: u64) -> Result<StateCheckpoint> {
+        let accumulator = self.accumulator.read();
+        let nullifiers = self.nullifiers.read();
+
+        Ok(StateCheckpoint {
+            height,
+            accumulator_value: accumulator.value().to_vec(),
+            element_count: accumulator.element_count(),
+            frontier: accumulator
+                .frontier(self.parameters.anonymity_set_size())
+                .to_vec(),
+            peaks: accumulator.peaks().to_vec(),
+            nullifier_root: nullifier::nullifier_merkle_root(nullifiers.iter()),
+            nullifiers: nullifiers.iter().cloned().collect(),
+        })
+    }
+
+    /// Bootstrap this state from a checkpoint
+    ///
+    /// Replaces the accumulator and nullifier set. The accumulator head is
+    /// trusted as-is; see the [`checkpoint`] module for the trust assumption.
+    pub fn import_checkpoint(&self, checkpoint: &StateCheckpoint) -> Result<()> {
+        if !checkpoint.verify_nullifier_root() {
+            return Err(LelantusError::AccumulatorError(
+                "checkpoint nullifier root mismatch".to_string(),
+            ));
+        }
+
+        let accumulator = Accumulator::from_checkpoint(
+            &self.parameters,
+            checkpoint.accumulator_value.clone(),
+            checkpoint.element_count,
+            checkpoint.frontier.clone(),
+            checkpoint.peaks.clone(),
+        )?;
+
+        *self.accumulator.write() = accumulator;
+        *self.nullifiers.write() = checkpoint.nullifiers.iter().cloned().collect();
+        self.witness_cache.write().clear();
+        self.verification_cache.clear();
+        Ok(())
+    }
+
+    /// Stream the accumulator, coin index and spent serial numbers to
+    /// `writer`
+    ///
+    /// See the [`snapshot`] module for the format.
+    pub fn export_snapshot(&self, writer: impl std::io::Write) -> Result<()> {
+        let accumulator = self.accumulator.read();
+        let nullifiers = self.nullifiers.read();
+        snapshot::write_snapshot(
+            writer,
+            &self.parameters.fingerprint()?,
+            &accumulator,
+            &nullifiers,
+        )
+    }
+
+    /// Replace this state with a snapshot read from `reader`
+    ///
+    /// Fails with `ParameterMismatch` if the snapshot was taken under other
+    /// parameters, leaving the state untouched on any error.
+    pub fn import_snapshot(&self, reader: impl std::io::Read) -> Result<()> {
+        let stored = snapshot::read_snapshot(reader)?;
+        if stored.parameters_fingerprint != self.parameters.fingerprint()? {
+            return Err(LelantusError::ParameterMismatch);
+        }
+
+        *self.accumulator.write() = stored.accumulator;
+        *self.nullifiers.write() = stored.nullifiers.into_iter().collect();
+        self.witness_cache.write().clear();
+        self.verification_cache.clear();
+        Ok(())
+    }
+
+    /// Estimate the memory held by this state
+    ///
+    /// Counts element, nullifier and witness contents plus their container
+    /// entries. Allocator and collection bookkeeping are not included.
+    pub fn memory_estimate(&self) -> MemoryEstimate {
+        let accumulator_bytes = self
+            .accumulator
+            .read()
+            .elements()
+            .iter()
+            .map(|element| vec_bytes(element.value.len()))
+            .sum();
+        let nullifier_bytes = self
+            .nullifiers
+            .read()
+            .iter()
+            .map(|nullifier| vec_bytes(nullifier.as_bytes().len()))
+            .sum();
+        let witness_cache_bytes = self
+            .witness_cache
+            .read()
+            .iter()
+            .map(|(key, witness)| {
+                vec_bytes(key.len()) + std::mem::size_of::<Witness>() + witness_heap_bytes(witness)
+            })
+            .sum();
+
+        MemoryEstimate {
+            accumulator_bytes,
+            nullifier_bytes,
+            witness_cache_bytes,
+        }
+    }
+
+    /// Estimate the memory a state would hold, for capacity planning
+    ///
+    /// Assumes the default Pedersen scheme and witnesses carrying a single
+    /// digest as membership proof, keyed by a nullifier-sized key.
+    pub fn estimate_for(
+        element_count: usize,
+        nullifier_count: usize,
+        cache_size: usize,
+    ) -> MemoryEstimate {
+        let witness_bytes = vec_bytes(ESTIMATED_NULLIFIER_SIZE)
+            + std::mem::size_of::<Witness>()
+            + 3 * ESTIMATED_ELEMENT_VALUE_SIZE
+            + ESTIMATED_RANDOMNESS_SIZE
+            + witness::AMOUNT_METADATA_OFFSET;
+
+        MemoryEstimate {
+            accumulator_bytes: element_count * vec_bytes(ESTIMATED_ELEMENT_VALUE_SIZE),
+            nullifier_bytes: nullifier_count * vec_bytes(ESTIMATED_NULLIFIER_SIZE),
+            witness_cache_bytes: cache_size * witness_bytes,
+        }
+    }
+
+    /// Get commitment scheme
+    pub fn commitment_scheme(&self) -> Arc<CommitmentScheme> {
+        Arc::clone(&self.commitment_scheme)
+    }
+
+    /// Get parameters
     pub fn parameters(&self) -> Arc<LelantusParameters> {
         Arc::clone(&self.parameters)
     }
 
     /// Cache a witness
     pub fn cache_witness(&self, key: Vec<u8>, witness: Witness) -> Result<()> {
+        witness.verify()?;
         let mut cache = self.witness_cache.write();
         cache.put(key, witness);
         Ok(())
     }
 
+    /// Cache a witness that must never be evicted or expire
+    pub fn pin_witness(&self, key: Vec<u8>, witness: Witness) -> Result<()> {
+        witness.verify()?;
+        self.witness_cache.write().pin(key, witness);
+        Ok(())
+    }
+
+    /// Let a pinned witness be evicted again, returning whether it was pinned
+    pub fn unpin_witness(&self, key: &[u8]) -> bool {
+        self.witness_cache.write().unpin(key)
+    }
+
+    /// Witness cache hit, miss and eviction counters
+    pub fn cache_stats(&self) -> CacheStats {
+        self.witness_cache.read().stats()
+    }
+
+    /// Check the witness cache invariants
+    ///
+    /// The cache must be within capacity and hold only witnesses that pass
+    /// [`Witness::verify`], as [`cache_witness`](Self::cache_witness) admits
+    /// nothing else.
+    pub fn cache_health_check(&self) -> bool {
+        let cache = self.witness_cache.read();
+        cache.unpinned_len() <= cache.capacity()
+            && cache
+                .iter()
+                .all(|(_, witness)| witness.verify().unwrap_or(false))
+    }
+
+    /// Rebuild the witness cache from scratch if its invariants are broken
+    ///
+    /// Gives operators a recovery path after a panic interrupted a cache
+    /// update. Cached and pinned witnesses are dropped and must be re-added.
+    /// Returns whether the cache was reset.
+    pub fn reset_cache_if_unhealthy(&self) -> bool {
+        if self.cache_health_check() {
+            return false;
+        }
+
+        self.witness_cache.write().clear();
+        true
+    }
+
     /// Get cached witness
     pub fn get_cached_witness(&self, key: &[u8]) -> Option<Witness> {
-        let mut cache = self.witness_cache.write();
-        cache.get(key).cloned()
+        self.witness_cache.write().get(key)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
 
@@ -287,6 +1888,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_add_coin_unchecked_matches_checked() -> Result<()> {
+        let checked = LelantusState::new(LelantusParameters::default())?;
+        let unchecked = LelantusState::new(LelantusParameters::default())?;
+
+        for amount in [1000, 2000, 3000] {
+            let commitment = checked.commitment_scheme().commit(amount)?;
+            checked.add_coin(&commitment)?;
+            unchecked.add_coin_unchecked(&commitment)?;
+        }
+        assert_eq!(
+            checked.accumulator_snapshot(),
+            unchecked.accumulator_snapshot()
+        );
+
+        let malformed = Commitment {
+            value: vec![b'z'; 2 * pedersen::POINT_SIZE],
+            randomness: vec![2; 16],
+        };
+        assert!(checked.add_coin(&malformed).is_err());
+        assert!(unchecked.add_coin_unchecked(&malformed).is_ok());
+        Ok(())
+    }
+
     #[test]
     fn test_accumulator_serialization() -> Result<()> {
         let params = LelantusParameters::default();
@@ -296,4 +1921,1450 @@ mod tests {
         let _accumulator = state.get_accumulator()?;
         Ok(())
     }
+
+    fn serial(byte: u8) -> Result<SerialNumber> {
+        SerialNumber::from_bytes(vec![byte; 64])
+    }
+
+    fn owned_coin(
+        state: &LelantusState,
+        index: usize,
+        amount: u64,
+    ) -> Result<(Commitment, Witness)> {
+        let commitment = state.commitment_scheme().commit(amount)?;
+        let encrypted = Witness::encrypt_amount(&commitment, index, amount)?;
+        let witness = Witness::new(
+            commitment.clone(),
+            index,
+            state.get_accumulator()?,
+            vec![1; 32],
+            encrypted,
+        );
+        Ok((commitment, witness))
+    }
+
+    #[test]
+    fn test_joinsplit_roundtrip() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+
+        let joinsplit = state.create_joinsplit(vec![input], vec![600, 300], 100)?;
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        let mut tampered = joinsplit.clone();
+        tampered.fee = 99;
+        assert!(!state.verify_joinsplit(&tampered)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_joinsplit_needs_pedersen() -> Result<()> {
+        // Hash commitments can be minted but not balanced, so not spent
+        for hash_function in ["blake3", "sha512"] {
+            let params = LelantusParameters {
+                hash_function: hash_function.to_string(),
+                ..LelantusParameters::default()
+            };
+            let state = LelantusState::new(params)?;
+            let input = owned_coin(&state, 0, 1000)?;
+            state.add_coin(&input.0)?;
+
+            assert!(matches!(
+                state.create_joinsplit(vec![input], vec![600, 300], 100),
+                Err(LelantusError::InvalidParameter(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_coins_batch() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = (0..3)
+            .map(|i| owned_coin(&state, i, 1000).map(|(commitment, _)| commitment))
+            .collect::<Result<Vec<_>>>()?;
+        state.add_coin(&coins[0])?;
+        assert_eq!(state.add_coins(&coins[1..])?, vec![1, 2]);
+        assert_eq!(state.accumulator.read().element_count(), 3);
+
+        let mut malformed = coins[0].clone();
+        malformed.value.pop();
+        assert!(state.add_coins(&[coins[0].clone(), malformed]).is_err());
+        assert_eq!(state.accumulator.read().element_count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_export_import() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = state.mint_batch(&[1_000, 2_000])?;
+        let joinsplit = state.create_joinsplit(coins[..1].to_vec(), vec![900], 100)?;
+        state.mark_spent(&joinsplit.nullifiers[0])?;
+
+        let mut snapshot = Vec::new();
+        state.export_snapshot(&mut snapshot)?;
+        let synced = LelantusState::new(LelantusParameters::default())?;
+        synced.import_snapshot(snapshot.as_slice())?;
+        assert_eq!(synced.get_accumulator()?, state.get_accumulator()?);
+        assert!(synced.is_spent(&joinsplit.nullifiers[0]));
+
+        // Every coin keeps its index, so old coins stay spendable
+        let spend = synced.create_joinsplit(coins[1..].to_vec(), vec![1_900], 100)?;
+        assert!(synced.verify_joinsplit(&spend)?);
+
+        let other = LelantusState::new(LelantusParameters::with_privacy_level(
+            PrivacyLevel::Enhanced,
+        ))?;
+        assert!(matches!(
+            other.import_snapshot(snapshot.as_slice()),
+            Err(LelantusError::ParameterMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verification_cache() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let joinsplit = state.create_joinsplit(state.mint_batch(&[1_000])?, vec![900], 100)?;
+
+        let first = state.verify_joinsplit_metered(&joinsplit)?;
+        assert!(first.valid);
+        assert_eq!(state.verification_cache.len(), 1);
+        assert_eq!(state.verify_joinsplit_metered(&joinsplit)?, first);
+        assert_eq!(
+            state.verify_joinsplit_batch(std::slice::from_ref(&joinsplit))?,
+            vec![true]
+        );
+
+        // A tampered copy misses the cache and fails as before
+        let mut tampered = joinsplit.clone();
+        tampered.fee = 99;
+        assert!(!state.verify_joinsplit(&tampered)?);
+        assert_eq!(state.verification_cache.len(), 1);
+
+        // A new head keys a fresh entry
+        state.mint_batch(&[1])?;
+        assert!(!state.verify_joinsplit(&joinsplit)?);
+
+        state.invalidate_verification_cache();
+        assert_eq!(state.verification_cache.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_joinsplit_with_many_outputs() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 2000)?;
+        state.add_coin(&input.0)?;
+
+        let outputs = vec![100; MAX_JOINSPLIT_OUTPUTS];
+        let joinsplit = state.create_joinsplit(vec![input.clone()], outputs, 400)?;
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        // Legacy parameters keep the two-output limit for creation and checks
+        let legacy = LelantusState::new(LelantusParameters {
+            protocol_version: 1,
+            ..Default::default()
+        })?;
+        legacy.add_coin(&input.0)?;
+        assert!(matches!(
+            legacy.create_joinsplit(vec![input], vec![100; 3], 1700),
+            Err(LelantusError::InvalidOutputCount)
+        ));
+        assert!(matches!(
+            legacy.verify_joinsplit(&joinsplit),
+            Err(LelantusError::InvalidOutputCount)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_joinsplit_with_seeded_rng() -> Result<()> {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let inputs = state.mint_batch(&[600, 500])?;
+        let create = |seed| {
+            state.create_joinsplit_with_rng(
+                inputs.clone(),
+                vec![700, 300],
+                100,
+                &mut StdRng::seed_from_u64(seed),
+            )
+        };
+
+        // The same seed reproduces the JoinSplit byte for byte
+        let joinsplit = create(42)?;
+        assert_eq!(
+            joinsplit.consensus_encode()?,
+            create(42)?.consensus_encode()?
+        );
+        assert_ne!(joinsplit.outputs, create(43)?.outputs);
+        assert!(state.verify_joinsplit(&joinsplit)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let inputs = (0..MAX_JOINSPLIT_INPUTS)
+            .map(|i| owned_coin(&state, i, 1000 + i as u64))
+            .collect::<Result<Vec<_>>>()?;
+        for (commitment, _) in &inputs {
+            state.add_coin(commitment)?;
+        }
+        let total: u64 = (0..MAX_JOINSPLIT_INPUTS as u64).map(|i| 1000 + i).sum();
+
+        let joinsplit = state.sweep(inputs, 100)?;
+        assert_eq!(joinsplit.input_count(), MAX_JOINSPLIT_INPUTS);
+        assert_eq!(joinsplit.output_count(), 1);
+        assert_eq!(joinsplit.fee, 100);
+        assert!(state
+            .commitment_scheme()
+            .verify(&joinsplit.outputs[0], total - 100)?);
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        // Applying it spends every input and adds the swept coin
+        let consumed = state.verify_block(std::slice::from_ref(&joinsplit))?;
+        assert_eq!(consumed.len(), MAX_JOINSPLIT_INPUTS);
+        for nullifier in &consumed {
+            state.mark_spent(nullifier)?;
+        }
+        assert!(matches!(
+            state.verify_block(std::slice::from_ref(&joinsplit)),
+            Err(LelantusError::DoubleSpend)
+        ));
+        state.add_coin(&joinsplit.outputs[0])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_inputs_reject_one_bad_witness() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let mut inputs = state.mint_batch(&[1000; MAX_JOINSPLIT_INPUTS])?;
+        let total = 1000 * MAX_JOINSPLIT_INPUTS as u64;
+
+        let joinsplit = state.create_joinsplit(inputs.clone(), vec![total - 100], 100)?;
+        assert_eq!(joinsplit.nullifiers.len(), MAX_JOINSPLIT_INPUTS);
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        // A single bad input fails the whole JoinSplit, however inputs are
+        // checked
+        inputs[11].1.encrypted_value[0] ^= 1;
+        assert!(matches!(
+            state.create_joinsplit(inputs, vec![total - 100], 100),
+            Err(LelantusError::InvalidWitness(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep_rejects_non_positive_output() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let inputs = vec![owned_coin(&state, 0, 100)?, owned_coin(&state, 1, 50)?];
+
+        assert!(matches!(
+            state.sweep(inputs.clone(), 150),
+            Err(LelantusError::BalanceMismatch)
+        ));
+        assert!(matches!(
+            state.sweep(inputs, 200),
+            Err(LelantusError::BalanceMismatch)
+        ));
+
+        let too_many = (0..=MAX_JOINSPLIT_INPUTS)
+            .map(|i| owned_coin(&state, i, 10))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(matches!(
+            state.sweep(too_many, 1),
+            Err(LelantusError::InvalidInputCount)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_rejects_empty_outputs() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let commitment = state.commitment_scheme().commit_input_with_rng(
+            &NATIVE_ASSET,
+            1000,
+            &mut rand::rngs::OsRng,
+        )?;
+        let zk_proof = ZKProof::create(
+            &ZkStatement {
+                inputs: core::slice::from_ref(&commitment),
+                nullifiers: &[],
+                outputs: &[],
+                fee: 1000,
+                accumulator_value: state.accumulator.read().value(),
+                binding_key: &[],
+            },
+            &state.parameters(),
+        )?;
+
+        let joinsplit = JoinSplit {
+            inputs: vec![commitment],
+            outputs: vec![],
+            proof: JoinSplitProof {
+                range_proofs: vec![],
+                aggregated_range_proof: None,
+                asset_proof: None,
+                zk_proof,
+                anonymity_sets: vec![],
+                membership_proofs: vec![],
+            },
+            fee: 1000,
+            nullifiers: Vec::new(),
+            change_tags: Vec::new(),
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key: Vec::new(),
+            binding_signature: None,
+            binding_secret: None,
+            padding: Vec::new(),
+        };
+
+        assert!(matches!(
+            state.verify_joinsplit(&joinsplit),
+            Err(LelantusError::InvalidOutputCount)
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_timing_snapshot() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+
+        for i in 0..5 {
+            let input = owned_coin(&state, i, 1000)?;
+            state.add_coin(&input.0)?;
+            let joinsplit = state.create_joinsplit(vec![input], vec![600, 300], 100)?;
+            state.verify_joinsplit(&joinsplit)?;
+        }
+
+        // One aggregated range proof covers both outputs of each JoinSplit
+        let snapshot = state.timing_snapshot();
+        assert_eq!(snapshot.range_proof_creation.count(), 5);
+        assert_eq!(snapshot.zk_proof_creation.count(), 5);
+        assert_eq!(snapshot.range_proof_verification.count(), 5);
+        assert_eq!(snapshot.zk_proof_verification.count(), 5);
+        assert!(snapshot.zk_proof_creation.percentile(50.0) > std::time::Duration::ZERO);
+        assert!(
+            snapshot.zk_proof_creation.percentile(99.0)
+                >= snapshot.zk_proof_creation.percentile(50.0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_relay_bundle() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let first = owned_coin(&state, 0, 700)?;
+        let second = owned_coin(&state, 1, 400)?;
+        state.add_coin(&first.0)?;
+        state.add_coin(&second.0)?;
+        let joinsplit = state.create_joinsplit(vec![first, second], vec![1000], 100)?;
+
+        // Both inputs are proven against set 0, whose index is written once
+        assert_eq!(joinsplit.proof.anonymity_sets, vec![0, 0]);
+        let bundle = joinsplit.to_relay_bundle()?;
+        assert!(bundle.len() < joinsplit.consensus_encode()?.len());
+        let decoded = JoinSplit::from_relay_bundle(&bundle)?;
+        assert_eq!(decoded.consensus_encode()?, joinsplit.consensus_encode()?);
+        assert!(state.verify_joinsplit(&decoded)?);
+
+        assert!(JoinSplit::from_relay_bundle(&bundle[..bundle.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_with_snapshot() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+
+        let joinsplit = state.create_joinsplit(vec![input], vec![900], 100)?;
+        let snapshot = state.accumulator_snapshot();
+        assert_eq!(
+            state.verify_joinsplit_with_snapshot(&joinsplit, &snapshot)?,
+            state.verify_joinsplit(&joinsplit)?
+        );
+
+        // The snapshot keeps verifying against the head it captured
+        state.add_coin(&state.commitment_scheme().commit(5)?)?;
+        assert!(!state.verify_joinsplit(&joinsplit)?);
+        assert!(state.verify_joinsplit_with_snapshot(&joinsplit, &snapshot)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_stream() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let amounts = [1000, 2000, 3000];
+        let inputs = amounts
+            .into_iter()
+            .enumerate()
+            .map(|(index, amount)| owned_coin(&state, index, amount))
+            .collect::<Result<Vec<_>>>()?;
+        for (commitment, _) in &inputs {
+            state.add_coin(commitment)?;
+        }
+        let mut joinsplits = Vec::new();
+        for (input, amount) in inputs.into_iter().zip(amounts) {
+            joinsplits.push(state.create_joinsplit(vec![input], vec![amount - 100], 100)?);
+        }
+        joinsplits[1].fee += 1;
+
+        let pulled = std::cell::Cell::new(0);
+        let source = joinsplits
+            .into_iter()
+            .inspect(|_| pulled.set(pulled.get() + 1));
+        let mut stream = state.verify_stream(source);
+        assert_eq!(pulled.get(), 0);
+
+        // Coins added after the stream starts do not move its snapshot
+        state.add_coin(&state.commitment_scheme().commit(5)?)?;
+
+        let (_, first) = stream
+            .next()
+            .ok_or(LelantusError::invalid_parameter("stream"))?;
+        assert!(first?);
+        assert_eq!(pulled.get(), 1);
+
+        let rest: Vec<bool> = stream.map(|(_, result)| result.unwrap_or(false)).collect();
+        assert_eq!(rest, vec![false, true]);
+        assert_eq!(pulled.get(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_parallel_matches_sequential() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let inputs = (0..8)
+            .map(|index| owned_coin(&state, index, 1000 + index as u64))
+            .collect::<Result<Vec<_>>>()?;
+        for (commitment, _) in &inputs {
+            state.add_coin(commitment)?;
+        }
+        let mut joinsplits = Vec::new();
+        for (index, input) in inputs.into_iter().enumerate() {
+            let amount = 1000 + index as u64;
+            let mut joinsplit = state.create_joinsplit(vec![input], vec![amount - 100], 100)?;
+            if index % 3 == 1 {
+                joinsplit.fee += 1;
+            }
+            joinsplits.push(joinsplit);
+        }
+
+        let sequential: Vec<bool> = joinsplits
+            .iter()
+            .map(|js| state.verify_joinsplit(js).unwrap_or(false))
+            .collect();
+        let parallel: Vec<bool> = state
+            .verify_parallel(joinsplits, 4)
+            .into_iter()
+            .map(|result| result.unwrap_or(false))
+            .collect();
+
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel.iter().filter(|&&valid| !valid).count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_joinsplit_batch_matches_sequential() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let inputs = (0..6)
+            .map(|index| owned_coin(&state, index, 2000 + index as u64))
+            .collect::<Result<Vec<_>>>()?;
+        for (commitment, _) in &inputs {
+            state.add_coin(commitment)?;
+        }
+        let mut joinsplits = Vec::new();
+        for (index, input) in inputs.into_iter().enumerate() {
+            let amount = 2000 + index as u64;
+            let mut joinsplit = state.create_joinsplit(vec![input], vec![amount - 100], 100)?;
+            match index {
+                1 => joinsplit.fee += 1,
+                4 => joinsplit.proof.aggregated_range_proof = None,
+                _ => {}
+            }
+            joinsplits.push(joinsplit);
+        }
+
+        let sequential: Vec<bool> = joinsplits
+            .iter()
+            .map(|js| state.verify_joinsplit(js).unwrap_or(false))
+            .collect();
+        let batch = state.verify_joinsplit_batch(&joinsplits)?;
+
+        assert_eq!(batch, sequential);
+        assert_eq!(batch, vec![true, false, true, true, false, true]);
+        assert!(state.verify_joinsplit_batch(&[])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_output_flag() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+        let mut joinsplit = state.create_joinsplit(vec![input], vec![600, 300], 100)?;
+
+        // Every output carries a flag before any is marked
+        assert_eq!(joinsplit.change_tags.len(), 2);
+        let sender = ViewKey::generate();
+        assert!(!joinsplit.is_change_output(1, &sender)?);
+
+        joinsplit.mark_change_output(1, &sender)?;
+        assert!(joinsplit.is_change_output(1, &sender)?);
+        assert!(!joinsplit.is_change_output(0, &sender)?);
+
+        let observer = ViewKey::generate();
+        assert!(!joinsplit.is_change_output(0, &observer)?);
+        assert!(!joinsplit.is_change_output(1, &observer)?);
+
+        assert!(state.verify_joinsplit(&joinsplit)?);
+        assert!(joinsplit.is_change_output(2, &sender).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_memo() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+        let mut joinsplit = state.create_joinsplit(vec![input], vec![600, 300], 100)?;
+
+        let recipient = ViewKey::generate();
+        assert_eq!(joinsplit.decrypt_memo(0, &recipient)?, None);
+        joinsplit.set_memo(0, b"rent for May", &recipient)?;
+        assert!(joinsplit.set_memo(1, &[0; 513], &recipient).is_err());
+
+        // The other output carries a decoy memo of the same size
+        assert_eq!(joinsplit.memos.len(), 2);
+        assert_eq!(joinsplit.memos[0].len(), joinsplit.memos[1].len());
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        let restored = JoinSplit::consensus_decode(&joinsplit.consensus_encode()?)?;
+        assert_eq!(
+            restored.decrypt_memo(0, &recipient)?,
+            Some(b"rent for May".to_vec())
+        );
+        assert_eq!(restored.decrypt_memo(1, &recipient)?, None);
+        assert_eq!(restored.decrypt_memo(0, &ViewKey::generate())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_metered_verification() -> Result<()> {
+        let report_for = |level| -> Result<VerifyReport> {
+            let state = LelantusState::new(LelantusParameters::with_privacy_level(level))?;
+            let input = owned_coin(&state, 0, 1000)?;
+            state.add_coin(&input.0)?;
+            let joinsplit = state.create_joinsplit(vec![input], vec![600, 300], 100)?;
+            state.verify_joinsplit_metered(&joinsplit)
+        };
+
+        let standard = report_for(PrivacyLevel::Standard)?;
+        let maximum = report_for(PrivacyLevel::Maximum)?;
+        assert!(standard.valid && maximum.valid);
+        assert_eq!(standard.range_proofs_checked, 1);
+        assert!(maximum.weight > standard.weight);
+
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+        let mut joinsplit = state.create_joinsplit(vec![input], vec![900], 100)?;
+        joinsplit.fee += 1;
+        let report = state.verify_joinsplit_metered(&joinsplit)?;
+        assert!(!report.valid);
+        assert_eq!(report.range_proofs_checked, 1);
+        assert!(report.weight < standard.weight);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() -> Result<()> {
+        let source = LelantusState::new(LelantusParameters::default())?;
+        for amount in 1..=100 {
+            source.add_coin(&source.commitment_scheme().commit(amount)?)?;
+        }
+        source.mark_spent(&serial(1)?)?;
+        source.mark_spent(&serial(2)?)?;
+        assert!(matches!(
+            source.mark_spent(&serial(1)?),
+            Err(LelantusError::DoubleSpend)
+        ));
+
+        let checkpoint = source.export_checkpoint(100)?;
+        assert_eq!(checkpoint.height, 100);
+        assert_eq!(checkpoint.element_count, 100);
+        assert_eq!(
+            checkpoint.frontier.len(),
+            source.parameters().anonymity_set_size()
+        );
+
+        let node = LelantusState::new(LelantusParameters::default())?;
+        node.import_checkpoint(&checkpoint)?;
+        assert_eq!(node.accumulator_snapshot(), source.accumulator_snapshot());
+        assert!(node.is_spent(&serial(1)?));
+
+        // A transaction built after the checkpoint verifies on the new node
+        let input = owned_coin(&source, 0, 1000)?;
+        source.add_coin(&input.0)?;
+        node.add_coin(&input.0)?;
+        let joinsplit = source.create_joinsplit(vec![input], vec![900], 100)?;
+        assert!(node.verify_joinsplit(&joinsplit)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_rejects_tampered_nullifiers() -> Result<()> {
+        let source = LelantusState::new(LelantusParameters::default())?;
+        source.mark_spent(&serial(1)?)?;
+
+        let mut checkpoint = source.export_checkpoint(1)?;
+        checkpoint.nullifiers.clear();
+
+        let node = LelantusState::new(LelantusParameters::default())?;
+        assert!(node.import_checkpoint(&checkpoint).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed_proof_fields() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+        let joinsplit = state.create_joinsplit(vec![input], vec![900], 100)?;
+
+        let mut overlong = joinsplit.clone();
+        if let Some(range_proof) = overlong.proof.aggregated_range_proof.as_mut() {
+            range_proof.inner_product.left.pop();
+        }
+        assert!(matches!(
+            state.verify_joinsplit(&overlong),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        let mut truncated = joinsplit;
+        truncated.proof.zk_proof.response.truncate(10);
+        assert!(matches!(
+            state.verify_joinsplit(&truncated),
+            Err(LelantusError::InvalidProof)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_rejects_unknown_anonymity_set() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+        let mut joinsplit = state.create_joinsplit(vec![input], vec![900], 100)?;
+
+        joinsplit.proof.anonymity_sets[0] = 1;
+        let error = state
+            .verify_joinsplit(&joinsplit)
+            .err()
+            .ok_or(LelantusError::ProofVerificationFailed)?;
+        assert!(matches!(error, LelantusError::UnknownAnonymitySet(1)));
+        assert!(error.is_consensus_failure());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_rejects_negative_output() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+        let joinsplit = state.create_joinsplit(vec![input], vec![900], 100)?;
+
+        // Values sum modulo the group order, so outputs of 1_100 and q − 200
+        // balance the input and fee
+        let scheme = state.commitment_scheme();
+        let large = scheme.commit(1_100)?;
+        let mut negative = scheme.commit(200)?;
+        let shift = p521::ProjectivePoint::GENERATOR * p521::Scalar::from(400u64);
+        let point = pedersen::decode_hex(&negative.value)? - shift;
+        negative.value = hex::encode(pedersen::encode(&point)).into_bytes();
+
+        let mut forged = joinsplit.clone();
+        forged.outputs = vec![large.clone(), negative.clone()];
+        forged.change_tags.clear();
+        let accumulator_value = state.accumulator.read().value().to_vec();
+        let statement = ZkStatement {
+            inputs: &forged.inputs,
+            nullifiers: &forged.nullifiers,
+            outputs: &forged.outputs,
+            fee: forged.fee,
+            accumulator_value: &accumulator_value,
+            binding_key: &forged.binding_key,
+        };
+        let zk_proof = ZKProof::create(&statement, &state.parameters())?;
+        assert!(zk_proof.verify(&statement, &state.parameters())?);
+        forged.proof.zk_proof = zk_proof;
+
+        // Only the range proofs stand in the way, and none can be made
+        let values = [1_100, 200];
+        assert!(AggregatedRangeProof::create(&forged.outputs, &values, &[], &scheme).is_err());
+        assert!(!state.verify_joinsplit(&forged)?);
+
+        // A proof for another commitment does not carry over
+        let decoy = scheme.commit(200)?;
+        let borrowed = AggregatedRangeProof::create(&[large, decoy], &values, &[], &scheme)?;
+        forged.proof.aggregated_range_proof = Some(borrowed);
+        assert!(!state.verify_joinsplit(&forged)?);
+
+        // Nor does one moved onto the output as a per-output proof
+        let mut moved = RangeProof::create(&scheme.commit(200)?, 200, &state.parameters())?;
+        moved.commitment = negative.value.clone();
+        let large_proof = RangeProof::create(&forged.outputs[0], 1_100, &state.parameters())?;
+        forged.proof.aggregated_range_proof = None;
+        forged.proof.range_proofs = vec![large_proof, moved];
+        assert!(!matches!(state.verify_joinsplit(&forged), Ok(true)));
+
+        // Without any range proof the JoinSplit is malformed
+        forged.proof.range_proofs.clear();
+        assert!(matches!(
+            state.verify_joinsplit(&forged),
+            Err(LelantusError::InvalidProof)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_required_fee() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let inputs = vec![owned_coin(&state, 0, 700)?, owned_coin(&state, 1, 400)?];
+
+        assert_eq!(state.required_fee(&inputs, &[600, 400])?, Amount::new(100)?);
+        assert_eq!(state.required_fee(&inputs, &[1000, 100])?, Amount::ZERO);
+        assert!(matches!(
+            state.required_fee(&inputs, &[1000, 101]),
+            Err(LelantusError::BalanceMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_supply_delta() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 500)?;
+        state.add_coin(&input.0)?;
+        let joinsplit = state.create_joinsplit(vec![input], vec![490], 10)?;
+        let (mint, _) = state.create_mint(1000)?;
+
+        let mints = vec![mint];
+        let joinsplits = vec![joinsplit];
+        assert!(state.verify_supply_delta(&mints, &joinsplits, 990)?);
+        assert!(!state.verify_supply_delta(&mints, &joinsplits, 1000)?);
+        assert!(state.verify_supply_delta(&[], &joinsplits, -10)?);
+        assert!(!state.verify_supply_delta(&[], &joinsplits, 10)?);
+
+        // Inflating the mint value breaks its opening proof
+        let mut inflated = mints.clone();
+        inflated[0].value = 2000;
+        assert!(!state.verify_supply_delta(&inflated, &joinsplits, 1990)?);
+
+        // Lowering the fee breaks the JoinSplit proof
+        let mut underpaid = joinsplits.clone();
+        underpaid[0].fee = 0;
+        assert!(!state.verify_supply_delta(&mints, &underpaid, 1000)?);
+
+        // Replaying a JoinSplit within the batch, or one already spent,
+        // creates no supply
+        let replayed = vec![joinsplits[0].clone(), joinsplits[0].clone()];
+        assert!(!state.verify_supply_delta(&mints, &replayed, 980)?);
+        assert!(!state.verify_supply_delta(&mints, &replayed, 990)?);
+        for nullifier in joinsplits[0].nullifiers() {
+            state.mark_spent(nullifier)?;
+        }
+        assert!(!state.verify_supply_delta(&mints, &joinsplits, 990)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prove_not_spent() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let (commitment, witness) = owned_coin(&state, 0, 500)?;
+        state.add_coin(&commitment)?;
+        state.mark_spent(&serial(7)?)?;
+
+        let proof = state.prove_not_spent(&commitment, &witness)?;
+        assert!(state.verify_not_spent(&proof));
+
+        // The nullifier must be the coin's serial, and the coin must be in
+        // the accumulator
+        let (outsider, outsider_witness) = owned_coin(&state, 1, 500)?;
+        let mut borrowed = proof.clone();
+        borrowed.nullifier = nullifier::opening_nullifier(&outsider)?;
+        assert!(!state.verify_not_spent(&borrowed));
+        let outside = state.prove_not_spent(&outsider, &outsider_witness)?;
+        assert!(!state.verify_not_spent(&outside));
+
+        // The proof goes stale once the set changes, and a spent coin has none
+        state.mark_spent(&SerialNumber::from_opening(&commitment)?)?;
+        assert!(!state.verify_not_spent(&proof));
+        assert!(matches!(
+            state.prove_not_spent(&commitment, &witness),
+            Err(LelantusError::DoubleSpend)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_prove_not_spent_with_key() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let owner = SpendKey::from_seed(&[5; 32])?;
+        let coins = state.mint_to(&owner.full_viewing_key()?, &[1100])?;
+        let (commitment, witness) = &coins[0];
+
+        let proof = state.prove_not_spent_with_key(&owner, commitment, witness)?;
+        assert!(state.verify_not_spent(&proof));
+        assert_eq!(
+            proof.nullifier,
+            SerialNumber::from_coin(&owner, commitment)?.as_bytes()
+        );
+
+        let stranger = SpendKey::from_seed(&[6; 32])?;
+        assert!(matches!(
+            state.prove_not_spent_with_key(&stranger, commitment, witness),
+            Err(LelantusError::NotCoinOwner)
+        ));
+
+        // Spending the coin reveals the nullifier the proof was about
+        let joinsplit = state.create_joinsplit_with_key(&owner, coins.clone(), vec![1000], 100)?;
+        state.mark_spent(&joinsplit.nullifiers[0])?;
+        assert!(!state.verify_not_spent(&proof));
+        assert!(matches!(
+            state.prove_not_spent_with_key(&owner, commitment, witness),
+            Err(LelantusError::DoubleSpend)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_recovery() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let (_, witness) = owned_coin(&state, 0, 500)?;
+        state.cache_witness(b"coin".to_vec(), witness.clone())?;
+        assert!(state.cache_health_check());
+        assert!(!state.reset_cache_if_unhealthy());
+
+        // Simulate an interrupted update leaving a broken entry behind
+        let mut broken = witness.clone();
+        broken.membership_proof.clear();
+        assert!(state
+            .cache_witness(b"broken".to_vec(), broken.clone())
+            .is_err());
+        state.witness_cache.write().put(b"broken".to_vec(), broken);
+        assert!(!state.cache_health_check());
+
+        assert!(state.reset_cache_if_unhealthy());
+        assert!(state.cache_health_check());
+        assert!(state.get_cached_witness(b"broken").is_none());
+
+        state.cache_witness(b"coin".to_vec(), witness)?;
+        assert!(state.get_cached_witness(b"coin").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepared_verifier_agrees() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 500)?;
+        state.add_coin(&input.0)?;
+        let joinsplit = state.create_joinsplit(vec![input], vec![490], 10)?;
+
+        let verifier = PreparedVerifier::new(&state.parameters())?;
+        let head = state.accumulator.read().clone();
+        assert!(state.verify_joinsplit(&joinsplit)?);
+        assert!(verifier.verify(&joinsplit, &head)?);
+
+        let mut tampered = joinsplit.clone();
+        tampered.fee = 11;
+        assert!(!state.verify_joinsplit(&tampered)?);
+        assert!(!verifier.verify(&tampered, &head)?);
+
+        tampered.proof.zk_proof.challenge.pop();
+        assert!(state.verify_joinsplit(&tampered).is_err());
+        assert!(verifier.verify(&tampered, &head).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_padded_joinsplit_verifies() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 500)?;
+        state.add_coin(&input.0)?;
+        let mut joinsplit = state.create_joinsplit(vec![input], vec![490], 10)?;
+
+        let target = LelantusSerializable::to_bytes(&joinsplit)?.len() + 1000;
+        joinsplit.pad_to(target)?;
+        assert_eq!(LelantusSerializable::to_bytes(&joinsplit)?.len(), target);
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        let restored = <JoinSplit as LelantusSerializable>::from_bytes(
+            &LelantusSerializable::to_bytes(&joinsplit)?,
+        )?;
+        assert_eq!(restored.padding, joinsplit.padding);
+        assert!(joinsplit.pad_to(target - 2000).is_err());
+
+        // Non-zero padding is a malleability vector and is rejected
+        joinsplit.padding[0] = 1;
+        assert!(state.verify_joinsplit(&joinsplit).is_err());
+        assert!(LelantusSerializable::to_bytes(&joinsplit).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_block_nullifiers() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coins = [
+            owned_coin(&state, 0, 300)?,
+            owned_coin(&state, 1, 200)?,
+            owned_coin(&state, 2, 500)?,
+        ];
+        for (commitment, _) in &coins {
+            state.add_coin(commitment)?;
+        }
+
+        let first = state.create_joinsplit(coins[..2].to_vec(), vec![490], 10)?;
+        let second = state.create_joinsplit(coins[2..].to_vec(), vec![490], 10)?;
+        assert_eq!(first.nullifiers().len(), first.input_count());
+        assert_eq!(second.nullifiers().len(), 1);
+
+        let consumed = state.verify_block(&[first.clone(), second.clone()])?;
+        let expected: BTreeSet<SerialNumber> = first
+            .nullifiers()
+            .iter()
+            .chain(second.nullifiers())
+            .cloned()
+            .collect();
+        assert_eq!(consumed, expected);
+        assert_eq!(consumed.len(), 3);
+
+        // The same spend twice in a block, or after it is spent, is rejected
+        assert!(matches!(
+            state.verify_block(&[second.clone(), second.clone()]),
+            Err(LelantusError::DoubleSpend)
+        ));
+        state.mark_spent(&second.nullifiers()[0])?;
+        assert!(matches!(
+            state.verify_block(&[second]),
+            Err(LelantusError::DoubleSpend)
+        ));
+
+        // Nullifiers are bound to the inputs
+        let mut swapped = first;
+        swapped.nullifiers.swap(0, 1);
+        assert!(state.verify_block(&[swapped]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullifier_is_bound_to_coin() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let coin = owned_coin(&state, 0, 500)?;
+        let decoy = owned_coin(&state, 1, 500)?;
+        state.add_coin(&coin.0)?;
+        state.add_coin(&decoy.0)?;
+
+        // Every spend of a coin reveals the same nullifier
+        let spent = state.create_joinsplit(vec![coin.clone()], vec![490], 10)?;
+        let forged = state.create_joinsplit(vec![coin.clone()], vec![490], 10)?;
+        assert_eq!(spent.nullifiers, forged.nullifiers);
+        assert_eq!(spent.nullifiers[0], SerialNumber::from_opening(&coin.0)?);
+        assert!(matches!(
+            state.verify_block(&[spent.clone(), forged.clone()]),
+            Err(LelantusError::DoubleSpend)
+        ));
+        state.mark_spent(&spent.nullifiers[0])?;
+        assert!(matches!(
+            state.verify_block(std::slice::from_ref(&forged)),
+            Err(LelantusError::DoubleSpend)
+        ));
+
+        // A fresh or borrowed nullifier does not match the coin's serial
+        let mut fresh = spent.clone();
+        fresh.nullifiers[0] = serial(9)?;
+        assert!(!state.verify_joinsplit(&fresh)?);
+        let mut borrowed = spent.clone();
+        borrowed.nullifiers[0] = SerialNumber::from_opening(&decoy.0)?;
+        assert!(!state.verify_joinsplit(&borrowed)?);
+
+        // The published input is a fresh commitment, not the coin itself
+        assert_ne!(spent.inputs[0].value, coin.0.value);
+        assert_ne!(spent.inputs, forged.inputs);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_legacy_witness() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let (commitment, mut witness) = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&commitment)?;
+        state.add_coin(&state.commitment_scheme().commit(5)?)?;
+        assert!(state.witness_needs_upgrade(&witness));
+        assert!(witness.typed_membership_proof().is_err());
+
+        state.upgrade_witness(&mut witness)?;
+        assert!(!state.witness_needs_upgrade(&witness));
+        assert_eq!(witness.get_amount()?, 1000);
+
+        let accumulator = state.accumulator.read();
+        assert!(witness.verify_against(&accumulator)?);
+        assert!(accumulator.verify_membership_proof(&witness.typed_membership_proof()?)?);
+        drop(accumulator);
+
+        // A witness for a coin the accumulator does not hold cannot be upgraded
+        let (_, mut stranger) = owned_coin(&state, 1, 10)?;
+        assert!(matches!(
+            state.upgrade_witness(&mut stranger),
+            Err(LelantusError::InvalidWitness(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_proof_size() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let inputs = vec![owned_coin(&state, 0, 600)?, owned_coin(&state, 1, 400)?];
+        for (commitment, _) in &inputs {
+            state.add_coin(commitment)?;
+        }
+
+        let metrics = state.dry_run_proof(&inputs, &[700, 290], 10)?;
+        let joinsplit = state.create_joinsplit(inputs, vec![700, 290], 10)?;
+        let actual = LelantusSerializable::to_bytes(&joinsplit.proof)?.len();
+
+        assert!(metrics.serialized_size.abs_diff(actual) <= actual / 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_at_anchor() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+
+        let anchor = state.accumulator_snapshot().value().to_vec();
+        let joinsplit = state.create_joinsplit(vec![input], vec![900], 100)?;
+
+        // The accumulator advances past the build-time head
+        for amount in 1..=5 {
+            state.add_coin(&state.commitment_scheme().commit(amount)?)?;
+        }
+        assert!(!state.verify_joinsplit(&joinsplit)?);
+        assert!(state.verify_joinsplit_at_anchor(&joinsplit, &anchor)?);
+
+        assert!(state
+            .verify_joinsplit_at_anchor(&joinsplit, b"unknown")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_estimate() -> Result<()> {
+        let small = LelantusState::estimate_for(1_000, 100, 10);
+        let large = LelantusState::estimate_for(100_000, 100, 10);
+        assert!(large.accumulator_bytes > small.accumulator_bytes);
+        assert_eq!(large.nullifier_bytes, small.nullifier_bytes);
+
+        let state = LelantusState::new(LelantusParameters::default())?;
+        assert_eq!(state.memory_estimate().total(), 0);
+
+        for index in 0..50 {
+            let commitment = state.commitment_scheme().commit(1000)?;
+            state.add_coin(&commitment)?;
+            let nullifier = SerialNumber::from_opening(&commitment)?;
+            state.mark_spent(&nullifier)?;
+            if index < 5 {
+                let witness = Witness::new(
+                    commitment.clone(),
+                    index,
+                    state.accumulator_snapshot().value().to_vec(),
+                    state.accumulator_snapshot().value().to_vec(),
+                    Witness::encrypt_amount(&commitment, index, 1000)?,
+                );
+                state.cache_witness(nullifier.into(), witness)?;
+            }
+        }
+
+        let actual = state.memory_estimate();
+        let expected = LelantusState::estimate_for(50, 50, 5);
+        assert_eq!(actual.accumulator_bytes, expected.accumulator_bytes);
+        assert_eq!(actual.nullifier_bytes, expected.nullifier_bytes);
+        assert!(actual.total().abs_diff(expected.total()) <= expected.total() / 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mint_batch() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let values: Vec<u64> = (1..=50).map(|i| i * 100).collect();
+        let coins = state.mint_batch(&values)?;
+        assert_eq!(coins.len(), 50);
+
+        let snapshot = state.accumulator_snapshot();
+        assert_eq!(snapshot.element_count(), 50);
+        for (index, (commitment, witness)) in coins.iter().enumerate() {
+            assert_eq!(witness.index, index);
+            assert!(witness.verify_against(&state.accumulator.read())?);
+            assert!(state
+                .accumulator
+                .read()
+                .verify_membership_proof(&witness.typed_membership_proof()?)?);
+            assert_eq!(&witness.commitment, commitment);
+        }
+
+        let spent = coins[10].clone();
+        let joinsplit = state.create_joinsplit(vec![spent], vec![1000], 100)?;
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        // An invalid value aborts the whole batch
+        assert!(state.mint_batch(&[100, 0]).is_err());
+        assert_eq!(state.accumulator_snapshot().element_count(), 50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_denominations_are_enforced() -> Result<()> {
+        let params = LelantusParameters {
+            denominations: vec![100, 500, 1_000],
+            ..Default::default()
+        };
+        let state = LelantusState::new(params)?;
+        let coins = state.mint_batch(&[1_000, 500])?;
+        assert!(state.mint_batch(&[1_000, 300]).is_err());
+        assert_eq!(state.accumulator_snapshot().element_count(), 2);
+
+        let outputs = state.parameters().split_into_denominations(1_400)?;
+        assert_eq!(outputs, vec![1_000, 100, 100, 100, 100]);
+        let joinsplit = state.create_joinsplit(coins.clone(), outputs, 100)?;
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        assert!(matches!(
+            state.create_joinsplit(coins, vec![1_000, 400], 100),
+            Err(LelantusError::InvalidParameter(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_asset_joinsplit_balances_per_asset() -> Result<()> {
+        use rand::SeedableRng;
+
+        let state = LelantusState::new(LelantusParameters {
+            hash_function: "pedersen".to_string(),
+            ..LelantusParameters::default()
+        })?;
+        let token = [7; 32];
+        let native = state.mint_batch(&[1_000])?;
+        let tokens = state.mint_assets(&token, &[500])?;
+
+        // A seeded RNG reproduces the minted commitments
+        let seeded = |seed| {
+            LelantusState::new((*state.parameters()).clone())?.mint_assets_with_rng(
+                &token,
+                &[500],
+                &mut rand::rngs::StdRng::seed_from_u64(seed),
+            )
+        };
+        assert_eq!(seeded(42)?[0].0, seeded(42)?[0].0);
+        assert_ne!(seeded(42)?[0].0, seeded(43)?[0].0);
+
+        let inputs = || {
+            vec![
+                (NATIVE_ASSET, native[0].0.clone(), native[0].1.clone()),
+                (token, tokens[0].0.clone(), tokens[0].1.clone()),
+            ]
+        };
+
+        let joinsplit = state.create_asset_joinsplit(
+            inputs(),
+            vec![(NATIVE_ASSET, 900), (token, 300), (token, 200)],
+            100,
+        )?;
+        assert!(state.verify_joinsplit(&joinsplit)?);
+        let scheme = state.commitment_scheme();
+        assert!(scheme.verify_asset(&joinsplit.outputs[1], &token, 300)?);
+
+        // Outputs carry blinded tags, not asset ids: the ring names each
+        // asset once, but not which output holds which
+        let asset_proof = joinsplit.proof.asset_proof.clone().expect("asset proof");
+        assert_eq!(asset_proof.assets, vec![NATIVE_ASSET, token]);
+        assert_ne!(asset_proof.tags[1], asset_proof.tags[2]);
+        let bytes = joinsplit.consensus_encode()?;
+        let mentions = bytes
+            .windows(token.len())
+            .filter(|window| *window == token)
+            .count();
+        assert_eq!(mentions, 1);
+        let decoded = JoinSplit::consensus_decode(&bytes)?;
+        assert_eq!(decoded.proof.asset_proof, Some(asset_proof.clone()));
+
+        // The range proof is only checked against proven tags
+        let mut untagged = joinsplit.clone();
+        untagged.proof.asset_proof = None;
+        assert!(!state.verify_joinsplit(&untagged)?);
+        let mut swapped = joinsplit.clone();
+        if let Some(proof) = swapped.proof.asset_proof.as_mut() {
+            proof.tags.swap(1, 2);
+        }
+        assert!(!state.verify_joinsplit(&swapped)?);
+
+        // Native JoinSplits need no asset proof
+        let native_only = state.create_asset_joinsplit(
+            vec![(NATIVE_ASSET, native[0].0.clone(), native[0].1.clone())],
+            vec![(NATIVE_ASSET, 900)],
+            100,
+        )?;
+        assert!(native_only.proof.asset_proof.is_none());
+
+        // Value cannot move between assets, even when the total balances
+        assert!(matches!(
+            state.create_asset_joinsplit(inputs(), vec![(NATIVE_ASSET, 1_000), (token, 400)], 100),
+            Err(LelantusError::BalanceMismatch)
+        ));
+
+        // Inputs must open to the stated asset
+        let mut mislabeled = inputs();
+        mislabeled[1].0 = [8; 32];
+        assert!(state
+            .create_asset_joinsplit(mislabeled, vec![(NATIVE_ASSET, 900), ([8; 32], 500)], 100)
+            .is_err());
+
+        // Plain JoinSplits only spend native coins
+        assert!(state.create_joinsplit(tokens, vec![400], 100).is_err());
+
+        // The verifier rejects value moved between assets, even with a
+        // balance proof made from the creator's blindings
+        let scheme = state.commitment_scheme();
+        let rng = &mut rand::thread_rng();
+        let mut with_outputs = |values: Vec<(AssetId, u64)>| -> Result<JoinSplit> {
+            let mut forged = joinsplit.clone();
+            forged.outputs = values
+                .iter()
+                .map(|(asset, value)| scheme.commit_asset_with_rng(asset, *value, rng))
+                .collect::<Result<_>>()?;
+            let assets: Vec<AssetId> = values.iter().map(|(asset, _)| *asset).collect();
+            let amounts: Vec<u64> = values.iter().map(|(_, value)| *value).collect();
+            let asset_proof = AssetSurjectionProof::create(
+                &scheme,
+                &forged.outputs,
+                &assets,
+                &[NATIVE_ASSET, token],
+            )?;
+            forged.proof.aggregated_range_proof = Some(AggregatedRangeProof::create(
+                &forged.outputs,
+                &amounts,
+                &asset_proof.tags,
+                &scheme,
+            )?);
+            forged.proof.asset_proof = Some(asset_proof);
+            forged.proof.zk_proof = ZKProof::prove_openings(
+                &ZkStatement {
+                    inputs: &forged.inputs,
+                    nullifiers: &forged.nullifiers,
+                    outputs: &forged.outputs,
+                    fee: forged.fee,
+                    accumulator_value: state.accumulator.read().value(),
+                    binding_key: &forged.binding_key,
+                },
+                &state.parameters(),
+            )?;
+            Ok(forged)
+        };
+        let balanced = with_outputs(vec![(NATIVE_ASSET, 900), (token, 300), (token, 200)])?;
+        assert!(state.verify_joinsplit(&balanced)?);
+        let cross_asset = with_outputs(vec![(NATIVE_ASSET, 1_000), (token, 300), (token, 100)])?;
+        assert!(!state.verify_joinsplit(&cross_asset)?);
+
+        // Hash commitments cannot be balanced per asset by verifiers
+        let hashed = LelantusState::new(LelantusParameters {
+            hash_function: "sha512".to_string(),
+            ..Default::default()
+        })?;
+        assert!(matches!(
+            hashed.mint_assets(&token, &[500]),
+            Err(LelantusError::InvalidParameter(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_spend_requires_owning_key() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let owner = SpendKey::from_seed(&[5; 32])?;
+        let coins = state.mint_to(&owner.full_viewing_key()?, &[1100, 2000])?;
+        assert_eq!(
+            coins[1].1.get_amount_with_view_key(&owner.view_key()?)?,
+            2000
+        );
+
+        let stranger = SpendKey::from_seed(&[6; 32])?;
+        assert!(matches!(
+            state.create_joinsplit_with_key(&stranger, vec![coins[0].clone()], vec![1000], 100),
+            Err(LelantusError::NotCoinOwner)
+        ));
+
+        let joinsplit =
+            state.create_joinsplit_with_key(&owner, vec![coins[0].clone()], vec![1000], 100)?;
+        assert!(state.verify_joinsplit(&joinsplit)?);
+
+        // The revealed serial is keyed: the coin's randomness alone does not
+        // give it
+        let serial = SerialNumber::from_coin(&owner, &coins[0].0)?;
+        assert_ne!(serial, SerialNumber::from_opening(&coins[0].0)?);
+        assert_eq!(state.derive_nullifier(&owner, &coins[0].0)?, serial);
+        assert_eq!(joinsplit.nullifiers, [serial]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_witnesses() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let (_, mut witnesses): (Vec<_>, Vec<_>) =
+            state.mint_batch(&[100, 200])?.into_iter().unzip();
+        state.mint_batch(&[300])?;
+        let (_, later): (Vec<_>, Vec<_>) = state.mint_batch(&[400])?.into_iter().unzip();
+        witnesses.extend(later);
+
+        state.update_witnesses(&mut witnesses)?;
+        let accumulator = state.accumulator.read();
+        for witness in &witnesses {
+            assert!(witness.verify_against(&accumulator)?);
+            assert!(accumulator.verify_membership_proof(&witness.typed_membership_proof()?)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_and_flush() -> Result<()> {
+        let dir =
+            tempfile::tempdir().map_err(|e| LelantusError::SerializationError(e.to_string()))?;
+        let path = dir.path().join("state.bin");
+
+        let state = LelantusState::open(LelantusParameters::default(), &path)?;
+        state.mint_batch(&[100, 200])?;
+        state.mark_spent(&serial(7)?)?;
+        state.flush()?;
+        let value = state.accumulator_snapshot();
+        drop(state);
+
+        let reopened = LelantusState::open(LelantusParameters::default(), &path)?;
+        assert_eq!(reopened.accumulator_snapshot(), value);
+        assert!(reopened.is_spent(&serial(7)?));
+
+        let other = LelantusParameters {
+            privacy_level: PrivacyLevel::Maximum,
+            ..Default::default()
+        };
+        assert!(matches!(
+            LelantusState::open(other, &path),
+            Err(LelantusError::ParameterMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_stage_verification() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let first = owned_coin(&state, 0, 1000)?;
+        let second = owned_coin(&state, 1, 500)?;
+        state.add_coin(&first.0)?;
+        state.add_coin(&second.0)?;
+
+        let joinsplit = state.create_joinsplit(vec![first, second], vec![1400], 100)?;
+        let verifier = PreparedVerifier::new(&state.parameters())?;
+        joinsplit.verify_cheap_checks(&verifier)?;
+        assert!(joinsplit.verify_expensive(&state)?);
+
+        // Spending the same coin twice is caught before any proof is checked
+        let mut duplicated = joinsplit.clone();
+        duplicated.inputs[1] = duplicated.inputs[0].clone();
+        duplicated.nullifiers[1] = duplicated.nullifiers[0].clone();
+        assert!(matches!(
+            duplicated.verify_cheap_checks(&verifier),
+            Err(LelantusError::DoubleSpend)
+        ));
+        assert!(state.verify_joinsplit_metered(&duplicated).is_err());
+
+        let mut overpaid = joinsplit;
+        overpaid.fee = u64::MAX;
+        assert!(overpaid.verify_cheap_checks(&verifier).is_err());
+
+        #[cfg(feature = "metrics")]
+        assert_eq!(state.timing_snapshot().zk_proof_verification.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullifier_root_is_order_independent() -> Result<()> {
+        let nullifiers = (0..8u8).map(serial).collect::<Result<Vec<_>>>()?;
+        let forward = LelantusState::new(LelantusParameters::default())?;
+        let backward = LelantusState::new(LelantusParameters::default())?;
+        for nullifier in &nullifiers {
+            forward.mark_spent(nullifier)?;
+        }
+        for nullifier in nullifiers.iter().rev() {
+            backward.mark_spent(nullifier)?;
+        }
+        let root = forward.nullifier_root();
+        assert_eq!(root, backward.nullifier_root());
+
+        forward.mark_spent(&serial(42)?)?;
+        backward.mark_spent(&serial(42)?)?;
+        assert_ne!(forward.nullifier_root(), root);
+        assert_eq!(forward.nullifier_root(), backward.nullifier_root());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_nullifier_root_matches_state() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        assert_eq!(
+            state.export_checkpoint(0)?.nullifier_root,
+            state.nullifier_root()
+        );
+
+        for index in [5u8, 1, 3] {
+            state.mark_spent(&serial(index)?)?;
+        }
+        let checkpoint = state.export_checkpoint(1)?;
+        assert_eq!(checkpoint.nullifier_root, state.nullifier_root());
+        assert!(checkpoint.verify_nullifier_root());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serial_numbers_prevent_double_spend() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let input = owned_coin(&state, 0, 1000)?;
+        state.add_coin(&input.0)?;
+        let joinsplit = state.create_joinsplit(vec![input.clone()], vec![900], 100)?;
+
+        let serials = joinsplit.nullifiers();
+        assert_eq!(serials, [SerialNumber::from_opening(&input.0)?]);
+        assert!(!state.is_spent(&serials[0]));
+
+        state.mark_spent(&serials[0])?;
+        assert!(state.is_spent(&serials[0]));
+        assert!(matches!(
+            state.verify_block(std::slice::from_ref(&joinsplit)),
+            Err(LelantusError::DoubleSpend)
+        ));
+        assert!(matches!(
+            state.mark_spent(&serials[0]),
+            Err(LelantusError::DoubleSpend)
+        ));
+        Ok(())
+    }
 }
@@ -0,0 +1,342 @@
+//! Counted binary Merkle trees over SHA-256
+//!
+//! Leaves are hashed with a length prefix, an unpaired last node is promoted
+//! unchanged, and the root also commits to the leaf count, so a proof can
+//! show that a leaf is the first or last one. Shared by the spent nullifier
+//! set, block commitments and the coin accumulator.
+//!
+//! The tree can also be grown one leaf at a time from its peaks: the roots
+//! of the perfect subtrees given by the binary digits of the leaf count,
+//! largest first. Bagging the peaks from the right yields the same root as
+//! hashing all leaves.
+
+use crate::errors::{ErrorContext, LelantusError, Result};
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Hash of a Merkle leaf
+pub(crate) fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update((data.len() as u64).to_le_bytes());
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Hash of an inner Merkle node
+pub(crate) fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Bind the tree top to the number of leaves
+fn root_with_count(top: &[u8; 32], leaf_count: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x02]);
+    hasher.update(leaf_count.to_le_bytes());
+    hasher.update(top);
+    hasher.finalize().into()
+}
+
+/// Reduce one tree level, promoting an unpaired last node unchanged
+fn level_up(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+/// Merkle root over `leaves` in the given order
+pub fn merkle_root<'a>(leaves: impl ExactSizeIterator<Item = &'a [u8]>) -> [u8; 32] {
+    let leaf_count = leaves.len() as u64;
+    let mut level: Vec<[u8; 32]> = leaves.map(leaf_hash).collect();
+    while level.len() > 1 {
+        level = level_up(&level);
+    }
+    root_with_count(&level.first().copied().unwrap_or([0; 32]), leaf_count)
+}
+
+/// Every level of the tree over `leaves`, from the leaves up to the top node
+pub(crate) fn tree_levels(leaves: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves.to_vec()];
+    while levels[levels.len() - 1].len() > 1 {
+        let next = level_up(&levels[levels.len() - 1]);
+        levels.push(next);
+    }
+    levels
+}
+
+/// Sibling hashes of leaf `index` read off precomputed [`tree_levels`]
+pub(crate) fn path_from_levels(levels: &[Vec<[u8; 32]>], index: usize) -> Vec<[u8; 32]> {
+    levels
+        .iter()
+        .enumerate()
+        .filter_map(|(height, level)| level.get((index >> height) ^ 1).copied())
+        .collect()
+}
+
+/// Sibling hashes from leaf `index` upwards (levels without a sibling omitted)
+pub(crate) fn authentication_path(leaves: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+    path_from_levels(&tree_levels(leaves), index)
+}
+
+/// Whether each sibling on the path of leaf `index` sits on the left
+pub(crate) fn sibling_sides(index: u64, leaf_count: u64) -> Vec<bool> {
+    let mut sides = Vec::new();
+    let mut position = index;
+    let mut width = leaf_count;
+    while width > 1 {
+        if position ^ 1 < width {
+            sides.push(!position.is_multiple_of(2));
+        }
+        position /= 2;
+        width = width.div_ceil(2);
+    }
+    sides
+}
+
+/// Append a leaf hash to the peaks of a tree of `leaf_count` leaves
+pub(crate) fn append_peak(peaks: &mut Vec<[u8; 32]>, leaf_count: u64, leaf: [u8; 32]) {
+    peaks.push(leaf);
+    let mut carry = leaf_count;
+    while carry & 1 == 1 {
+        if let (Some(right), Some(left)) = (peaks.pop(), peaks.pop()) {
+            peaks.push(node_hash(&left, &right));
+        }
+        carry >>= 1;
+    }
+}
+
+/// Root of a tree of `leaf_count` leaves from its peaks
+///
+/// Returns `None` if the number of peaks does not fit the leaf count.
+pub(crate) fn root_from_peaks(peaks: &[[u8; 32]], leaf_count: u64) -> Option<[u8; 32]> {
+    if peaks.len() != leaf_count.count_ones() as usize {
+        return None;
+    }
+    let top = peaks
+        .iter()
+        .rev()
+        .copied()
+        .reduce(|right, left| node_hash(&left, &right))
+        .unwrap_or([0; 32]);
+    Some(root_with_count(&top, leaf_count))
+}
+
+/// Advance the path of leaf `index` as `new_leaves` are appended
+///
+/// `siblings` and `peaks` describe a tree of `leaf_count` leaves. Returns the
+/// leaf's new siblings, or `None` if the path does not lead from `leaf` to
+/// its peak.
+pub(crate) fn advance_path(
+    leaf: [u8; 32],
+    index: u64,
+    leaf_count: u64,
+    siblings: &[[u8; 32]],
+    peaks: &[[u8; 32]],
+    new_leaves: &[[u8; 32]],
+) -> Option<Vec<[u8; 32]>> {
+    if index >= leaf_count || peaks.len() != leaf_count.count_ones() as usize {
+        return None;
+    }
+
+    // Peaks as (height, hash), largest first, and the one holding the leaf
+    let mut nodes: Vec<(u32, [u8; 32])> = (0..u64::BITS)
+        .rev()
+        .filter(|height| leaf_count >> height & 1 == 1)
+        .zip(peaks.iter().copied())
+        .collect();
+    let mut start = 0;
+    let mut own = 0;
+    for (position, (height, _)) in nodes.iter().enumerate() {
+        if index < start + (1 << height) {
+            own = position;
+            break;
+        }
+        start += 1 << height;
+    }
+
+    // Siblings inside the leaf's own peak
+    let own_height = nodes[own].0 as usize;
+    let mut inner = siblings.get(..own_height)?.to_vec();
+    let mut current = leaf;
+    for (height, sibling) in inner.iter().enumerate() {
+        current = if (index >> height).is_multiple_of(2) {
+            node_hash(&current, sibling)
+        } else {
+            node_hash(sibling, &current)
+        };
+    }
+    if current != nodes[own].1 {
+        return None;
+    }
+
+    // Replay the appends, recording siblings whenever the leaf's peak merges
+    for &new_leaf in new_leaves {
+        nodes.push((0, new_leaf));
+        while let [.., (left_height, _), (right_height, _)] = nodes[..] {
+            if left_height != right_height {
+                break;
+            }
+            let (_, right) = nodes.pop()?;
+            let (_, left) = nodes.pop()?;
+            let merged = nodes.len();
+            if own == merged + 1 {
+                inner.push(left);
+                own = merged;
+            } else if own == merged {
+                inner.push(right);
+            }
+            nodes.push((left_height + 1, node_hash(&left, &right)));
+        }
+    }
+
+    // Then the bagged peaks to the right and each peak to the left
+    let new_peaks: Vec<[u8; 32]> = nodes.iter().map(|(_, hash)| *hash).collect();
+    if let Some(bag) = new_peaks[own + 1..]
+        .iter()
+        .rev()
+        .copied()
+        .reduce(|right, left| node_hash(&left, &right))
+    {
+        inner.push(bag);
+    }
+    inner.extend(new_peaks[..own].iter().rev());
+    Some(inner)
+}
+
+/// Recompute the root from a leaf hash and its authentication path
+///
+/// Returns `None` if the path does not fit a tree of `leaf_count` leaves.
+pub(crate) fn root_from_path(
+    leaf: [u8; 32],
+    index: u64,
+    leaf_count: u64,
+    siblings: &[[u8; 32]],
+) -> Option<[u8; 32]> {
+    if index >= leaf_count {
+        return None;
+    }
+
+    let mut current = leaf;
+    let mut position = index;
+    let mut width = leaf_count;
+    let mut siblings = siblings.iter();
+    while width > 1 {
+        if position ^ 1 < width {
+            let sibling = siblings.next()?;
+            current = if position.is_multiple_of(2) {
+                node_hash(&current, sibling)
+            } else {
+                node_hash(sibling, &current)
+            };
+        }
+        position /= 2;
+        width = width.div_ceil(2);
+    }
+
+    siblings
+        .next()
+        .is_none()
+        .then(|| root_with_count(&current, leaf_count))
+}
+
+/// Proof that a leaf sits at a position in a Merkle tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Position of the leaf
+    pub index: u64,
+
+    /// Number of leaves in the tree
+    pub leaf_count: u64,
+
+    /// Sibling hashes from the leaf upwards (levels without a sibling omitted)
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleProof {
+    /// Prove the leaf at `index` of `leaves`
+    pub fn create<'a>(
+        leaves: impl ExactSizeIterator<Item = &'a [u8]>,
+        index: usize,
+    ) -> Result<Self> {
+        let leaf_count = leaves.len();
+        if index >= leaf_count {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("index")
+                    .expected(format!("< {}", leaf_count))
+                    .actual(index),
+            ));
+        }
+
+        let hashes: Vec<[u8; 32]> = leaves.map(leaf_hash).collect();
+        Ok(Self {
+            index: index as u64,
+            leaf_count: leaf_count as u64,
+            siblings: authentication_path(&hashes, index),
+        })
+    }
+
+    /// Check that `leaf` is at this proof's position under `root`
+    pub fn verify(&self, leaf: &[u8], root: &[u8; 32]) -> bool {
+        root_from_path(leaf_hash(leaf), self.index, self.leaf_count, &self.siblings) == Some(*root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<[u8; 32]> {
+        (0..count).map(|i| leaf_hash(&[i])).collect()
+    }
+
+    #[test]
+    fn test_peaks_match_full_tree() {
+        let mut peaks = Vec::new();
+        for count in 0..20u8 {
+            let data: Vec<[u8; 1]> = (0..count).map(|i| [i]).collect();
+            let expected = merkle_root(data.iter().map(|leaf| leaf.as_slice()));
+            assert_eq!(root_from_peaks(&peaks, count as u64), Some(expected));
+            append_peak(&mut peaks, count as u64, leaf_hash(&[count]));
+        }
+    }
+
+    #[test]
+    fn test_advance_path() {
+        for start in 1..12u8 {
+            for index in 0..start {
+                let before = leaves(start);
+                let mut peaks = Vec::new();
+                for (count, leaf) in before.iter().enumerate() {
+                    append_peak(&mut peaks, count as u64, *leaf);
+                }
+                let siblings = authentication_path(&before, index as usize);
+
+                let after = leaves(start + 5);
+                let advanced = advance_path(
+                    before[index as usize],
+                    index as u64,
+                    start as u64,
+                    &siblings,
+                    &peaks,
+                    &after[start as usize..],
+                );
+                let expected = authentication_path(&after, index as usize);
+                assert_eq!(
+                    sibling_sides(index as u64, after.len() as u64).len(),
+                    expected.len()
+                );
+                assert_eq!(advanced, Some(expected));
+            }
+        }
+    }
+}
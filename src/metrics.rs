@@ -0,0 +1,214 @@
+//! In-process proof timing metrics
+//!
+//! Enabled with the `metrics` feature. Durations are aggregated into
+//! power-of-two histograms so snapshots from different states or processes
+//! can be merged by adding bucket counts.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Number of histogram buckets (bucket `b` holds durations below `2^b` ns)
+const BUCKET_COUNT: usize = 65;
+
+/// Proof operation being timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProofOperation {
+    /// Range proof creation
+    RangeProofCreation,
+    /// Range proof verification
+    RangeProofVerification,
+    /// Zero-knowledge proof creation
+    ZkProofCreation,
+    /// Zero-knowledge proof verification
+    ZkProofVerification,
+}
+
+/// Histogram of operation durations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimingHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    total_nanos: u128,
+}
+
+impl Default for TimingHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; BUCKET_COUNT],
+            count: 0,
+            total_nanos: 0,
+        }
+    }
+}
+
+impl TimingHistogram {
+    /// Record a single duration
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total_nanos += u128::from(nanos);
+    }
+
+    /// Add the samples of another histogram to this one
+    pub fn merge(&mut self, other: &TimingHistogram) {
+        for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.total_nanos += other.total_nanos;
+    }
+
+    /// Number of recorded samples
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Mean recorded duration
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        let mean = self.total_nanos / u128::from(self.count);
+        Duration::from_nanos(u64::try_from(mean).unwrap_or(u64::MAX))
+    }
+
+    /// Upper bound of the bucket containing the given percentile (0-100)
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let rank = rank.max(1);
+
+        let mut seen = 0u64;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(bucket_upper_bound(bucket));
+            }
+        }
+
+        Duration::from_nanos(u64::MAX)
+    }
+}
+
+/// Largest duration in nanoseconds that falls into `bucket`
+fn bucket_upper_bound(bucket: usize) -> u64 {
+    match bucket {
+        0 => 0,
+        64.. => u64::MAX,
+        b => (1u64 << b) - 1,
+    }
+}
+
+/// Point-in-time copy of all proof timings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimingSnapshot {
+    /// Range proof creation durations
+    pub range_proof_creation: TimingHistogram,
+
+    /// Range proof verification durations
+    pub range_proof_verification: TimingHistogram,
+
+    /// Zero-knowledge proof creation durations
+    pub zk_proof_creation: TimingHistogram,
+
+    /// Zero-knowledge proof verification durations
+    pub zk_proof_verification: TimingHistogram,
+}
+
+impl TimingSnapshot {
+    /// Get the histogram for an operation
+    pub fn histogram(&self, operation: ProofOperation) -> &TimingHistogram {
+        match operation {
+            ProofOperation::RangeProofCreation => &self.range_proof_creation,
+            ProofOperation::RangeProofVerification => &self.range_proof_verification,
+            ProofOperation::ZkProofCreation => &self.zk_proof_creation,
+            ProofOperation::ZkProofVerification => &self.zk_proof_verification,
+        }
+    }
+
+    fn histogram_mut(&mut self, operation: ProofOperation) -> &mut TimingHistogram {
+        match operation {
+            ProofOperation::RangeProofCreation => &mut self.range_proof_creation,
+            ProofOperation::RangeProofVerification => &mut self.range_proof_verification,
+            ProofOperation::ZkProofCreation => &mut self.zk_proof_creation,
+            ProofOperation::ZkProofVerification => &mut self.zk_proof_verification,
+        }
+    }
+
+    /// Add the samples of another snapshot to this one
+    pub fn merge(&mut self, other: &TimingSnapshot) {
+        self.range_proof_creation.merge(&other.range_proof_creation);
+        self.range_proof_verification
+            .merge(&other.range_proof_verification);
+        self.zk_proof_creation.merge(&other.zk_proof_creation);
+        self.zk_proof_verification
+            .merge(&other.zk_proof_verification);
+    }
+}
+
+/// Thread-safe recorder of proof timings
+#[derive(Debug, Default)]
+pub struct ProofTimings {
+    snapshot: Mutex<TimingSnapshot>,
+}
+
+impl ProofTimings {
+    /// Record a duration for an operation
+    pub fn record(&self, operation: ProofOperation, duration: Duration) {
+        self.snapshot
+            .lock()
+            .histogram_mut(operation)
+            .record(duration);
+    }
+
+    /// Run `f` and record how long it took
+    pub fn time<T>(&self, operation: ProofOperation, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(operation, start.elapsed());
+        result
+    }
+
+    /// Copy the current timings
+    pub fn snapshot(&self) -> TimingSnapshot {
+        self.snapshot.lock().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let mut histogram = TimingHistogram::default();
+        for nanos in 1..=100 {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.mean(), Duration::from_nanos(50));
+        assert_eq!(histogram.percentile(50.0), Duration::from_nanos(63));
+        assert_eq!(histogram.percentile(100.0), Duration::from_nanos(127));
+        assert_eq!(TimingHistogram::default().percentile(50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_histogram_merge() {
+        let mut a = TimingHistogram::default();
+        let mut b = TimingHistogram::default();
+        a.record(Duration::from_micros(1));
+        b.record(Duration::from_micros(3));
+
+        a.merge(&b);
+        assert_eq!(a.count(), 2);
+        assert_eq!(a.mean(), Duration::from_micros(2));
+    }
+}
@@ -0,0 +1,84 @@
+//! Mint transactions for Lelantus
+//!
+//! A mint converts a transparent amount into a private coin. The amount is
+//! public, so anyone can check that the new commitment opens to it:
+//! [`LelantusMint`] carries a proof of opening rather than the coin
+//! randomness, and needs the `pedersen` scheme.
+
+use crate::commitment::{AccumulatorElement, Commitment, CommitmentScheme};
+use crate::errors::Result;
+use crate::pedersen::OpeningProof;
+use crate::serialization::LelantusSerializable;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Shielding mint that keeps the coin randomness private
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct LelantusMint {
+    /// Public commitment of the minted coin
+    pub commitment: AccumulatorElement,
+
+    /// Transparent value entering the shielded pool
+    pub value: u64,
+
+    /// Proof that the commitment opens to `value`
+    pub mint_proof: OpeningProof,
+}
+
+impl LelantusSerializable for LelantusMint {}
+
+impl LelantusMint {
+    /// Mint a coin of `value`, returning the mint and the coin's opening
+    ///
+    /// Fails with `InvalidParameter` unless `scheme` is the `pedersen` scheme.
+    pub fn create(scheme: &CommitmentScheme, value: u64) -> Result<(Self, Commitment)> {
+        let coin = scheme.commit(value)?;
+        let mint = Self {
+            commitment: coin.to_element()?,
+            value,
+            mint_proof: scheme.prove_opening(&coin, value)?,
+        };
+        Ok((mint, coin))
+    }
+
+    /// Check that the commitment opens to the declared value
+    pub fn verify(&self, scheme: &CommitmentScheme) -> Result<bool> {
+        scheme.verify_opening(&self.commitment.value, self.value, &self.mint_proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::LelantusParameters;
+    use crate::serialization::SerializationFormat;
+
+    #[test]
+    fn test_lelantus_mint() -> Result<()> {
+        let params = LelantusParameters {
+            hash_function: "pedersen".to_string(),
+            ..Default::default()
+        };
+        let scheme = CommitmentScheme::new(&params)?;
+        let (mint, coin) = LelantusMint::create(&scheme, 1000)?;
+        assert!(mint.verify(&scheme)?);
+        assert!(scheme.verify(&coin, 1000)?);
+
+        // The mint does not carry the coin randomness
+        let encoded = mint.to_bytes_format(SerializationFormat::Borsh)?;
+        assert!(!encoded
+            .windows(coin.randomness.len())
+            .any(|window| window == coin.randomness.as_slice()));
+
+        let mut inflated = mint.clone();
+        inflated.value = 1001;
+        assert!(!inflated.verify(&scheme)?);
+
+        let sha512 = CommitmentScheme::new(&LelantusParameters {
+            hash_function: "sha512".to_string(),
+            ..Default::default()
+        })?;
+        assert!(LelantusMint::create(&sha512, 1000).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,381 @@
+//! Nullifier derivation for Lelantus spends
+//!
+//! A nullifier is the coin's serial number: a digest of the randomness the
+//! coin is committed under. As in Lelantus, the `pedersen` scheme commits to
+//! it inside the coin as `serial·G₁`, and a spend proves membership of the
+//! coin minus `serial·G₁`, so the revealed nullifier is bound to the coin
+//! spent. It is deterministic, so a coin always yields the same nullifier.
+//!
+//! Coins owned by a [`SpendKey`] are committed under a keyed opening of
+//! their randomness (see [`SpendKey::open`]), so their nullifiers are a
+//! keyed PRF of the coin: computing one, or linking a spend back to its
+//! coin, needs the owner's keys and not just the coin's randomness. For
+//! coins minted without an owner the randomness is the opening, and whoever
+//! holds it can both spend the coin and compute its nullifier.
+
+use crate::commitment::{serial_digest, Commitment, CommitmentScheme};
+use crate::errors::{LelantusError, Result};
+use crate::keys::SpendKey;
+use crate::merkle;
+use crate::pedersen::SerialProof;
+use crate::serial::SerialNumber;
+pub use crate::serial::NULLIFIER_SIZE;
+use serde::{Deserialize, Serialize};
+
+/// Maximum deviation (in bits) from the expected half-weight tolerated by the
+/// unlinkability check; roughly 5.6 standard deviations for 512 bits
+const HAMMING_TOLERANCE: u32 = 64;
+
+/// Derive the nullifier of a coin owned by `spend_key`
+///
+/// Fails with `NotCoinOwner` if the coin is not owned by the key, and with
+/// `InvalidCommitment` for a commitment published without its randomness.
+pub fn derive_nullifier(spend_key: &SpendKey, commitment: &Commitment) -> Result<Vec<u8>> {
+    if commitment.randomness.is_empty() {
+        return Err(LelantusError::InvalidCommitment);
+    }
+    opening_nullifier(&spend_key.open(commitment)?)
+}
+
+/// Nullifier committed in a coin opening
+///
+/// Fails with `InvalidCommitment` for a commitment published without its
+/// randomness.
+pub(crate) fn opening_nullifier(opening: &Commitment) -> Result<Vec<u8>> {
+    if opening.randomness.is_empty() {
+        return Err(LelantusError::InvalidCommitment);
+    }
+    Ok(serial_digest(&opening.randomness))
+}
+
+/// Merkle root over nullifiers given in sorted order
+///
+/// The root also commits to the leaf count, so a proof can show that a
+/// leaf is the first or last one in the set.
+pub fn nullifier_merkle_root<'a>(
+    nullifiers: impl ExactSizeIterator<Item = &'a SerialNumber>,
+) -> [u8; 32] {
+    merkle::merkle_root(nullifiers.map(SerialNumber::as_bytes))
+}
+
+/// A nullifier in the sorted set with its Merkle authentication path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NullifierLeaf {
+    /// The nullifier
+    pub nullifier: Vec<u8>,
+
+    /// Position in the sorted set
+    pub index: u64,
+
+    /// Sibling hashes from the leaf upwards (levels without a sibling omitted)
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl NullifierLeaf {
+    /// Build the leaf proof for position `index` of a sorted set
+    fn prove(sorted: &[&SerialNumber], index: usize) -> Self {
+        let leaves: Vec<[u8; 32]> = sorted
+            .iter()
+            .map(|n| merkle::leaf_hash(n.as_bytes()))
+            .collect();
+        Self {
+            nullifier: sorted[index].as_bytes().to_vec(),
+            index: index as u64,
+            siblings: merkle::authentication_path(&leaves, index),
+        }
+    }
+
+    /// Check the path against `root` for a set of `leaf_count` nullifiers
+    fn verify(&self, root: &[u8; 32], leaf_count: u64) -> bool {
+        merkle::root_from_path(
+            merkle::leaf_hash(&self.nullifier),
+            self.index,
+            leaf_count,
+            &self.siblings,
+        ) == Some(*root)
+    }
+}
+
+/// Proof that a coin's nullifier is absent from the spent set
+///
+/// Shows that the nullifier is the serial number the coin commits to, then
+/// shows the nullifier's would-be neighbours in the sorted set and that they
+/// are adjacent, so nothing lies between them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonSpendProof {
+    /// Commitment of the coin shown to be unspent
+    pub commitment: Vec<u8>,
+
+    /// Proof that `commitment` commits to `nullifier` as its serial number
+    pub serial_proof: SerialProof,
+
+    /// The nullifier shown to be unspent
+    pub nullifier: Vec<u8>,
+
+    /// Merkle root of the spent set the proof was made against
+    pub root: [u8; 32],
+
+    /// Number of nullifiers in that set
+    pub leaf_count: u64,
+
+    /// Greatest spent nullifier below `nullifier`, if any
+    pub lower: Option<NullifierLeaf>,
+
+    /// Smallest spent nullifier above `nullifier`, if any
+    pub upper: Option<NullifierLeaf>,
+}
+
+impl NonSpendProof {
+    /// Build a proof that the coin `commitment` of `value` is absent from a
+    /// sorted spent set
+    ///
+    /// `commitment` is the coin's opening; for an owned coin, as given by
+    /// [`SpendKey::open`]. Needs the `pedersen` scheme. Fails with
+    /// `DoubleSpend` if the coin's nullifier is in the set.
+    pub fn create(
+        scheme: &CommitmentScheme,
+        sorted: &[&SerialNumber],
+        commitment: &Commitment,
+        value: u64,
+    ) -> Result<Self> {
+        let nullifier = opening_nullifier(commitment)?;
+        let serial_proof = scheme.prove_serial(commitment, value)?;
+        Self::absent(sorted, nullifier, commitment.value.clone(), serial_proof)
+    }
+
+    /// Place `nullifier` between its neighbours in `sorted`
+    fn absent(
+        sorted: &[&SerialNumber],
+        nullifier: Vec<u8>,
+        commitment: Vec<u8>,
+        serial_proof: SerialProof,
+    ) -> Result<Self> {
+        let position = match sorted.binary_search_by(|n| n.as_bytes().cmp(&nullifier)) {
+            Ok(_) => return Err(LelantusError::DoubleSpend),
+            Err(position) => position,
+        };
+
+        Ok(Self {
+            commitment,
+            serial_proof,
+            root: nullifier_merkle_root(sorted.iter().copied()),
+            leaf_count: sorted.len() as u64,
+            lower: position
+                .checked_sub(1)
+                .map(|index| NullifierLeaf::prove(sorted, index)),
+            upper: (position < sorted.len()).then(|| NullifierLeaf::prove(sorted, position)),
+            nullifier,
+        })
+    }
+
+    /// Check the proof against a spent-set root
+    ///
+    /// Does not check that `commitment` is a coin; see
+    /// [`LelantusState::verify_not_spent`](crate::LelantusState::verify_not_spent).
+    pub fn verify(&self, scheme: &CommitmentScheme, root: &[u8; 32]) -> Result<bool> {
+        Ok(self.verify_absent(root)
+            && scheme.verify_serial(&self.commitment, &self.nullifier, &self.serial_proof)?)
+    }
+
+    /// Check that `nullifier` lies between adjacent leaves under `root`
+    fn verify_absent(&self, root: &[u8; 32]) -> bool {
+        if self.root != *root {
+            return false;
+        }
+
+        let lower_ok = match &self.lower {
+            Some(leaf) => leaf.nullifier < self.nullifier && leaf.verify(root, self.leaf_count),
+            None => true,
+        };
+        let upper_ok = match &self.upper {
+            Some(leaf) => leaf.nullifier > self.nullifier && leaf.verify(root, self.leaf_count),
+            None => true,
+        };
+        let adjacent = match (&self.lower, &self.upper) {
+            (Some(lower), Some(upper)) => upper.index == lower.index + 1,
+            (Some(lower), None) => lower.index + 1 == self.leaf_count,
+            (None, Some(upper)) => upper.index == 0,
+            (None, None) => self.leaf_count == 0,
+        };
+
+        lower_ok && upper_ok && adjacent
+    }
+}
+
+/// Number of differing bits between two equal-length byte strings
+fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+/// Whether a bit count is within tolerance of half of `bits`
+fn near_half(count: u32, bits: u32) -> bool {
+    count.abs_diff(bits / 2) <= HAMMING_TOLERANCE
+}
+
+/// Check that the nullifiers `spend_key` derives for `coins` are unlinkable
+/// to the coins
+///
+/// For every coin this verifies that derivation is deterministic, that the
+/// nullifier looks uniformly random (balanced Hamming weight), and that it is
+/// uncorrelated with the coin bytes. For every pair of coins it verifies that
+/// the nullifiers are distinct and uncorrelated with each other. Returns
+/// `false` if any property fails.
+pub fn verify_nullifier_unlinkability(spend_key: &SpendKey, coins: &[Commitment]) -> Result<bool> {
+    let bits = (NULLIFIER_SIZE * 8) as u32;
+    let mut nullifiers = Vec::with_capacity(coins.len());
+
+    for coin in coins {
+        let nullifier = derive_nullifier(spend_key, coin)?;
+        if nullifier != derive_nullifier(spend_key, coin)? {
+            return Ok(false);
+        }
+
+        let weight: u32 = nullifier.iter().map(|b| b.count_ones()).sum();
+        if !near_half(weight, bits) {
+            return Ok(false);
+        }
+
+        // Compare against the coin bytes, cycled to the nullifier length
+        let coin_bytes: Vec<u8> = coin
+            .value
+            .iter()
+            .chain(&coin.randomness)
+            .copied()
+            .cycle()
+            .take(NULLIFIER_SIZE)
+            .collect();
+        if coin_bytes.len() == NULLIFIER_SIZE
+            && !near_half(hamming_distance(&nullifier, &coin_bytes), bits)
+        {
+            return Ok(false);
+        }
+
+        nullifiers.push(nullifier);
+    }
+
+    for (i, a) in nullifiers.iter().enumerate() {
+        for b in &nullifiers[i + 1..] {
+            if !near_half(hamming_distance(a, b), bits) {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::CommitmentScheme;
+    use crate::parameters::LelantusParameters;
+
+    fn coins(key: &SpendKey, count: u8) -> Result<Vec<Commitment>> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let owner = key.full_viewing_key()?;
+        (0..count)
+            .map(|i| scheme.commit_to(&owner, 1000 + i as u64))
+            .collect()
+    }
+
+    #[test]
+    fn test_nullifier_is_deterministic() -> Result<()> {
+        let key = SpendKey::from_seed(&[5; 32])?;
+        let coins = coins(&key, 2)?;
+
+        let nullifier = derive_nullifier(&key, &coins[0])?;
+        assert_eq!(nullifier.len(), NULLIFIER_SIZE);
+        assert_eq!(nullifier, derive_nullifier(&key, &coins[0])?);
+        assert_ne!(nullifier, derive_nullifier(&key, &coins[1])?);
+
+        // The coin's randomness without the key does not give the nullifier
+        assert_ne!(nullifier, serial_digest(&coins[0].randomness));
+        assert!(matches!(
+            derive_nullifier(&SpendKey::from_seed(&[6; 32])?, &coins[0]),
+            Err(LelantusError::NotCoinOwner)
+        ));
+
+        // The published commitment alone does not give the nullifier
+        let published = Commitment::from_value(coins[0].value.clone());
+        assert!(matches!(
+            derive_nullifier(&key, &published),
+            Err(LelantusError::InvalidCommitment)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullifier_unlinkability() -> Result<()> {
+        let key = SpendKey::from_seed(&[5; 32])?;
+        assert!(verify_nullifier_unlinkability(&key, &coins(&key, 16)?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_spend_proof_positions() -> Result<()> {
+        let spent = (1..=5u8)
+            .map(|i| SerialNumber::from_bytes(vec![i * 10; 64]))
+            .collect::<Result<Vec<_>>>()?;
+        let sorted: Vec<&SerialNumber> = spent.iter().collect();
+        let root = nullifier_merkle_root(spent.iter());
+        let absent = |sorted: &[&SerialNumber], nullifier| {
+            let serial_proof = SerialProof {
+                nonce_point: Vec::new(),
+                response: Vec::new(),
+            };
+            NonSpendProof::absent(sorted, nullifier, Vec::new(), serial_proof)
+        };
+
+        // Before the first, between two, and after the last spent nullifier
+        for candidate in [vec![5; 64], vec![25; 64], vec![99; 64]] {
+            assert!(absent(&sorted, candidate)?.verify_absent(&root));
+        }
+
+        let empty = absent(&[], vec![1; 64])?;
+        assert!(empty.verify_absent(&nullifier_merkle_root(std::iter::empty())));
+        assert!(!empty.verify_absent(&root));
+
+        assert!(matches!(
+            absent(&sorted, vec![30; 64]),
+            Err(LelantusError::DoubleSpend)
+        ));
+
+        // Neighbours that are not adjacent leave room for a spent nullifier
+        let mut proof = absent(&sorted, vec![25; 64])?;
+        proof.upper = Some(NullifierLeaf::prove(&sorted, 3));
+        assert!(!proof.verify_absent(&root));
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_spend_proof_binds_coin() -> Result<()> {
+        let params = LelantusParameters::default();
+        let scheme = CommitmentScheme::new(&params)?;
+        let coin = scheme.commit(700)?;
+        let other = scheme.commit(700)?;
+        let spent = [SerialNumber::from_opening(&other)?];
+        let sorted: Vec<&SerialNumber> = spent.iter().collect();
+        let root = nullifier_merkle_root(spent.iter());
+
+        let proof = NonSpendProof::create(&scheme, &sorted, &coin, 700)?;
+        assert!(proof.verify(&scheme, &root)?);
+        assert!(NonSpendProof::create(&scheme, &sorted, &coin, 699).is_err());
+
+        // The nullifier of another coin, or another coin for the nullifier,
+        // fails the serial proof
+        let mut swapped = proof.clone();
+        swapped.nullifier = vec![99; 64];
+        assert!(!swapped.verify(&scheme, &root)?);
+        let mut swapped = proof;
+        swapped.commitment = other.value.clone();
+        assert!(!swapped.verify(&scheme, &root)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hamming_helpers() {
+        assert_eq!(hamming_distance(&[0xff, 0x00], &[0x0f, 0x00]), 4);
+        assert!(near_half(256, 512));
+        assert!(!near_half(0, 512));
+    }
+}
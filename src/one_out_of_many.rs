@@ -0,0 +1,522 @@
+//! Groth–Kohlweiss one-out-of-many proofs
+//!
+//! Proves knowledge of an index `l` and blinding `r` with
+//! `set[l] - offset = r·H`, without revealing `l`. With `offset` a fresh
+//! commitment to the value of the spent coin plus `serial·G₁` for its
+//! revealed serial number (see [`CommitmentScheme::spend_offset`]), this
+//! shows the coin is in the anonymity set and commits to that serial without
+//! revealing which one it is or what it holds.
+//!
+//! Runs over the Pedersen scheme (`hash_function = "pedersen"`) with binary
+//! digits, so proofs hold `log2(N)` entries per vector for an anonymity set
+//! of `N = PrivacyLevel::anonymity_set_size()` commitments. Shorter sets are
+//! padded by repeating their last commitment, which costs a single point
+//! multiplication however long the padding is.
+
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::errors::{ErrorContext, LelantusError, Result};
+use crate::parameters::PrivacyLevel;
+use crate::pedersen::{
+    decode, decode_hex, decode_scalar, encode, scalar_from_digest, Generators, POINT_SIZE,
+    SCALAR_SIZE,
+};
+use crate::serialization::binary::{self, LENGTH_PREFIX_SIZE, U64_SIZE};
+use crate::serialization::LelantusSerializable;
+use crate::transcript::{ProofDomain, ProofTranscript};
+use borsh::{BorshDeserialize, BorshSerialize};
+use p521::elliptic_curve::{Field, PrimeField};
+use p521::{ProjectivePoint, Scalar};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Base of the index digits
+const DIGIT_BASE: u64 = 2;
+
+/// One-out-of-many membership proof
+///
+/// Points are compressed SEC1 encodings, scalars big-endian.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct OneOutOfManyProof {
+    /// Commitments to the index bits
+    pub bit_commitments: Vec<Vec<u8>>,
+
+    /// Commitments to the bit masks
+    pub mask_commitments: Vec<Vec<u8>>,
+
+    /// Commitments to `bit·mask`, showing each bit is 0 or 1
+    pub product_commitments: Vec<Vec<u8>>,
+
+    /// Commitments to the low-order coefficients of the selector polynomials
+    pub polynomial_commitments: Vec<Vec<u8>>,
+
+    /// Masked bits `bit·x + mask`
+    pub bit_responses: Vec<Vec<u8>>,
+
+    /// Blinding responses for the mask commitments
+    pub mask_responses: Vec<Vec<u8>>,
+
+    /// Blinding responses for the product commitments
+    pub product_responses: Vec<Vec<u8>>,
+
+    /// Blinding response for the selected commitment
+    pub blinding_response: Vec<u8>,
+}
+
+impl LelantusSerializable for OneOutOfManyProof {}
+
+/// Number of binary digits indexing an anonymity set of `level`
+fn digits(level: PrivacyLevel) -> usize {
+    level.anonymity_set_size().trailing_zeros() as usize
+}
+
+/// `set - offset`, checking that `set` fits the anonymity set size of `level`
+///
+/// Members past the end are padding and equal the last one.
+fn shifted_set(
+    set: &[Vec<u8>],
+    offset: &[u8],
+    level: PrivacyLevel,
+) -> Result<Vec<ProjectivePoint>> {
+    let size = level.anonymity_set_size();
+    if set.is_empty() {
+        return Err(LelantusError::invalid_parameter("anonymity_set"));
+    }
+    if set.len() > size {
+        return Err(LelantusError::InvalidParameter(
+            ErrorContext::new("anonymity_set")
+                .expected(format!("<= {} members", size))
+                .actual(set.len()),
+        ));
+    }
+
+    let offset = decode_hex(offset)?;
+    set.iter()
+        .map(|value| Ok(decode_hex(value)? - offset))
+        .collect()
+}
+
+/// Bit `j` of `index` as a scalar
+fn bit(index: usize, j: usize) -> Scalar {
+    if (index >> j) & 1 == 1 {
+        Scalar::ONE
+    } else {
+        Scalar::ZERO
+    }
+}
+
+/// Fiat-Shamir challenge over the generators, the statement and the first
+/// prover message
+fn challenge(
+    generators: &Generators,
+    set: &[Vec<u8>],
+    offset: &[u8],
+    level: PrivacyLevel,
+    proof: &OneOutOfManyProof,
+) -> Scalar {
+    let mut transcript = ProofTranscript::new(ProofDomain::MembershipProof);
+    transcript.append_message(b"value-generator", &encode(&ProjectivePoint::GENERATOR));
+    transcript.append_message(
+        b"blinding-generator",
+        &encode(&generators.blinding_generator()),
+    );
+    transcript.append_u64(b"digit-base", DIGIT_BASE);
+    transcript.append_u64(b"digits", digits(level) as u64);
+    transcript.append_u64(b"set-size", set.len() as u64);
+    for value in set {
+        transcript.append_message(b"member", value);
+    }
+    transcript.append_message(b"offset", offset);
+    for (label, points) in [
+        (&b"bit-commitments"[..], &proof.bit_commitments),
+        (b"mask-commitments", &proof.mask_commitments),
+        (b"product-commitments", &proof.product_commitments),
+        (b"polynomial-commitments", &proof.polynomial_commitments),
+    ] {
+        transcript.append_u64(b"count", points.len() as u64);
+        for point in points {
+            transcript.append_message(label, point);
+        }
+    }
+    scalar_from_digest(&transcript.challenge_bytes(b"challenge"))
+}
+
+/// Decode a vector of `len` points
+pub(crate) fn decode_points(points: &[Vec<u8>], len: usize) -> Option<Vec<ProjectivePoint>> {
+    (points.len() == len)
+        .then(|| points.iter().map(|point| decode(point)).collect())
+        .flatten()
+}
+
+/// Decode a vector of `len` scalars
+pub(crate) fn decode_scalars(scalars: &[Vec<u8>], len: usize) -> Option<Vec<Scalar>> {
+    (scalars.len() == len)
+        .then(|| scalars.iter().map(|scalar| decode_scalar(scalar)).collect())
+        .flatten()
+}
+
+/// `value·G + blinding·H`
+fn commit(generators: &Generators, value: Scalar, blinding: Scalar) -> ProjectivePoint {
+    ProjectivePoint::GENERATOR * value + generators.blinding_generator() * blinding
+}
+
+/// Encode scalars as big-endian bytes
+pub(crate) fn encode_scalars(scalars: &[Scalar]) -> Vec<Vec<u8>> {
+    scalars
+        .iter()
+        .map(|scalar| scalar.to_repr().to_vec())
+        .collect()
+}
+
+/// Append a count-prefixed list of byte fields
+pub(crate) fn put_fields(buf: &mut Vec<u8>, fields: &[Vec<u8>]) -> Result<()> {
+    binary::put_u64(buf, fields.len() as u64);
+    fields
+        .iter()
+        .try_for_each(|field| binary::put_bytes(buf, field))
+}
+
+/// Read a list written by [`put_fields`]
+pub(crate) fn read_fields(reader: &mut binary::Reader<'_>) -> Result<Vec<Vec<u8>>> {
+    (0..reader.read_u64()?)
+        .map(|_| reader.read_bytes())
+        .collect()
+}
+
+impl OneOutOfManyProof {
+    /// Prove that `set[index] - offset` is a commitment to zero
+    ///
+    /// `randomness` opens `set[index]`, and `offset` must commit to the same
+    /// value; fails with `InvalidCommitment` if the difference does not open
+    /// to zero.
+    pub fn create(
+        scheme: &CommitmentScheme,
+        set: &[Vec<u8>],
+        index: usize,
+        randomness: &[u8],
+        offset: &Commitment,
+        level: PrivacyLevel,
+    ) -> Result<Self> {
+        Self::create_with_rng(scheme, set, index, randomness, offset, level, &mut OsRng)
+    }
+
+    /// Prove that `set[index] - offset` is a commitment to zero, with all
+    /// proof randomness drawn from `rng`
+    pub fn create_with_rng(
+        scheme: &CommitmentScheme,
+        set: &[Vec<u8>],
+        index: usize,
+        randomness: &[u8],
+        offset: &Commitment,
+        level: PrivacyLevel,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self> {
+        let generators = scheme.pedersen_generators()?;
+        let shifted = shifted_set(set, &offset.value, level)?;
+        let m = digits(level);
+        let blinding = Generators::blinding_factor(randomness, None)
+            - Generators::blinding_factor(&offset.randomness, None);
+        if shifted.get(index) != Some(&(generators.blinding_generator() * blinding)) {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let mut random =
+            |len: usize| -> Vec<Scalar> { (0..len).map(|_| Scalar::random(&mut *rng)).collect() };
+        let bits: Vec<Scalar> = (0..m).map(|j| bit(index, j)).collect();
+        let (bit_blindings, masks, mask_blindings) = (random(m), random(m), random(m));
+        let (product_blindings, polynomial_blindings) = (random(m), random(m));
+
+        // Coefficients of p_i(x) = prod_j f_{j,i_j}(x), where
+        // f_{j,1}(x) = bit_j·x + mask_j and f_{j,0}(x) = x - f_{j,1}(x)
+        let mut polynomial_sums = vec![ProjectivePoint::IDENTITY; m];
+        let mut padding_coefficients = vec![Scalar::ZERO; m];
+        for i in 0..level.anonymity_set_size() {
+            let mut coefficients = vec![Scalar::ONE];
+            for j in 0..m {
+                let (constant, linear) = if (i >> j) & 1 == 1 {
+                    (masks[j], bits[j])
+                } else {
+                    (-masks[j], Scalar::ONE - bits[j])
+                };
+                let mut next = vec![Scalar::ZERO; coefficients.len() + 1];
+                for (k, coefficient) in coefficients.iter().enumerate() {
+                    next[k] += *coefficient * constant;
+                    next[k + 1] += *coefficient * linear;
+                }
+                coefficients = next;
+            }
+            match shifted.get(i) {
+                Some(point) => {
+                    for (sum, coefficient) in polynomial_sums.iter_mut().zip(&coefficients) {
+                        *sum += *point * coefficient;
+                    }
+                }
+                None => {
+                    for (sum, coefficient) in padding_coefficients.iter_mut().zip(&coefficients) {
+                        *sum += coefficient;
+                    }
+                }
+            }
+        }
+        let padding = shifted[shifted.len() - 1];
+        for (sum, coefficient) in polynomial_sums.iter_mut().zip(&padding_coefficients) {
+            *sum += padding * coefficient;
+        }
+
+        let mut proof = Self {
+            bit_commitments: (0..m)
+                .map(|j| encode(&commit(generators, bits[j], bit_blindings[j])))
+                .collect(),
+            mask_commitments: (0..m)
+                .map(|j| encode(&commit(generators, masks[j], mask_blindings[j])))
+                .collect(),
+            product_commitments: (0..m)
+                .map(|j| {
+                    encode(&commit(
+                        generators,
+                        bits[j] * masks[j],
+                        product_blindings[j],
+                    ))
+                })
+                .collect(),
+            polynomial_commitments: polynomial_sums
+                .iter()
+                .zip(&polynomial_blindings)
+                .map(|(sum, rho)| encode(&(*sum + generators.blinding_generator() * rho)))
+                .collect(),
+            bit_responses: Vec::new(),
+            mask_responses: Vec::new(),
+            product_responses: Vec::new(),
+            blinding_response: Vec::new(),
+        };
+
+        let x = challenge(generators, set, &offset.value, level, &proof);
+        let responses: Vec<Scalar> = (0..m).map(|j| bits[j] * x + masks[j]).collect();
+        let mask_responses: Vec<Scalar> = (0..m)
+            .map(|j| bit_blindings[j] * x + mask_blindings[j])
+            .collect();
+        let product_responses: Vec<Scalar> = (0..m)
+            .map(|j| bit_blindings[j] * (x - responses[j]) + product_blindings[j])
+            .collect();
+
+        let mut x_power = Scalar::ONE;
+        let mut blinding_response = Scalar::ZERO;
+        for rho in &polynomial_blindings {
+            blinding_response -= *rho * x_power;
+            x_power *= x;
+        }
+        blinding_response += blinding * x_power;
+
+        proof.bit_responses = encode_scalars(&responses);
+        proof.mask_responses = encode_scalars(&mask_responses);
+        proof.product_responses = encode_scalars(&product_responses);
+        proof.blinding_response = blinding_response.to_repr().to_vec();
+        Ok(proof)
+    }
+
+    /// Verify that some `set[l] - offset` is a commitment to zero
+    ///
+    /// Returns `Ok(false)` for malformed or invalid proofs, and errors only if
+    /// the scheme, set or offset cannot be used.
+    pub fn verify(
+        &self,
+        scheme: &CommitmentScheme,
+        set: &[Vec<u8>],
+        offset: &[u8],
+        level: PrivacyLevel,
+    ) -> Result<bool> {
+        let generators = scheme.pedersen_generators()?;
+        let shifted = shifted_set(set, offset, level)?;
+        let m = digits(level);
+
+        let decoded = (|| {
+            Some((
+                decode_points(&self.bit_commitments, m)?,
+                decode_points(&self.mask_commitments, m)?,
+                decode_points(&self.product_commitments, m)?,
+                decode_points(&self.polynomial_commitments, m)?,
+                decode_scalars(&self.bit_responses, m)?,
+                decode_scalars(&self.mask_responses, m)?,
+                decode_scalars(&self.product_responses, m)?,
+                decode_scalar(&self.blinding_response)?,
+            ))
+        })();
+        let Some((bit_c, mask_c, product_c, polynomial_c, f, z_a, z_b, z_d)) = decoded else {
+            return Ok(false);
+        };
+
+        let x = challenge(generators, set, offset, level, self);
+        let h = generators.blinding_generator();
+        for j in 0..m {
+            // Masked bits open correctly and each bit is 0 or 1
+            if bit_c[j] * x + mask_c[j] != commit(generators, f[j], z_a[j])
+                || bit_c[j] * (x - f[j]) + product_c[j] != h * z_b[j]
+            {
+                return Ok(false);
+            }
+        }
+
+        // sum_i p_i(x)·C_i - sum_k x^k·D_k must be z_d·H
+        let mut total = ProjectivePoint::IDENTITY;
+        let mut padding_selector = Scalar::ZERO;
+        for i in 0..level.anonymity_set_size() {
+            let selector = (0..m).fold(Scalar::ONE, |product, j| {
+                product * if (i >> j) & 1 == 1 { f[j] } else { x - f[j] }
+            });
+            match shifted.get(i) {
+                Some(point) => total += *point * selector,
+                None => padding_selector += selector,
+            }
+        }
+        total += shifted[shifted.len() - 1] * padding_selector;
+        let mut x_power = Scalar::ONE;
+        for point in &polynomial_c {
+            total -= *point * x_power;
+            x_power *= x;
+        }
+        Ok(total == h * z_d)
+    }
+
+    /// Size of [`consensus_encode`](Self::consensus_encode) for an anonymity set of `level`
+    pub fn encoded_size(level: PrivacyLevel) -> usize {
+        let m = digits(level);
+        4 * (U64_SIZE + m * (LENGTH_PREFIX_SIZE + POINT_SIZE))
+            + 3 * (U64_SIZE + m * (LENGTH_PREFIX_SIZE + SCALAR_SIZE))
+            + LENGTH_PREFIX_SIZE
+            + SCALAR_SIZE
+    }
+
+    /// Encode the proof in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        put_fields(&mut buf, &self.bit_commitments)?;
+        put_fields(&mut buf, &self.mask_commitments)?;
+        put_fields(&mut buf, &self.product_commitments)?;
+        put_fields(&mut buf, &self.polynomial_commitments)?;
+        put_fields(&mut buf, &self.bit_responses)?;
+        put_fields(&mut buf, &self.mask_responses)?;
+        put_fields(&mut buf, &self.product_responses)?;
+        binary::put_bytes(&mut buf, &self.blinding_response)?;
+        Ok(buf)
+    }
+
+    /// Decode the proof from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let proof = Self {
+            bit_commitments: read_fields(&mut reader)?,
+            mask_commitments: read_fields(&mut reader)?,
+            product_commitments: read_fields(&mut reader)?,
+            polynomial_commitments: read_fields(&mut reader)?,
+            bit_responses: read_fields(&mut reader)?,
+            mask_responses: read_fields(&mut reader)?,
+            product_responses: read_fields(&mut reader)?,
+            blinding_response: reader.read_bytes()?,
+        };
+        reader.finish()?;
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::{serial_digest, NATIVE_ASSET};
+    use crate::parameters::LelantusParameters;
+
+    /// Offset spending `coin` as a fresh commitment to `value`
+    fn offset(scheme: &CommitmentScheme, coin: &Commitment, value: u64) -> Result<Commitment> {
+        let input = scheme.commit_input_with_rng(&NATIVE_ASSET, value, &mut OsRng)?;
+        scheme.spend_offset(&input, &serial_digest(&coin.randomness))
+    }
+
+    #[test]
+    fn test_one_out_of_many_proof() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let coins = (0..10u64)
+            .map(|i| scheme.commit(100 * (i + 1)))
+            .collect::<Result<Vec<Commitment>>>()?;
+        let set: Vec<Vec<u8>> = coins.iter().map(|coin| coin.value.clone()).collect();
+        let offset = offset(&scheme, &coins[3], 400)?;
+        let level = PrivacyLevel::Standard;
+
+        let proof =
+            OneOutOfManyProof::create(&scheme, &set, 3, &coins[3].randomness, &offset, level)?;
+        assert_eq!(proof.bit_commitments.len(), 6);
+        assert!(proof.verify(&scheme, &set, &offset.value, level)?);
+
+        // Another offset, another set, another level or a tampered proof all
+        // fail
+        let other = scheme.commit(400)?;
+        assert!(!proof.verify(&scheme, &set, &other.value, level)?);
+        assert!(!proof.verify(&scheme, &set[..9], &offset.value, level)?);
+        assert!(!proof.verify(&scheme, &set, &offset.value, PrivacyLevel::Enhanced)?);
+        let mut tampered = proof.clone();
+        tampered.bit_responses.swap(0, 1);
+        assert!(!tampered.verify(&scheme, &set, &offset.value, level)?);
+
+        let bytes = proof.consensus_encode()?;
+        assert_eq!(bytes.len(), OneOutOfManyProof::encoded_size(level));
+        let round_trip = OneOutOfManyProof::consensus_decode(&bytes)?;
+        assert!(round_trip.verify(&scheme, &set, &offset.value, level)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_out_of_many_binds_generators() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let coin = scheme.commit(300)?;
+        let offset = offset(&scheme, &coin, 300)?;
+        let set = vec![coin.value.clone()];
+        let level = PrivacyLevel::Standard;
+        let proof = OneOutOfManyProof::create(&scheme, &set, 0, &coin.randomness, &offset, level)?;
+        assert!(proof.verify(&scheme, &set, &offset.value, level)?);
+
+        // The same commitments checked under another blinding generator
+        let other = CommitmentScheme::new(&LelantusParameters {
+            generator: vec![4; 512],
+            ..Default::default()
+        })?;
+        assert!(!proof.verify(&other, &set, &offset.value, level)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_out_of_many_rejects_wrong_opening() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let coin = scheme.commit(300)?;
+        let set = vec![coin.value.clone()];
+
+        // Wrong value, or a serial the coin does not commit to
+        let input = scheme.commit_input_with_rng(&NATIVE_ASSET, 300, &mut OsRng)?;
+        for offset in [
+            offset(&scheme, &coin, 400)?,
+            scheme.spend_offset(&input, &[7; 64])?,
+        ] {
+            let result = OneOutOfManyProof::create(
+                &scheme,
+                &set,
+                0,
+                &coin.randomness,
+                &offset,
+                PrivacyLevel::Standard,
+            );
+            assert!(matches!(result, Err(LelantusError::InvalidCommitment)));
+        }
+
+        let sha512 = CommitmentScheme::new(&LelantusParameters {
+            hash_function: "sha512".to_string(),
+            ..Default::default()
+        })?;
+        let result = OneOutOfManyProof::create(
+            &sha512,
+            &set,
+            0,
+            &coin.randomness,
+            &input,
+            PrivacyLevel::Standard,
+        );
+        assert!(matches!(result, Err(LelantusError::InvalidParameter(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! Commitment opening oracle for verifier tests
+//!
+//! Soundness tests need a trusted way to learn what a commitment actually
+//! commits to. The oracle opens commitments with their randomness, which no
+//! verifier may do, so this module only exists in test builds.
+
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::proof::RangeProof;
+
+/// Trusted opener of commitments
+pub(crate) trait CommitmentOracle {
+    /// Scheme the commitments were made under
+    fn scheme(&self) -> &CommitmentScheme;
+
+    /// Check that `commitment` opens to `value`
+    fn opens_to(&self, commitment: &Commitment, value: u64) -> bool {
+        self.scheme().verify(commitment, value).unwrap_or(false)
+    }
+
+    /// Check that `proof` was built over `commitment` holding `claimed`
+    fn range_proof_is_sound(
+        &self,
+        proof: &RangeProof,
+        commitment: &Commitment,
+        claimed: u64,
+    ) -> bool {
+        proof.commitment == commitment.value
+            && (proof.min_value..=proof.max_value).contains(&claimed)
+            && self.opens_to(commitment, claimed)
+    }
+}
+
+impl CommitmentOracle for CommitmentScheme {
+    fn scheme(&self) -> &CommitmentScheme {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Result;
+    use crate::parameters::LelantusParameters;
+
+    #[test]
+    fn test_oracle_opens_commitments() -> Result<()> {
+        let params = LelantusParameters::default();
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1000)?;
+
+        assert!(scheme.opens_to(&commitment, 1000));
+        assert!(!scheme.opens_to(&commitment, 999));
+        Ok(())
+    }
+
+    #[test]
+    fn test_oracle_flags_mismatched_range_proof() -> Result<()> {
+        let params = LelantusParameters {
+            hash_function: "pedersen".to_string(),
+            ..LelantusParameters::default()
+        };
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1000)?;
+        let other = scheme.commit(1000)?;
+
+        let proof = RangeProof::create_bounded(&commitment, 1000, 0, 5000, &params)?;
+        assert!(scheme.range_proof_is_sound(&proof, &commitment, 1000));
+
+        // Claimed amount the commitment does not hold
+        assert!(!scheme.range_proof_is_sound(&proof, &commitment, 2000));
+
+        // Proof over a different commitment
+        assert!(!scheme.range_proof_is_sound(&proof, &other, 1000));
+        Ok(())
+    }
+}
@@ -1,11 +1,21 @@
 //! Lelantus protocol parameters
 
-use crate::errors::{LelantusError, Result};
+use crate::amount::MAX_MONEY;
+use crate::errors::{ErrorContext, LelantusError, Result};
+use crate::pedersen::MAX_RANGE_BITS;
+use crate::prelude::*;
+use crate::serialization::binary::{self, U64_SIZE};
+use crate::{
+    LEGACY_MAX_JOINSPLIT_OUTPUTS, LELANTUS_VERSION, MAX_JOINSPLIT_OUTPUTS, MULTI_OUTPUT_VERSION,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
-use silver_core::MIST_PER_SLVR;
+use sha2::{Digest, Sha256, Sha512};
 
 /// Privacy level for Lelantus transactions
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+)]
 pub enum PrivacyLevel {
     /// Standard privacy (default)
     Standard,
@@ -33,10 +43,90 @@ impl PrivacyLevel {
             PrivacyLevel::Maximum => 8192,
         }
     }
+
+    /// Get the stable numeric wire representation
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            PrivacyLevel::Standard => 0,
+            PrivacyLevel::Enhanced => 1,
+            PrivacyLevel::Maximum => 2,
+        }
+    }
+
+    /// Parse a numeric wire representation
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(PrivacyLevel::Standard),
+            1 => Ok(PrivacyLevel::Enhanced),
+            2 => Ok(PrivacyLevel::Maximum),
+            _ => Err(LelantusError::InvalidParameter(
+                ErrorContext::new("privacy_level").actual(value),
+            )),
+        }
+    }
+}
+
+/// Hash algorithm used for commitments
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+)]
+pub enum HashAlgo {
+    /// SHA-512
+    Sha512,
+    /// BLAKE3 with its default 32-byte output
+    Blake3,
+    /// Pedersen commitments over P-521 (additively homomorphic; default)
+    Pedersen,
+    /// Poseidon over the P-521 scalar field (requires the `poseidon` feature)
+    ///
+    /// Only meaningful with a proof system that arithmetizes Poseidon.
+    #[cfg(feature = "poseidon")]
+    Poseidon,
+}
+
+impl HashAlgo {
+    /// Parse a `hash_function` identifier
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "sha512" => Ok(HashAlgo::Sha512),
+            "blake3" => Ok(HashAlgo::Blake3),
+            "pedersen" => Ok(HashAlgo::Pedersen),
+            #[cfg(feature = "poseidon")]
+            "poseidon" => Ok(HashAlgo::Poseidon),
+            _ => Err(LelantusError::invalid_parameter("hash_function")),
+        }
+    }
+
+    /// Get the `hash_function` identifier
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgo::Sha512 => "sha512",
+            HashAlgo::Blake3 => "blake3",
+            HashAlgo::Pedersen => "pedersen",
+            #[cfg(feature = "poseidon")]
+            HashAlgo::Poseidon => "poseidon",
+        }
+    }
+
+    /// Length of a raw hash output in bytes
+    pub fn output_len(&self) -> usize {
+        match self {
+            HashAlgo::Sha512 => 64,
+            HashAlgo::Blake3 => 32,
+            HashAlgo::Pedersen => crate::pedersen::POINT_SIZE,
+            #[cfg(feature = "poseidon")]
+            HashAlgo::Poseidon => 66,
+        }
+    }
+
+    /// Length of a hex-encoded output, as stored in commitments
+    pub fn encoded_len(&self) -> usize {
+        2 * self.output_len()
+    }
 }
 
 /// Lelantus protocol parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct LelantusParameters {
     /// Privacy level
     pub privacy_level: PrivacyLevel,
@@ -62,67 +152,448 @@ pub struct LelantusParameters {
     /// Generator point for commitments
     pub generator: Vec<u8>,
 
-    /// Hash function identifier
+    /// Commitment scheme identifier; see [`HashAlgo`]
+    ///
+    /// Defaults to `pedersen`, whose commitments add homomorphically so that
+    /// JoinSplit balance is checked on the commitments themselves. The hash
+    /// schemes are kept for legacy coins and cannot prove balance.
     pub hash_function: String,
 
     /// Proof system identifier
     pub proof_system: String,
+
+    /// Maximum number of elements the accumulator may hold
+    #[serde(default = "default_max_elements")]
+    pub max_elements: usize,
+
+    /// Bytes used to encode a coin value in commitments and transcripts
+    #[serde(default = "default_value_byte_width")]
+    pub value_byte_width: usize,
+
+    /// Protocol version these parameters follow
+    ///
+    /// Parameter sets serialized without one follow the first version.
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u32,
+
+    /// Most outputs a JoinSplit may create from [`MULTI_OUTPUT_VERSION`] on
+    #[serde(default = "default_max_outputs")]
+    pub max_outputs: usize,
+
+    /// Coin values that may be minted or created, ascending
+    ///
+    /// Empty allows any value between `min_coin_value` and `max_coin_value`.
+    #[serde(default)]
+    pub denominations: Vec<u64>,
+}
+
+/// Domain from which the preset accumulator base is derived
+pub const NUMS_BASE_DOMAIN: &str = "silver-lelantus/accumulator-base/v1";
+
+/// Size of a derived accumulator base, matching the default base
+const NUMS_BASE_SIZE: usize = 512;
+
+/// Derive a nothing-up-my-sleeve accumulator base from `domain`
+///
+/// SHA-512 in counter mode over the domain string, so anyone can reproduce
+/// the base and see it was not hand-picked.
+pub fn nums_base(domain: &str) -> Vec<u8> {
+    let mut base = Vec::with_capacity(NUMS_BASE_SIZE);
+    let mut counter = 0u32;
+    while base.len() < NUMS_BASE_SIZE {
+        let mut hasher = Sha512::new();
+        hasher.update(b"lelantus_nums_base");
+        hasher.update(counter.to_le_bytes());
+        hasher.update(domain.as_bytes());
+        base.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    base.truncate(NUMS_BASE_SIZE);
+    base
+}
+
+fn default_max_elements() -> usize {
+    1 << 20
+}
+
+fn default_value_byte_width() -> usize {
+    U64_SIZE
+}
+
+fn default_protocol_version() -> u32 {
+    1
+}
+
+fn default_max_outputs() -> usize {
+    MAX_JOINSPLIT_OUTPUTS
 }
 
 impl Default for LelantusParameters {
     fn default() -> Self {
-        const MAX_SUPPLY_SLVR: u64 = 21_000_000; // 21M SLVR
-        let max_coin_value = MAX_SUPPLY_SLVR.saturating_mul(MIST_PER_SLVR);
-
         Self {
             privacy_level: PrivacyLevel::Standard,
             accumulator_modulus_bits: 4096,
             randomness_bits: 512,
             range_proof_bits: 128,
-            max_coin_value,
+            max_coin_value: MAX_MONEY,
             min_coin_value: 1,
             accumulator_base: vec![2; 512],
             generator: vec![3; 512],
-            hash_function: "sha512".to_string(),
+            hash_function: "pedersen".to_string(),
             proof_system: "bulletproofs".to_string(),
+            max_elements: default_max_elements(),
+            value_byte_width: default_value_byte_width(),
+            protocol_version: LELANTUS_VERSION,
+            max_outputs: default_max_outputs(),
+            denominations: Vec::new(),
         }
     }
 }
 
 impl LelantusParameters {
     /// Create parameters with specified privacy level
+    ///
+    /// Presets use the accumulator base derived from [`NUMS_BASE_DOMAIN`].
     pub fn with_privacy_level(privacy_level: PrivacyLevel) -> Self {
         Self {
             privacy_level,
+            accumulator_base: nums_base(NUMS_BASE_DOMAIN),
             ..Default::default()
         }
     }
 
+    /// Check that `accumulator_base` is the one derived from `domain`
+    ///
+    /// Returns false for hand-picked bases, including the `Default` one.
+    pub fn verify_nums_base(&self, domain: &str) -> bool {
+        self.accumulator_base == nums_base(domain)
+    }
+
     /// Validate parameters
     pub fn validate(&self) -> Result<()> {
         if self.accumulator_modulus_bits < 1024 {
-            return Err(LelantusError::InvalidParameter);
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("accumulator_modulus_bits")
+                    .expected(">= 1024")
+                    .actual(self.accumulator_modulus_bits),
+            ));
         }
 
         if self.randomness_bits < 512 {
-            return Err(LelantusError::InvalidParameter);
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("randomness_bits")
+                    .expected(">= 512")
+                    .actual(self.randomness_bits),
+            ));
         }
 
         if self.range_proof_bits < 32 {
-            return Err(LelantusError::InvalidParameter);
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("range_proof_bits")
+                    .expected(">= 32")
+                    .actual(self.range_proof_bits),
+            ));
+        }
+
+        if self.range_proof_bits > MAX_RANGE_BITS {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("range_proof_bits")
+                    .expected(format!("<= {}", MAX_RANGE_BITS))
+                    .actual(self.range_proof_bits),
+            ));
+        }
+
+        // Range proofs must be wide enough to cover every valid coin value
+        if self.range_proof_bits < self.max_coin_value_bits() {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("range_proof_bits")
+                    .expected(format!(">= {}", self.max_coin_value_bits()))
+                    .actual(self.range_proof_bits),
+            ));
         }
 
         if self.max_coin_value <= self.min_coin_value {
-            return Err(LelantusError::InvalidParameter);
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("max_coin_value")
+                    .expected(format!("> {}", self.min_coin_value))
+                    .actual(self.max_coin_value),
+            ));
+        }
+
+        if self.accumulator_base.is_empty() {
+            return Err(LelantusError::invalid_parameter("accumulator_base"));
+        }
+
+        if self.generator.is_empty() {
+            return Err(LelantusError::invalid_parameter("generator"));
+        }
+
+        if self.max_elements == 0 {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("max_elements")
+                    .expected("> 0")
+                    .actual(self.max_elements),
+            ));
+        }
+
+        // Every valid coin value must fit the value encoding
+        if self.value_byte_width > U64_SIZE
+            || self.value_byte_width < self.max_coin_value_bits().div_ceil(8).max(1)
+        {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("value_byte_width")
+                    .expected(format!(
+                        "{}..={}",
+                        self.max_coin_value_bits().div_ceil(8).max(1),
+                        U64_SIZE
+                    ))
+                    .actual(self.value_byte_width),
+            ));
+        }
+
+        if self.protocol_version == 0 || self.protocol_version > LELANTUS_VERSION {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("protocol_version")
+                    .expected(format!("1..={}", LELANTUS_VERSION))
+                    .actual(self.protocol_version),
+            ));
         }
 
-        if self.accumulator_base.is_empty() || self.generator.is_empty() {
-            return Err(LelantusError::InvalidParameter);
+        if self.max_outputs == 0 || self.max_outputs > MAX_JOINSPLIT_OUTPUTS {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("max_outputs")
+                    .expected(format!("1..={}", MAX_JOINSPLIT_OUTPUTS))
+                    .actual(self.max_outputs),
+            ));
         }
 
+        for (index, pair) in self.denominations.windows(2).enumerate() {
+            if pair[0] >= pair[1] {
+                return Err(LelantusError::InvalidParameter(
+                    ErrorContext::new("denominations")
+                        .at(index + 1)
+                        .expected(format!("> {}", pair[0]))
+                        .actual(pair[1]),
+                ));
+            }
+        }
+        if let (Some(&first), Some(&last)) = (self.denominations.first(), self.denominations.last())
+        {
+            if first < self.min_coin_value || last > self.max_coin_value {
+                return Err(LelantusError::InvalidParameter(
+                    ErrorContext::new("denominations")
+                        .expected(format!("{}..={}", self.min_coin_value, self.max_coin_value))
+                        .actual(format!("{}..={}", first, last)),
+                ));
+            }
+        }
+
+        self.hash_algo()?;
+
         Ok(())
     }
 
+    /// Whether coins of `value` may be created under these parameters
+    ///
+    /// Always true without denominations.
+    pub fn is_denomination(&self, value: u64) -> bool {
+        self.denominations.is_empty() || self.denominations.binary_search(&value).is_ok()
+    }
+
+    /// Check every value in `values` is a denomination
+    ///
+    /// Fails with `InvalidParameter` naming `field` and the first offending
+    /// index.
+    pub fn check_denominations(&self, field: &'static str, values: &[u64]) -> Result<()> {
+        match values
+            .iter()
+            .position(|&value| !self.is_denomination(value))
+        {
+            Some(index) => Err(LelantusError::InvalidParameter(
+                ErrorContext::new(field)
+                    .at(index)
+                    .expected("a denomination")
+                    .actual(values[index]),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Split `amount` into denominations, largest first
+    ///
+    /// Greedy, which finds a split whenever one exists for canonical sets
+    /// such as powers of ten or a 1-2-5 series. Without denominations the
+    /// amount is returned whole. Fails with `InvalidParameter` if a
+    /// remainder is left over.
+    pub fn split_into_denominations(&self, amount: u64) -> Result<Vec<u64>> {
+        if self.denominations.is_empty() {
+            return Ok(if amount == 0 {
+                Vec::new()
+            } else {
+                vec![amount]
+            });
+        }
+
+        let mut remaining = amount;
+        let mut coins = Vec::new();
+        for &denomination in self.denominations.iter().rev() {
+            while remaining >= denomination {
+                remaining -= denomination;
+                coins.push(denomination);
+            }
+        }
+        if remaining != 0 {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("amount")
+                    .expected("a sum of denominations")
+                    .actual(amount),
+            ));
+        }
+        Ok(coins)
+    }
+
+    /// Most outputs a JoinSplit may create under these parameters
+    ///
+    /// Versions before [`MULTI_OUTPUT_VERSION`] keep the original limit of
+    /// [`LEGACY_MAX_JOINSPLIT_OUTPUTS`] whatever `max_outputs` says.
+    pub fn max_joinsplit_outputs(&self) -> usize {
+        if self.protocol_version < MULTI_OUTPUT_VERSION {
+            LEGACY_MAX_JOINSPLIT_OUTPUTS
+        } else {
+            self.max_outputs
+        }
+    }
+
+    /// Number of bits needed to represent `max_coin_value`
+    pub fn max_coin_value_bits(&self) -> usize {
+        (u64::BITS - self.max_coin_value.leading_zeros()) as usize
+    }
+
+    /// Encode `value` in `value_byte_width` little-endian bytes
+    ///
+    /// Fails with `InvalidParameter` if the value does not fit, so the
+    /// encoding stays injective.
+    pub fn encode_value(&self, value: u64) -> Result<Vec<u8>> {
+        let bytes = value.to_le_bytes();
+        let width = self.value_byte_width.min(U64_SIZE);
+        if bytes[width..].iter().any(|&b| b != 0) {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("value")
+                    .expected(format!("<= {} bytes", width))
+                    .actual(value),
+            ));
+        }
+        Ok(bytes[..width].to_vec())
+    }
+
+    /// Longest membership proof path a verifier will hash
+    ///
+    /// Paths hold one sibling per tree level, and a full accumulator's tree
+    /// has `max_elements + 1` leaves, so no honest path is longer than its
+    /// depth.
+    pub fn max_proof_path_len(&self) -> usize {
+        (usize::BITS - self.max_elements.leading_zeros()) as usize
+    }
+
+    /// Output size in bytes of the configured hash
+    ///
+    /// Reports the SHA-512 size for an unknown `hash_function`; such
+    /// parameters never pass [`validate`](Self::validate).
+    pub fn hash_output_len(&self) -> usize {
+        self.hash_algo().unwrap_or(HashAlgo::Sha512).output_len()
+    }
+
+    /// Get the hash algorithm named by `hash_function`
+    pub fn hash_algo(&self) -> Result<HashAlgo> {
+        HashAlgo::from_name(&self.hash_function)
+    }
+
+    /// Fingerprint identifying this parameter set
+    ///
+    /// Two parameter sets share a fingerprint only if every field matches, so
+    /// it can be embedded in exported data to detect cross-network misuse.
+    pub fn fingerprint(&self) -> Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        hasher.update(b"lelantus_parameters");
+        hasher.update(self.consensus_encode()?);
+        Ok(hasher.finalize().into())
+    }
+
+    /// Encode the parameters for consensus
+    ///
+    /// Integers are fixed-width little-endian and byte fields are
+    /// length-prefixed, in declaration order. Denominations follow as a
+    /// count and values only when there are any, so parameter sets without
+    /// them keep their encoding and fingerprint.
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        buf.push(self.privacy_level.as_u8());
+        binary::put_u64(&mut buf, self.accumulator_modulus_bits as u64);
+        binary::put_u64(&mut buf, self.randomness_bits as u64);
+        binary::put_u64(&mut buf, self.range_proof_bits as u64);
+        binary::put_u64(&mut buf, self.max_coin_value);
+        binary::put_u64(&mut buf, self.min_coin_value);
+        binary::put_bytes(&mut buf, &self.accumulator_base)?;
+        binary::put_bytes(&mut buf, &self.generator)?;
+        binary::put_bytes(&mut buf, self.hash_function.as_bytes())?;
+        binary::put_bytes(&mut buf, self.proof_system.as_bytes())?;
+        binary::put_u64(&mut buf, self.max_elements as u64);
+        binary::put_u64(&mut buf, self.value_byte_width as u64);
+        binary::put_u64(&mut buf, self.protocol_version as u64);
+        binary::put_u64(&mut buf, self.max_outputs as u64);
+        if !self.denominations.is_empty() {
+            binary::put_u64(&mut buf, self.denominations.len() as u64);
+            for &denomination in &self.denominations {
+                binary::put_u64(&mut buf, denomination);
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Decode parameters from their consensus encoding
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let read_usize = |reader: &mut binary::Reader<'_>| {
+            usize::try_from(reader.read_u64()?)
+                .map_err(|_| LelantusError::invalid_parameter("parameters"))
+        };
+        let read_string = |reader: &mut binary::Reader<'_>| {
+            String::from_utf8(reader.read_bytes()?)
+                .map_err(|e| LelantusError::SerializationError(e.to_string()))
+        };
+
+        let mut parameters = Self {
+            privacy_level: PrivacyLevel::from_u8(reader.read_u8()?)?,
+            accumulator_modulus_bits: read_usize(&mut reader)?,
+            randomness_bits: read_usize(&mut reader)?,
+            range_proof_bits: read_usize(&mut reader)?,
+            max_coin_value: reader.read_u64()?,
+            min_coin_value: reader.read_u64()?,
+            accumulator_base: reader.read_bytes()?,
+            generator: reader.read_bytes()?,
+            hash_function: read_string(&mut reader)?,
+            proof_system: read_string(&mut reader)?,
+            max_elements: read_usize(&mut reader)?,
+            value_byte_width: read_usize(&mut reader)?,
+            protocol_version: u32::try_from(reader.read_u64()?)
+                .map_err(|_| LelantusError::invalid_parameter("protocol_version"))?,
+            max_outputs: read_usize(&mut reader)?,
+            denominations: Vec::new(),
+        };
+        if reader.position() < data.len() {
+            // An explicit empty list would give a second encoding
+            let count = reader.read_u64()?;
+            if count == 0 {
+                return Err(LelantusError::invalid_parameter("denominations"));
+            }
+            for _ in 0..count {
+                parameters.denominations.push(reader.read_u64()?);
+            }
+        }
+        reader.finish()?;
+        Ok(parameters)
+    }
+
     /// Get anonymity set size
     pub fn anonymity_set_size(&self) -> usize {
         self.privacy_level.anonymity_set_size()
@@ -152,6 +623,54 @@ mod tests {
         assert_eq!(PrivacyLevel::Maximum.anonymity_set_size(), 1024);
     }
 
+    #[test]
+    fn test_privacy_level_wire_values() -> Result<()> {
+        for level in [
+            PrivacyLevel::Standard,
+            PrivacyLevel::Enhanced,
+            PrivacyLevel::Maximum,
+        ] {
+            assert_eq!(PrivacyLevel::from_u8(level.as_u8())?, level);
+        }
+        assert_eq!(PrivacyLevel::Maximum.as_u8(), 2);
+        assert!(matches!(
+            PrivacyLevel::from_u8(99),
+            Err(LelantusError::InvalidParameter(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_proof_width_validation() {
+        let mut params = LelantusParameters::default();
+        assert_eq!(params.max_coin_value_bits(), 55);
+
+        params.range_proof_bits = 64;
+        assert!(params.validate().is_ok());
+
+        params.range_proof_bits = 512;
+        assert!(params.validate().is_err());
+
+        params.range_proof_bits = 32;
+        assert!(params.validate().is_err());
+
+        params.max_coin_value = u32::MAX as u64;
+        assert!(params.validate().is_ok());
+    }
+
+    #[test]
+    fn test_fingerprint() -> Result<()> {
+        let params = LelantusParameters::default();
+        assert_eq!(
+            params.fingerprint()?,
+            LelantusParameters::default().fingerprint()?
+        );
+
+        let enhanced = LelantusParameters::with_privacy_level(PrivacyLevel::Enhanced);
+        assert_ne!(params.fingerprint()?, enhanced.fingerprint()?);
+        Ok(())
+    }
+
     #[test]
     fn test_parameter_validation() {
         let mut params = LelantusParameters::default();
@@ -160,4 +679,91 @@ mod tests {
         params.accumulator_modulus_bits = 512;
         assert!(params.validate().is_err());
     }
+
+    #[test]
+    fn test_max_outputs_follow_protocol_version() -> Result<()> {
+        let mut params = LelantusParameters::default();
+        assert_eq!(params.max_joinsplit_outputs(), MAX_JOINSPLIT_OUTPUTS);
+
+        params.max_outputs = 4;
+        let decoded = LelantusParameters::consensus_decode(&params.consensus_encode()?)?;
+        assert_eq!(decoded.max_joinsplit_outputs(), 4);
+
+        params.protocol_version = 1;
+        assert!(params.validate().is_ok());
+        assert_eq!(params.max_joinsplit_outputs(), LEGACY_MAX_JOINSPLIT_OUTPUTS);
+
+        params.max_outputs = MAX_JOINSPLIT_OUTPUTS + 1;
+        assert!(params.validate().is_err());
+        params.max_outputs = 4;
+        params.protocol_version = LELANTUS_VERSION + 1;
+        assert!(params.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_denominations() -> Result<()> {
+        let mut params = LelantusParameters::default();
+        let fingerprint = params.fingerprint()?;
+        assert!(params.is_denomination(1_234));
+        assert_eq!(params.split_into_denominations(1_234)?, vec![1_234]);
+
+        params.denominations = vec![1, 2, 5, 10, 20, 50, 100];
+        params.validate()?;
+        assert_ne!(params.fingerprint()?, fingerprint);
+        let decoded = LelantusParameters::consensus_decode(&params.consensus_encode()?)?;
+        assert_eq!(decoded.denominations, params.denominations);
+
+        assert_eq!(
+            params.split_into_denominations(388)?,
+            vec![100, 100, 100, 50, 20, 10, 5, 2, 1]
+        );
+        assert!(params.check_denominations("outputs", &[50, 20]).is_ok());
+        assert!(matches!(
+            params.check_denominations("outputs", &[50, 30]),
+            Err(LelantusError::InvalidParameter(ErrorContext {
+                index: Some(1),
+                ..
+            }))
+        ));
+
+        params.denominations = vec![10, 5];
+        assert!(params.validate().is_err());
+        params.denominations = vec![0, 5];
+        assert!(params.validate().is_err());
+        params.denominations = vec![10, 25];
+        assert!(params.split_into_denominations(30).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_algo_names() -> Result<()> {
+        let mut params = LelantusParameters::default();
+        assert_eq!(params.hash_algo()?, HashAlgo::Pedersen);
+        assert_eq!(HashAlgo::Pedersen.name(), params.hash_function);
+
+        params.hash_function = "sha512".to_string();
+        assert_eq!(params.hash_algo()?, HashAlgo::Sha512);
+        assert_eq!(params.hash_output_len(), 64);
+
+        params.hash_function = "blake3".to_string();
+        assert!(params.validate().is_ok());
+        assert_eq!(params.hash_algo()?, HashAlgo::Blake3);
+        assert_eq!(params.hash_output_len(), 32);
+        assert_eq!(params.hash_algo()?.encoded_len(), 64);
+
+        params.hash_function = "md5".to_string();
+        assert!(params.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nums_base() {
+        let preset = LelantusParameters::with_privacy_level(PrivacyLevel::Enhanced);
+        assert!(preset.verify_nums_base(NUMS_BASE_DOMAIN));
+        assert!(!preset.verify_nums_base("another domain"));
+        assert_eq!(preset.accumulator_base.len(), 512);
+
+        assert!(!LelantusParameters::default().verify_nums_base(NUMS_BASE_DOMAIN));
+    }
 }
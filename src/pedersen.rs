@@ -0,0 +1,1026 @@
+//! Pedersen commitments over P-521
+//!
+//! Selected with `hash_function = "pedersen"`. A coin commitment is
+//! `serial·G₁ + value·G + blinding·H` encoded as a compressed SEC1 point, as
+//! in Lelantus: `G` is the P-521 base point, `H` and `G₁` are derived from the
+//! `generator` parameter by try-and-increment, so nobody knows their discrete
+//! logs relative to `G` or each other, and `serial` is the coin's serial
+//! number, revealed as its nullifier when spent. Unlike the hash-based schemes these
+//! commitments are additively homomorphic, which [`DifferenceProof`] and
+//! [`BoundProof`] rely on. The part `blinding·H + serial·G₁` hiding the value
+//! is called the mask below.
+
+use crate::commitment::serial_digest;
+use crate::errors::{LelantusError, Result};
+use crate::prelude::*;
+use crate::serialization::LelantusSerializable;
+use borsh::{BorshDeserialize, BorshSerialize};
+use p521::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p521::elliptic_curve::{Field, PrimeField};
+use p521::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+/// Size of a compressed SEC1 P-521 point
+pub const POINT_SIZE: usize = 67;
+
+/// Size of a big-endian P-521 scalar
+pub(crate) const SCALAR_SIZE: usize = 66;
+
+/// Domain separator for deriving the blinding generator
+const GENERATOR_DOMAIN: &[u8] = b"lelantus_pedersen_h";
+
+/// Domain separator for deriving the serial generator
+const SERIAL_GENERATOR_DOMAIN: &[u8] = b"lelantus_pedersen_serial";
+
+/// Domain separator for deriving value generators other than `G`
+const VALUE_GENERATOR_DOMAIN: &[u8] = b"lelantus_pedersen_value";
+
+/// Domain separator for deriving the vector generators of inner-product
+/// arguments
+const VECTOR_GENERATOR_DOMAIN: &[u8] = b"lelantus_pedersen_vector";
+
+/// Domain separator for deriving the inner-product generator
+const INNER_PRODUCT_GENERATOR_DOMAIN: &[u8] = b"lelantus_pedersen_inner_product";
+
+/// Domain separator for turning commitment randomness into a blinding factor
+const BLINDING_DOMAIN: &[u8] = b"lelantus_pedersen_blinding";
+
+/// Domain separator for turning commitment randomness into an asset tag
+/// blinding factor
+const ASSET_TAG_DOMAIN: &[u8] = b"lelantus_pedersen_asset_tag";
+
+/// Domain separator for difference proof challenges
+const DIFFERENCE_DOMAIN: &[u8] = b"lelantus_pedersen_difference";
+
+/// Domain separator for opening proof challenges
+const OPENING_DOMAIN: &[u8] = b"lelantus_pedersen_opening";
+
+/// Domain separator for serial proof challenges
+const SERIAL_PROOF_DOMAIN: &[u8] = b"lelantus_pedersen_serial_proof";
+
+/// Domain separator for bit proof challenges
+const BIT_DOMAIN: &[u8] = b"lelantus_pedersen_bit";
+
+/// Domain separator for the challenge of a bound proof's mask
+const MASK_DOMAIN: &[u8] = b"lelantus_pedersen_mask";
+
+/// Most bits a range proof may span
+///
+/// Keeps every proven value, and any sum of a JoinSplit's outputs, far below
+/// the group order, so a value cannot wrap around to a negative one.
+pub const MAX_RANGE_BITS: usize = 256;
+
+/// Number of bits needed to cover the range `[min_value, max_value]`
+pub(crate) fn bound_bits(min_value: u64, max_value: u64) -> usize {
+    let span = max_value.saturating_sub(min_value);
+    ((u64::BITS - span.leading_zeros()) as usize).max(1)
+}
+
+/// Shift added to `value - min_value` so that it fits in [`bound_bits`]
+/// exactly when `value <= max_value`
+fn upper_offset(min_value: u64, max_value: u64) -> u64 {
+    let bits = bound_bits(min_value, max_value);
+    let top = if bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << bits) - 1
+    };
+    top - (max_value - min_value)
+}
+
+/// Statement of one half of a bound proof, absorbed into its challenges
+fn bound_statement(label: &[u8], commitment: &[u8], min_value: u64, max_value: u64) -> Vec<u8> {
+    let mut statement = label.to_vec();
+    statement.extend_from_slice(commitment);
+    statement.extend_from_slice(&min_value.to_le_bytes());
+    statement.extend_from_slice(&max_value.to_le_bytes());
+    statement
+}
+
+/// Statement of a width proof, absorbed into its challenges
+fn width_statement(
+    label: &[u8],
+    commitment: &[u8],
+    value_generator: &ProjectivePoint,
+    bits: usize,
+) -> Vec<u8> {
+    let mut statement = label.to_vec();
+    statement.extend_from_slice(commitment);
+    statement.extend_from_slice(&encode(value_generator));
+    statement.extend_from_slice(&(bits as u64).to_le_bytes());
+    statement
+}
+
+/// Weights `2^i` of the first `bits` bits
+fn bit_weights(bits: usize) -> impl Iterator<Item = Scalar> {
+    core::iter::successors(Some(Scalar::ONE), |weight| Some(weight.double())).take(bits)
+}
+
+/// Interpret a SHA-512 digest as a scalar (any 512-bit value is below the order)
+pub(crate) fn scalar_from_digest(digest: &[u8]) -> Scalar {
+    let mut repr = FieldBytes::default();
+    let offset = repr.len() - digest.len();
+    repr[offset..].copy_from_slice(digest);
+    Scalar::from_repr(repr).unwrap_or(Scalar::ZERO)
+}
+
+/// Compressed encoding of a point
+pub(crate) fn encode(point: &ProjectivePoint) -> Vec<u8> {
+    point.to_affine().to_encoded_point(true).as_bytes().to_vec()
+}
+
+/// Decode a compressed point
+pub(crate) fn decode(bytes: &[u8]) -> Option<ProjectivePoint> {
+    let encoded = EncodedPoint::from_bytes(bytes).ok()?;
+    Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded))
+        .map(ProjectivePoint::from)
+}
+
+/// Decode a hex-encoded point, as stored in a commitment value
+pub(crate) fn decode_hex(value: &[u8]) -> Result<ProjectivePoint> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| decode(&bytes))
+        .ok_or(LelantusError::InvalidCommitment)
+}
+
+/// Decode a canonical big-endian scalar
+pub(crate) fn decode_scalar(bytes: &[u8]) -> Option<Scalar> {
+    FieldBytes::from_exact_iter(bytes.iter().copied())
+        .and_then(|repr| Option::<Scalar>::from(Scalar::from_repr(repr)))
+}
+
+/// Encode the blinding and serial responses of a mask proof, in that order
+pub(crate) fn encode_mask_response(blinding: Scalar, serial: Scalar) -> Vec<u8> {
+    let mut response = blinding.to_repr().to_vec();
+    response.extend_from_slice(&serial.to_repr());
+    response
+}
+
+/// Decode a response written by [`encode_mask_response`]
+pub(crate) fn decode_mask_response(bytes: &[u8]) -> Option<(Scalar, Scalar)> {
+    if bytes.len() != 2 * SCALAR_SIZE {
+        return None;
+    }
+    let (blinding, serial) = bytes.split_at(SCALAR_SIZE);
+    Some((decode_scalar(blinding)?, decode_scalar(serial)?))
+}
+
+/// `Σ inputs − Σ outputs − fee·G` over hex-encoded commitments
+///
+/// This is a mask, with no `G` component, exactly when the committed values
+/// balance.
+pub(crate) fn balance_excess<'a>(
+    inputs: impl IntoIterator<Item = &'a [u8]>,
+    outputs: impl IntoIterator<Item = &'a [u8]>,
+    fee: u64,
+) -> Result<ProjectivePoint> {
+    let mut excess = -(ProjectivePoint::GENERATOR * Scalar::from(fee));
+    for input in inputs {
+        excess += decode_hex(input)?;
+    }
+    for output in outputs {
+        excess -= decode_hex(output)?;
+    }
+    Ok(excess)
+}
+
+/// Generators of a Pedersen commitment scheme
+#[derive(Debug, Clone)]
+pub(crate) struct Generators {
+    blinding: ProjectivePoint,
+    serial: ProjectivePoint,
+}
+
+/// Hash `seed` to a point by try-and-increment, so nobody knows its discrete
+/// log relative to `G`
+fn hash_to_point(domain: &[u8], seed: &[u8]) -> ProjectivePoint {
+    let mut counter = 0u64;
+    loop {
+        let mut hasher = Sha512::new();
+        hasher.update(domain);
+        hasher.update(counter.to_le_bytes());
+        hasher.update(seed);
+        let digest = hasher.finalize();
+
+        // 0x02 tag, then a 66-byte x-coordinate below the field modulus
+        let mut encoded = vec![0x02, 0, 0];
+        encoded.extend_from_slice(&digest);
+        if let Some(point) = decode(&encoded) {
+            return point;
+        }
+        counter += 1;
+    }
+}
+
+impl Generators {
+    /// Derive the blinding generator `H` and serial generator `G₁` from `seed`
+    pub(crate) fn new(seed: &[u8]) -> Self {
+        Self {
+            blinding: hash_to_point(GENERATOR_DOMAIN, seed),
+            serial: hash_to_point(SERIAL_GENERATOR_DOMAIN, seed),
+        }
+    }
+
+    /// Value generator derived from `seed`, such as an asset's generator
+    ///
+    /// Independent of `G` and `H`, so value committed under it cannot be
+    /// passed off as value under another generator.
+    pub(crate) fn value_generator(seed: &[u8]) -> ProjectivePoint {
+        hash_to_point(VALUE_GENERATOR_DOMAIN, seed)
+    }
+
+    /// Blinding generator `H`
+    pub(crate) fn blinding_generator(&self) -> ProjectivePoint {
+        self.blinding
+    }
+
+    /// Generator vectors `(G_i, H_i)` of an inner-product argument over
+    /// `count` entries
+    ///
+    /// Derived from `H` by try-and-increment, so no discrete log relation
+    /// among them or to the commitment generators is known.
+    pub(crate) fn vector_generators(
+        &self,
+        count: usize,
+    ) -> (Vec<ProjectivePoint>, Vec<ProjectivePoint>) {
+        let seed = encode(&self.blinding);
+        let derive = |side: &[u8], index: usize| {
+            let mut input = seed.clone();
+            input.extend_from_slice(side);
+            input.extend_from_slice(&(index as u64).to_le_bytes());
+            hash_to_point(VECTOR_GENERATOR_DOMAIN, &input)
+        };
+        (
+            (0..count).map(|i| derive(b"g", i)).collect(),
+            (0..count).map(|i| derive(b"h", i)).collect(),
+        )
+    }
+
+    /// Generator `U` carrying the inner product of an inner-product argument
+    pub(crate) fn inner_product_generator(&self) -> ProjectivePoint {
+        hash_to_point(INNER_PRODUCT_GENERATOR_DOMAIN, &encode(&self.blinding))
+    }
+
+    /// Mask `blinding·H + serial·G₁`
+    pub(crate) fn mask(&self, blinding: Scalar, serial: Scalar) -> ProjectivePoint {
+        self.blinding * blinding + self.serial * serial
+    }
+
+    /// Serial number committed by `randomness`, as a scalar
+    ///
+    /// Any 512-bit serial number digest is below the group order.
+    pub(crate) fn serial_factor(randomness: &[u8]) -> Scalar {
+        scalar_from_digest(&serial_digest(randomness))
+    }
+
+    /// Blinding `τ` of the asset tag of the coin committed by `randomness`
+    ///
+    /// Independent of the coin's blinding factor, so a published tag
+    /// `A + τ·H` reveals nothing about the coin's opening.
+    pub(crate) fn asset_blinding_factor(randomness: &[u8]) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(ASSET_TAG_DOMAIN);
+        hasher.update(randomness);
+        scalar_from_digest(&hasher.finalize())
+    }
+
+    /// Asset tag `value_generator + τ·H` of the coin committed by
+    /// `randomness`
+    ///
+    /// The coin `v·A + b·H + s·G₁` is then `v·T + (b − v·τ)·H + s·G₁`: a
+    /// commitment to the same value under its own blinded generator `T`.
+    pub(crate) fn asset_tag(
+        &self,
+        value_generator: &ProjectivePoint,
+        randomness: &[u8],
+    ) -> ProjectivePoint {
+        *value_generator + self.blinding * Self::asset_blinding_factor(randomness)
+    }
+
+    /// `input + serial·G₁` for a revealed serial number
+    ///
+    /// A coin minus this offset opens to zero only if the coin commits to
+    /// `serial` and to the value and asset of `input`.
+    pub(crate) fn spend_offset(&self, input: &[u8], serial: &[u8]) -> Result<Vec<u8>> {
+        let point = decode_hex(input)? + self.serial * scalar_from_digest(serial);
+        Ok(hex::encode(encode(&point)).into_bytes())
+    }
+
+    /// Schnorr proof of knowledge of `(blinding, serial)` behind a mask
+    ///
+    /// `challenge` derives the Fiat-Shamir challenge from the compressed
+    /// nonce point. Returns the nonce point and the encoded responses.
+    fn prove_mask(
+        &self,
+        blinding: Scalar,
+        serial: Scalar,
+        challenge: impl FnOnce(&[u8]) -> Scalar,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> (Vec<u8>, Vec<u8>) {
+        let nonces = [Scalar::random(&mut *rng), Scalar::random(&mut *rng)];
+        let nonce_point = encode(&self.mask(nonces[0], nonces[1]));
+        let challenge = challenge(&nonce_point);
+        let response = encode_mask_response(
+            nonces[0] + challenge * blinding,
+            nonces[1] + challenge * serial,
+        );
+        (nonce_point, response)
+    }
+
+    /// Check a proof from [`prove_mask`](Self::prove_mask) that `target` is
+    /// a mask
+    fn verify_mask(
+        &self,
+        target: ProjectivePoint,
+        nonce_point: &[u8],
+        response: &[u8],
+        challenge: Scalar,
+    ) -> Result<bool> {
+        let nonce_point = decode(nonce_point).ok_or(LelantusError::InvalidProof)?;
+        let (blinding, serial) =
+            decode_mask_response(response).ok_or(LelantusError::InvalidProof)?;
+        Ok(self.mask(blinding, serial) == nonce_point + target * challenge)
+    }
+
+    /// Blinding factor committed by `randomness`
+    pub(crate) fn blinding_factor(randomness: &[u8], payload_hash: Option<&[u8]>) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(BLINDING_DOMAIN);
+        hasher.update(randomness);
+        if let Some(payload_hash) = payload_hash {
+            hasher.update(payload_hash);
+        }
+        scalar_from_digest(&hasher.finalize())
+    }
+
+    /// Hex-encoded coin commitment `serial·G₁ + value·value_generator + blinding·H`
+    pub(crate) fn commit_to(
+        &self,
+        value_generator: &ProjectivePoint,
+        value: u64,
+        randomness: &[u8],
+        payload_hash: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let mask = self.mask(
+            Self::blinding_factor(randomness, payload_hash),
+            Self::serial_factor(randomness),
+        );
+        let point = *value_generator * Scalar::from(value) + mask;
+        hex::encode(encode(&point)).into_bytes()
+    }
+
+    /// Hex-encoded commitment `value·value_generator + blinding·H` without a
+    /// serial, as published in place of a spent coin
+    pub(crate) fn commit_input_to(
+        &self,
+        value_generator: &ProjectivePoint,
+        value: u64,
+        randomness: &[u8],
+    ) -> Vec<u8> {
+        let point = *value_generator * Scalar::from(value)
+            + self.blinding * Self::blinding_factor(randomness, None);
+        hex::encode(encode(&point)).into_bytes()
+    }
+
+    /// Hex-encoded commitment to `value` under the sum of the masks of the
+    /// coins opened by `randomness`, which opens a homomorphic sum of them
+    pub(crate) fn commit_sum(&self, value: u64, randomness: &[&[u8]]) -> Vec<u8> {
+        let mask = randomness
+            .iter()
+            .map(|r| self.mask(Self::blinding_factor(r, None), Self::serial_factor(r)))
+            .fold(ProjectivePoint::IDENTITY, |sum, mask| sum + mask);
+        let point = ProjectivePoint::GENERATOR * Scalar::from(value) + mask;
+        hex::encode(encode(&point)).into_bytes()
+    }
+
+    /// `c1 - c2 - diff·G`, which is a mask when the difference holds
+    fn excess(&self, c1: &[u8], c2: &[u8], diff: u64) -> Result<ProjectivePoint> {
+        Ok(decode_hex(c1)? - decode_hex(c2)? - ProjectivePoint::GENERATOR * Scalar::from(diff))
+    }
+
+    /// Fiat-Shamir challenge of a difference proof
+    fn challenge(nonce_point: &[u8], c1: &[u8], c2: &[u8], diff: u64) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(DIFFERENCE_DOMAIN);
+        hasher.update(nonce_point);
+        hasher.update(c1);
+        hasher.update(c2);
+        hasher.update(diff.to_le_bytes());
+        scalar_from_digest(&hasher.finalize())
+    }
+
+    /// Prove that `c1 - c2` opens to `diff·G`
+    pub(crate) fn prove_difference(
+        &self,
+        c1: &[u8],
+        c2: &[u8],
+        diff: u64,
+        r1: &[u8],
+        r2: &[u8],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<DifferenceProof> {
+        let blinding = Self::blinding_factor(r1, None) - Self::blinding_factor(r2, None);
+        let serial = Self::serial_factor(r1) - Self::serial_factor(r2);
+        if self.excess(c1, c2, diff)? != self.mask(blinding, serial) {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        // Schnorr proof of knowledge of the mask difference
+        let (nonce_point, response) = self.prove_mask(
+            blinding,
+            serial,
+            |nonce_point| Self::challenge(nonce_point, c1, c2, diff),
+            rng,
+        );
+        Ok(DifferenceProof {
+            nonce_point,
+            response,
+        })
+    }
+
+    /// Fiat-Shamir challenge of an opening proof
+    fn opening_challenge(nonce_point: &[u8], commitment: &[u8], value: u64) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(OPENING_DOMAIN);
+        hasher.update(nonce_point);
+        hasher.update(commitment);
+        hasher.update(value.to_le_bytes());
+        scalar_from_digest(&hasher.finalize())
+    }
+
+    /// Prove that `commitment` opens to `value` without revealing `randomness`
+    pub(crate) fn prove_opening(
+        &self,
+        commitment: &[u8],
+        value: u64,
+        randomness: &[u8],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<OpeningProof> {
+        let blinding = Self::blinding_factor(randomness, None);
+        let serial = Self::serial_factor(randomness);
+        let excess = decode_hex(commitment)? - ProjectivePoint::GENERATOR * Scalar::from(value);
+        if excess != self.mask(blinding, serial) {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        // Schnorr proof of knowledge of the mask
+        let (nonce_point, response) = self.prove_mask(
+            blinding,
+            serial,
+            |nonce_point| Self::opening_challenge(nonce_point, commitment, value),
+            rng,
+        );
+        Ok(OpeningProof {
+            nonce_point,
+            response,
+        })
+    }
+
+    /// Verify a proof that `commitment` opens to `value`
+    pub(crate) fn verify_opening(
+        &self,
+        commitment: &[u8],
+        value: u64,
+        proof: &OpeningProof,
+    ) -> Result<bool> {
+        let excess = decode_hex(commitment)? - ProjectivePoint::GENERATOR * Scalar::from(value);
+        let challenge = Self::opening_challenge(&proof.nonce_point, commitment, value);
+        self.verify_mask(excess, &proof.nonce_point, &proof.response, challenge)
+    }
+
+    /// Fiat-Shamir challenge of a serial proof
+    fn serial_challenge(nonce_point: &[u8], commitment: &[u8], serial: &[u8]) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(SERIAL_PROOF_DOMAIN);
+        hasher.update(nonce_point);
+        hasher.update(commitment);
+        hasher.update(serial);
+        scalar_from_digest(&hasher.finalize())
+    }
+
+    /// Prove that `commitment` commits to the serial number of `randomness`
+    ///
+    /// Shows knowledge of `value` and the blinding behind
+    /// `commitment − serial·G₁`, revealing neither.
+    pub(crate) fn prove_serial(
+        &self,
+        commitment: &[u8],
+        value: u64,
+        randomness: &[u8],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<SerialProof> {
+        let serial = serial_digest(randomness);
+        let value = Scalar::from(value);
+        let blinding = Self::blinding_factor(randomness, None);
+        let rest = decode_hex(commitment)? - self.serial * scalar_from_digest(&serial);
+        if rest != ProjectivePoint::GENERATOR * value + self.blinding * blinding {
+            return Err(LelantusError::InvalidCommitment);
+        }
+
+        let nonces = [Scalar::random(&mut *rng), Scalar::random(&mut *rng)];
+        let nonce_point =
+            encode(&(ProjectivePoint::GENERATOR * nonces[0] + self.blinding * nonces[1]));
+        let challenge = Self::serial_challenge(&nonce_point, commitment, &serial);
+        Ok(SerialProof {
+            nonce_point,
+            response: encode_mask_response(
+                nonces[0] + challenge * value,
+                nonces[1] + challenge * blinding,
+            ),
+        })
+    }
+
+    /// Verify a proof that `commitment` commits to the serial number `serial`
+    pub(crate) fn verify_serial(
+        &self,
+        commitment: &[u8],
+        serial: &[u8],
+        proof: &SerialProof,
+    ) -> Result<bool> {
+        let rest = decode_hex(commitment)? - self.serial * scalar_from_digest(serial);
+        let nonce_point = decode(&proof.nonce_point).ok_or(LelantusError::InvalidProof)?;
+        let (value, blinding) =
+            decode_mask_response(&proof.response).ok_or(LelantusError::InvalidProof)?;
+        let challenge = Self::serial_challenge(&proof.nonce_point, commitment, serial);
+        Ok(
+            ProjectivePoint::GENERATOR * value + self.blinding * blinding
+                == nonce_point + rest * challenge,
+        )
+    }
+
+    /// Fiat-Shamir challenge of the bit proof at `index`
+    fn bit_challenge(
+        statement: &[u8],
+        index: usize,
+        commitment: &[u8],
+        nonce_points: &[ProjectivePoint; 2],
+    ) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(BIT_DOMAIN);
+        hasher.update(statement);
+        hasher.update((index as u64).to_le_bytes());
+        hasher.update(commitment);
+        for nonce_point in nonce_points {
+            hasher.update(encode(nonce_point));
+        }
+        scalar_from_digest(&hasher.finalize())
+    }
+
+    /// Prove that `value·V + blinding·H` commits to a value below `2^bits`,
+    /// for the value generator `V`
+    ///
+    /// Commits to each bit of `value` under blindings weighted to sum to
+    /// `blinding`, and shows each bit commitment opens to 0 or 1 with a
+    /// Cramer-Damgård-Schoenmakers OR proof. Bits of `value` above `bits`
+    /// are dropped, so the proof only verifies if `value < 2^bits`; bits
+    /// past the 64th are zero.
+    fn prove_bits(
+        &self,
+        statement: &[u8],
+        value_generator: ProjectivePoint,
+        value: u64,
+        blinding: Scalar,
+        bits: usize,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Vec<BitProof> {
+        let mut blindings: Vec<Scalar> = (0..bits).map(|_| Scalar::random(&mut *rng)).collect();
+        let weighted = blindings
+            .iter()
+            .zip(bit_weights(bits))
+            .skip(1)
+            .fold(Scalar::ZERO, |sum, (r, weight)| sum + *r * weight);
+        blindings[0] = blinding - weighted;
+
+        blindings
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let bit = (value.checked_shr(i as u32).unwrap_or(0) & 1) as usize;
+                let point = value_generator * Scalar::from(bit as u64) + self.blinding * r;
+                let candidates = [point, point - value_generator];
+
+                // Simulate the branch for the other bit, then answer the real one
+                let other = 1 - bit;
+                let mut challenges = [Scalar::ZERO; 2];
+                let mut responses = [Scalar::ZERO; 2];
+                challenges[other] = Scalar::random(&mut *rng);
+                responses[other] = Scalar::random(&mut *rng);
+                let nonce = Scalar::random(&mut *rng);
+                let mut nonce_points = [ProjectivePoint::IDENTITY; 2];
+                nonce_points[bit] = self.blinding * nonce;
+                nonce_points[other] =
+                    self.blinding * responses[other] - candidates[other] * challenges[other];
+
+                let commitment = encode(&point);
+                let challenge = Self::bit_challenge(statement, i, &commitment, &nonce_points);
+                challenges[bit] = challenge - challenges[other];
+                responses[bit] = nonce + challenges[bit] * r;
+
+                BitProof {
+                    commitment,
+                    challenges: challenges.iter().map(|c| c.to_repr().to_vec()).collect(),
+                    responses: responses.iter().map(|z| z.to_repr().to_vec()).collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Verify that `proofs` show `target` commits to a value below `2^bits`
+    /// under `value_generator`
+    fn verify_bits(
+        &self,
+        statement: &[u8],
+        value_generator: ProjectivePoint,
+        target: ProjectivePoint,
+        bits: usize,
+        proofs: &[BitProof],
+    ) -> Result<bool> {
+        if proofs.len() != bits {
+            return Ok(false);
+        }
+
+        let mut sum = ProjectivePoint::IDENTITY;
+        for ((i, proof), weight) in proofs.iter().enumerate().zip(bit_weights(bits)) {
+            let point = decode(&proof.commitment).ok_or(LelantusError::InvalidProof)?;
+            let scalars = |encoded: &[Vec<u8>]| -> Result<[Scalar; 2]> {
+                match encoded {
+                    [a, b] => Ok([
+                        decode_scalar(a).ok_or(LelantusError::InvalidProof)?,
+                        decode_scalar(b).ok_or(LelantusError::InvalidProof)?,
+                    ]),
+                    _ => Err(LelantusError::InvalidProof),
+                }
+            };
+            let challenges = scalars(&proof.challenges)?;
+            let responses = scalars(&proof.responses)?;
+
+            let candidates = [point, point - value_generator];
+            let nonce_points =
+                [0, 1].map(|j| self.blinding * responses[j] - candidates[j] * challenges[j]);
+            let challenge = Self::bit_challenge(statement, i, &proof.commitment, &nonce_points);
+            if challenges[0] + challenges[1] != challenge {
+                return Ok(false);
+            }
+            sum += point * weight;
+        }
+        Ok(sum == target)
+    }
+
+    /// Fiat-Shamir challenge of a bound proof's mask proof
+    fn mask_challenge(statement: &[u8], mask: &[u8], nonce_point: &[u8]) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(MASK_DOMAIN);
+        hasher.update(statement);
+        hasher.update(mask);
+        hasher.update(nonce_point);
+        scalar_from_digest(&hasher.finalize())
+    }
+
+    /// Build a bound proof for `commitment` without checking the opening
+    ///
+    /// Only [`prove_bound`](Self::prove_bound) should call this outside tests;
+    /// an out-of-range `value` yields a proof that does not verify.
+    pub(crate) fn build_bound(
+        &self,
+        commitment: &[u8],
+        value: u64,
+        randomness: &[u8],
+        min_value: u64,
+        max_value: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> BoundProof {
+        let bits = bound_bits(min_value, max_value);
+        let shifted = value.wrapping_sub(min_value);
+        let lower = bound_statement(b"lower", commitment, min_value, max_value);
+        let upper = bound_statement(b"upper", commitment, min_value, max_value);
+        let statement = bound_statement(b"mask", commitment, min_value, max_value);
+        let (bit_blinding, mut proof) = self.split_mask(&statement, randomness, rng);
+
+        let g = ProjectivePoint::GENERATOR;
+        proof.lower = self.prove_bits(&lower, g, shifted, bit_blinding, bits, rng);
+        proof.upper = self.prove_bits(
+            &upper,
+            g,
+            shifted.wrapping_add(upper_offset(min_value, max_value)),
+            bit_blinding,
+            bits,
+            rng,
+        );
+        proof
+    }
+
+    /// Split the mask off a bound proof
+    ///
+    /// The bits take all of the blinding but a fresh share, which stays in
+    /// the mask with the serial so the mask reveals neither. Returns the
+    /// blinding left for the bits and a proof holding only the mask.
+    fn split_mask(
+        &self,
+        statement: &[u8],
+        randomness: &[u8],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> (Scalar, BoundProof) {
+        let share = Scalar::random(&mut *rng);
+        let serial = Self::serial_factor(randomness);
+        let bit_blinding = Self::blinding_factor(randomness, None) - share;
+        let mask = encode(&self.mask(share, serial));
+        let (mask_nonce_point, mask_response) = self.prove_mask(
+            share,
+            serial,
+            |nonce_point| Self::mask_challenge(statement, &mask, nonce_point),
+            rng,
+        );
+
+        let proof = BoundProof {
+            lower: Vec::new(),
+            upper: Vec::new(),
+            mask,
+            mask_nonce_point,
+            mask_response,
+        };
+        (bit_blinding, proof)
+    }
+
+    /// Check the mask proof of a bound proof, returning the mask if it holds
+    fn checked_mask(
+        &self,
+        statement: &[u8],
+        proof: &BoundProof,
+    ) -> Result<Option<ProjectivePoint>> {
+        let mask = decode(&proof.mask).ok_or(LelantusError::InvalidProof)?;
+        let challenge = Self::mask_challenge(statement, &proof.mask, &proof.mask_nonce_point);
+        let valid = self.verify_mask(
+            mask,
+            &proof.mask_nonce_point,
+            &proof.mask_response,
+            challenge,
+        )?;
+        Ok(valid.then_some(mask))
+    }
+
+    /// Check that `commitment` opens to `value` of `value_generator` under
+    /// `randomness`
+    pub(crate) fn check_opening(
+        &self,
+        commitment: &[u8],
+        value_generator: ProjectivePoint,
+        value: u64,
+        randomness: &[u8],
+    ) -> Result<()> {
+        let mask = self.mask(
+            Self::blinding_factor(randomness, None),
+            Self::serial_factor(randomness),
+        );
+        if decode_hex(commitment)? != value_generator * Scalar::from(value) + mask {
+            return Err(LelantusError::InvalidCommitment);
+        }
+        Ok(())
+    }
+
+    /// Prove that `commitment` opens to a value within `[min_value, max_value]`
+    ///
+    /// `randomness` is the commitment randomness; the value is not revealed.
+    pub(crate) fn prove_bound(
+        &self,
+        commitment: &[u8],
+        value: u64,
+        randomness: &[u8],
+        min_value: u64,
+        max_value: u64,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<BoundProof> {
+        self.check_opening(commitment, ProjectivePoint::GENERATOR, value, randomness)?;
+        if min_value > max_value || !(min_value..=max_value).contains(&value) {
+            return Err(LelantusError::RangeProofError(format!(
+                "value outside range [{}, {}]",
+                min_value, max_value
+            )));
+        }
+
+        Ok(self.build_bound(commitment, value, randomness, min_value, max_value, rng))
+    }
+
+    /// Verify a proof that `commitment` opens to a value within
+    /// `[min_value, max_value]`
+    ///
+    /// `C - min·G - M` must commit to a value below `2^bits`, and so must
+    /// `C - min·G - M + offset·G`, where the offset makes it overflow `bits`
+    /// exactly when the value exceeds `max_value` and `M` is the proof's
+    /// mask, shown to have no `G` component.
+    pub(crate) fn verify_bound(
+        &self,
+        commitment: &[u8],
+        min_value: u64,
+        max_value: u64,
+        proof: &BoundProof,
+    ) -> Result<bool> {
+        if min_value > max_value {
+            return Ok(false);
+        }
+
+        let statement = bound_statement(b"mask", commitment, min_value, max_value);
+        let Some(mask) = self.checked_mask(&statement, proof)? else {
+            return Ok(false);
+        };
+
+        let bits = bound_bits(min_value, max_value);
+        let shifted =
+            decode_hex(commitment)? - ProjectivePoint::GENERATOR * Scalar::from(min_value) - mask;
+        let offset = ProjectivePoint::GENERATOR * Scalar::from(upper_offset(min_value, max_value));
+        let lower = bound_statement(b"lower", commitment, min_value, max_value);
+        let upper = bound_statement(b"upper", commitment, min_value, max_value);
+        let g = ProjectivePoint::GENERATOR;
+        Ok(self.verify_bits(&lower, g, shifted, bits, &proof.lower)?
+            && self.verify_bits(&upper, g, shifted + offset, bits, &proof.upper)?)
+    }
+
+    /// Prove that `commitment` opens to a value of `value_generator` below
+    /// `2^bits`
+    ///
+    /// A bound proof whose upper half is left empty: with a power-of-two
+    /// range, the bits of the value alone show it is in range, and `bits`
+    /// may exceed 64. `randomness` is the commitment randomness.
+    pub(crate) fn prove_width(
+        &self,
+        commitment: &[u8],
+        value_generator: ProjectivePoint,
+        value: u64,
+        randomness: &[u8],
+        bits: usize,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<BoundProof> {
+        self.check_opening(commitment, value_generator, value, randomness)?;
+        if bits == 0 || bits > MAX_RANGE_BITS || value.checked_shr(bits as u32).unwrap_or(0) != 0 {
+            return Err(LelantusError::RangeProofError(format!(
+                "value does not fit in {} bits",
+                bits
+            )));
+        }
+
+        let statement = width_statement(b"mask", commitment, &value_generator, bits);
+        let (bit_blinding, mut proof) = self.split_mask(&statement, randomness, rng);
+        let lower = width_statement(b"width", commitment, &value_generator, bits);
+        proof.lower = self.prove_bits(&lower, value_generator, value, bit_blinding, bits, rng);
+        Ok(proof)
+    }
+
+    /// Verify a proof that `commitment` opens to a value of
+    /// `value_generator` below `2^bits`
+    pub(crate) fn verify_width(
+        &self,
+        commitment: &[u8],
+        value_generator: ProjectivePoint,
+        bits: usize,
+        proof: &BoundProof,
+    ) -> Result<bool> {
+        if bits == 0 || bits > MAX_RANGE_BITS || !proof.upper.is_empty() {
+            return Ok(false);
+        }
+
+        let statement = width_statement(b"mask", commitment, &value_generator, bits);
+        let Some(mask) = self.checked_mask(&statement, proof)? else {
+            return Ok(false);
+        };
+        let lower = width_statement(b"width", commitment, &value_generator, bits);
+        let target = decode_hex(commitment)? - mask;
+        self.verify_bits(&lower, value_generator, target, bits, &proof.lower)
+    }
+
+    /// Verify a proof that `c1 - c2` opens to `diff·G`
+    pub(crate) fn verify_difference(
+        &self,
+        c1: &[u8],
+        c2: &[u8],
+        diff: u64,
+        proof: &DifferenceProof,
+    ) -> Result<bool> {
+        let excess = self.excess(c1, c2, diff)?;
+        let challenge = Self::challenge(&proof.nonce_point, c1, c2, diff);
+        self.verify_mask(excess, &proof.nonce_point, &proof.response, challenge)
+    }
+}
+
+/// Hex-encoded sum of two hex-encoded commitments
+pub(crate) fn add(c1: &[u8], c2: &[u8]) -> Result<Vec<u8>> {
+    Ok(hex::encode(encode(&(decode_hex(c1)? + decode_hex(c2)?))).into_bytes())
+}
+
+/// Hex-encoded difference of two hex-encoded commitments
+pub(crate) fn sub(c1: &[u8], c2: &[u8]) -> Result<Vec<u8>> {
+    Ok(hex::encode(encode(&(decode_hex(c1)? - decode_hex(c2)?))).into_bytes())
+}
+
+/// Compressed SEC1 point of a hex-encoded commitment
+pub(crate) fn to_point_bytes(value: &[u8]) -> Result<Vec<u8>> {
+    Ok(encode(&decode_hex(value)?))
+}
+
+/// Hex-encoded commitment from a compressed SEC1 point
+///
+/// Rejects encodings that are not a curve point.
+pub(crate) fn from_point_bytes(bytes: &[u8]) -> Result<Vec<u8>> {
+    let point = decode(bytes).ok_or(LelantusError::InvalidCommitment)?;
+    Ok(hex::encode(encode(&point)).into_bytes())
+}
+
+/// Proof that two commitments differ by a public amount
+///
+/// Reveals neither committed value, only that `c1 - c2` commits to `diff`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct DifferenceProof {
+    /// Compressed nonce mask `k·H + k₁·G₁`
+    pub nonce_point: Vec<u8>,
+
+    /// Response scalars `k + e·(b1 - b2)` and `k₁ + e·(s1 - s2)`, concatenated
+    pub response: Vec<u8>,
+}
+
+impl LelantusSerializable for DifferenceProof {}
+
+/// Proof that a commitment opens to a public value
+///
+/// Reveals the value but not the commitment randomness.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct OpeningProof {
+    /// Compressed nonce mask `k·H + k₁·G₁`
+    pub nonce_point: Vec<u8>,
+
+    /// Response scalars `k + e·b` and `k₁ + e·s`, concatenated
+    pub response: Vec<u8>,
+}
+
+impl LelantusSerializable for OpeningProof {}
+
+/// Proof that a commitment commits to a public serial number
+///
+/// Reveals neither the value nor the blinding of the commitment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct SerialProof {
+    /// Compressed nonce commitment `k·G + k'·H`
+    pub nonce_point: Vec<u8>,
+
+    /// Response scalars `k + e·value` and `k' + e·b`, concatenated
+    pub response: Vec<u8>,
+}
+
+impl LelantusSerializable for SerialProof {}
+
+/// Proof that a committed bit is 0 or 1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct BitProof {
+    /// Compressed bit commitment `bit·G + r·H`
+    pub commitment: Vec<u8>,
+
+    /// Challenge of each branch; they sum to the Fiat-Shamir challenge
+    pub challenges: Vec<Vec<u8>>,
+
+    /// Response scalar of each branch
+    pub responses: Vec<Vec<u8>>,
+}
+
+/// Proof that a commitment opens to a value within public bounds
+///
+/// Reveals neither the value nor the commitment randomness. Grows linearly
+/// with the number of bits spanned by the bounds. A width proof, showing
+/// only that the value is below a power of two, leaves `upper` empty.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct BoundProof {
+    /// Bits of `value - min_value`
+    pub lower: Vec<BitProof>,
+
+    /// Bits of `value - min_value` shifted so it overflows past `max_value`
+    pub upper: Vec<BitProof>,
+
+    /// Compressed mask left once the bits are taken from the commitment:
+    /// the serial and a fresh share of the blinding
+    pub mask: Vec<u8>,
+
+    /// Compressed nonce mask of the proof that `mask` is a mask
+    pub mask_nonce_point: Vec<u8>,
+
+    /// Responses of that proof, concatenated
+    pub mask_response: Vec<u8>,
+}
+
+impl LelantusSerializable for BoundProof {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commitments_are_homomorphic() {
+        let generators = Generators::new(b"seed");
+        let g = ProjectivePoint::GENERATOR;
+        let c1 = decode_hex(&generators.commit_to(&g, 700, b"r1", None)).ok();
+        let c2 = decode_hex(&generators.commit_to(&g, 300, b"r1", None)).ok();
+
+        // Same randomness: the blinding and serial cancel, leaving 400·G
+        let zero_blinding = ProjectivePoint::GENERATOR * Scalar::from(400u64);
+        assert_eq!(c1.zip(c2).map(|(a, b)| a - b), Some(zero_blinding));
+        assert_eq!(encode(&generators.blinding).len(), POINT_SIZE);
+    }
+}
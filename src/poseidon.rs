@@ -0,0 +1,152 @@
+//! Poseidon hash over the P-521 scalar field
+//!
+//! Enabled with the `poseidon` feature. Poseidon is cheap to express as an
+//! arithmetic circuit, so commitments hashed with it can be opened inside a
+//! SNARK. It is only meaningful together with a proof system that arithmetizes
+//! the same permutation; the hash-based proofs in this crate gain nothing from
+//! it over SHA-512.
+
+use p521::elliptic_curve::ff::PrimeField;
+use p521::{FieldBytes, Scalar};
+use sha2::{Digest, Sha512};
+use std::sync::OnceLock;
+
+/// Number of field elements in the permutation state
+const WIDTH: usize = 3;
+
+/// Number of state elements absorbed per permutation
+const RATE: usize = 2;
+
+/// Rounds applying the S-box to the whole state
+const FULL_ROUNDS: usize = 8;
+
+/// Rounds applying the S-box to the first element only
+const PARTIAL_ROUNDS: usize = 56;
+
+/// Bytes packed into one field element (strictly below the 521-bit modulus)
+const CHUNK_SIZE: usize = 64;
+
+/// Domain separator for round constant generation
+const ROUND_CONSTANT_DOMAIN: &[u8] = b"lelantus_poseidon_p521_rc";
+
+/// Round constants and MDS matrix of the permutation
+struct Constants {
+    round: Vec<[Scalar; WIDTH]>,
+    mds: [[Scalar; WIDTH]; WIDTH],
+}
+
+fn constants() -> &'static Constants {
+    static CONSTANTS: OnceLock<Constants> = OnceLock::new();
+    CONSTANTS.get_or_init(|| {
+        let round = (0..FULL_ROUNDS + PARTIAL_ROUNDS)
+            .map(|r| {
+                let mut row = [Scalar::ZERO; WIDTH];
+                for (i, constant) in row.iter_mut().enumerate() {
+                    let mut hasher = Sha512::new();
+                    hasher.update(ROUND_CONSTANT_DOMAIN);
+                    hasher.update(((r * WIDTH + i) as u64).to_le_bytes());
+                    *constant = scalar_from_chunk(&hasher.finalize());
+                }
+                row
+            })
+            .collect();
+
+        // Cauchy matrix 1 / (x_i + y_j) with distinct x_i = i, y_j = WIDTH + j
+        let mut mds = [[Scalar::ZERO; WIDTH]; WIDTH];
+        for (i, row) in mds.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = Scalar::from((i + WIDTH + j) as u64)
+                    .invert()
+                    .unwrap_or(Scalar::ONE);
+            }
+        }
+
+        Constants { round, mds }
+    })
+}
+
+/// Interpret up to `CHUNK_SIZE` big-endian bytes as a field element
+fn scalar_from_chunk(chunk: &[u8]) -> Scalar {
+    let mut repr = FieldBytes::default();
+    let offset = repr.len() - chunk.len();
+    repr[offset..].copy_from_slice(chunk);
+    // Cannot fail: any 512-bit value is below the modulus
+    Scalar::from_repr(repr).unwrap_or(Scalar::ZERO)
+}
+
+/// S-box `x^5` (5 is coprime to n - 1, so this is a permutation)
+fn sbox(x: Scalar) -> Scalar {
+    x.square().square() * x
+}
+
+/// Apply the Poseidon permutation to `state`
+fn permute(state: &mut [Scalar; WIDTH]) {
+    let constants = constants();
+    let half_full = FULL_ROUNDS / 2;
+
+    for (r, round_constants) in constants.round.iter().enumerate() {
+        for (element, constant) in state.iter_mut().zip(round_constants) {
+            *element += constant;
+        }
+
+        if r < half_full || r >= half_full + PARTIAL_ROUNDS {
+            for element in state.iter_mut() {
+                *element = sbox(*element);
+            }
+        } else {
+            state[0] = sbox(state[0]);
+        }
+
+        let mut mixed = [Scalar::ZERO; WIDTH];
+        for (out, row) in mixed.iter_mut().zip(&constants.mds) {
+            *out = row.iter().zip(state.iter()).map(|(m, s)| *m * s).sum();
+        }
+        *state = mixed;
+    }
+}
+
+/// Hash a sequence of byte strings to a field element
+///
+/// Each input is length-prefixed before packing, so distinct sequences never
+/// share an encoding. Returns the big-endian field element representation.
+pub fn hash(inputs: &[&[u8]]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for input in inputs {
+        encoded.extend_from_slice(&(input.len() as u64).to_le_bytes());
+        encoded.extend_from_slice(input);
+    }
+
+    let elements: Vec<Scalar> = encoded.chunks(CHUNK_SIZE).map(scalar_from_chunk).collect();
+
+    // The capacity element carries the input length as domain separation
+    let mut state = [Scalar::ZERO; WIDTH];
+    state[RATE] = Scalar::from(encoded.len() as u64);
+
+    for block in elements.chunks(RATE) {
+        for (element, input) in state.iter_mut().zip(block) {
+            *element += input;
+        }
+        permute(&mut state);
+    }
+
+    state[0].to_repr().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_is_deterministic() {
+        let a = hash(&[b"generator", &1000u64.to_le_bytes()]);
+        assert_eq!(a, hash(&[b"generator", &1000u64.to_le_bytes()]));
+        assert_ne!(a, hash(&[b"generator", &1001u64.to_le_bytes()]));
+        assert_eq!(a.len(), FieldBytes::default().len());
+    }
+
+    #[test]
+    fn test_input_boundaries_are_separated() {
+        assert_ne!(hash(&[b"ab", b"c"]), hash(&[b"a", b"bc"]));
+        assert_ne!(hash(&[b""]), hash(&[]));
+    }
+}
@@ -1,14 +1,91 @@
 //! Zero-knowledge proofs for Lelantus
 
-use crate::commitment::Commitment;
-use crate::errors::Result;
-use crate::parameters::LelantusParameters;
-use crate::witness::Witness;
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::errors::{LelantusError, Result};
+use crate::inner_product::{multiply, InnerProductProof};
+use crate::parameters::{HashAlgo, LelantusParameters};
+use crate::pedersen::{
+    self, bound_bits, BoundProof, Generators, MAX_RANGE_BITS, POINT_SIZE, SCALAR_SIZE,
+};
+use crate::prelude::*;
+use crate::serial::SerialNumber;
+use crate::serialization::binary::{self, LENGTH_PREFIX_SIZE, U64_SIZE};
+use crate::serialization::LelantusSerializable;
+use crate::transcript::{ProofDomain, ProofTranscript, CHALLENGE_SIZE};
+use borsh::{BorshDeserialize, BorshSerialize};
+use p521::elliptic_curve::{Field, PrimeField};
+use p521::{ProjectivePoint, Scalar};
+use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha512};
+
+/// Length of a hex-encoded proof digest field
+///
+/// Proof fields are transcript challenges or SHA-512 digests whichever hash
+/// the commitments use, so the size does not depend on `hash_function`.
+const PROOF_DIGEST_SIZE: usize = 2 * CHALLENGE_SIZE;
+
+/// Check that a proof field is a well-formed hex-encoded digest of `digest_size`
+fn check_digest_field(field: &[u8], digest_size: usize) -> Result<()> {
+    if field.len() != digest_size || !field.iter().all(|b| b.is_ascii_hexdigit()) {
+        return Err(LelantusError::InvalidProof);
+    }
+    Ok(())
+}
+
+/// Scalar of a hex-encoded transcript challenge
+fn challenge_scalar(challenge: &[u8]) -> Result<Scalar> {
+    let digest = hex::decode(challenge).map_err(|_| LelantusError::InvalidProof)?;
+    Ok(pedersen::scalar_from_digest(&digest))
+}
+
+/// Largest value representable in `bit_length` bits, saturating at `u64::MAX`
+fn max_for_bits(bit_length: usize) -> u64 {
+    if bit_length >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << bit_length) - 1
+    }
+}
+
+/// Generators for the balance proof, which needs the `pedersen` scheme
+///
+/// Hash commitments are not homomorphic, so no proof can show that their
+/// values balance; fails with `InvalidParameter` under those schemes.
+fn balance_generators(parameters: &LelantusParameters) -> Result<Generators> {
+    if parameters.hash_algo()? != HashAlgo::Pedersen {
+        return Err(LelantusError::invalid_parameter("hash_function"));
+    }
+    Ok(Generators::new(&parameters.generator))
+}
+
+/// Generators for bounded range proofs, which need the `pedersen` scheme
+fn bound_generators(parameters: &LelantusParameters) -> Result<Generators> {
+    if parameters.hash_algo()? != HashAlgo::Pedersen {
+        return Err(bound_scheme_error());
+    }
+    Ok(Generators::new(&parameters.generator))
+}
+
+/// Error for a bounded range proof under a hash scheme
+fn bound_scheme_error() -> LelantusError {
+    LelantusError::RangeProofError("bounded range proofs need the pedersen scheme".to_string())
+}
+
+/// Size and cost of a proof generated without keeping it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofMetrics {
+    /// Serialized size of the JoinSplit proof in bytes
+    pub serialized_size: usize,
+
+    /// Wall-clock time spent generating the proof
+    pub generation_time: core::time::Duration,
+}
 
 /// Range proof for a commitment
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Carries a bit-decomposition argument over the commitment point, so the
+/// verifier checks the range against the committed value itself.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct RangeProof {
     /// Proof data
     pub proof_data: Vec<u8>,
@@ -18,39 +95,943 @@ pub struct RangeProof {
 
     /// Bit length of the range
     pub bit_length: usize,
+
+    /// Inclusive lower bound of the proven range
+    #[serde(default)]
+    pub min_value: u64,
+
+    /// Inclusive upper bound of the proven range
+    #[serde(default = "default_max_value")]
+    pub max_value: u64,
+
+    /// Argument that the committed value lies within the bounds
+    pub bound_proof: BoundProof,
+}
+
+impl LelantusSerializable for RangeProof {
+    const MIN_ENCODING_VERSION: u8 = 5;
+}
+
+fn default_max_value() -> u64 {
+    u64::MAX
 }
 
 impl RangeProof {
-    /// Create a range proof
-    pub fn create(commitment: &Commitment, parameters: &LelantusParameters) -> Result<Self> {
-        // Generate range proof using Bulletproofs
-        let mut hasher = Sha512::new();
-        hasher.update(&commitment.value);
-        hasher.update(parameters.range_proof_bits.to_le_bytes());
+    /// Create a range proof that `commitment` holds `value` in exactly
+    /// `parameters.range_proof_bits` bits
+    ///
+    /// Needs the `pedersen` scheme: hash commitments have no algebraic
+    /// structure to prove a range over. The proof commits to each of the
+    /// `range_proof_bits` bits, so narrower widths give smaller proofs.
+    #[cfg(feature = "std")]
+    pub fn create(
+        commitment: &Commitment,
+        value: u64,
+        parameters: &LelantusParameters,
+    ) -> Result<Self> {
+        Self::create_with_rng(commitment, value, parameters, &mut rand::rngs::OsRng)
+    }
+
+    /// Create a range proof of `range_proof_bits` bits with nonces drawn
+    /// from `rng`
+    pub fn create_with_rng(
+        commitment: &Commitment,
+        value: u64,
+        parameters: &LelantusParameters,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self> {
+        let bit_length = parameters.range_proof_bits;
+        let bound_proof = bound_generators(parameters)?.prove_width(
+            &commitment.value,
+            ProjectivePoint::GENERATOR,
+            value,
+            &commitment.randomness,
+            bit_length,
+            rng,
+        )?;
+        Ok(Self::build(
+            commitment,
+            bit_length,
+            0,
+            max_for_bits(bit_length),
+            bound_proof,
+        ))
+    }
+
+    /// Create a range proof that `commitment` holds a value within
+    /// `[min_value, max_value]`
+    ///
+    /// Needs the `pedersen` scheme: the proof carries a bit-decomposition
+    /// argument over the commitment point, so the verifier checks the bounds
+    /// against the committed value itself. The argument only spans as many
+    /// bits as the range requires, so tightly bounded outputs (e.g. fees) do
+    /// not need a full-width proof.
+    #[cfg(feature = "std")]
+    pub fn create_bounded(
+        commitment: &Commitment,
+        value: u64,
+        min_value: u64,
+        max_value: u64,
+        parameters: &LelantusParameters,
+    ) -> Result<Self> {
+        Self::create_bounded_with_rng(
+            commitment,
+            value,
+            min_value,
+            max_value,
+            parameters,
+            &mut rand::rngs::OsRng,
+        )
+    }
+
+    /// Create a bounded range proof with nonces drawn from `rng`
+    pub fn create_bounded_with_rng(
+        commitment: &Commitment,
+        value: u64,
+        min_value: u64,
+        max_value: u64,
+        parameters: &LelantusParameters,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self> {
+        if min_value > max_value {
+            return Err(LelantusError::RangeProofError(
+                "lower bound exceeds upper bound".to_string(),
+            ));
+        }
+
+        if value < min_value || value > max_value {
+            return Err(LelantusError::RangeProofError(format!(
+                "value outside range [{}, {}]",
+                min_value, max_value
+            )));
+        }
+
+        let generators = bound_generators(parameters)?;
+        let bound_proof = generators.prove_bound(
+            &commitment.value,
+            value,
+            &commitment.randomness,
+            min_value,
+            max_value,
+            rng,
+        )?;
+
+        let bit_length = bound_bits(min_value, max_value);
+        Ok(Self::build(
+            commitment,
+            bit_length,
+            min_value,
+            max_value,
+            bound_proof,
+        ))
+    }
+
+    fn build(
+        commitment: &Commitment,
+        bit_length: usize,
+        min_value: u64,
+        max_value: u64,
+        bound_proof: BoundProof,
+    ) -> Self {
+        Self {
+            proof_data: Self::digest(&commitment.value, bit_length, min_value, max_value),
+            commitment: commitment.value.clone(),
+            bit_length,
+            min_value,
+            max_value,
+            bound_proof,
+        }
+    }
+
+    /// Whether this is a width proof from [`create`](Self::create) rather
+    /// than a bounded one
+    fn is_width_proof(&self) -> bool {
+        self.bound_proof.upper.is_empty()
+    }
+
+    /// Number of bit commitments the proof carries
+    #[cfg(feature = "std")]
+    pub(crate) fn bit_count(&self) -> usize {
+        self.bound_proof.lower.len() + self.bound_proof.upper.len()
+    }
 
-        let proof_data = hex::encode(hasher.finalize()).into_bytes();
+    /// Proof digest binding the commitment to the range
+    fn digest(commitment: &[u8], bit_length: usize, min_value: u64, max_value: u64) -> Vec<u8> {
+        let mut transcript = ProofTranscript::new(ProofDomain::RangeProof);
+        transcript.append_message(b"commitment", commitment);
+        transcript.append_u64(b"bit-length", bit_length as u64);
+        transcript.append_u64(b"min-value", min_value);
+        transcript.append_u64(b"max-value", max_value);
+        transcript.challenge(b"proof")
+    }
 
+    /// Check that proof fields are structurally well-formed
+    ///
+    /// Run before verification so that truncated or overlong fields are
+    /// rejected with [`LelantusError::InvalidProof`] without any hashing.
+    /// Also bounds the number of bit commitments by the declared width.
+    pub fn check_encoding(&self) -> Result<()> {
+        check_digest_field(&self.proof_data, PROOF_DIGEST_SIZE)?;
+
+        let upper = if self.is_width_proof() {
+            0
+        } else {
+            self.bit_length
+        };
+        if self.bit_length > MAX_RANGE_BITS
+            || self.bound_proof.lower.len() != self.bit_length
+            || self.bound_proof.upper.len() != upper
+        {
+            return Err(LelantusError::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Verify the range proof against the commitment
+    pub fn verify(&self, parameters: &LelantusParameters) -> Result<bool> {
+        if !self.digest_matches() {
+            return Ok(false);
+        }
+        self.verify_bits(&bound_generators(parameters)?)
+    }
+
+    /// Verify the range proof with the generators of an existing scheme
+    #[cfg(feature = "std")]
+    pub(crate) fn verify_with(&self, scheme: &CommitmentScheme) -> Result<bool> {
+        if !self.digest_matches() {
+            return Ok(false);
+        }
+        let generators = scheme
+            .pedersen_generators()
+            .map_err(|_| bound_scheme_error())?;
+        self.verify_bits(generators)
+    }
+
+    /// Whether the bounds are ordered and the digest binds them
+    fn digest_matches(&self) -> bool {
+        self.min_value <= self.max_value
+            && self.proof_data
+                == Self::digest(
+                    &self.commitment,
+                    self.bit_length,
+                    self.min_value,
+                    self.max_value,
+                )
+    }
+
+    /// Verify the bit-decomposition proof behind the digest
+    fn verify_bits(&self, generators: &Generators) -> Result<bool> {
+        if self.is_width_proof() {
+            if self.min_value != 0 || self.max_value != max_for_bits(self.bit_length) {
+                return Ok(false);
+            }
+            generators.verify_width(
+                &self.commitment,
+                ProjectivePoint::GENERATOR,
+                self.bit_length,
+                &self.bound_proof,
+            )
+        } else {
+            if self.bit_length != bound_bits(self.min_value, self.max_value) {
+                return Ok(false);
+            }
+            generators.verify_bound(
+                &self.commitment,
+                self.min_value,
+                self.max_value,
+                &self.bound_proof,
+            )
+        }
+    }
+
+    /// Verify that the committed value lies within exactly `[min_value, max_value]`
+    ///
+    /// Only proofs from [`create_bounded`](Self::create_bounded) carry the
+    /// bound argument this needs.
+    pub fn verify_bounded(
+        &self,
+        min_value: u64,
+        max_value: u64,
+        parameters: &LelantusParameters,
+    ) -> Result<bool> {
+        if self.min_value != min_value
+            || self.max_value != max_value
+            || self.bit_length != bound_bits(min_value, max_value)
+            || self.is_width_proof()
+        {
+            return Ok(false);
+        }
+
+        self.verify(parameters)
+    }
+
+    /// Check that the proof shows `commitment` lies within a parameter set's range
+    ///
+    /// The proof must verify against the committed value, be bound to
+    /// `commitment`, and prove a range no wider than
+    /// `parameters.range_proof_bits` allows.
+    pub fn proves_under(&self, commitment: &Commitment, parameters: &LelantusParameters) -> bool {
+        self.commitment == commitment.value
+            && self.bit_length <= parameters.range_proof_bits
+            && self.max_value <= max_for_bits(parameters.range_proof_bits)
+            && self.verify(parameters).unwrap_or(false)
+    }
+
+    /// Encode range proof in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_bytes(&mut buf, &self.proof_data)?;
+        binary::put_bytes(&mut buf, &self.commitment)?;
+        binary::put_u64(&mut buf, self.bit_length as u64);
+        binary::put_u64(&mut buf, self.min_value);
+        binary::put_u64(&mut buf, self.max_value);
+        binary::put_bytes(&mut buf, &self.bound_proof.to_bytes()?)?;
+        Ok(buf)
+    }
+
+    /// Decode range proof from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let proof_data = reader.read_bytes()?;
+        let commitment = reader.read_bytes()?;
+        let bit_length = usize::try_from(reader.read_u64()?)
+            .map_err(|_| LelantusError::SerializationError("bit length overflow".to_string()))?;
+        let min_value = reader.read_u64()?;
+        let max_value = reader.read_u64()?;
+        let bound_proof = BoundProof::from_bytes(&reader.read_bytes()?)?;
+        reader.finish()?;
         Ok(Self {
             proof_data,
-            commitment: commitment.value.clone(),
-            bit_length: parameters.range_proof_bits,
+            commitment,
+            bit_length,
+            min_value,
+            max_value,
+            bound_proof,
         })
     }
+}
+
+/// Group terms of an aggregated range proof for the outputs sharing one
+/// value generator
+///
+/// Native outputs hold value under `G` and tagged outputs under their own
+/// blinded asset tag, so the polynomial each group's bits contribute to is
+/// committed and opened under the group's generator.
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+)]
+pub struct AssetTerms {
+    /// Commitment `T₁` to the linear coefficient of the asset's polynomial
+    pub t1: Vec<u8>,
 
-    /// Verify the range proof
-    pub fn verify(&self, _parameters: &LelantusParameters) -> Result<bool> {
-        // Verify range proof
-        let mut hasher = Sha512::new();
-        hasher.update(&self.commitment);
-        hasher.update(self.bit_length.to_le_bytes());
+    /// Commitment `T₂` to the quadratic coefficient
+    pub t2: Vec<u8>,
+
+    /// The group's share `t̂` of the inner product at the challenge
+    pub evaluation: Vec<u8>,
+
+    /// Mask `(τₓ, σₓ)` opening `t̂` against the group's commitments
+    pub mask_response: Vec<u8>,
+}
+
+/// Range proof covering several commitments at once
+///
+/// An aggregated Bulletproof: the bits of every value are committed in two
+/// points and shown to be bits of the committed values by one
+/// inner-product argument, so the proof grows with the logarithm of the
+/// number of bits. Outputs are padded with zero-valued blocks to a power of
+/// two. Outputs of a JoinSplit moving several assets carry value under
+/// their blinded asset tags (see [`CommitmentScheme::asset_tag`]) rather
+/// than `G`, and each distinct value generator opens its share of the inner
+/// product separately; no asset id is published. The commitments and tags
+/// are not stored; the proof is checked against the outputs it covers.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AggregatedRangeProof {
+    /// Bit length of the range of each commitment, a power of two
+    pub bit_length: usize,
+
+    /// Number of commitments covered
+    pub output_count: usize,
+
+    /// Commitment `A` to the bits and their complements
+    pub bit_commitment: Vec<u8>,
+
+    /// Commitment `S` to the blinding vectors
+    pub blinding_commitment: Vec<u8>,
+
+    /// Terms of each distinct value generator, in order of first appearance
+    pub asset_terms: Vec<AssetTerms>,
+
+    /// Blinding `μ` of `A + x·S`
+    pub blinding_response: Vec<u8>,
+
+    /// Argument for the inner product of the bit vectors
+    pub inner_product: InnerProductProof,
+}
+
+impl LelantusSerializable for AggregatedRangeProof {
+    const MIN_ENCODING_VERSION: u8 = 7;
+}
+
+/// How the outputs of an aggregated range proof map onto its bit vectors
+struct AggregatedLayout {
+    /// Bits per output
+    bits: usize,
+
+    /// Generator group of each block of `bits` entries, padding included
+    groups: Vec<usize>,
+
+    /// Distinct value generators, in order of first appearance
+    generators: Vec<ProjectivePoint>,
+}
+
+impl AggregatedLayout {
+    /// Layout for outputs holding value under `value_generators`, each
+    /// proven in `bits` bits
+    ///
+    /// Padding blocks join the first generator's group.
+    fn new(value_generators: &[ProjectivePoint], bits: usize) -> Self {
+        let mut distinct: Vec<ProjectivePoint> = Vec::new();
+        let mut groups = Vec::with_capacity(value_generators.len().next_power_of_two());
+        for generator in value_generators {
+            let group = match distinct.iter().position(|known| known == generator) {
+                Some(group) => group,
+                None => {
+                    distinct.push(*generator);
+                    distinct.len() - 1
+                }
+            };
+            groups.push(group);
+        }
+        groups.resize(value_generators.len().next_power_of_two(), 0);
+        Self {
+            bits,
+            groups,
+            generators: distinct,
+        }
+    }
+
+    /// Length of the bit vectors
+    fn size(&self) -> usize {
+        self.bits * self.groups.len()
+    }
+
+    /// Generator group of entry `index` of the bit vectors
+    fn group_of(&self, index: usize) -> usize {
+        self.groups[index / self.bits]
+    }
+}
+
+/// Powers `1, x, x², …` of `x`
+fn powers(x: Scalar, count: usize) -> Vec<Scalar> {
+    let mut powers = Vec::with_capacity(count);
+    let mut power = Scalar::ONE;
+    for _ in 0..count {
+        powers.push(power);
+        power *= x;
+    }
+    powers
+}
+
+/// Challenges of an aggregated range proof before the inner-product argument
+struct AggregatedChallenges {
+    /// Powers of `y` over the bit vectors
+    y: Vec<Scalar>,
+
+    /// Inverse powers of `y`
+    y_inverse: Vec<Scalar>,
+
+    z: Scalar,
+
+    /// Weight `z^{2+j}` of block `j`
+    block_weights: Vec<Scalar>,
+
+    /// Powers of two within a block
+    two: Vec<Scalar>,
+}
+
+impl AggregatedChallenges {
+    /// Draw `y` and `z` after the transcript has absorbed `A` and `S`
+    fn draw(transcript: &mut ProofTranscript, layout: &AggregatedLayout) -> Result<Self> {
+        let y = pedersen::scalar_from_digest(&transcript.challenge_bytes(b"y"));
+        let z = pedersen::scalar_from_digest(&transcript.challenge_bytes(b"z"));
+        let y_inverse = Option::<Scalar>::from(y.invert()).ok_or(LelantusError::InvalidProof)?;
+        Ok(Self {
+            y: powers(y, layout.size()),
+            y_inverse: powers(y_inverse, layout.size()),
+            z,
+            block_weights: powers(z, layout.groups.len())
+                .into_iter()
+                .map(|power| power * z.square())
+                .collect(),
+            two: powers(Scalar::from(2u64), layout.bits),
+        })
+    }
+
+    /// Constant `zz_j·2^k` entry `index` adds to `r`
+    fn shift(&self, index: usize) -> Scalar {
+        let bits = self.two.len();
+        self.block_weights[index / bits] * self.two[index % bits]
+    }
+}
+
+/// Value generator of each of `count` outputs: its asset tag, or `G` when
+/// the outputs are untagged native coins
+///
+/// `None` if there is neither one tag per output nor none, or a tag is not a
+/// point.
+fn output_generators(tags: &[Vec<u8>], count: usize) -> Option<Vec<ProjectivePoint>> {
+    if tags.is_empty() {
+        return Some(vec![ProjectivePoint::GENERATOR; count]);
+    }
+    (tags.len() == count)
+        .then(|| tags.iter().map(|tag| pedersen::decode(tag)).collect())
+        .flatten()
+}
+
+/// Transcript of an aggregated range proof over `commitments` tagged with
+/// `tags`
+fn aggregated_transcript(
+    scheme: &CommitmentScheme,
+    bit_length: usize,
+    commitments: &[Commitment],
+    tags: &[Vec<u8>],
+) -> ProofTranscript {
+    let mut transcript = ProofTranscript::new(ProofDomain::AggregatedRangeProof);
+    transcript.append_message(b"generator", scheme.generator());
+    transcript.append_u64(b"bit-length", bit_length as u64);
+    transcript.append_commitments(b"commitment", commitments.iter());
+    for tag in tags {
+        transcript.append_message(b"asset-tag", tag);
+    }
+    transcript
+}
+
+/// Absorb the group terms and `μ`, then draw the inner-product weight `w`
+fn absorb_responses(
+    transcript: &mut ProofTranscript,
+    asset_terms: &[AssetTerms],
+    blinding_response: &[u8],
+) -> Scalar {
+    for terms in asset_terms {
+        transcript.append_message(b"evaluation", &terms.evaluation);
+        transcript.append_message(b"mask-response", &terms.mask_response);
+    }
+    transcript.append_message(b"blinding-response", blinding_response);
+    pedersen::scalar_from_digest(&transcript.challenge_bytes(b"w"))
+}
+
+/// Absorb `T₁` and `T₂` of each group, then draw `x`
+fn absorb_polynomials(transcript: &mut ProofTranscript, asset_terms: &[AssetTerms]) -> Scalar {
+    for terms in asset_terms {
+        transcript.append_message(b"t1", &terms.t1);
+        transcript.append_message(b"t2", &terms.t2);
+    }
+    pedersen::scalar_from_digest(&transcript.challenge_bytes(b"x"))
+}
+
+impl AggregatedRangeProof {
+    /// Prove every commitment holds its value below `2^bit_length`
+    ///
+    /// `tags` holds the asset tag of each commitment, proving its value
+    /// under the tag, or is empty for native coins proven under `G`. The
+    /// bit length is the width of `max_coin_value` rounded up to a power of
+    /// two. Needs the `pedersen` scheme, like [`RangeProof::create`].
+    #[cfg(feature = "std")]
+    pub fn create(
+        commitments: &[Commitment],
+        values: &[u64],
+        tags: &[Vec<u8>],
+        scheme: &CommitmentScheme,
+    ) -> Result<Self> {
+        Self::create_with_rng(commitments, values, tags, scheme, &mut rand::rngs::OsRng)
+    }
+
+    /// Create an aggregated range proof with nonces drawn from `rng`
+    pub fn create_with_rng(
+        commitments: &[Commitment],
+        values: &[u64],
+        tags: &[Vec<u8>],
+        scheme: &CommitmentScheme,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self> {
+        if commitments.is_empty() {
+            return Err(LelantusError::RangeProofError(
+                "no commitments to aggregate".to_string(),
+            ));
+        }
+        if commitments.len() != values.len() {
+            return Err(LelantusError::RangeProofError(
+                "one value per commitment required".to_string(),
+            ));
+        }
+
+        let generators = scheme.pedersen_generators()?;
+        let bit_length = Self::bit_length_for(scheme.parameters());
+        let value_generators = output_generators(tags, commitments.len())
+            .ok_or(LelantusError::invalid_parameter("tags"))?;
+        let layout = AggregatedLayout::new(&value_generators, bit_length);
+
+        // Value, blinding and serial of each block, padding opening to zero.
+        // A tagged coin `v·A + b·H + s·G₁` opens under its tag `A + τ·H`
+        // with blinding `b − v·τ`.
+        let mut openings = Vec::with_capacity(layout.groups.len());
+        for ((commitment, value), value_generator) in
+            commitments.iter().zip(values).zip(&value_generators)
+        {
+            let mut blinding = Generators::blinding_factor(&commitment.randomness, None);
+            if !tags.is_empty() {
+                blinding -= Scalar::from(*value)
+                    * Generators::asset_blinding_factor(&commitment.randomness);
+            }
+            let serial = Generators::serial_factor(&commitment.randomness);
+            if pedersen::decode_hex(&commitment.value)?
+                != *value_generator * Scalar::from(*value) + generators.mask(blinding, serial)
+            {
+                return Err(LelantusError::InvalidCommitment);
+            }
+            if value.checked_shr(bit_length as u32).unwrap_or(0) != 0 {
+                return Err(LelantusError::RangeProofError(format!(
+                    "value does not fit in {} bits",
+                    bit_length
+                )));
+            }
+            openings.push((*value, blinding, serial));
+        }
+        openings.resize(layout.groups.len(), (0, Scalar::ZERO, Scalar::ZERO));
+
+        let size = layout.size();
+        let bits_left: Vec<Scalar> = openings
+            .iter()
+            .flat_map(|(value, _, _)| {
+                (0..bit_length)
+                    .map(move |k| Scalar::from(value.checked_shr(k as u32).unwrap_or(0) & 1))
+            })
+            .collect();
+        let bits_right: Vec<Scalar> = bits_left.iter().map(|bit| *bit - Scalar::ONE).collect();
+        let blinding_left: Vec<Scalar> = (0..size).map(|_| Scalar::random(&mut *rng)).collect();
+        let blinding_right: Vec<Scalar> = (0..size).map(|_| Scalar::random(&mut *rng)).collect();
+
+        let (g, h) = generators.vector_generators(size);
+        let blinding_generator = generators.blinding_generator();
+        let alpha = Scalar::random(&mut *rng);
+        let rho = Scalar::random(&mut *rng);
+        let bit_commitment = pedersen::encode(
+            &(blinding_generator * alpha + multiply(&g, &bits_left) + multiply(&h, &bits_right)),
+        );
+        let blinding_commitment = pedersen::encode(
+            &(blinding_generator * rho
+                + multiply(&g, &blinding_left)
+                + multiply(&h, &blinding_right)),
+        );
+
+        let mut transcript = aggregated_transcript(scheme, bit_length, commitments, tags);
+        transcript.append_message(b"bit-commitment", &bit_commitment);
+        transcript.append_message(b"blinding-commitment", &blinding_commitment);
+        let challenges = AggregatedChallenges::draw(&mut transcript, &layout)?;
+
+        // l(x) = l0 + l1·x and r(x) = r0 + r1·x
+        let z = challenges.z;
+        let l0: Vec<Scalar> = bits_left.iter().map(|bit| *bit - z).collect();
+        let r0: Vec<Scalar> = (0..size)
+            .map(|i| challenges.y[i] * (bits_right[i] + z) + challenges.shift(i))
+            .collect();
+        let r1: Vec<Scalar> = (0..size)
+            .map(|i| challenges.y[i] * blinding_right[i])
+            .collect();
+        let l1 = blinding_left;
+
+        // Coefficients of each group's share of <l(x), r(x)>, committed
+        // under the group's value generator
+        let mut asset_terms = Vec::with_capacity(layout.generators.len());
+        let mut nonces = Vec::with_capacity(layout.generators.len());
+        for (group, value_generator) in layout.generators.iter().enumerate() {
+            let (mut t1, mut t2) = (Scalar::ZERO, Scalar::ZERO);
+            for i in (0..size).filter(|&i| layout.group_of(i) == group) {
+                t1 += l0[i] * r1[i] + l1[i] * r0[i];
+                t2 += l1[i] * r1[i];
+            }
+            let group_nonces: [Scalar; 4] = core::array::from_fn(|_| Scalar::random(&mut *rng));
+            asset_terms.push(AssetTerms {
+                t1: pedersen::encode(
+                    &(*value_generator * t1 + generators.mask(group_nonces[0], group_nonces[1])),
+                ),
+                t2: pedersen::encode(
+                    &(*value_generator * t2 + generators.mask(group_nonces[2], group_nonces[3])),
+                ),
+                ..Default::default()
+            });
+            nonces.push(group_nonces);
+        }
+        let x = absorb_polynomials(&mut transcript, &asset_terms);
+
+        let l: Vec<Scalar> = l0.iter().zip(&l1).map(|(c, d)| *c + *d * x).collect();
+        let r: Vec<Scalar> = r0.iter().zip(&r1).map(|(c, d)| *c + *d * x).collect();
+        for (group, (terms, group_nonces)) in asset_terms.iter_mut().zip(&nonces).enumerate() {
+            let mut evaluation = Scalar::ZERO;
+            for i in (0..size).filter(|&i| layout.group_of(i) == group) {
+                evaluation += l[i] * r[i];
+            }
+            let mut blinding = group_nonces[2] * x.square() + group_nonces[0] * x;
+            let mut serial = group_nonces[3] * x.square() + group_nonces[1] * x;
+            for (j, (_, gamma, serial_factor)) in openings.iter().enumerate() {
+                if layout.groups[j] == group {
+                    blinding += challenges.block_weights[j] * gamma;
+                    serial += challenges.block_weights[j] * serial_factor;
+                }
+            }
+            terms.evaluation = evaluation.to_repr().to_vec();
+            terms.mask_response = pedersen::encode_mask_response(blinding, serial);
+        }
+        let blinding_response = (alpha + rho * x).to_repr().to_vec();
+        let w = absorb_responses(&mut transcript, &asset_terms, &blinding_response);
+
+        let h_prime: Vec<ProjectivePoint> = h
+            .iter()
+            .zip(&challenges.y_inverse)
+            .map(|(point, weight)| *point * weight)
+            .collect();
+        let inner_product = InnerProductProof::create(
+            &mut transcript,
+            g,
+            h_prime,
+            generators.inner_product_generator() * w,
+            l,
+            r,
+        )?;
+
+        Ok(Self {
+            bit_length,
+            output_count: commitments.len(),
+            bit_commitment,
+            blinding_commitment,
+            asset_terms,
+            blinding_response,
+            inner_product,
+        })
+    }
+
+    /// Bit length proofs are created with under `parameters`
+    fn bit_length_for(parameters: &LelantusParameters) -> usize {
+        parameters.max_coin_value_bits().next_power_of_two()
+    }
+
+    /// Size of [`consensus_encode`](Self::consensus_encode) for `outputs`
+    /// commitments under `groups` distinct value generators under
+    /// `parameters`
+    pub fn encoded_size(parameters: &LelantusParameters, outputs: usize, groups: usize) -> usize {
+        let size = Self::bit_length_for(parameters) * outputs.next_power_of_two();
+        let rounds = size.trailing_zeros() as usize;
+        3 * U64_SIZE
+            + 2 * (LENGTH_PREFIX_SIZE + POINT_SIZE)
+            + groups * (4 * LENGTH_PREFIX_SIZE + 2 * POINT_SIZE + 3 * SCALAR_SIZE)
+            + LENGTH_PREFIX_SIZE
+            + SCALAR_SIZE
+            + InnerProductProof::encoded_size(rounds)
+    }
+
+    /// Check that proof fields are structurally well-formed
+    ///
+    /// The bit length must be a power of two, there must be at least one
+    /// group of terms and no more than one per output, and the
+    /// inner-product argument must have one round per halving of the bit
+    /// vectors. Whether the groups match the value generators of the outputs
+    /// is checked by [`verify`](Self::verify).
+    pub fn check_encoding(&self) -> Result<()> {
+        if !self.bit_length.is_power_of_two() || self.bit_length > MAX_RANGE_BITS {
+            return Err(LelantusError::InvalidProof);
+        }
+        let size = self
+            .output_count
+            .checked_next_power_of_two()
+            .and_then(|blocks| blocks.checked_mul(self.bit_length))
+            .ok_or(LelantusError::InvalidProof)?;
+        let well_formed = (1..=self.output_count).contains(&self.asset_terms.len())
+            && self.bit_commitment.len() == POINT_SIZE
+            && self.blinding_commitment.len() == POINT_SIZE
+            && self.blinding_response.len() == SCALAR_SIZE
+            && self.asset_terms.iter().all(|terms| {
+                terms.t1.len() == POINT_SIZE
+                    && terms.t2.len() == POINT_SIZE
+                    && terms.evaluation.len() == SCALAR_SIZE
+                    && terms.mask_response.len() == 2 * SCALAR_SIZE
+            });
+        if !well_formed {
+            return Err(LelantusError::InvalidProof);
+        }
+        self.inner_product.check_encoding(size)
+    }
+
+    /// Length of the bit vectors the proof argues over, padding included
+    #[cfg(feature = "std")]
+    pub(crate) fn bit_count(&self) -> usize {
+        self.output_count
+            .checked_next_power_of_two()
+            .map_or(usize::MAX, |blocks| blocks.saturating_mul(self.bit_length))
+    }
+
+    /// Verify that the proof covers exactly `commitments`, in order
+    ///
+    /// `tags` holds the asset tag of each commitment, already shown by an
+    /// [`AssetSurjectionProof`](crate::AssetSurjectionProof) to blind an
+    /// asset generator, or is empty for native coins. The proven range may
+    /// be no wider than `range_proof_bits`, rounded up to a power of two.
+    pub fn verify(
+        &self,
+        commitments: &[Commitment],
+        tags: &[Vec<u8>],
+        scheme: &CommitmentScheme,
+    ) -> Result<bool> {
+        if commitments.is_empty()
+            || commitments.len() != self.output_count
+            || self.bit_length > scheme.parameters().range_proof_bits.next_power_of_two()
+        {
+            return Ok(false);
+        }
+        self.check_encoding()?;
+
+        let generators = scheme.pedersen_generators()?;
+        let Some(value_generators) = output_generators(tags, commitments.len()) else {
+            return Ok(false);
+        };
+        let layout = AggregatedLayout::new(&value_generators, self.bit_length);
+        if self.asset_terms.len() != layout.generators.len() {
+            return Ok(false);
+        }
+        let size = layout.size();
+
+        let mut transcript = aggregated_transcript(scheme, self.bit_length, commitments, tags);
+        transcript.append_message(b"bit-commitment", &self.bit_commitment);
+        transcript.append_message(b"blinding-commitment", &self.blinding_commitment);
+        let challenges = AggregatedChallenges::draw(&mut transcript, &layout)?;
+        let x = absorb_polynomials(&mut transcript, &self.asset_terms);
+        let w = absorb_responses(&mut transcript, &self.asset_terms, &self.blinding_response);
+        let check = self.inner_product.verification(&mut transcript, size)?;
+
+        let decode_point =
+            |bytes: &[u8]| pedersen::decode(bytes).ok_or(LelantusError::InvalidProof);
+        let decode_scalar =
+            |bytes: &[u8]| pedersen::decode_scalar(bytes).ok_or(LelantusError::InvalidProof);
+
+        // Each group's share of t̂ opens the weighted sum of its commitments
+        // plus δ, once the committed coefficients are added back:
+        // t̂·A + mask(τₓ, σₓ) = Σ z^{2+j}·V_j + δ·A + x·T₁ + x²·T₂
+        let z = challenges.z;
+        let two_sum = challenges.two.iter().fold(Scalar::ZERO, |sum, t| sum + t);
+        let mut evaluation_sum = Scalar::ZERO;
+        for (group, terms) in self.asset_terms.iter().enumerate() {
+            let evaluation = decode_scalar(&terms.evaluation)?;
+            let (blinding, serial) = pedersen::decode_mask_response(&terms.mask_response)
+                .ok_or(LelantusError::InvalidProof)?;
+            let mut delta = Scalar::ZERO;
+            let mut weighted = ProjectivePoint::IDENTITY;
+            for j in (0..layout.groups.len()).filter(|&j| layout.groups[j] == group) {
+                let block = j * self.bit_length..(j + 1) * self.bit_length;
+                let y_sum = challenges.y[block]
+                    .iter()
+                    .fold(Scalar::ZERO, |sum, y| sum + y);
+                delta += (z - z.square()) * y_sum - challenges.block_weights[j] * z * two_sum;
+                if let Some(commitment) = commitments.get(j) {
+                    weighted +=
+                        pedersen::decode_hex(&commitment.value)? * challenges.block_weights[j];
+                }
+            }
+            let value_generator = layout.generators[group];
+            let lhs = value_generator * evaluation + generators.mask(blinding, serial);
+            let rhs = weighted
+                + value_generator * delta
+                + decode_point(&terms.t1)? * x
+                + decode_point(&terms.t2)? * x.square();
+            if lhs != rhs {
+                return Ok(false);
+            }
+            evaluation_sum += evaluation;
+        }
+
+        // A + x·S − μ·H = <l, G> + <r, H'> once the public parts of l and r
+        // are taken out, and the inner-product argument opens <l, r> = t̂
+        let (g, h) = generators.vector_generators(size);
+        let g_weights: Vec<Scalar> = check
+            .weights
+            .iter()
+            .map(|weight| -z - check.a * weight)
+            .collect();
+        let h_weights: Vec<Scalar> = (0..size)
+            .map(|i| {
+                z + (challenges.shift(i) - check.b * check.weights[size - 1 - i])
+                    * challenges.y_inverse[i]
+            })
+            .collect();
+        let point = decode_point(&self.bit_commitment)?
+            + decode_point(&self.blinding_commitment)? * x
+            - generators.blinding_generator() * decode_scalar(&self.blinding_response)?
+            + generators.inner_product_generator() * (w * (evaluation_sum - check.a * check.b))
+            + check
+                .cross_terms
+                .iter()
+                .fold(ProjectivePoint::IDENTITY, |sum, (term, weight)| {
+                    sum + *term * weight
+                })
+            + multiply(&g, &g_weights)
+            + multiply(&h, &h_weights);
+        Ok(point == ProjectivePoint::IDENTITY)
+    }
+
+    /// Encode the proof in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_u64(&mut buf, self.bit_length as u64);
+        binary::put_u64(&mut buf, self.output_count as u64);
+        binary::put_bytes(&mut buf, &self.bit_commitment)?;
+        binary::put_bytes(&mut buf, &self.blinding_commitment)?;
+        binary::put_u64(&mut buf, self.asset_terms.len() as u64);
+        for terms in &self.asset_terms {
+            binary::put_bytes(&mut buf, &terms.t1)?;
+            binary::put_bytes(&mut buf, &terms.t2)?;
+            binary::put_bytes(&mut buf, &terms.evaluation)?;
+            binary::put_bytes(&mut buf, &terms.mask_response)?;
+        }
+        binary::put_bytes(&mut buf, &self.blinding_response)?;
+        self.inner_product.put(&mut buf)?;
+        Ok(buf)
+    }
 
-        let expected_proof = hex::encode(hasher.finalize()).into_bytes();
-        Ok(self.proof_data == expected_proof)
+    /// Decode the proof from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let bit_length = usize::try_from(reader.read_u64()?)
+            .map_err(|_| LelantusError::SerializationError("bit length overflow".to_string()))?;
+        let output_count = usize::try_from(reader.read_u64()?)
+            .map_err(|_| LelantusError::SerializationError("output count overflow".to_string()))?;
+        let bit_commitment = reader.read_bytes()?;
+        let blinding_commitment = reader.read_bytes()?;
+        let group_count = reader.read_u64()?;
+        let mut asset_terms = Vec::new();
+        for _ in 0..group_count {
+            asset_terms.push(AssetTerms {
+                t1: reader.read_bytes()?,
+                t2: reader.read_bytes()?,
+                evaluation: reader.read_bytes()?,
+                mask_response: reader.read_bytes()?,
+            });
+        }
+        let blinding_response = reader.read_bytes()?;
+        let inner_product = InnerProductProof::read(&mut reader)?;
+        reader.finish()?;
+        Ok(Self {
+            bit_length,
+            output_count,
+            bit_commitment,
+            blinding_commitment,
+            asset_terms,
+            blinding_response,
+            inner_product,
+        })
     }
 }
 
 /// Zero-knowledge proof for JoinSplit
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct ZKProof {
     /// Proof data
     pub proof_data: Vec<u8>,
@@ -60,141 +1041,920 @@ pub struct ZKProof {
 
     /// Response
     pub response: Vec<u8>,
+
+    /// Anonymity set size the proof was built for
+    #[serde(default)]
+    pub anonymity_set_size: u64,
 }
 
-impl ZKProof {
-    /// Create a zero-knowledge proof
-    pub fn create(
-        inputs: &[(Commitment, Witness)],
-        outputs: &[Commitment],
-        fee: u64,
-        _parameters: &LelantusParameters,
-    ) -> Result<Self> {
-        // Create challenge
-        let mut hasher = Sha512::new();
+impl LelantusSerializable for ZKProof {}
 
-        for (commitment, _) in inputs {
-            hasher.update(&commitment.value);
-        }
+/// Public statement a JoinSplit's zero-knowledge proof is made over
+#[derive(Debug, Clone, Copy)]
+pub struct ZkStatement<'a> {
+    /// Commitments published in place of the spent coins, which carry no
+    /// serial; see [`CommitmentScheme::commit_input_with_rng`](crate::commitment::CommitmentScheme::commit_input_with_rng)
+    pub inputs: &'a [Commitment],
 
-        for commitment in outputs {
-            hasher.update(&commitment.value);
-        }
+    /// Nullifiers revealed for the inputs, in input order
+    pub nullifiers: &'a [SerialNumber],
 
-        hasher.update(fee.to_le_bytes());
+    /// Commitments of the created coins
+    pub outputs: &'a [Commitment],
 
-        let challenge = hex::encode(hasher.finalize()).into_bytes();
+    /// Transparent fee
+    pub fee: u64,
 
-        // Create response
-        let mut response_hasher = Sha512::new();
-        response_hasher.update(&challenge);
+    /// Accumulator head the inputs are proven against
+    pub accumulator_value: &'a [u8],
 
-        for (_, witness) in inputs {
-            response_hasher.update(&witness.commitment.randomness);
-        }
+    /// The JoinSplit's binding verification key; see [`crate::binding`]
+    pub binding_key: &'a [u8],
+}
 
-        let response = hex::encode(response_hasher.finalize()).into_bytes();
+impl ZKProof {
+    /// Create a zero-knowledge proof with blinding drawn from the OS RNG
+    #[cfg(feature = "std")]
+    pub fn create(statement: &ZkStatement<'_>, parameters: &LelantusParameters) -> Result<Self> {
+        Self::create_with_rng(statement, parameters, &mut rand::rngs::OsRng)
+    }
 
-        // Create proof data
-        let mut proof_hasher = Sha512::new();
-        proof_hasher.update(&challenge);
-        proof_hasher.update(&response);
+    /// Create a zero-knowledge proof with blinding drawn from `rng`
+    ///
+    /// A Schnorr proof of knowledge of the blinding excess `e` and serial
+    /// excess `s` with `Σ inputs − Σ outputs − fee·G = e·H + s·G₁`; the
+    /// serial excess is that of the outputs, as inputs carry no serial.
+    /// `proof_data` holds the nonce point `k·H + k₁·G₁` and the response is
+    /// `z = k + c·e` followed by `z₁ = k₁ + c·s`, where the challenge `c`
+    /// binds the whole statement. Computing `e` and `s` takes the randomness
+    /// of every commitment, which only the creator holds.
+    ///
+    /// Fails with `BalanceMismatch` if the values do not balance, as then the
+    /// excess has a `G` component and no such `e` and `s` exist, and with
+    /// `InvalidParameter` under the hash schemes. A fixed-seed RNG reproduces
+    /// a proof byte for byte, which lets auditors reconstruct and inspect it.
+    pub fn create_with_rng(
+        statement: &ZkStatement<'_>,
+        parameters: &LelantusParameters,
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self> {
+        let generators = balance_generators(parameters)?;
+        let anonymity_set_size = parameters.anonymity_set_size() as u64;
+        let mut transcript = Self::transcript(statement, parameters, anonymity_set_size)?;
+        let blinding = Self::blinding_excess(statement);
+        let serial = Self::serial_excess(statement);
+        if Self::balance_excess(statement)? != generators.mask(blinding, serial) {
+            return Err(LelantusError::BalanceMismatch);
+        }
 
-        let proof_data = hex::encode(proof_hasher.finalize()).into_bytes();
+        let nonces = [Scalar::random(&mut *rng), Scalar::random(&mut *rng)];
+        let proof_data =
+            hex::encode(pedersen::encode(&generators.mask(nonces[0], nonces[1]))).into_bytes();
+        transcript.append_message(b"nonce-point", &proof_data);
+        let challenge = transcript.challenge(b"challenge");
+        let c = challenge_scalar(&challenge)?;
+        let response =
+            pedersen::encode_mask_response(nonces[0] + c * blinding, nonces[1] + c * serial);
 
         Ok(Self {
             proof_data,
             challenge,
-            response,
+            response: hex::encode(response).into_bytes(),
+            anonymity_set_size,
         })
     }
 
-    /// Verify the zero-knowledge proof
-    pub fn verify(
+    /// Check the Schnorr equation `z·H + z₁·G₁ = R + c·E` for the balance
+    /// excess `E`
+    fn verify_balance(
         &self,
-        inputs: &[Commitment],
-        outputs: &[Commitment],
-        accumulator_value: &[u8],
-        _parameters: &LelantusParameters,
+        mut transcript: ProofTranscript,
+        statement: &ZkStatement<'_>,
+        generators: &Generators,
     ) -> Result<bool> {
-        // Recreate challenge
-        let mut hasher = Sha512::new();
-
-        for commitment in inputs {
-            hasher.update(&commitment.value);
+        transcript.append_message(b"nonce-point", &self.proof_data);
+        if self.challenge != transcript.challenge(b"challenge") {
+            return Ok(false);
         }
 
-        for commitment in outputs {
-            hasher.update(&commitment.value);
+        let nonce_point = hex::decode(&self.proof_data)
+            .ok()
+            .and_then(|bytes| pedersen::decode(&bytes))
+            .ok_or(LelantusError::InvalidProof)?;
+        let (blinding, serial) = hex::decode(&self.response)
+            .ok()
+            .and_then(|bytes| pedersen::decode_mask_response(&bytes))
+            .ok_or(LelantusError::InvalidProof)?;
+        let excess = Self::balance_excess(statement)?;
+
+        Ok(generators.mask(blinding, serial)
+            == nonce_point + excess * challenge_scalar(&self.challenge)?)
+    }
+
+    /// Balance excess point of the statement
+    fn balance_excess(statement: &ZkStatement<'_>) -> Result<ProjectivePoint> {
+        pedersen::balance_excess(
+            statement.inputs.iter().map(|c| c.value.as_slice()),
+            statement.outputs.iter().map(|c| c.value.as_slice()),
+            statement.fee,
+        )
+    }
+
+    /// Blinding of the input commitments minus that of the outputs
+    fn blinding_excess(statement: &ZkStatement<'_>) -> Scalar {
+        let blinding = |c: &Commitment| Generators::blinding_factor(&c.randomness, None);
+        statement
+            .inputs
+            .iter()
+            .map(blinding)
+            .fold(Scalar::ZERO, |sum, b| sum + b)
+            - statement
+                .outputs
+                .iter()
+                .map(blinding)
+                .fold(Scalar::ZERO, |sum, b| sum + b)
+    }
+
+    /// Serial of the input commitments minus that of the outputs
+    ///
+    /// Inputs carry no serial, so this is minus the outputs' serials.
+    fn serial_excess(statement: &ZkStatement<'_>) -> Scalar {
+        -statement
+            .outputs
+            .iter()
+            .map(|c| Generators::serial_factor(&c.randomness))
+            .fold(Scalar::ZERO, |sum, s| sum + s)
+    }
+
+    /// Fiat-Shamir transcript binding the generators, the whole statement and
+    /// the anonymity set size
+    fn transcript(
+        statement: &ZkStatement<'_>,
+        parameters: &LelantusParameters,
+        anonymity_set_size: u64,
+    ) -> Result<ProofTranscript> {
+        let mut transcript = ProofTranscript::new(ProofDomain::ZkProof);
+        transcript.append_message(b"generator", &parameters.generator);
+        transcript.append_u64(b"anonymity-set-size", anonymity_set_size);
+        transcript.append_message(b"accumulator-root", statement.accumulator_value);
+        transcript.append_message(b"fee", &parameters.encode_value(statement.fee)?);
+        transcript.append_commitments(b"inputs", statement.inputs.iter());
+        transcript.append_commitments(b"outputs", statement.outputs.iter());
+        transcript.append_u64(b"nullifiers", statement.nullifiers.len() as u64);
+        for nullifier in statement.nullifiers {
+            transcript.append_message(b"nullifier", nullifier.as_bytes());
         }
+        transcript.append_message(b"binding-key", statement.binding_key);
+        Ok(transcript)
+    }
 
-        hasher.update(accumulator_value);
+    /// Check that proof fields are structurally well-formed
+    ///
+    /// The proof data is a hex-encoded point, the challenge a transcript
+    /// challenge and the response two hex-encoded scalars.
+    pub fn check_encoding(&self) -> Result<()> {
+        check_digest_field(&self.challenge, PROOF_DIGEST_SIZE)?;
+        check_digest_field(&self.proof_data, 2 * POINT_SIZE)?;
+        check_digest_field(&self.response, 4 * SCALAR_SIZE)
+    }
 
-        let expected_challenge = hex::encode(hasher.finalize()).into_bytes();
+    /// Verify the zero-knowledge proof
+    ///
+    /// Checks the Schnorr proof that the inputs balance the outputs and fee;
+    /// see [`create_with_rng`](Self::create_with_rng). A proof built for
+    /// another anonymity set size fails with `PrivacyLevelMismatch`, and the
+    /// hash schemes fail with `InvalidParameter`.
+    pub fn verify(
+        &self,
+        statement: &ZkStatement<'_>,
+        parameters: &LelantusParameters,
+    ) -> Result<bool> {
+        let generators = balance_generators(parameters)?;
+        self.verify_with(statement, parameters, &generators)
+    }
 
-        // Verify challenge matches
-        if self.challenge != expected_challenge {
-            return Ok(false);
+    /// Verify the zero-knowledge proof with already derived generators
+    pub(crate) fn verify_with(
+        &self,
+        statement: &ZkStatement<'_>,
+        parameters: &LelantusParameters,
+        generators: &Generators,
+    ) -> Result<bool> {
+        if self.anonymity_set_size != parameters.anonymity_set_size() as u64 {
+            return Err(LelantusError::PrivacyLevelMismatch);
         }
 
-        // Verify proof data
-        let mut proof_hasher = Sha512::new();
-        proof_hasher.update(&self.challenge);
-        proof_hasher.update(&self.response);
+        let transcript = Self::transcript(statement, parameters, self.anonymity_set_size)?;
+        self.verify_balance(transcript, statement, generators)
+    }
+
+    /// Encode proof in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_bytes(&mut buf, &self.proof_data)?;
+        binary::put_bytes(&mut buf, &self.challenge)?;
+        binary::put_bytes(&mut buf, &self.response)?;
+        binary::put_u64(&mut buf, self.anonymity_set_size);
+        Ok(buf)
+    }
+
+    /// Decode proof from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let proof_data = reader.read_bytes()?;
+        let challenge = reader.read_bytes()?;
+        let response = reader.read_bytes()?;
+        let anonymity_set_size = reader.read_u64()?;
+        reader.finish()?;
+        Ok(Self {
+            proof_data,
+            challenge,
+            response,
+            anonymity_set_size,
+        })
+    }
+}
 
-        let expected_proof = hex::encode(proof_hasher.finalize()).into_bytes();
+#[cfg(test)]
+impl ZKProof {
+    /// Balance proof for `statement` from its openings, skipping the check
+    /// that the values balance
+    pub(crate) fn prove_openings(
+        statement: &ZkStatement<'_>,
+        parameters: &LelantusParameters,
+    ) -> Result<Self> {
+        Self::prove_excess(statement, parameters, Self::blinding_excess(statement))
+    }
 
-        Ok(self.proof_data == expected_proof)
+    /// Balance proof for `statement` made with the given blinding `excess`
+    /// rather than the one the openings give
+    pub(crate) fn prove_excess(
+        statement: &ZkStatement<'_>,
+        parameters: &LelantusParameters,
+        excess: Scalar,
+    ) -> Result<Self> {
+        let anonymity_set_size = parameters.anonymity_set_size() as u64;
+        let mut transcript = Self::transcript(statement, parameters, anonymity_set_size)?;
+        let nonce = Scalar::from(42u64);
+        let nonce_point = Generators::new(&parameters.generator).mask(nonce, nonce);
+        let proof_data = hex::encode(pedersen::encode(&nonce_point)).into_bytes();
+        transcript.append_message(b"nonce-point", &proof_data);
+        let challenge = transcript.challenge(b"challenge");
+        let c = challenge_scalar(&challenge)?;
+        let response = pedersen::encode_mask_response(
+            nonce + c * excess,
+            nonce + c * Self::serial_excess(statement),
+        );
+        Ok(Self {
+            proof_data,
+            challenge,
+            response: hex::encode(response).into_bytes(),
+            anonymity_set_size,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commitment::NATIVE_ASSET;
+    use crate::parameters::PrivacyLevel;
 
-    #[test]
     #[test]
     fn test_range_proof_creation() -> Result<()> {
         let params = LelantusParameters::default();
-        let commitment = Commitment {
-            value: vec![1; 32],
-            randomness: vec![2; 32],
-        };
+        let commitment = CommitmentScheme::new(&params)?.commit(1000)?;
 
-        let _proof = RangeProof::create(&commitment, &params)?;
+        let proof = RangeProof::create(&commitment, 1000, &params)?;
+        assert_eq!(proof.bit_length, params.range_proof_bits);
+        assert_eq!(proof.bit_count(), params.range_proof_bits);
+
+        // The prover cannot claim a value the commitment does not hold
+        assert!(RangeProof::create(&commitment, 999, &params).is_err());
+        assert!(RangeProof::create(&commitment, 1000, &sha512_parameters()).is_err());
         Ok(())
     }
 
     #[test]
     fn test_range_proof_verification() -> Result<()> {
         let params = LelantusParameters::default();
-        let commitment = Commitment {
-            value: vec![1; 32],
-            randomness: vec![2; 32],
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1000)?;
+
+        let proof = RangeProof::create(&commitment, 1000, &params)?;
+        assert!(proof.verify(&params)?);
+        assert!(RangeProof::consensus_decode(&proof.consensus_encode()?)?.verify(&params)?);
+
+        // The bits are checked against the commitment itself
+        let mut moved = proof.clone();
+        moved.commitment = scheme.commit(1000)?.value.clone();
+        moved.proof_data =
+            RangeProof::digest(&moved.commitment, moved.bit_length, 0, moved.max_value);
+        assert!(!moved.verify(&params)?);
+        let mut tampered = proof;
+        tampered.bound_proof.lower.swap(0, 1);
+        assert!(!tampered.verify(&params)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_proves_under_parameters() -> Result<()> {
+        let wide = LelantusParameters::default();
+        let narrow = LelantusParameters {
+            range_proof_bits: 32,
+            max_coin_value: u32::MAX.into(),
+            ..Default::default()
         };
+        let scheme = CommitmentScheme::new(&wide)?;
+        let commitment = scheme.commit(1000)?;
+
+        let proof = RangeProof::create(&commitment, 1000, &wide)?;
+        assert!(proof.proves_under(&commitment, &wide));
+        assert!(!proof.proves_under(&commitment, &narrow));
+
+        let narrow_proof = RangeProof::create(&commitment, 1000, &narrow)?;
+        assert!(narrow_proof.proves_under(&commitment, &narrow));
+        assert!(narrow_proof.proves_under(&commitment, &wide));
 
-        let proof = RangeProof::create(&commitment, &params)?;
-        let valid = proof.verify(&params)?;
-        assert!(valid);
+        let other = scheme.commit(1000)?;
+        assert!(!proof.proves_under(&other, &wide));
         Ok(())
     }
 
     #[test]
-    fn test_zk_proof_creation() {
+    fn test_range_proof_size_follows_bit_width() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let commitment = scheme.commit(1 << 40)?;
+        let proof_for = |bits| {
+            let params = LelantusParameters {
+                range_proof_bits: bits,
+                ..Default::default()
+            };
+            RangeProof::create(&commitment, 1 << 40, &params)
+        };
+
+        let narrow = proof_for(64)?;
+        let wide = proof_for(128)?;
+        assert_eq!(narrow.bit_count(), 64);
+        assert_eq!(wide.bit_count(), 128);
+        assert!(narrow.consensus_encode()?.len() < wide.consensus_encode()?.len());
+        assert!(narrow.verify(&LelantusParameters::default())?);
+        assert!(wide.verify(&LelantusParameters::default())?);
+
+        // A width the value does not fit in has no proof
+        assert!(proof_for(32).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregated_range_proof() -> Result<()> {
+        let params = LelantusParameters {
+            range_proof_bits: 32,
+            max_coin_value: u32::MAX.into(),
+            ..Default::default()
+        };
+        let scheme = CommitmentScheme::new(&params)?;
+        let token = [7; 32];
+        let assets = [NATIVE_ASSET, NATIVE_ASSET, token];
+        let values = [1, u32::MAX.into(), 300];
+        let rng = &mut rand::thread_rng();
+        let commitments = assets
+            .iter()
+            .zip(&values)
+            .map(|(asset, value)| scheme.commit_asset_with_rng(asset, *value, rng))
+            .collect::<Result<Vec<_>>>()?;
+        let tags = commitments
+            .iter()
+            .zip(&assets)
+            .map(|(commitment, asset)| scheme.asset_tag(asset, &commitment.randomness))
+            .collect::<Result<Vec<_>>>()?;
+
+        let proof = AggregatedRangeProof::create(&commitments, &values, &tags, &scheme)?;
+        assert_eq!(proof.bit_length, 32);
+        // Three outputs are padded to four
+        assert_eq!(proof.bit_count(), 4 * 32);
+        // Every tag is blinded differently, so each output is its own group
+        assert_eq!(proof.asset_terms.len(), 3);
+        assert!(proof.verify(&commitments, &tags, &scheme)?);
+        assert!(!proof.verify(&commitments[..2], &tags[..2], &scheme)?);
+        let mut reordered = commitments.clone();
+        reordered.swap(0, 1);
+        assert!(!proof.verify(&reordered, &tags, &scheme)?);
+        assert!(AggregatedRangeProof::create(&[], &[], &[], &scheme).is_err());
+
+        // Each output is proven under its own tag, and tagged outputs are
+        // not native coins
+        let mut swapped = tags.clone();
+        swapped.swap(1, 2);
+        assert!(!proof.verify(&commitments, &swapped, &scheme)?);
+        assert!(!proof.verify(&commitments, &[], &scheme)?);
+        assert!(AggregatedRangeProof::create(&commitments, &values, &[], &scheme).is_err());
+
+        // Untagged native outputs share one group under `G`
+        let native = AggregatedRangeProof::create(&commitments[..2], &values[..2], &[], &scheme)?;
+        assert_eq!(native.asset_terms.len(), 1);
+        assert!(native.verify(&commitments[..2], &[], &scheme)?);
+
+        // Values must open the commitments they are proven for
+        assert!(
+            AggregatedRangeProof::create(&commitments, &[values[1]; 3], &tags, &scheme).is_err()
+        );
+
+        let mut tampered = proof.clone();
+        tampered.asset_terms[1].evaluation = tampered.asset_terms[0].evaluation.clone();
+        assert!(!tampered.verify(&commitments, &tags, &scheme)?);
+        let mut truncated = proof.clone();
+        truncated.inner_product.left.pop();
+        assert!(truncated.verify(&commitments, &tags, &scheme).is_err());
+
+        let encoded = proof.consensus_encode()?;
+        assert_eq!(
+            encoded.len(),
+            AggregatedRangeProof::encoded_size(&params, 3, 3)
+        );
+        let decoded = AggregatedRangeProof::consensus_decode(&encoded)?;
+        decoded.check_encoding()?;
+        assert!(decoded.verify(&commitments, &tags, &scheme)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregated_range_proof_grows_logarithmically() -> Result<()> {
         let params = LelantusParameters::default();
-        let commitment = Commitment {
-            value: vec![1; 32],
-            randomness: vec![2; 32],
+        let scheme = CommitmentScheme::new(&params)?;
+        let proof_over = |count: usize| -> Result<AggregatedRangeProof> {
+            let commitments = (0..count)
+                .map(|i| scheme.commit(i as u64 + 1))
+                .collect::<Result<Vec<_>>>()?;
+            let values: Vec<_> = (0..count).map(|i| i as u64 + 1).collect();
+            let proof = AggregatedRangeProof::create(&commitments, &values, &[], &scheme)?;
+            assert!(proof.verify(&commitments, &[], &scheme)?);
+            Ok(proof)
         };
-        let witness = Witness::new(commitment.clone(), 0, vec![3; 32], vec![4; 32], vec![5; 32]);
 
-        let output = Commitment {
-            value: vec![6; 32],
-            randomness: vec![7; 32],
+        // Doubling the outputs adds one inner-product round, whatever the
+        // bit length
+        let one = proof_over(1)?;
+        let two = proof_over(2)?;
+        assert_eq!(
+            two.inner_product.left.len(),
+            one.inner_product.left.len() + 1
+        );
+        assert_eq!(
+            AggregatedRangeProof::encoded_size(&params, 2, 1)
+                - AggregatedRangeProof::encoded_size(&params, 1, 1),
+            2 * (LENGTH_PREFIX_SIZE + POINT_SIZE)
+        );
+        assert_eq!(
+            two.consensus_encode()?.len(),
+            AggregatedRangeProof::encoded_size(&params, 2, 1)
+        );
+        Ok(())
+    }
+
+    fn pedersen_parameters() -> LelantusParameters {
+        LelantusParameters::default()
+    }
+
+    /// Hash commitments, which have no range proofs
+    fn sha512_parameters() -> LelantusParameters {
+        LelantusParameters {
+            hash_function: "sha512".to_string(),
+            ..LelantusParameters::default()
+        }
+    }
+
+    #[test]
+    fn test_bounded_range_proof() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+
+        for value in [100, 550, 1000] {
+            let commitment = scheme.commit(value)?;
+            let proof = RangeProof::create_bounded(&commitment, value, 100, 1000, &params)?;
+            assert_eq!(proof.bit_length, 10);
+            assert!(proof.verify_bounded(100, 1000, &params)?);
+            assert!(RangeProof::consensus_decode(&proof.consensus_encode()?)?
+                .verify_bounded(100, 1000, &params)?);
+        }
+
+        let commitment = scheme.commit(1001)?;
+        assert!(RangeProof::create_bounded(&commitment, 1001, 100, 1000, &params).is_err());
+        // The prover cannot claim a value the commitment does not hold
+        assert!(RangeProof::create_bounded(&commitment, 1000, 100, 1000, &params).is_err());
+        assert!(
+            RangeProof::create_bounded(&commitment, 1001, 0, 5000, &sha512_parameters()).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounded_range_proof_rejects_other_bounds() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+
+        let commitment = scheme.commit(1001)?;
+        let proof = RangeProof::create_bounded(&commitment, 1001, 100, 2000, &params)?;
+        assert!(!proof.verify_bounded(100, 1000, &params)?);
+
+        // Narrowing the declared bounds and recomputing the digests does not
+        // carry the bound argument over
+        let forged = RangeProof::build(&commitment, 10, 100, 1000, proof.bound_proof.clone());
+        assert!(!forged.verify_bounded(100, 1000, &params)?);
+
+        let mut widened =
+            RangeProof::create_bounded(&scheme.commit(500)?, 500, 100, 1000, &params)?;
+        widened.max_value = 2000;
+        assert!(!widened.verify(&params)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounded_range_proof_rejects_out_of_range_value() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1001)?;
+
+        // Hand-built proof for a commitment to 1001 under [100, 1000], with
+        // the prover's own opening
+        let generators = Generators::new(&params.generator);
+        let bound_proof = generators.build_bound(
+            &commitment.value,
+            1001,
+            &commitment.randomness,
+            100,
+            1000,
+            &mut rand::rngs::OsRng,
+        );
+        let proof = RangeProof::build(&commitment, 10, 100, 1000, bound_proof);
+        assert!(!proof.verify_bounded(100, 1000, &params)?);
+        Ok(())
+    }
+
+    /// Commitment published in place of a spent coin worth `value`
+    fn input(scheme: &CommitmentScheme, value: u64) -> Result<Commitment> {
+        scheme.commit_input_with_rng(&NATIVE_ASSET, value, &mut rand::thread_rng())
+    }
+
+    /// Statement over `inputs` and `outputs` with no nullifiers or binding key
+    fn statement<'a>(
+        inputs: &'a [Commitment],
+        outputs: &'a [Commitment],
+        fee: u64,
+        accumulator_value: &'a [u8],
+    ) -> ZkStatement<'a> {
+        ZkStatement {
+            inputs,
+            nullifiers: &[],
+            outputs,
+            fee,
+            accumulator_value,
+            binding_key: &[],
+        }
+    }
+
+    #[test]
+    fn test_zk_proof_creation() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let inputs = [input(&scheme, 600)?, input(&scheme, 500)?];
+        let outputs = [scheme.commit(700)?, scheme.commit(300)?];
+        let balanced = statement(&inputs, &outputs, 100, &[8; 32]);
+
+        let proof = ZKProof::create(&balanced, &params)?;
+        proof.check_encoding()?;
+        assert_eq!(proof.anonymity_set_size, params.anonymity_set_size() as u64);
+        let decoded = ZKProof::consensus_decode(&proof.consensus_encode()?)?;
+        assert!(decoded.verify(&balanced, &params)?);
+
+        // A tampered fee no longer balances the commitments
+        for fee in [0, 99, 101, u64::MAX] {
+            assert!(!decoded.verify(&statement(&inputs, &outputs, fee, &[8; 32]), &params)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_verification() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let inputs = [input(&scheme, 1_000)?];
+        let outputs = [scheme.commit(900)?];
+
+        let proof = ZKProof::create(&statement(&inputs, &outputs, 100, &[8; 32]), &params)?;
+        assert!(proof.verify(&statement(&inputs, &outputs, 100, &[8; 32]), &params)?);
+
+        // Fee, accumulator state and binding key are bound by the challenge
+        assert!(!proof.verify(&statement(&inputs, &outputs, 101, &[8; 32]), &params)?);
+        assert!(!proof.verify(&statement(&inputs, &outputs, 100, &[9; 32]), &params)?);
+        let rebound = ZkStatement {
+            binding_key: b"key",
+            ..statement(&inputs, &outputs, 100, &[8; 32])
+        };
+        assert!(!proof.verify(&rebound, &params)?);
+
+        // Hash commitments cannot be balanced
+        assert!(matches!(
+            ZKProof::create(
+                &statement(&inputs, &outputs, 100, &[8; 32]),
+                &sha512_parameters()
+            ),
+            Err(LelantusError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            proof.verify(
+                &statement(&inputs, &outputs, 100, &[8; 32]),
+                &sha512_parameters()
+            ),
+            Err(LelantusError::InvalidParameter(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_binds_nullifiers() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let inputs = [input(&scheme, 600)?, input(&scheme, 500)?];
+        let outputs = [scheme.commit(1_000)?];
+        let nullifiers = [
+            SerialNumber::from_bytes(vec![5; 64])?,
+            SerialNumber::from_bytes(vec![6; 64])?,
+        ];
+        let bound = ZkStatement {
+            nullifiers: &nullifiers,
+            ..statement(&inputs, &outputs, 100, &[8; 32])
         };
+        let proof = ZKProof::create(&bound, &params)?;
+        assert!(proof.verify(&bound, &params)?);
+
+        // Swapped or replaced nullifiers break the challenge
+        let swapped = [nullifiers[1].clone(), nullifiers[0].clone()];
+        assert!(!proof.verify(
+            &ZkStatement {
+                nullifiers: &swapped,
+                ..bound
+            },
+            &params
+        )?);
+        let replaced = [
+            nullifiers[0].clone(),
+            SerialNumber::from_bytes(vec![7; 64])?,
+        ];
+        assert!(!proof.verify(
+            &ZkStatement {
+                nullifiers: &replaced,
+                ..bound
+            },
+            &params
+        )?);
+        Ok(())
+    }
 
-        let proof = ZKProof::create(&[(commitment, witness)], &[output], 100, &params);
+    #[test]
+    fn test_zk_proof_rejects_shifted_commitment_bytes() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let input = input(&scheme, 1_000)?;
+        let output = scheme.commit(900)?;
+        let proof = ZKProof::create(
+            &statement(
+                core::slice::from_ref(&input),
+                core::slice::from_ref(&output),
+                100,
+                &[8; 32],
+            ),
+            &params,
+        )?;
+
+        // Same concatenated bytes, split differently between input and output
+        let mut shifted_input = input.clone();
+        let moved = shifted_input.value.pop();
+        let mut shifted_output = output.clone();
+        shifted_output.value.splice(0..0, moved);
+        assert!(!matches!(
+            proof.verify(
+                &statement(&[shifted_input], &[shifted_output], 100, &[8; 32]),
+                &params
+            ),
+            Ok(true)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_balance() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let inputs = [input(&scheme, 1_000)?];
+        let outputs = [scheme.commit(900)?];
 
-        assert!(proof.is_ok());
+        let balanced = statement(&inputs, &outputs, 100, &[8; 32]);
+        let proof = ZKProof::create(&balanced, &params)?;
+        proof.check_encoding()?;
+        assert!(proof.verify(&balanced, &params)?);
+        assert!(!proof.verify(&statement(&inputs, &outputs, 99, &[8; 32]), &params)?);
+
+        // Outputs worth more than the inputs cannot be proven
+        let inflated = [scheme.commit(1_000)?];
+        assert!(matches!(
+            ZKProof::create(&statement(&inputs, &inflated, 100, &[8; 32]), &params),
+            Err(LelantusError::BalanceMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_rejects_forged_excess() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let inputs = [input(&scheme, 1_000)?];
+        let outputs = [scheme.commit(900)?];
+        let balanced = statement(&inputs, &outputs, 100, &[8; 32]);
+        let excess = ZKProof::blinding_excess(&balanced);
+        assert!(ZKProof::prove_excess(&balanced, &params, excess)?.verify(&balanced, &params)?);
+
+        // Published commitments carry no openings, so a third party can only
+        // guess the excess
+        let public = |commitments: &[Commitment]| -> Vec<Commitment> {
+            commitments
+                .iter()
+                .map(|c| Commitment::from_value(c.value.clone()))
+                .collect()
+        };
+        let (public_inputs, public_outputs) = (public(&inputs), public(&outputs));
+        let published = statement(&public_inputs, &public_outputs, 100, &[8; 32]);
+        let guessed = ZKProof::blinding_excess(&published);
+        let forged = ZKProof::prove_excess(&published, &params, guessed)?;
+        forged.check_encoding()?;
+        assert!(!forged.verify(&published, &params)?);
+        assert!(matches!(
+            ZKProof::create(&published, &params),
+            Err(LelantusError::BalanceMismatch)
+        ));
+
+        // Knowing every blinding does not open a value excess
+        let inflated_outputs = [scheme.commit(1_000)?];
+        let inflated = statement(&inputs, &inflated_outputs, 100, &[8; 32]);
+        let excess = ZKProof::blinding_excess(&inflated);
+        assert!(!ZKProof::prove_excess(&inflated, &params, excess)?.verify(&inflated, &params)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_rejects_cross_asset_balance() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let rng = &mut rand::thread_rng();
+        let token = [7; 32];
+        let inputs = [
+            input(&scheme, 1_000)?,
+            scheme.commit_input_with_rng(&token, 500, rng)?,
+        ];
+
+        // Each asset balances on its own
+        let outputs = [
+            scheme.commit(900)?,
+            scheme.commit_asset_with_rng(&token, 500, rng)?,
+        ];
+        let balanced = statement(&inputs, &outputs, 100, &[8; 32]);
+        assert!(ZKProof::create(&balanced, &params)?.verify(&balanced, &params)?);
+
+        // 100 token units turned into native coin: the total still balances
+        let outputs = [
+            scheme.commit(1_000)?,
+            scheme.commit_asset_with_rng(&token, 400, rng)?,
+        ];
+        let cross_asset = statement(&inputs, &outputs, 100, &[8; 32]);
+        assert!(matches!(
+            ZKProof::create(&cross_asset, &params),
+            Err(LelantusError::BalanceMismatch)
+        ));
+        let excess = ZKProof::blinding_excess(&cross_asset);
+        assert!(
+            !ZKProof::prove_excess(&cross_asset, &params, excess)?.verify(&cross_asset, &params)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_rejects_fabricated_response() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let inputs = [input(&scheme, 1_000)?];
+        let outputs = [scheme.commit(900)?];
+        let mut proof = ZKProof::create(&statement(&inputs, &outputs, 100, &[8; 32]), &params)?;
+
+        // A well-formed response that does not come from the excess
+        let seven = Scalar::from(7u64);
+        proof.response = hex::encode(pedersen::encode_mask_response(seven, seven)).into_bytes();
+
+        proof.check_encoding()?;
+        assert!(!proof.verify(&statement(&inputs, &outputs, 100, &[8; 32]), &params)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_with_seeded_rng() -> Result<()> {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let inputs = [input(&scheme, 1_000)?];
+        let outputs = [scheme.commit(900)?];
+        let prove = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            ZKProof::create_with_rng(
+                &statement(&inputs, &outputs, 100, &[8; 32]),
+                &params,
+                &mut rng,
+            )
+        };
+
+        let proof = prove(7)?;
+        assert_eq!(proof.consensus_encode()?, prove(7)?.consensus_encode()?);
+
+        let other = prove(8)?;
+        assert_ne!(proof.proof_data, other.proof_data);
+        assert_ne!(proof.response, other.response);
+        for proof in [proof, other] {
+            assert!(proof.verify(&statement(&inputs, &outputs, 100, &[8; 32]), &params)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_zk_proof_privacy_level_mismatch() -> Result<()> {
+        let enhanced = LelantusParameters {
+            privacy_level: PrivacyLevel::Enhanced,
+            ..pedersen_parameters()
+        };
+        let standard = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&standard)?;
+        let inputs = [input(&scheme, 1_000)?];
+        let outputs = [scheme.commit(900)?];
+        let statement = statement(&inputs, &outputs, 100, &[8; 32]);
+        let mut proof = ZKProof::create(&statement, &enhanced)?;
+        assert!(proof.verify(&statement, &enhanced)?);
+
+        assert!(matches!(
+            proof.verify(&statement, &standard),
+            Err(LelantusError::PrivacyLevelMismatch)
+        ));
+
+        // Relabelling the proof breaks the challenge
+        proof.anonymity_set_size = standard.anonymity_set_size() as u64;
+        assert!(!proof.verify(&statement, &standard)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_encoding() -> Result<()> {
+        let params = pedersen_parameters();
+        let scheme = CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1_000)?;
+
+        let mut range_proof = RangeProof::create(&commitment, 1_000, &params)?;
+        range_proof.check_encoding()?;
+
+        // A missing bit commitment is caught before any verification
+        let mut short = range_proof.clone();
+        short.bound_proof.lower.pop();
+        assert!(matches!(
+            short.check_encoding(),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        range_proof.proof_data.pop();
+        assert!(matches!(
+            range_proof.check_encoding(),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        range_proof.proof_data.extend_from_slice(b"00");
+        assert!(matches!(
+            range_proof.check_encoding(),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        let inputs = [input(&scheme, 1_000)?];
+        let zk_proof = ZKProof::create(&statement(&inputs, &[], 1_000, &[8; 32]), &params)?;
+        zk_proof.check_encoding()?;
+
+        let mut truncated = zk_proof.clone();
+        truncated.challenge.truncate(64);
+        assert!(matches!(
+            truncated.check_encoding(),
+            Err(LelantusError::InvalidProof)
+        ));
+
+        let mut non_hex = zk_proof;
+        non_hex.response[0] = b'z';
+        assert!(matches!(
+            non_hex.check_encoding(),
+            Err(LelantusError::InvalidProof)
+        ));
+        Ok(())
     }
 }
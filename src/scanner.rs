@@ -0,0 +1,153 @@
+//! Wallet scanning for received coins
+//!
+//! A sender seals the amount and randomness of each payment to the
+//! recipient's view key with [`JoinSplit::set_amount_note`]; neither is
+//! published otherwise. A [`Scanner`] holding that view key trial-decrypts
+//! the amount notes of every output it is shown, and returns a
+//! [`CoinRecord`] for each output that opens under the key and whose
+//! commitment opens to the recovered amount and randomness.
+
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::errors::Result;
+use crate::joinsplit::JoinSplit;
+use crate::keys::ViewKey;
+use crate::parameters::LelantusParameters;
+
+/// Coin recovered from a JoinSplit output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoinRecord {
+    /// Output commitment, with the randomness recovered from its note
+    pub commitment: Commitment,
+
+    /// Amount committed to
+    pub amount: u64,
+
+    /// Commitment randomness
+    pub randomness: Vec<u8>,
+
+    /// Id of the JoinSplit carrying the output
+    pub txid: [u8; 32],
+
+    /// Position of the output in the JoinSplit
+    pub output_index: usize,
+
+    /// Memo attached for the recipient, if any
+    pub memo: Option<Vec<u8>>,
+}
+
+/// Finds the outputs belonging to a view key
+#[derive(Debug)]
+pub struct Scanner {
+    view_key: ViewKey,
+    commitment_scheme: CommitmentScheme,
+}
+
+impl Scanner {
+    /// Scan for outputs sealed to `view_key` under `parameters`
+    pub fn new(parameters: &LelantusParameters, view_key: &ViewKey) -> Result<Self> {
+        Ok(Self {
+            view_key: view_key.clone(),
+            commitment_scheme: CommitmentScheme::new(parameters)?,
+        })
+    }
+
+    /// Recover the coins a single JoinSplit pays to the view key
+    ///
+    /// Outputs whose note does not open, or opens to an amount and
+    /// randomness the commitment does not commit to, are skipped.
+    pub fn scan_joinsplit(&self, joinsplit: &JoinSplit) -> Result<Vec<CoinRecord>> {
+        let txid = joinsplit.txid()?;
+        let mut records = Vec::new();
+        for output_index in 0..joinsplit.outputs.len() {
+            let (amount, commitment) = match joinsplit.open_output(output_index, &self.view_key)? {
+                Some(opened) => opened,
+                None => continue,
+            };
+            if !self.commitment_scheme.verify(&commitment, amount)? {
+                continue;
+            }
+
+            records.push(CoinRecord {
+                randomness: commitment.randomness.clone(),
+                commitment,
+                amount,
+                txid,
+                output_index,
+                memo: joinsplit.decrypt_memo(output_index, &self.view_key)?,
+            });
+        }
+        Ok(records)
+    }
+
+    /// Recover coins from a stream of JoinSplits, in stream order
+    pub fn scan<'a, I>(&'a self, joinsplits: I) -> impl Iterator<Item = Result<CoinRecord>> + 'a
+    where
+        I: IntoIterator<Item = JoinSplit>,
+        I::IntoIter: 'a,
+    {
+        joinsplits
+            .into_iter()
+            .flat_map(move |joinsplit| match self.scan_joinsplit(&joinsplit) {
+                Ok(records) => records.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e)],
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LelantusState;
+
+    #[test]
+    fn test_scanner_finds_own_outputs() -> Result<()> {
+        let params = LelantusParameters::default();
+        let state = LelantusState::new(params.clone())?;
+        let recipient = ViewKey::generate();
+        let other = ViewKey::generate();
+
+        let coins = state.mint_batch(&[5_000, 3_000])?;
+        let mut first = state.create_joinsplit(vec![coins[0].clone()], vec![3_000, 1_900], 100)?;
+        first.set_amount_note(0, 3_000, &recipient)?;
+        first.set_memo(0, b"thanks", &recipient)?;
+        first.set_amount_note(1, 1_900, &other)?;
+        let mut second = state.create_joinsplit(vec![coins[1].clone()], vec![2_900], 100)?;
+        second.set_amount_note(0, 2_900, &recipient)?;
+        assert!(state.verify_joinsplit(&first)?);
+        let unrelated = state.create_joinsplit(state.mint_batch(&[800])?, vec![700], 100)?;
+
+        // Scan the JoinSplits as published, without their openings
+        let published = [&first, &unrelated, &second]
+            .into_iter()
+            .map(|joinsplit| JoinSplit::consensus_decode(&joinsplit.consensus_encode()?))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(published[0].outputs[0].randomness.is_empty());
+
+        let scanner = Scanner::new(&params, &recipient)?;
+        let records = scanner.scan(published).collect::<Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].amount, 3_000);
+        assert_eq!(records[0].commitment, first.outputs[0]);
+        assert_eq!(records[0].randomness, first.outputs[0].randomness);
+        assert_eq!(records[0].txid, first.txid()?);
+        assert_eq!(records[0].memo, Some(b"thanks".to_vec()));
+        assert_eq!((records[1].amount, records[1].output_index), (2_900, 0));
+        assert_eq!(records[1].memo, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scanner_rejects_wrong_amount() -> Result<()> {
+        let params = LelantusParameters::default();
+        let state = LelantusState::new(params.clone())?;
+        let recipient = ViewKey::generate();
+
+        // A note claiming more than the output commits to is not a coin
+        let mut joinsplit =
+            state.create_joinsplit(state.mint_batch(&[2_000])?, vec![1_900], 100)?;
+        joinsplit.set_amount_note(0, 19_000, &recipient)?;
+        let scanner = Scanner::new(&params, &recipient)?;
+        assert!(scanner.scan_joinsplit(&joinsplit)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,534 @@
+//! Byte layouts of the consensus encodings
+//!
+//! Each wire type describes its encoding as an ordered list of fields so that
+//! integrators can parse it without reading the encoder, and so that any drift
+//! between the description and `consensus_encode` is caught by tests.
+
+use crate::commitment::{AccumulatorElement, Commitment};
+use crate::errors::{LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use crate::proof::{AggregatedRangeProof, RangeProof, ZKProof};
+use crate::serialization::binary::{LENGTH_PREFIX_SIZE, U64_SIZE};
+use serde::{Deserialize, Serialize};
+
+/// Encoding of a single field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldEncoding {
+    /// Fixed-width little-endian `u64`
+    U64,
+    /// Little-endian `u32` length followed by that many bytes
+    Bytes,
+    /// `u64` count followed by that many `u64` values
+    U64List,
+    /// `u64` count followed by that many records of `n` `Bytes` fields each
+    BytesList(usize),
+}
+
+/// Description of one field in a layout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldSpec {
+    /// Field name
+    pub name: &'static str,
+
+    /// Field encoding
+    pub encoding: FieldEncoding,
+}
+
+/// Location of a field's payload within an encoded value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldSpan {
+    /// Field name
+    pub name: &'static str,
+
+    /// Offset of the payload (after any length prefix or list count)
+    pub offset: usize,
+
+    /// Size of the payload in bytes
+    pub size: usize,
+}
+
+/// Binary layout of a wire type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldLayout {
+    /// Name of the described type
+    pub type_name: &'static str,
+
+    /// Fields in encoding order
+    pub fields: Vec<FieldSpec>,
+}
+
+impl FieldLayout {
+    fn new(type_name: &'static str, fields: &[(&'static str, FieldEncoding)]) -> Self {
+        Self {
+            type_name,
+            fields: fields
+                .iter()
+                .map(|&(name, encoding)| FieldSpec { name, encoding })
+                .collect(),
+        }
+    }
+
+    /// Smallest possible encoded size (all byte fields and lists empty)
+    pub fn min_size(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| match field.encoding {
+                FieldEncoding::U64 | FieldEncoding::U64List | FieldEncoding::BytesList(_) => {
+                    U64_SIZE
+                }
+                FieldEncoding::Bytes => LENGTH_PREFIX_SIZE,
+            })
+            .sum()
+    }
+
+    /// Compute the offset and size of every field in an encoded value
+    pub fn field_spans(&self, data: &[u8]) -> Result<Vec<FieldSpan>> {
+        let mut spans = Vec::with_capacity(self.fields.len());
+        let mut offset = 0usize;
+
+        for field in &self.fields {
+            let (payload_offset, end) = match field.encoding {
+                FieldEncoding::U64 => (offset, offset + slice_at(data, offset, U64_SIZE)?.len()),
+                FieldEncoding::Bytes => (offset + LENGTH_PREFIX_SIZE, bytes_end(data, offset)?),
+                FieldEncoding::U64List => {
+                    let payload = offset + U64_SIZE;
+                    let size = list_count(data, offset)?
+                        .checked_mul(U64_SIZE)
+                        .ok_or_else(truncated)?;
+                    (payload, payload + slice_at(data, payload, size)?.len())
+                }
+                FieldEncoding::BytesList(fields) => {
+                    let payload = offset + U64_SIZE;
+                    let items = list_count(data, offset)?
+                        .checked_mul(fields)
+                        .ok_or_else(truncated)?;
+                    let end = (0..items).try_fold(payload, |end, _| bytes_end(data, end))?;
+                    (payload, end)
+                }
+            };
+
+            spans.push(FieldSpan {
+                name: field.name,
+                offset: payload_offset,
+                size: end - payload_offset,
+            });
+            offset = end;
+        }
+
+        if offset != data.len() {
+            return Err(LelantusError::SerializationError(
+                "trailing bytes after value".to_string(),
+            ));
+        }
+
+        Ok(spans)
+    }
+}
+
+fn truncated() -> LelantusError {
+    LelantusError::SerializationError("unexpected end of input".to_string())
+}
+
+/// Bytes at `offset..offset + size`, if `data` holds them
+fn slice_at(data: &[u8], offset: usize, size: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(size)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(truncated)
+}
+
+/// End of the length-prefixed byte field at `offset`
+fn bytes_end(data: &[u8], offset: usize) -> Result<usize> {
+    let mut len = [0u8; LENGTH_PREFIX_SIZE];
+    len.copy_from_slice(slice_at(data, offset, LENGTH_PREFIX_SIZE)?);
+    let payload = offset + LENGTH_PREFIX_SIZE;
+    Ok(payload + slice_at(data, payload, u32::from_le_bytes(len) as usize)?.len())
+}
+
+/// Item count of the list at `offset`
+fn list_count(data: &[u8], offset: usize) -> Result<usize> {
+    let mut count = [0u8; U64_SIZE];
+    count.copy_from_slice(slice_at(data, offset, U64_SIZE)?);
+    usize::try_from(u64::from_le_bytes(count)).map_err(|_| truncated())
+}
+
+impl Commitment {
+    /// Layout of [`consensus_encode`](Self::consensus_encode) for a commitment
+    pub fn byte_layout() -> FieldLayout {
+        FieldLayout::new(
+            "Commitment",
+            &[
+                ("value", FieldEncoding::Bytes),
+                ("randomness", FieldEncoding::Bytes),
+            ],
+        )
+    }
+}
+
+impl AccumulatorElement {
+    /// Layout of [`consensus_encode`](Self::consensus_encode) for an accumulator element
+    pub fn byte_layout() -> FieldLayout {
+        FieldLayout::new("AccumulatorElement", &[("value", FieldEncoding::Bytes)])
+    }
+}
+
+impl RangeProof {
+    /// Layout of [`consensus_encode`](Self::consensus_encode) for a range proof
+    pub fn byte_layout() -> FieldLayout {
+        FieldLayout::new(
+            "RangeProof",
+            &[
+                ("proof_data", FieldEncoding::Bytes),
+                ("commitment", FieldEncoding::Bytes),
+                ("bit_length", FieldEncoding::U64),
+                ("min_value", FieldEncoding::U64),
+                ("max_value", FieldEncoding::U64),
+                ("bound_proof", FieldEncoding::Bytes),
+            ],
+        )
+    }
+}
+
+impl AggregatedRangeProof {
+    /// Layout of [`consensus_encode`](Self::consensus_encode) for an
+    /// aggregated range proof
+    ///
+    /// `asset_terms` holds `t1`, `t2`, `evaluation` and `mask_response` of
+    /// each value generator group in turn.
+    pub fn byte_layout() -> FieldLayout {
+        FieldLayout::new(
+            "AggregatedRangeProof",
+            &[
+                ("bit_length", FieldEncoding::U64),
+                ("output_count", FieldEncoding::U64),
+                ("bit_commitment", FieldEncoding::Bytes),
+                ("blinding_commitment", FieldEncoding::Bytes),
+                ("asset_terms", FieldEncoding::BytesList(4)),
+                ("blinding_response", FieldEncoding::Bytes),
+                ("inner_product_left", FieldEncoding::BytesList(1)),
+                ("inner_product_right", FieldEncoding::BytesList(1)),
+                ("inner_product_a", FieldEncoding::Bytes),
+                ("inner_product_b", FieldEncoding::Bytes),
+            ],
+        )
+    }
+}
+
+impl ZKProof {
+    /// Layout of [`consensus_encode`](Self::consensus_encode) for a zero-knowledge proof
+    pub fn byte_layout() -> FieldLayout {
+        FieldLayout::new(
+            "ZKProof",
+            &[
+                ("proof_data", FieldEncoding::Bytes),
+                ("challenge", FieldEncoding::Bytes),
+                ("response", FieldEncoding::Bytes),
+                ("anonymity_set_size", FieldEncoding::U64),
+            ],
+        )
+    }
+}
+
+impl JoinSplit {
+    /// Layout of [`consensus_encode`](Self::consensus_encode) for a JoinSplit
+    ///
+    /// Commitments are written without their randomness, and each proof
+    /// list item is that proof's own consensus encoding.
+    pub fn byte_layout() -> FieldLayout {
+        FieldLayout::new(
+            "JoinSplit",
+            &[
+                ("inputs", FieldEncoding::BytesList(1)),
+                ("outputs", FieldEncoding::BytesList(1)),
+                ("range_proofs", FieldEncoding::BytesList(1)),
+                ("aggregated_range_proof", FieldEncoding::BytesList(1)),
+                ("asset_proof", FieldEncoding::BytesList(1)),
+                ("zk_proof", FieldEncoding::Bytes),
+                ("anonymity_sets", FieldEncoding::U64List),
+                ("membership_proofs", FieldEncoding::BytesList(1)),
+                ("fee", FieldEncoding::U64),
+                ("nullifiers", FieldEncoding::BytesList(1)),
+                ("change_tags", FieldEncoding::BytesList(1)),
+                ("memos", FieldEncoding::BytesList(1)),
+                ("amount_notes", FieldEncoding::BytesList(1)),
+                ("ephemeral_keys", FieldEncoding::BytesList(1)),
+                ("binding_key", FieldEncoding::Bytes),
+                ("binding_signature", FieldEncoding::BytesList(1)),
+                ("padding", FieldEncoding::Bytes),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::CommitmentScheme;
+    use crate::parameters::LelantusParameters;
+    use crate::serialization::{binary, LelantusSerializable};
+    use crate::LelantusState;
+
+    fn field<'a>(data: &'a [u8], spans: &[FieldSpan], name: &str) -> &'a [u8] {
+        let span = spans
+            .iter()
+            .find(|s| s.name == name)
+            .expect("missing field");
+        &data[span.offset..span.offset + span.size]
+    }
+
+    #[test]
+    fn test_commitment_layout() -> Result<()> {
+        let commitment = Commitment {
+            value: vec![1; 128],
+            randomness: vec![2; 64],
+        };
+
+        let bytes = commitment.consensus_encode()?;
+        let spans = Commitment::byte_layout().field_spans(&bytes)?;
+        assert_eq!(field(&bytes, &spans, "value"), &commitment.value[..]);
+        assert_eq!(
+            field(&bytes, &spans, "randomness"),
+            &commitment.randomness[..]
+        );
+
+        let element = commitment.to_element()?;
+        let bytes = element.consensus_encode()?;
+        let spans = AccumulatorElement::byte_layout().field_spans(&bytes)?;
+        assert_eq!(field(&bytes, &spans, "value"), &element.value[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_layouts() -> Result<()> {
+        let params = LelantusParameters::default();
+        let commitment = CommitmentScheme::new(&params)?.commit(1000)?;
+
+        let range_proof = RangeProof::create(&commitment, 1000, &params)?;
+        let bytes = range_proof.consensus_encode()?;
+        let spans = RangeProof::byte_layout().field_spans(&bytes)?;
+        assert_eq!(
+            field(&bytes, &spans, "proof_data"),
+            &range_proof.proof_data[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "commitment"),
+            &range_proof.commitment[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "bit_length"),
+            &(range_proof.bit_length as u64).to_le_bytes()[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "min_value"),
+            &range_proof.min_value.to_le_bytes()[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "max_value"),
+            &range_proof.max_value.to_le_bytes()[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "bound_proof"),
+            &range_proof.bound_proof.to_bytes()?[..]
+        );
+
+        let zk_proof = ZKProof {
+            proof_data: vec![5; 128],
+            challenge: vec![6; 128],
+            response: vec![7; 128],
+            anonymity_set_size: 64,
+        };
+        let bytes = zk_proof.consensus_encode()?;
+        let spans = ZKProof::byte_layout().field_spans(&bytes)?;
+        assert_eq!(
+            field(&bytes, &spans, "proof_data"),
+            &zk_proof.proof_data[..]
+        );
+        assert_eq!(field(&bytes, &spans, "challenge"), &zk_proof.challenge[..]);
+        assert_eq!(field(&bytes, &spans, "response"), &zk_proof.response[..]);
+        assert_eq!(
+            field(&bytes, &spans, "anonymity_set_size"),
+            &zk_proof.anonymity_set_size.to_le_bytes()[..]
+        );
+        Ok(())
+    }
+
+    /// Payload of a list of `Bytes` items, as laid out after its count
+    fn list<T: AsRef<[u8]>>(items: impl IntoIterator<Item = T>) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for item in items {
+            binary::put_bytes(&mut buf, item.as_ref())?;
+        }
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_joinsplit_layouts() -> Result<()> {
+        let state = LelantusState::new(LelantusParameters::default())?;
+        let joinsplit = state.create_joinsplit(state.mint_batch(&[1_000])?, vec![900], 100)?;
+
+        let bytes = joinsplit.consensus_encode()?;
+        let spans = JoinSplit::byte_layout().field_spans(&bytes)?;
+        assert_eq!(spans.len(), JoinSplit::byte_layout().fields.len());
+        let values = |commitments: &[Commitment]| list(commitments.iter().map(|c| &c.value));
+        assert_eq!(field(&bytes, &spans, "inputs"), values(&joinsplit.inputs)?);
+        assert_eq!(
+            field(&bytes, &spans, "outputs"),
+            values(&joinsplit.outputs)?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "range_proofs"),
+            list(
+                joinsplit
+                    .proof
+                    .range_proofs
+                    .iter()
+                    .map(RangeProof::consensus_encode)
+                    .collect::<Result<Vec<_>>>()?
+            )?
+        );
+        let aggregated = joinsplit
+            .proof
+            .aggregated_range_proof
+            .as_ref()
+            .expect("aggregated range proof");
+        assert_eq!(
+            field(&bytes, &spans, "aggregated_range_proof"),
+            list([aggregated.consensus_encode()?])?
+        );
+        assert_eq!(field(&bytes, &spans, "asset_proof"), list::<Vec<u8>>([])?);
+        assert_eq!(
+            field(&bytes, &spans, "zk_proof"),
+            joinsplit.proof.zk_proof.consensus_encode()?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "anonymity_sets"),
+            joinsplit
+                .proof
+                .anonymity_sets
+                .iter()
+                .flat_map(|set| set.to_le_bytes())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            field(&bytes, &spans, "membership_proofs"),
+            list(
+                joinsplit
+                    .proof
+                    .membership_proofs
+                    .iter()
+                    .map(|proof| proof.consensus_encode())
+                    .collect::<Result<Vec<_>>>()?
+            )?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "fee"),
+            &joinsplit.fee.to_le_bytes()[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "nullifiers"),
+            list(joinsplit.nullifiers.iter().map(|n| n.as_bytes()))?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "change_tags"),
+            list(&joinsplit.change_tags)?
+        );
+        assert_eq!(field(&bytes, &spans, "memos"), list(&joinsplit.memos)?);
+        assert_eq!(
+            field(&bytes, &spans, "amount_notes"),
+            list(&joinsplit.amount_notes)?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "ephemeral_keys"),
+            list(&joinsplit.ephemeral_keys)?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "binding_key"),
+            &joinsplit.binding_key[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "binding_signature"),
+            list(
+                joinsplit
+                    .binding_signature
+                    .iter()
+                    .map(|signature| signature.consensus_encode())
+                    .collect::<Result<Vec<_>>>()?
+            )?
+        );
+        assert_eq!(field(&bytes, &spans, "padding"), &joinsplit.padding[..]);
+
+        let bytes = aggregated.consensus_encode()?;
+        let spans = AggregatedRangeProof::byte_layout().field_spans(&bytes)?;
+        assert_eq!(
+            field(&bytes, &spans, "bit_length"),
+            &(aggregated.bit_length as u64).to_le_bytes()[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "output_count"),
+            &(aggregated.output_count as u64).to_le_bytes()[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "bit_commitment"),
+            &aggregated.bit_commitment[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "blinding_commitment"),
+            &aggregated.blinding_commitment[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "asset_terms"),
+            list(aggregated.asset_terms.iter().flat_map(|terms| {
+                [
+                    &terms.t1,
+                    &terms.t2,
+                    &terms.evaluation,
+                    &terms.mask_response,
+                ]
+            }))?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "blinding_response"),
+            &aggregated.blinding_response[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "inner_product_left"),
+            list(&aggregated.inner_product.left)?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "inner_product_right"),
+            list(&aggregated.inner_product.right)?
+        );
+        assert_eq!(
+            field(&bytes, &spans, "inner_product_a"),
+            &aggregated.inner_product.a[..]
+        );
+        assert_eq!(
+            field(&bytes, &spans, "inner_product_b"),
+            &aggregated.inner_product.b[..]
+        );
+
+        // A truncated proof is rejected
+        assert!(AggregatedRangeProof::byte_layout()
+            .field_spans(&bytes[..bytes.len() - 1])
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_layout_rejects_malformed_input() -> Result<()> {
+        let layout = Commitment::byte_layout();
+        assert_eq!(layout.min_size(), 2 * LENGTH_PREFIX_SIZE);
+
+        let bytes = Commitment {
+            value: vec![1; 16],
+            randomness: vec![2; 16],
+        }
+        .consensus_encode()?;
+        assert!(layout.field_spans(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut padded = bytes;
+        padded.push(0);
+        assert!(layout.field_spans(&padded).is_err());
+        Ok(())
+    }
+}
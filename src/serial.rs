@@ -0,0 +1,111 @@
+//! Serial numbers for double-spend detection
+//!
+//! A spend reveals the serial number of each coin it consumes. Serial
+//! numbers are the nullifiers of the [`nullifier`](crate::nullifier) module
+//! under their protocol name: keyed by the owner's spend key and committed
+//! inside the coin, so a coin always yields the same serial number, a spend
+//! cannot reveal any other, and it cannot be linked back to its commitment
+//! without the owner's keys.
+
+#[cfg(feature = "std")]
+use crate::commitment::Commitment;
+use crate::errors::{ErrorContext, LelantusError, Result};
+#[cfg(feature = "std")]
+use crate::keys::SpendKey;
+#[cfg(feature = "std")]
+use crate::nullifier::{derive_nullifier, opening_nullifier};
+use crate::prelude::*;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Size of a nullifier in bytes
+pub const NULLIFIER_SIZE: usize = 64;
+
+/// Serial number revealed when a coin is spent
+///
+/// Encodes exactly like the raw nullifier bytes it wraps.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(transparent)]
+pub struct SerialNumber(Vec<u8>);
+
+impl SerialNumber {
+    /// Derive the serial number of a coin owned by `spend_key`
+    #[cfg(feature = "std")]
+    pub fn from_coin(spend_key: &SpendKey, commitment: &Commitment) -> Result<Self> {
+        derive_nullifier(spend_key, commitment).map(Self)
+    }
+
+    /// Serial number committed in a coin opening
+    #[cfg(feature = "std")]
+    pub(crate) fn from_opening(opening: &Commitment) -> Result<Self> {
+        opening_nullifier(opening).map(Self)
+    }
+
+    /// Wrap raw serial number bytes
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        if bytes.len() != NULLIFIER_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("serial_number")
+                    .expected(NULLIFIER_SIZE)
+                    .actual(bytes.len()),
+            ));
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Raw serial number bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SerialNumber {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<SerialNumber> for Vec<u8> {
+    fn from(serial: SerialNumber) -> Self {
+        serial.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_number_from_coin() -> Result<()> {
+        let key = SpendKey::from_seed(&[2; 32])?;
+        let commitment = Commitment {
+            value: vec![1; 128],
+            randomness: key.full_viewing_key()?.coin_randomness(64)?,
+        };
+        let serial = SerialNumber::from_coin(&key, &commitment)?;
+        assert_eq!(serial, SerialNumber::from_coin(&key, &commitment)?);
+        assert_eq!(serial.as_bytes(), &derive_nullifier(&key, &commitment)?[..]);
+        assert_eq!(serial, SerialNumber::from_opening(&key.open(&commitment)?)?);
+        assert_eq!(SerialNumber::from_bytes(serial.clone().into())?, serial);
+        assert!(SerialNumber::from_bytes(vec![0; 32]).is_err());
+
+        // Same encoding as the raw bytes
+        assert_eq!(
+            borsh::to_vec(&serial).ok(),
+            borsh::to_vec(&serial.as_bytes().to_vec()).ok()
+        );
+        Ok(())
+    }
+}
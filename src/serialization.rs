@@ -1,25 +1,174 @@
 //! Serialization utilities for Lelantus
+//!
+//! [`LelantusSerializable::to_bytes`] is the compact binary encoding: a
+//! version byte followed by the Borsh encoding of the value. JSON is only
+//! available with the `json` feature, for debugging.
+//!
+//! [`bech32m`] and [`base58`] encode addresses and other payloads meant to
+//! be copied by hand; their decoders fail with a [`DecodeError`] saying why.
 
 use crate::errors::{LelantusError, Result};
+use crate::prelude::*;
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
-use serde_json;
+
+/// Version byte prefixing the binary encoding
+///
+/// Version 2 added aggregated range proofs to JoinSplit proofs, version 3
+/// binding keys and signatures to JoinSplits, version 4 dropped the
+/// randomness of JoinSplit inputs and outputs, version 5 replaced the
+/// padding of range proofs with bit-decomposition proofs, version 6 made
+/// aggregated range proofs Bulletproofs, and version 7 replaced their asset
+/// ids with blinded asset tags and asset surjection proofs.
+pub const ENCODING_VERSION: u8 = 7;
+
+/// Default upper bound on an encoded value, in bytes
+pub const DEFAULT_MAX_ENCODED_SIZE: usize = 4 << 20;
+
+/// Wire format of a tagged encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SerializationFormat {
+    /// JSON via serde
+    #[cfg(feature = "json")]
+    Json,
+    /// Borsh binary encoding
+    Borsh,
+    /// CBOR via serde
+    Cbor,
+}
+
+impl SerializationFormat {
+    /// One-byte tag prepended to tagged encodings
+    pub fn tag(self) -> u8 {
+        match self {
+            #[cfg(feature = "json")]
+            SerializationFormat::Json => 0x01,
+            SerializationFormat::Borsh => 0x02,
+            SerializationFormat::Cbor => 0x03,
+        }
+    }
+
+    /// Format for a tag byte
+    pub fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            #[cfg(feature = "json")]
+            0x01 => Ok(SerializationFormat::Json),
+            0x02 => Ok(SerializationFormat::Borsh),
+            0x03 => Ok(SerializationFormat::Cbor),
+            other => Err(LelantusError::SerializationError(format!(
+                "unknown format tag {:#04x}",
+                other
+            ))),
+        }
+    }
+
+    /// Detect the format of tagged data
+    pub fn detect(data: &[u8]) -> Result<Self> {
+        let tag = data
+            .first()
+            .ok_or_else(|| LelantusError::SerializationError("empty input".to_string()))?;
+        Self::from_tag(*tag)
+    }
+}
+
+/// Reject encodings longer than `limit` bytes
+fn check_size(len: usize, limit: usize) -> Result<()> {
+    if len > limit {
+        return Err(LelantusError::SerializationError(format!(
+            "encoding of {} bytes exceeds the {} byte limit",
+            len, limit
+        )));
+    }
+    Ok(())
+}
 
 /// Serializable wrapper for Lelantus types
-pub trait LelantusSerializable: Serialize + for<'de> Deserialize<'de> {
-    /// Serialize to bytes
+pub trait LelantusSerializable:
+    Serialize + for<'de> Deserialize<'de> + BorshSerialize + BorshDeserialize
+{
+    /// Largest accepted encoding, including the version or format byte
+    const MAX_ENCODED_SIZE: usize = DEFAULT_MAX_ENCODED_SIZE;
+
+    /// Oldest encoding version whose layout this type still decodes
+    const MIN_ENCODING_VERSION: u8 = 1;
+
+    /// Serialize to the versioned binary encoding
     fn to_bytes(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec(self).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        let mut buf = vec![ENCODING_VERSION];
+        borsh::to_writer(&mut buf, self)
+            .map_err(|e| LelantusError::SerializationError(e.to_string()))?;
+        check_size(buf.len(), Self::MAX_ENCODED_SIZE)?;
+        Ok(buf)
     }
 
-    /// Deserialize from bytes
+    /// Deserialize from the versioned binary encoding
+    ///
+    /// Rejects oversized input, unknown versions and trailing bytes.
     fn from_bytes(data: &[u8]) -> Result<Self> {
-        serde_json::from_slice(data).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        check_size(data.len(), Self::MAX_ENCODED_SIZE)?;
+        let (&version, body) = data
+            .split_first()
+            .ok_or_else(|| LelantusError::SerializationError("empty input".to_string()))?;
+        if !(Self::MIN_ENCODING_VERSION..=ENCODING_VERSION).contains(&version) {
+            return Err(LelantusError::SerializationError(format!(
+                "unsupported encoding version {}",
+                version
+            )));
+        }
+        borsh::from_slice(body).map_err(|e| LelantusError::SerializationError(e.to_string()))
+    }
+
+    /// Serialize in the given format, prefixed with its format tag
+    fn to_bytes_format(&self, format: SerializationFormat) -> Result<Vec<u8>> {
+        let mut buf = vec![format.tag()];
+        let encoded = match format {
+            #[cfg(feature = "json")]
+            SerializationFormat::Json => {
+                serde_json::to_writer(&mut buf, self).map_err(|e| e.to_string())
+            }
+            SerializationFormat::Borsh => {
+                borsh::to_writer(&mut buf, self).map_err(|e| e.to_string())
+            }
+            SerializationFormat::Cbor => {
+                ciborium::into_writer(self, &mut buf).map_err(|e| e.to_string())
+            }
+        };
+        encoded.map_err(LelantusError::SerializationError)?;
+        check_size(buf.len(), Self::MAX_ENCODED_SIZE)?;
+        Ok(buf)
+    }
+
+    /// Deserialize tagged data, requiring it to be in the given format
+    fn from_bytes_format(data: &[u8], format: SerializationFormat) -> Result<Self> {
+        let detected = SerializationFormat::detect(data)?;
+        if detected != format {
+            return Err(LelantusError::SerializationError(format!(
+                "expected {:?} encoding, found {:?}",
+                format, detected
+            )));
+        }
+        Self::from_tagged_bytes(data)
+    }
+
+    /// Deserialize tagged data, detecting the format from its tag
+    fn from_tagged_bytes(data: &[u8]) -> Result<Self> {
+        check_size(data.len(), Self::MAX_ENCODED_SIZE)?;
+        let format = SerializationFormat::detect(data)?;
+        let body = &data[1..];
+        let decoded = match format {
+            #[cfg(feature = "json")]
+            SerializationFormat::Json => serde_json::from_slice(body).map_err(|e| e.to_string()),
+            SerializationFormat::Borsh => borsh::from_slice(body).map_err(|e| e.to_string()),
+            SerializationFormat::Cbor => ciborium::from_reader(body).map_err(|e| e.to_string()),
+        };
+        decoded.map_err(LelantusError::SerializationError)
     }
 }
 
 /// Hex encoding utilities
 pub mod hex_util {
     use crate::errors::Result;
+    use crate::prelude::*;
 
     /// Encode bytes to hex string
     pub fn encode(data: &[u8]) -> String {
@@ -33,7 +182,375 @@ pub mod hex_util {
     }
 }
 
+/// Why an address or payload string failed to decode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Character outside the encoding's alphabet
+    InvalidCharacter(char),
+
+    /// Upper and lower case letters mixed in one string
+    MixedCase,
+
+    /// No separator between the prefix and the data
+    MissingSeparator,
+
+    /// String not in the encoding's format for another reason
+    Malformed(String),
+
+    /// Malformed human-readable prefix
+    InvalidPrefix(String),
+
+    /// Well-formed prefix other than the expected one
+    UnexpectedPrefix {
+        /// Prefix the caller expects
+        expected: String,
+
+        /// Prefix the string carries
+        actual: String,
+    },
+
+    /// Checksum does not match the data
+    InvalidChecksum,
+
+    /// Padding bits left over or not zero
+    InvalidPadding,
+
+    /// Decoded payload of the wrong length
+    InvalidLength {
+        /// Length the payload should have, in bytes
+        expected: usize,
+
+        /// Length the payload has, in bytes
+        actual: usize,
+    },
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::InvalidCharacter(c) => write!(f, "invalid character {:?}", c),
+            Self::MixedCase => f.write_str("mixed case"),
+            Self::MissingSeparator => f.write_str("missing separator"),
+            Self::Malformed(detail) => write!(f, "malformed string: {}", detail),
+            Self::InvalidPrefix(detail) => write!(f, "invalid prefix: {}", detail),
+            Self::UnexpectedPrefix { expected, actual } => {
+                write!(f, "prefix {} is not {}", actual, expected)
+            }
+            Self::InvalidChecksum => f.write_str("invalid checksum"),
+            Self::InvalidPadding => f.write_str("invalid padding"),
+            Self::InvalidLength { expected, actual } => {
+                write!(f, "expected {} bytes, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+impl From<DecodeError> for LelantusError {
+    fn from(error: DecodeError) -> Self {
+        Self::SerializationError(error.to_string())
+    }
+}
+
+/// Bech32m encoding with a validated human-readable prefix
+///
+/// Used for addresses and payment requests. Prefixes must be lowercase
+/// bech32 prefixes; decoding accepts all-lowercase or all-uppercase strings
+/// and rejects any prefix other than the expected one.
+pub mod bech32m {
+    use super::DecodeError;
+    use crate::errors::{LelantusError, Result};
+    use crate::prelude::*;
+    use bech32::primitives::decode::{
+        CharError, CheckedHrpstring, CheckedHrpstringError, UncheckedHrpstringError,
+    };
+    use bech32::{Bech32m, Hrp};
+
+    /// Parse `hrp`, which must be a lowercase bech32 prefix
+    pub fn validate_hrp(hrp: &str) -> core::result::Result<Hrp, DecodeError> {
+        let parsed = Hrp::parse(hrp).map_err(|e| DecodeError::InvalidPrefix(e.to_string()))?;
+        if hrp.bytes().any(|b| b.is_ascii_uppercase()) {
+            return Err(DecodeError::InvalidPrefix(format!(
+                "{} is not lowercase",
+                hrp
+            )));
+        }
+        Ok(parsed)
+    }
+
+    /// Encode `data` under the prefix `hrp`
+    pub fn encode(hrp: &str, data: &[u8]) -> Result<String> {
+        bech32::encode::<Bech32m>(validate_hrp(hrp)?, data)
+            .map_err(|e| LelantusError::SerializationError(e.to_string()))
+    }
+
+    /// Decode a string written by [`encode`] under the prefix `hrp`
+    pub fn decode(hrp: &str, encoded: &str) -> core::result::Result<Vec<u8>, DecodeError> {
+        let expected = validate_hrp(hrp)?;
+        let checked = CheckedHrpstring::new::<Bech32m>(encoded).map_err(|e| match e {
+            CheckedHrpstringError::Parse(UncheckedHrpstringError::Char(e)) => match e {
+                CharError::InvalidChar(c) => DecodeError::InvalidCharacter(c),
+                CharError::MixedCase => DecodeError::MixedCase,
+                CharError::MissingSeparator | CharError::NothingAfterSeparator => {
+                    DecodeError::MissingSeparator
+                }
+                e => DecodeError::Malformed(e.to_string()),
+            },
+            CheckedHrpstringError::Parse(UncheckedHrpstringError::Hrp(e)) => {
+                DecodeError::InvalidPrefix(e.to_string())
+            }
+            CheckedHrpstringError::Checksum(_) => DecodeError::InvalidChecksum,
+            e => DecodeError::Malformed(e.to_string()),
+        })?;
+
+        if checked.hrp() != expected {
+            return Err(DecodeError::UnexpectedPrefix {
+                expected: hrp.to_string(),
+                actual: checked.hrp().to_lowercase(),
+            });
+        }
+        checked
+            .validate_segwit_padding()
+            .map_err(|_| DecodeError::InvalidPadding)?;
+        Ok(checked.byte_iter().collect())
+    }
+}
+
+/// Base58 encoding with a double SHA-256 checksum, for legacy addresses
+///
+/// Uses the Bitcoin alphabet; leading zero bytes become leading `1`s.
+pub mod base58 {
+    use super::DecodeError;
+    use crate::prelude::*;
+    use sha2::{Digest, Sha256};
+
+    /// Base58 alphabet, omitting `0`, `O`, `I` and `l`
+    const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    /// Size in bytes of the checksum [`encode_check`] appends
+    pub const CHECKSUM_SIZE: usize = 4;
+
+    /// First [`CHECKSUM_SIZE`] bytes of SHA-256(SHA-256(`data`))
+    fn checksum(data: &[u8]) -> [u8; CHECKSUM_SIZE] {
+        let digest = Sha256::digest(Sha256::digest(data));
+        let mut checksum = [0u8; CHECKSUM_SIZE];
+        checksum.copy_from_slice(&digest[..CHECKSUM_SIZE]);
+        checksum
+    }
+
+    /// Encode bytes without a checksum
+    pub fn encode(data: &[u8]) -> String {
+        let zeros = data.iter().take_while(|&&b| b == 0).count();
+
+        // Base-58 digits, least significant first
+        let mut digits: Vec<u8> = Vec::with_capacity(data.len() * 138 / 100 + 1);
+        for &byte in &data[zeros..] {
+            let mut carry = u32::from(byte);
+            for digit in digits.iter_mut() {
+                carry += u32::from(*digit) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits.push((carry % 58) as u8);
+                carry /= 58;
+            }
+        }
+
+        let mut encoded = String::with_capacity(zeros + digits.len());
+        encoded.extend(core::iter::repeat_n('1', zeros));
+        encoded.extend(
+            digits
+                .iter()
+                .rev()
+                .map(|&digit| char::from(ALPHABET[usize::from(digit)])),
+        );
+        encoded
+    }
+
+    /// Decode a string written by [`encode`]
+    pub fn decode(encoded: &str) -> core::result::Result<Vec<u8>, DecodeError> {
+        let zeros = encoded.chars().take_while(|&c| c == '1').count();
+
+        // Bytes, least significant first
+        let mut bytes: Vec<u8> = Vec::with_capacity(encoded.len() * 733 / 1000 + 1);
+        for c in encoded.chars().skip(zeros) {
+            let mut carry = u8::try_from(c)
+                .ok()
+                .and_then(|b| ALPHABET.iter().position(|&a| a == b))
+                .ok_or(DecodeError::InvalidCharacter(c))? as u32;
+            for byte in bytes.iter_mut() {
+                carry += u32::from(*byte) * 58;
+                *byte = (carry & 0xff) as u8;
+                carry >>= 8;
+            }
+            while carry > 0 {
+                bytes.push((carry & 0xff) as u8);
+                carry >>= 8;
+            }
+        }
+
+        let mut decoded = vec![0u8; zeros];
+        decoded.extend(bytes.iter().rev());
+        Ok(decoded)
+    }
+
+    /// Encode bytes followed by their checksum
+    pub fn encode_check(data: &[u8]) -> String {
+        let mut payload = data.to_vec();
+        payload.extend_from_slice(&checksum(data));
+        encode(&payload)
+    }
+
+    /// Decode a string written by [`encode_check`], verifying its checksum
+    pub fn decode_check(encoded: &str) -> core::result::Result<Vec<u8>, DecodeError> {
+        let mut payload = decode(encoded)?;
+        if payload.len() < CHECKSUM_SIZE {
+            return Err(DecodeError::InvalidLength {
+                expected: CHECKSUM_SIZE,
+                actual: payload.len(),
+            });
+        }
+
+        let data_len = payload.len() - CHECKSUM_SIZE;
+        if payload[data_len..] != checksum(&payload[..data_len]) {
+            return Err(DecodeError::InvalidChecksum);
+        }
+        payload.truncate(data_len);
+        Ok(payload)
+    }
+}
+
+/// Length-prefixed binary encoding utilities
+///
+/// Byte fields are written as a little-endian `u32` length followed by the
+/// raw bytes; integers are written as fixed-width little-endian `u64`.
+pub mod binary {
+    use crate::errors::{LelantusError, Result};
+    use crate::prelude::*;
+
+    /// Size in bytes of the length prefix preceding each byte field
+    pub const LENGTH_PREFIX_SIZE: usize = 4;
+
+    /// Size in bytes of an encoded integer field
+    pub const U64_SIZE: usize = 8;
+
+    /// Append a length-prefixed byte field
+    pub fn put_bytes(buf: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+        let len = u32::try_from(data.len())
+            .map_err(|_| LelantusError::SerializationError("field too long".to_string()))?;
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Append a fixed-width integer field
+    pub fn put_u64(buf: &mut Vec<u8>, value: u64) {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Read one length-prefixed frame of at most `max_size` bytes from a
+    /// stream
+    ///
+    /// Returns `None` on a clean end of stream before the length prefix. A
+    /// larger prefix is rejected before any payload is read, and the buffer
+    /// only grows with the bytes actually received.
+    #[cfg(feature = "std")]
+    pub fn read_frame(reader: &mut impl std::io::Read, max_size: usize) -> Result<Option<Vec<u8>>> {
+        use std::io::Read;
+
+        let mut len = [0u8; LENGTH_PREFIX_SIZE];
+        let mut filled = 0;
+        while filled < len.len() {
+            match reader.read(&mut len[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(LelantusError::SerializationError(
+                        "unexpected end of input".to_string(),
+                    ))
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let len = u32::from_le_bytes(len) as usize;
+        super::check_size(len, max_size)?;
+        let mut frame = Vec::new();
+        if reader.take(len as u64).read_to_end(&mut frame)? != len {
+            return Err(LelantusError::SerializationError(
+                "unexpected end of input".to_string(),
+            ));
+        }
+        Ok(Some(frame))
+    }
+
+    /// Cursor over a binary-encoded buffer
+    pub struct Reader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        /// Create a reader positioned at the start of `data`
+        pub fn new(data: &'a [u8]) -> Self {
+            Self { data, pos: 0 }
+        }
+
+        /// Current offset into the buffer
+        pub fn position(&self) -> usize {
+            self.pos
+        }
+
+        fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+            let end = self
+                .pos
+                .checked_add(len)
+                .filter(|&end| end <= self.data.len())
+                .ok_or_else(|| {
+                    LelantusError::SerializationError("unexpected end of input".to_string())
+                })?;
+            let slice = &self.data[self.pos..end];
+            self.pos = end;
+            Ok(slice)
+        }
+
+        /// Read a length-prefixed byte field
+        pub fn read_bytes(&mut self) -> Result<Vec<u8>> {
+            let mut len = [0u8; LENGTH_PREFIX_SIZE];
+            len.copy_from_slice(self.take(LENGTH_PREFIX_SIZE)?);
+            let len = u32::from_le_bytes(len) as usize;
+            Ok(self.take(len)?.to_vec())
+        }
+
+        /// Read a single byte
+        pub fn read_u8(&mut self) -> Result<u8> {
+            Ok(self.take(1)?[0])
+        }
+
+        /// Read a fixed-width integer field
+        pub fn read_u64(&mut self) -> Result<u64> {
+            let mut value = [0u8; U64_SIZE];
+            value.copy_from_slice(self.take(U64_SIZE)?);
+            Ok(u64::from_le_bytes(value))
+        }
+
+        /// Ensure the whole buffer has been consumed
+        pub fn finish(self) -> Result<()> {
+            if self.pos != self.data.len() {
+                return Err(LelantusError::SerializationError(
+                    "trailing bytes after value".to_string(),
+                ));
+            }
+            Ok(())
+        }
+    }
+}
+
 /// JSON encoding utilities
+#[cfg(feature = "json")]
 pub mod json {
     use crate::errors::Result;
 
@@ -54,6 +571,8 @@ pub mod json {
 mod tests {
     use super::*;
     use crate::commitment::Commitment;
+    use crate::joinsplit::{JoinSplit, JoinSplitProof};
+    use crate::proof::ZKProof;
 
     #[test]
     fn test_hex_encoding() -> Result<()> {
@@ -65,6 +584,82 @@ mod tests {
     }
 
     #[test]
+    fn test_bech32m_decode_errors() -> Result<()> {
+        let encoded = bech32m::encode("lelantus", &[1, 2, 3])?;
+        assert_eq!(bech32m::decode("lelantus", &encoded)?, vec![1, 2, 3]);
+        assert_eq!(
+            bech32m::decode("lelantus", &encoded.to_uppercase())?,
+            vec![1, 2, 3]
+        );
+
+        assert_eq!(
+            bech32m::decode("other", &encoded),
+            Err(DecodeError::UnexpectedPrefix {
+                expected: "other".to_string(),
+                actual: "lelantus".to_string(),
+            })
+        );
+        let mixed = encoded.replacen('l', "L", 1);
+        assert_eq!(
+            bech32m::decode("lelantus", &mixed),
+            Err(DecodeError::MixedCase)
+        );
+        let corrupted = format!("{}q", &encoded[..encoded.len() - 1]);
+        assert_eq!(
+            bech32m::decode("lelantus", &corrupted),
+            Err(DecodeError::InvalidChecksum)
+        );
+        assert_eq!(
+            bech32m::decode("lelantus", &format!("{}b", encoded)),
+            Err(DecodeError::InvalidCharacter('b'))
+        );
+        assert!(matches!(
+            bech32m::validate_hrp("Lelantus"),
+            Err(DecodeError::InvalidPrefix(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_base58_encoding() -> core::result::Result<(), DecodeError> {
+        for (hex, encoded) in [
+            ("", ""),
+            ("61", "2g"),
+            ("626262", "a3gV"),
+            ("48656c6c6f20576f726c6421", "2NEpo7TZRRrLZSi2U"),
+            ("000000287fb4cd", "111233QC4"),
+            (
+                "00eb15231dfceb60925886b67d065299925915aeb172c06647",
+                "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L",
+            ),
+        ] {
+            let data = hex::decode(hex).map_err(|_| DecodeError::InvalidCharacter('?'))?;
+            assert_eq!(base58::encode(&data), encoded);
+            assert_eq!(base58::decode(encoded)?, data);
+        }
+
+        let encoded = base58::encode_check(b"legacy");
+        assert_eq!(base58::decode_check(&encoded)?, b"legacy");
+        let mut corrupted = encoded.into_bytes();
+        corrupted[0] = if corrupted[0] == b'2' { b'3' } else { b'2' };
+        let corrupted = String::from_utf8(corrupted).map_err(|_| DecodeError::MixedCase)?;
+        assert_eq!(
+            base58::decode_check(&corrupted),
+            Err(DecodeError::InvalidChecksum)
+        );
+        assert_eq!(
+            base58::decode("10OI"),
+            Err(DecodeError::InvalidCharacter('0'))
+        );
+        assert!(matches!(
+            base58::decode_check("2g"),
+            Err(DecodeError::InvalidLength { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
     fn test_json_encoding() -> Result<()> {
         let commitment = Commitment {
             value: vec![1; 32],
@@ -76,4 +671,144 @@ mod tests {
         assert_eq!(commitment.value, decoded.value);
         Ok(())
     }
+
+    fn sample_joinsplit() -> JoinSplit {
+        JoinSplit {
+            inputs: vec![Commitment::from_value(vec![1; 32])],
+            outputs: vec![Commitment::from_value(vec![3; 32])],
+            proof: JoinSplitProof {
+                range_proofs: vec![],
+                aggregated_range_proof: None,
+                asset_proof: None,
+                zk_proof: ZKProof {
+                    proof_data: vec![5; 32],
+                    challenge: vec![6; 32],
+                    response: vec![7; 32],
+                    anonymity_set_size: 64,
+                },
+                anonymity_sets: vec![],
+                membership_proofs: vec![],
+            },
+            fee: 100,
+            nullifiers: Vec::new(),
+            change_tags: Vec::new(),
+            memos: Vec::new(),
+            amount_notes: Vec::new(),
+            ephemeral_keys: Vec::new(),
+            binding_key: Vec::new(),
+            binding_signature: None,
+            binding_secret: None,
+            padding: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_format_roundtrip() -> Result<()> {
+        let joinsplit = sample_joinsplit();
+
+        for format in [
+            #[cfg(feature = "json")]
+            SerializationFormat::Json,
+            SerializationFormat::Borsh,
+            SerializationFormat::Cbor,
+        ] {
+            let encoded = joinsplit.to_bytes_format(format)?;
+            assert_eq!(SerializationFormat::detect(&encoded)?, format);
+
+            let decoded = JoinSplit::from_bytes_format(&encoded, format)?;
+            assert_eq!(decoded.inputs, joinsplit.inputs);
+            assert_eq!(decoded.outputs, joinsplit.outputs);
+            assert_eq!(decoded.fee, joinsplit.fee);
+
+            let detected = JoinSplit::from_tagged_bytes(&encoded)?;
+            assert_eq!(
+                detected.proof.zk_proof.challenge,
+                joinsplit.proof.zk_proof.challenge
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_mismatch() -> Result<()> {
+        let encoded = sample_joinsplit().to_bytes_format(SerializationFormat::Borsh)?;
+        assert!(JoinSplit::from_bytes_format(&encoded, SerializationFormat::Cbor).is_err());
+        assert!(JoinSplit::from_tagged_bytes(&[0xff, 0x00]).is_err());
+        assert!(JoinSplit::from_tagged_bytes(&[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_versioned_binary_round_trip() -> Result<()> {
+        let joinsplit = sample_joinsplit();
+        let bytes = LelantusSerializable::to_bytes(&joinsplit)?;
+        assert_eq!(bytes[0], ENCODING_VERSION);
+
+        let decoded = <JoinSplit as LelantusSerializable>::from_bytes(&bytes)?;
+        assert_eq!(LelantusSerializable::to_bytes(&decoded)?, bytes);
+
+        // Unknown versions, truncation and trailing bytes are rejected
+        let mut unknown = bytes.clone();
+        unknown[0] = ENCODING_VERSION + 1;
+        assert!(<JoinSplit as LelantusSerializable>::from_bytes(&unknown).is_err());
+        unknown[0] = ENCODING_VERSION - 1;
+        assert!(<JoinSplit as LelantusSerializable>::from_bytes(&unknown).is_err());
+        assert!(
+            <JoinSplit as LelantusSerializable>::from_bytes(&bytes[..bytes.len() - 1]).is_err()
+        );
+        let mut padded = bytes;
+        padded.push(0);
+        assert!(<JoinSplit as LelantusSerializable>::from_bytes(&padded).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let mut joinsplit = sample_joinsplit();
+        joinsplit.padding = vec![0; DEFAULT_MAX_ENCODED_SIZE];
+        assert!(LelantusSerializable::to_bytes(&joinsplit).is_err());
+        assert!(joinsplit
+            .to_bytes_format(SerializationFormat::Borsh)
+            .is_err());
+
+        let oversized = vec![ENCODING_VERSION; DEFAULT_MAX_ENCODED_SIZE + 1];
+        assert!(<JoinSplit as LelantusSerializable>::from_bytes(&oversized).is_err());
+        assert!(JoinSplit::from_tagged_bytes(&oversized).is_err());
+    }
+
+    #[test]
+    fn test_binary_encoding() -> Result<()> {
+        let commitment = Commitment {
+            value: vec![1; 32],
+            randomness: vec![2; 64],
+        };
+
+        let bytes = commitment.consensus_encode()?;
+        assert_eq!(Commitment::consensus_decode(&bytes)?, commitment);
+
+        // Truncated and padded buffers are rejected
+        assert!(Commitment::consensus_decode(&bytes[..bytes.len() - 1]).is_err());
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert!(Commitment::consensus_decode(&padded).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_frame_limits() -> Result<()> {
+        let mut stream = Vec::new();
+        binary::put_bytes(&mut stream, &[7; 16])?;
+        assert_eq!(
+            binary::read_frame(&mut stream.as_slice(), 16)?,
+            Some(vec![7; 16])
+        );
+        assert!(binary::read_frame(&mut [].as_slice(), 16)?.is_none());
+
+        // An oversized prefix fails before its payload is read, and a short
+        // payload fails
+        assert!(binary::read_frame(&mut stream.as_slice(), 15).is_err());
+        assert!(binary::read_frame(&mut u32::MAX.to_le_bytes().as_slice(), 16).is_err());
+        assert!(binary::read_frame(&mut &stream[..stream.len() - 1], 16).is_err());
+        Ok(())
+    }
 }
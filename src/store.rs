@@ -0,0 +1,154 @@
+//! Persistent state storage
+//!
+//! A [`StateStore`] keeps the accumulator and the spent nullifiers (the
+//! serial numbers of spent coins) across restarts. The accumulator holds the
+//! coins in insertion order, which is the coin index. [`FileStore`] writes
+//! the whole state to one file in the binary encoding, replacing it
+//! atomically on every save.
+
+use crate::accumulator::Accumulator;
+use crate::errors::{LelantusError, Result};
+use crate::serial::SerialNumber;
+use crate::serialization::LelantusSerializable;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Current stored state format version
+const STORED_STATE_VERSION: u32 = 1;
+
+/// Persisted part of a Lelantus state
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct StoredState {
+    /// Format version
+    pub version: u32,
+
+    /// Fingerprint of the parameters the state was created under
+    pub parameters_fingerprint: [u8; 32],
+
+    /// Accumulator, including its coins and recent anchors
+    pub accumulator: Accumulator,
+
+    /// Spent nullifiers in sorted order
+    pub nullifiers: Vec<SerialNumber>,
+}
+
+impl LelantusSerializable for StoredState {
+    const MAX_ENCODED_SIZE: usize = Accumulator::MAX_ENCODED_SIZE * 2;
+}
+
+impl StoredState {
+    /// Bundle state for storage
+    pub fn new(
+        parameters_fingerprint: [u8; 32],
+        accumulator: Accumulator,
+        nullifiers: Vec<SerialNumber>,
+    ) -> Self {
+        Self {
+            version: STORED_STATE_VERSION,
+            parameters_fingerprint,
+            accumulator,
+            nullifiers,
+        }
+    }
+}
+
+/// Backend that persists a Lelantus state
+pub trait StateStore: std::fmt::Debug + Send + Sync {
+    /// Load the stored state, or `None` if nothing has been saved yet
+    fn load(&self) -> Result<Option<StoredState>>;
+
+    /// Replace the stored state
+    fn save(&self, state: &StoredState) -> Result<()>;
+}
+
+/// State store backed by a single file
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    /// Store the state at `path`
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Path of the state file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl StateStore for FileStore {
+    fn load(&self) -> Result<Option<StoredState>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let state = <StoredState as LelantusSerializable>::from_bytes(&data)?;
+        if state.version != STORED_STATE_VERSION {
+            return Err(LelantusError::SerializationError(format!(
+                "unsupported stored state version {}",
+                state.version
+            )));
+        }
+        Ok(Some(state))
+    }
+
+    fn save(&self, state: &StoredState) -> Result<()> {
+        let data = LelantusSerializable::to_bytes(state)?;
+
+        // Write beside the target and rename, so a crash never leaves a
+        // partially written state behind
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        Ok(fs::rename(&temp, &self.path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::AccumulatorElement;
+    use crate::parameters::LelantusParameters;
+    use crate::pedersen::POINT_SIZE;
+
+    #[test]
+    fn test_file_store_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = FileStore::new(dir.path().join("state.bin"));
+        assert!(store.load()?.is_none());
+
+        let params = LelantusParameters::default();
+        let mut accumulator = Accumulator::new(&params)?;
+        accumulator.add_element(AccumulatorElement {
+            value: vec![1; 2 * POINT_SIZE],
+        })?;
+        let state = StoredState::new(
+            params.fingerprint()?,
+            accumulator,
+            vec![SerialNumber::from_bytes(vec![2; 64])?],
+        );
+        store.save(&state)?;
+
+        let loaded = store
+            .load()?
+            .ok_or(LelantusError::invalid_parameter("stored_state"))?;
+        assert_eq!(loaded.accumulator.value(), state.accumulator.value());
+        assert_eq!(loaded.nullifiers, state.nullifiers);
+        assert_eq!(loaded.parameters_fingerprint, state.parameters_fingerprint);
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+//! Asset surjection proofs
+//!
+//! A JoinSplit moving several assets publishes a blinded asset tag
+//! `T = A + τ·H` for each output in place of its asset id (see
+//! [`CommitmentScheme::asset_tag`]). An asset surjection proof shows that
+//! every tag blinds the generator of some asset in a published ring, without
+//! revealing which: for each output it proves knowledge of `τ` with
+//! `T − A_i = τ·H` for one ring asset `i`, as an OR of Schnorr proofs.
+//!
+//! The ring names the assets the outputs may hold, so it reveals which
+//! assets a transaction touches, but not which output holds which; wallets
+//! may widen it with decoy assets. The aggregated range proof then proves
+//! each output's value under its tag, and the balance proof shows value is
+//! conserved per asset.
+
+use crate::commitment::{AssetId, Commitment, CommitmentScheme};
+use crate::errors::{LelantusError, Result};
+use crate::one_out_of_many::{
+    decode_points, decode_scalars, encode_scalars, put_fields, read_fields,
+};
+use crate::pedersen::{encode, scalar_from_digest, Generators, POINT_SIZE, SCALAR_SIZE};
+use crate::serialization::binary::{self, LENGTH_PREFIX_SIZE, U64_SIZE};
+use crate::serialization::LelantusSerializable;
+use crate::transcript::{ProofDomain, ProofTranscript};
+use borsh::{BorshDeserialize, BorshSerialize};
+use p521::elliptic_curve::Field;
+use p521::{ProjectivePoint, Scalar};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Proof that the asset tag of every output blinds the generator of a ring
+/// asset
+///
+/// Points are compressed SEC1 encodings, scalars big-endian.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AssetSurjectionProof {
+    /// Assets the outputs may hold, strictly increasing
+    pub assets: Vec<AssetId>,
+
+    /// Blinded asset tag of each output
+    pub tags: Vec<Vec<u8>>,
+
+    /// Challenge of each output against each ring asset, output by output
+    pub challenges: Vec<Vec<u8>>,
+
+    /// Response of each output against each ring asset, in the same order
+    pub responses: Vec<Vec<u8>>,
+}
+
+impl LelantusSerializable for AssetSurjectionProof {
+    const MIN_ENCODING_VERSION: u8 = 7;
+}
+
+/// Transcript over the generators, the ring, the outputs and their tags
+fn transcript(
+    scheme: &CommitmentScheme,
+    generators: &Generators,
+    proof: &AssetSurjectionProof,
+    outputs: &[Commitment],
+) -> ProofTranscript {
+    let mut transcript = ProofTranscript::new(ProofDomain::AssetSurjection);
+    transcript.append_message(b"generator", scheme.generator());
+    transcript.append_message(
+        b"blinding-generator",
+        &encode(&generators.blinding_generator()),
+    );
+    transcript.append_u64(b"ring-size", proof.assets.len() as u64);
+    for asset in &proof.assets {
+        transcript.append_message(b"asset", asset);
+    }
+    transcript.append_commitments(b"output", outputs.iter());
+    for tag in &proof.tags {
+        transcript.append_message(b"tag", tag);
+    }
+    transcript
+}
+
+/// Absorb the nonces of one output, then draw its challenge
+fn output_challenge(
+    transcript: &mut ProofTranscript,
+    nonces: impl Iterator<Item = ProjectivePoint>,
+) -> Scalar {
+    for nonce in nonces {
+        transcript.append_message(b"nonce", &encode(&nonce));
+    }
+    scalar_from_digest(&transcript.challenge_bytes(b"challenge"))
+}
+
+impl AssetSurjectionProof {
+    /// Prove that each of `outputs`, holding the asset at the same index of
+    /// `assets`, is tagged with an asset of `ring`
+    ///
+    /// The ring is sorted and deduplicated. Needs the `pedersen` scheme;
+    /// fails with `InvalidParameter` if an output's asset is not in the
+    /// ring.
+    pub fn create(
+        scheme: &CommitmentScheme,
+        outputs: &[Commitment],
+        assets: &[AssetId],
+        ring: &[AssetId],
+    ) -> Result<Self> {
+        Self::create_with_rng(scheme, outputs, assets, ring, &mut OsRng)
+    }
+
+    /// Create an asset surjection proof with nonces drawn from `rng`
+    pub fn create_with_rng(
+        scheme: &CommitmentScheme,
+        outputs: &[Commitment],
+        assets: &[AssetId],
+        ring: &[AssetId],
+        rng: &mut (impl CryptoRng + RngCore),
+    ) -> Result<Self> {
+        let generators = scheme.pedersen_generators()?;
+        if outputs.is_empty() || outputs.len() != assets.len() {
+            return Err(LelantusError::invalid_parameter("outputs"));
+        }
+        let mut ring = ring.to_vec();
+        ring.sort_unstable();
+        ring.dedup();
+        let indices = assets
+            .iter()
+            .map(|asset| {
+                ring.binary_search(asset)
+                    .map_err(|_| LelantusError::invalid_parameter("ring"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let ring_generators: Vec<ProjectivePoint> = ring
+            .iter()
+            .map(|asset| scheme.asset_value_generator(asset))
+            .collect();
+        let tags: Vec<ProjectivePoint> = outputs
+            .iter()
+            .zip(&indices)
+            .map(|(output, &index)| {
+                generators.asset_tag(&ring_generators[index], &output.randomness)
+            })
+            .collect();
+        let mut proof = Self {
+            assets: ring,
+            tags: tags.iter().map(encode).collect(),
+            challenges: Vec::new(),
+            responses: Vec::new(),
+        };
+
+        // Simulate every ring member but the output's own asset, then split
+        // the challenge so the real one is answered with `τ`
+        let h = generators.blinding_generator();
+        let mut transcript = transcript(scheme, generators, &proof, outputs);
+        for ((output, tag), &index) in outputs.iter().zip(&tags).zip(&indices) {
+            let nonce = Scalar::random(&mut *rng);
+            let mut challenges: Vec<Scalar> = ring_generators
+                .iter()
+                .map(|_| Scalar::random(&mut *rng))
+                .collect();
+            let mut responses: Vec<Scalar> = ring_generators
+                .iter()
+                .map(|_| Scalar::random(&mut *rng))
+                .collect();
+            let nonces = ring_generators.iter().enumerate().map(|(i, generator)| {
+                if i == index {
+                    h * nonce
+                } else {
+                    h * responses[i] - (*tag - generator) * challenges[i]
+                }
+            });
+            let challenge = output_challenge(&mut transcript, nonces);
+
+            challenges[index] = Scalar::ZERO;
+            challenges[index] = challenge - challenges.iter().fold(Scalar::ZERO, |sum, c| sum + c);
+            responses[index] =
+                nonce + challenges[index] * Generators::asset_blinding_factor(&output.randomness);
+            proof.challenges.extend(encode_scalars(&challenges));
+            proof.responses.extend(encode_scalars(&responses));
+        }
+        Ok(proof)
+    }
+
+    /// Verify that the proof tags exactly `outputs`, in order, with assets
+    /// of its ring
+    pub fn verify(&self, scheme: &CommitmentScheme, outputs: &[Commitment]) -> Result<bool> {
+        let generators = scheme.pedersen_generators()?;
+        if self.tags.len() != outputs.len() {
+            return Ok(false);
+        }
+        self.check_encoding()?;
+
+        let ring = self.assets.len();
+        let entries = self.tags.len() * ring;
+        let decoded = (|| {
+            Some((
+                decode_points(&self.tags, self.tags.len())?,
+                decode_scalars(&self.challenges, entries)?,
+                decode_scalars(&self.responses, entries)?,
+            ))
+        })();
+        let Some((tags, challenges, responses)) = decoded else {
+            return Ok(false);
+        };
+
+        let ring_generators: Vec<ProjectivePoint> = self
+            .assets
+            .iter()
+            .map(|asset| scheme.asset_value_generator(asset))
+            .collect();
+        let h = generators.blinding_generator();
+        let mut transcript = transcript(scheme, generators, self, outputs);
+        for (j, tag) in tags.iter().enumerate() {
+            let challenges = &challenges[j * ring..(j + 1) * ring];
+            let responses = &responses[j * ring..(j + 1) * ring];
+            let nonces = ring_generators
+                .iter()
+                .zip(challenges.iter().zip(responses))
+                .map(|(generator, (challenge, response))| {
+                    h * response - (*tag - generator) * challenge
+                });
+            let challenge = output_challenge(&mut transcript, nonces);
+            if challenges.iter().fold(Scalar::ZERO, |sum, c| sum + c) != challenge {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Number of ring members checked against each output
+    pub(crate) fn entry_count(&self) -> usize {
+        self.tags.len() * self.assets.len()
+    }
+
+    /// Size of [`consensus_encode`](Self::consensus_encode) for `outputs`
+    /// tagged against a ring of `assets`
+    pub fn encoded_size(outputs: usize, assets: usize) -> usize {
+        4 * U64_SIZE
+            + assets * (LENGTH_PREFIX_SIZE + core::mem::size_of::<AssetId>())
+            + outputs * (LENGTH_PREFIX_SIZE + POINT_SIZE)
+            + 2 * outputs * assets * (LENGTH_PREFIX_SIZE + SCALAR_SIZE)
+    }
+
+    /// Check that proof fields are structurally well-formed
+    ///
+    /// The ring must be non-empty and strictly increasing, and there must be
+    /// one challenge and one response per output and ring asset.
+    pub fn check_encoding(&self) -> Result<()> {
+        let entries = self.tags.len().checked_mul(self.assets.len());
+        let well_formed = !self.assets.is_empty()
+            && !self.tags.is_empty()
+            && self.assets.windows(2).all(|pair| pair[0] < pair[1])
+            && self.tags.iter().all(|tag| tag.len() == POINT_SIZE)
+            && entries == Some(self.challenges.len())
+            && entries == Some(self.responses.len())
+            && self
+                .challenges
+                .iter()
+                .chain(&self.responses)
+                .all(|scalar| scalar.len() == SCALAR_SIZE);
+        if !well_formed {
+            return Err(LelantusError::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Encode the proof in the canonical binary format
+    pub fn consensus_encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        binary::put_u64(&mut buf, self.assets.len() as u64);
+        for asset in &self.assets {
+            binary::put_bytes(&mut buf, asset)?;
+        }
+        put_fields(&mut buf, &self.tags)?;
+        put_fields(&mut buf, &self.challenges)?;
+        put_fields(&mut buf, &self.responses)?;
+        Ok(buf)
+    }
+
+    /// Decode the proof from the canonical binary format
+    pub fn consensus_decode(data: &[u8]) -> Result<Self> {
+        let mut reader = binary::Reader::new(data);
+        let assets = (0..reader.read_u64()?)
+            .map(|_| {
+                AssetId::try_from(reader.read_bytes()?.as_slice())
+                    .map_err(|_| LelantusError::SerializationError("bad asset id".to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let proof = Self {
+            assets,
+            tags: read_fields(&mut reader)?,
+            challenges: read_fields(&mut reader)?,
+            responses: read_fields(&mut reader)?,
+        };
+        reader.finish()?;
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::NATIVE_ASSET;
+    use crate::parameters::LelantusParameters;
+
+    #[test]
+    fn test_asset_surjection_proof() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let token = [7; 32];
+        let assets = [NATIVE_ASSET, token, token];
+        let outputs = [
+            scheme.commit(900)?,
+            scheme.commit_asset_with_rng(&token, 300, &mut OsRng)?,
+            scheme.commit_asset_with_rng(&token, 200, &mut OsRng)?,
+        ];
+        let ring = [token, NATIVE_ASSET, [9; 32]];
+
+        let proof = AssetSurjectionProof::create(&scheme, &outputs, &assets, &ring)?;
+        assert_eq!(proof.assets, vec![NATIVE_ASSET, token, [9; 32]]);
+        assert!(proof.verify(&scheme, &outputs)?);
+
+        // Tags of one asset differ, and none is the plain asset generator
+        assert_ne!(proof.tags[1], proof.tags[2]);
+        assert_ne!(proof.tags[1], encode(&scheme.asset_value_generator(&token)));
+        assert_eq!(
+            proof.tags[1],
+            scheme.asset_tag(&token, &outputs[1].randomness)?
+        );
+
+        // Other outputs, a reordered proof or a ring missing an asset fail
+        assert!(!proof.verify(&scheme, &outputs[..2])?);
+        let mut swapped = proof.clone();
+        swapped.tags.swap(1, 2);
+        assert!(!swapped.verify(&scheme, &outputs)?);
+        let mut shrunk = proof.clone();
+        shrunk.assets.pop();
+        assert!(shrunk.verify(&scheme, &outputs).is_err());
+        assert!(matches!(
+            AssetSurjectionProof::create(&scheme, &outputs, &assets, &[NATIVE_ASSET]),
+            Err(LelantusError::InvalidParameter(_))
+        ));
+
+        let bytes = proof.consensus_encode()?;
+        assert_eq!(bytes.len(), AssetSurjectionProof::encoded_size(3, 3));
+        assert!(AssetSurjectionProof::consensus_decode(&bytes)?.verify(&scheme, &outputs)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_asset_surjection_rejects_tag_outside_ring() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        let (token, other) = ([7; 32], [8; 32]);
+        let outputs = [scheme.commit_asset_with_rng(&other, 300, &mut OsRng)?];
+
+        // A proof made for `other` cannot be passed off against a ring
+        // without it
+        let mut forged =
+            AssetSurjectionProof::create(&scheme, &outputs, &[other], &[other, token])?;
+        forged.assets = vec![NATIVE_ASSET, token];
+        assert!(!forged.verify(&scheme, &outputs)?);
+
+        // Nor can a tag relabeled to another asset's generator
+        let mut relabeled = AssetSurjectionProof::create(&scheme, &outputs, &[other], &[other])?;
+        relabeled.tags = vec![scheme.asset_tag(&token, &outputs[0].randomness)?];
+        assert!(!relabeled.verify(&scheme, &outputs)?);
+        Ok(())
+    }
+}
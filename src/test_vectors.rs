@@ -0,0 +1,259 @@
+//! Deterministic test vectors and property-test generators
+//!
+//! Fixtures are derived from fixed seeds only, so every build produces the
+//! same bytes and other implementations can check their encodings against
+//! them. Commitment randomness comes from
+//! [`CommitmentScheme::commit_deterministic`] and JoinSplit randomness from a
+//! seeded [`StdRng`].
+//!
+//! The [`strategies`] submodule exports proptest generators for wallets and
+//! language ports that want to fuzz their own round-trips against this crate.
+
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::errors::{LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use crate::parameters::LelantusParameters;
+use crate::witness::Witness;
+use crate::{LelantusState, MAX_MONEY};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+/// Seed, index and value of each commitment vector
+const COMMITMENT_CASES: &[(&[u8], u64, u64)] = &[
+    (&[0; 32], 0, 1),
+    (&[1; 32], 1, 1_000),
+    (b"silver-lelantus test vectors", 7, MAX_MONEY),
+];
+
+/// Seed, input values, output values and fee of each JoinSplit vector
+const JOINSPLIT_CASES: &[(u64, &[u64], &[u64], u64)] = &[
+    (1, &[1_000], &[900], 100),
+    (2, &[600, 500], &[700, 300], 100),
+    (3, &[5_000, 2_500, 1_500], &[4_000, 3_000, 500], 1_500),
+];
+
+/// Commitment to `value` derived from `seed` and `index`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentVector {
+    /// Hex-encoded seed
+    pub seed: String,
+    /// Derivation index
+    pub index: u64,
+    /// Committed value in mist
+    pub value: u64,
+    /// Hex-encoded commitment value
+    pub commitment: String,
+    /// Hex-encoded commitment randomness
+    pub randomness: String,
+}
+
+/// JoinSplit spending coins minted from `seed`, with its proof and encoding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinSplitVector {
+    /// Seed of the input commitments and the JoinSplit RNG
+    pub seed: u64,
+    /// Input values, minted in order into an empty accumulator
+    pub inputs: Vec<u64>,
+    /// Output values
+    pub outputs: Vec<u64>,
+    /// Fee in mist
+    pub fee: u64,
+    /// Hex-encoded accumulator value after minting the inputs
+    pub accumulator: String,
+    /// Hex-encoded zero-knowledge proof
+    pub zk_proof: String,
+    /// Hex-encoded consensus encoding of the JoinSplit
+    pub joinsplit: String,
+    /// Hex-encoded transaction id
+    pub txid: String,
+}
+
+/// State holding the input coins of a fixture, minted from `seed`
+///
+/// Input `i` is committed with [`CommitmentScheme::commit_deterministic`]
+/// under the little-endian seed and index `i`.
+pub fn fixture_state(
+    seed: u64,
+    inputs: &[u64],
+) -> Result<(LelantusState, Vec<(Commitment, Witness)>)> {
+    let state = LelantusState::new(LelantusParameters::default())?;
+    let scheme = state.commitment_scheme();
+    let commitments = inputs
+        .iter()
+        .zip(0u64..)
+        .map(|(&value, index)| scheme.commit_deterministic(&seed.to_le_bytes(), index, value))
+        .collect::<Result<Vec<_>>>()?;
+    let coins = state.add_minted(commitments, inputs, Witness::encrypt_amount)?;
+    Ok((state, coins))
+}
+
+/// JoinSplit of a fixture, with the state it spends from
+pub fn fixture_joinsplit(
+    seed: u64,
+    inputs: &[u64],
+    outputs: &[u64],
+    fee: u64,
+) -> Result<(LelantusState, JoinSplit)> {
+    let (state, coins) = fixture_state(seed, inputs)?;
+    let joinsplit = state.create_joinsplit_with_rng(
+        coins,
+        outputs.to_vec(),
+        fee,
+        &mut StdRng::seed_from_u64(seed),
+    )?;
+    Ok((state, joinsplit))
+}
+
+/// Every commitment vector
+pub fn commitment_vectors() -> Result<Vec<CommitmentVector>> {
+    let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+    COMMITMENT_CASES
+        .iter()
+        .map(|&(seed, index, value)| {
+            let commitment = scheme.commit_deterministic(seed, index, value)?;
+            Ok(CommitmentVector {
+                seed: hex::encode(seed),
+                index,
+                value,
+                commitment: hex::encode(&commitment.value),
+                randomness: hex::encode(&commitment.randomness),
+            })
+        })
+        .collect()
+}
+
+/// Every JoinSplit vector
+pub fn joinsplit_vectors() -> Result<Vec<JoinSplitVector>> {
+    JOINSPLIT_CASES
+        .iter()
+        .map(|&(seed, inputs, outputs, fee)| {
+            let (state, joinsplit) = fixture_joinsplit(seed, inputs, outputs, fee)?;
+            Ok(JoinSplitVector {
+                seed,
+                inputs: inputs.to_vec(),
+                outputs: outputs.to_vec(),
+                fee,
+                accumulator: hex::encode(state.get_accumulator()?),
+                zk_proof: hex::encode(joinsplit.proof.zk_proof.consensus_encode()?),
+                joinsplit: hex::encode(joinsplit.consensus_encode()?),
+                txid: hex::encode(joinsplit.txid()?),
+            })
+        })
+        .collect()
+}
+
+/// Decode `vector`'s JoinSplit and verify it against its minted inputs
+pub fn verify_joinsplit_vector(vector: &JoinSplitVector) -> Result<bool> {
+    let (state, _) = fixture_state(vector.seed, &vector.inputs)?;
+    let encoded = hex::decode(&vector.joinsplit)
+        .map_err(|e| LelantusError::SerializationError(e.to_string()))?;
+    let joinsplit = JoinSplit::consensus_decode(&encoded)?;
+    Ok(hex::encode(joinsplit.txid()?) == vector.txid && state.verify_joinsplit(&joinsplit)?)
+}
+
+/// Proptest generators for amounts, commitments and JoinSplits
+pub mod strategies {
+    use super::*;
+    use crate::amount::Amount;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    /// Largest input value generated, keeping sums far from the supply cap
+    const MAX_INPUT_VALUE: u64 = 1_000_000;
+
+    /// Any valid amount
+    pub fn amount() -> impl Strategy<Value = Amount> {
+        (0..=MAX_MONEY).prop_map(Amount::saturating)
+    }
+
+    /// Commitment under `scheme` from a random seed, index and value
+    pub fn commitment(scheme: CommitmentScheme) -> impl Strategy<Value = Commitment> {
+        (any::<[u8; 32]>(), any::<u64>(), 1..=MAX_MONEY)
+            .prop_filter_map("commitment failed", move |(seed, index, value)| {
+                scheme.commit_deterministic(&seed, index, value).ok()
+            })
+    }
+
+    /// Balanced seed, input values, output values and fee
+    ///
+    /// Outputs split what the inputs leave after the fee as evenly as
+    /// possible, so every case balances.
+    pub fn joinsplit_case() -> impl Strategy<Value = (u64, Vec<u64>, Vec<u64>, u64)> {
+        (
+            any::<u64>(),
+            vec(1..=MAX_INPUT_VALUE, 1..=4),
+            1..=4usize,
+            0..=1_000u64,
+        )
+            .prop_filter_map("inputs too small", |(seed, inputs, count, fee)| {
+                let spendable = inputs.iter().sum::<u64>().checked_sub(fee)?;
+                let share = spendable / count as u64;
+                if share == 0 {
+                    return None;
+                }
+                let mut outputs = vec![share; count];
+                outputs[0] += spendable % count as u64;
+                Some((seed, inputs, outputs, fee))
+            })
+    }
+
+    /// Valid JoinSplit of a [`joinsplit_case`]
+    pub fn joinsplit() -> impl Strategy<Value = JoinSplit> {
+        joinsplit_case().prop_filter_map("joinsplit failed", |(seed, inputs, outputs, fee)| {
+            fixture_joinsplit(seed, &inputs, &outputs, fee)
+                .ok()
+                .map(|(_, joinsplit)| joinsplit)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::LelantusSerializable;
+    use proptest::prelude::*;
+    use proptest::test_runner::TestRunner;
+
+    #[test]
+    fn test_vectors_are_deterministic() -> Result<()> {
+        assert_eq!(commitment_vectors()?, commitment_vectors()?);
+
+        let vectors = joinsplit_vectors()?;
+        assert_eq!(vectors, joinsplit_vectors()?);
+        assert_eq!(vectors.len(), JOINSPLIT_CASES.len());
+        for vector in &vectors {
+            assert!(verify_joinsplit_vector(vector)?);
+        }
+
+        let mut tampered = vectors[0].clone();
+        tampered.inputs[0] += 1;
+        assert!(!matches!(verify_joinsplit_vector(&tampered), Ok(true)));
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn test_joinsplit_roundtrip(joinsplit in strategies::joinsplit()) {
+            let encoded = joinsplit.consensus_encode()?;
+            let decoded = JoinSplit::consensus_decode(&encoded)?;
+            prop_assert_eq!(decoded.consensus_encode()?, encoded);
+            prop_assert_eq!(decoded.txid()?, joinsplit.txid()?);
+        }
+    }
+
+    #[test]
+    fn test_commitment_roundtrip() -> Result<()> {
+        let scheme = CommitmentScheme::new(&LelantusParameters::default())?;
+        TestRunner::default()
+            .run(&strategies::commitment(scheme), |commitment| {
+                let encoded = LelantusSerializable::to_bytes(&commitment)?;
+                let decoded = <Commitment as LelantusSerializable>::from_bytes(&encoded)?;
+                prop_assert_eq!(decoded, commitment);
+                Ok(())
+            })
+            .map_err(|e| LelantusError::SerializationError(e.to_string()))
+    }
+}
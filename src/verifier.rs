@@ -0,0 +1,349 @@
+//! Reusable JoinSplit verification
+//!
+//! [`PreparedVerifier`] derives everything verification needs from the
+//! parameters once, so servers verifying many transactions do not repeat
+//! that work per call.
+
+use crate::accumulator::Accumulator;
+use crate::commitment::{Commitment, CommitmentScheme};
+use crate::errors::{LelantusError, Result};
+use crate::joinsplit::JoinSplit;
+use crate::parameters::LelantusParameters;
+use crate::proof::{AggregatedRangeProof, RangeProof, ZkStatement};
+use crate::surjection::AssetSurjectionProof;
+use crate::MAX_JOINSPLIT_INPUTS;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+/// Weight of the fixed digests checked by a zero-knowledge proof
+const ZK_PROOF_BASE_WEIGHT: u64 = 3;
+
+/// Most verified JoinSplits remembered by a [`VerificationCache`]
+pub const VERIFICATION_CACHE_CAPACITY: usize = 4096;
+
+/// Domain separator for verification cache keys
+const VERIFICATION_CACHE_DOMAIN: &[u8] = b"lelantus_verified_proof";
+
+/// Outcome of a metered verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Whether the JoinSplit verified
+    pub valid: bool,
+
+    /// Work spent, in digest evaluations
+    pub weight: u64,
+
+    /// Number of range proofs checked before verification finished
+    pub range_proofs_checked: usize,
+}
+
+/// A range proof of a JoinSplit and the outputs it covers
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RangeCheck<'a> {
+    /// Proof for a single output
+    Output(&'a RangeProof),
+
+    /// Proof aggregated over all outputs, checked against the asset tags of
+    /// the asset surjection proof if there is one
+    Aggregated(
+        &'a AggregatedRangeProof,
+        &'a [Commitment],
+        Option<&'a AssetSurjectionProof>,
+    ),
+}
+
+impl<'a> RangeCheck<'a> {
+    /// Range proofs a JoinSplit carries, in verification order
+    pub(crate) fn of(joinsplit: &'a JoinSplit) -> Vec<Self> {
+        let aggregated = joinsplit.proof.aggregated_range_proof.iter().map(|proof| {
+            Self::Aggregated(
+                proof,
+                &joinsplit.outputs,
+                joinsplit.proof.asset_proof.as_ref(),
+            )
+        });
+        joinsplit
+            .proof
+            .range_proofs
+            .iter()
+            .map(Self::Output)
+            .chain(aggregated)
+            .collect()
+    }
+}
+
+/// Element values of the anonymity set of each input of `joinsplit`, as of
+/// `element_count` elements of `accumulator`
+///
+/// A set starting past `element_count` was named by the transaction, so it is
+/// reported as `UnknownAnonymitySet` rather than an accumulator failure.
+pub(crate) fn anonymity_sets(
+    joinsplit: &JoinSplit,
+    accumulator: &Accumulator,
+    element_count: usize,
+) -> Result<Vec<Vec<Vec<u8>>>> {
+    joinsplit
+        .proof
+        .anonymity_sets
+        .iter()
+        .map(
+            |&set| match accumulator.anonymity_set(set, element_count)? {
+                members if members.is_empty() => Err(LelantusError::UnknownAnonymitySet(set)),
+                members => Ok(members),
+            },
+        )
+        .collect()
+}
+
+/// Bounded set of JoinSplits whose proofs verified against an accumulator
+/// head
+///
+/// Keys hash the whole JoinSplit with the head, so a hit stands for exactly
+/// the verification that was done. Only successes are remembered.
+#[derive(Debug)]
+pub(crate) struct VerificationCache {
+    entries: Mutex<lru::LruCache<[u8; 32], ()>>,
+}
+
+impl VerificationCache {
+    /// Create an empty cache holding at most `capacity` entries
+    pub(crate) fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: Mutex::new(lru::LruCache::new(capacity)),
+        }
+    }
+
+    /// Key of `joinsplit` verified against `accumulator_value`
+    pub(crate) fn key(joinsplit: &JoinSplit, accumulator_value: &[u8]) -> Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        hasher.update(VERIFICATION_CACHE_DOMAIN);
+        hasher.update(joinsplit.txid()?);
+        hasher.update(accumulator_value);
+        Ok(hasher.finalize().into())
+    }
+
+    /// Whether `key` was verified, refreshing its recency
+    pub(crate) fn contains(&self, key: &[u8; 32]) -> bool {
+        self.entries.lock().get(key).is_some()
+    }
+
+    /// Remember a successful verification
+    pub(crate) fn insert(&self, key: [u8; 32]) {
+        self.entries.lock().put(key, ());
+    }
+
+    /// Forget every verification
+    pub(crate) fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    /// Number of remembered verifications
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+}
+
+impl Default for VerificationCache {
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(VERIFICATION_CACHE_CAPACITY).unwrap_or(NonZeroUsize::MIN))
+    }
+}
+
+/// JoinSplit verifier with parameter-derived constants cached
+#[derive(Debug, Clone)]
+pub struct PreparedVerifier {
+    parameters: Arc<LelantusParameters>,
+
+    /// Scheme holding the generators every proof is checked with
+    scheme: Arc<CommitmentScheme>,
+
+    /// Encoded size of a commitment value
+    value_len: usize,
+
+    /// Largest padding a JoinSplit may carry
+    max_padding: usize,
+
+    /// Anonymity set size each membership proof covers
+    anonymity_set_size: u64,
+}
+
+impl PreparedVerifier {
+    /// Prepare a verifier for `parameters`
+    pub fn new(parameters: &LelantusParameters) -> Result<Self> {
+        let scheme = CommitmentScheme::new(parameters)?;
+        Self::from_shared(Arc::new(parameters.clone()), Arc::new(scheme))
+    }
+
+    /// Prepare a verifier sharing already validated parameters and the
+    /// scheme built from them
+    pub(crate) fn from_shared(
+        parameters: Arc<LelantusParameters>,
+        scheme: Arc<CommitmentScheme>,
+    ) -> Result<Self> {
+        let value_len = parameters.hash_algo()?.encoded_len();
+        let max_padding = JoinSplit::encoded_size(
+            &parameters,
+            MAX_JOINSPLIT_INPUTS,
+            parameters.max_joinsplit_outputs(),
+        )?;
+        let anonymity_set_size = parameters.anonymity_set_size() as u64;
+        Ok(Self {
+            parameters,
+            scheme,
+            value_len,
+            max_padding,
+            anonymity_set_size,
+        })
+    }
+
+    /// Verify a JoinSplit against the current head of `accumulator`
+    pub fn verify(&self, joinsplit: &JoinSplit, accumulator: &Accumulator) -> Result<bool> {
+        self.check_structure(joinsplit)?;
+
+        for check in RangeCheck::of(joinsplit) {
+            if !self.verify_range_proof(check)? {
+                return Ok(false);
+            }
+        }
+
+        let sets = anonymity_sets(joinsplit, accumulator, accumulator.element_count())?;
+        self.verify_zk_proof(joinsplit, accumulator.value(), &sets)
+    }
+
+    /// Weight of a range proof: one for its mask plus every bit it argues
+    /// over, and every ring member each asset tag is checked against
+    pub(crate) fn range_proof_weight(&self, check: RangeCheck) -> u64 {
+        let parts = match check {
+            RangeCheck::Output(proof) => proof.bit_count(),
+            RangeCheck::Aggregated(proof, _, asset_proof) => proof
+                .bit_count()
+                .saturating_add(asset_proof.map_or(0, AssetSurjectionProof::entry_count)),
+        };
+        1 + parts as u64
+    }
+
+    /// Weight of a zero-knowledge proof: each input is proven against the
+    /// whole anonymity set
+    pub(crate) fn zk_proof_weight(&self, joinsplit: &JoinSplit) -> u64 {
+        ZK_PROOF_BASE_WEIGHT + joinsplit.inputs.len() as u64 * self.anonymity_set_size
+    }
+
+    /// Reject structurally invalid transactions before any crypto checks
+    ///
+    /// Covers counts, field sizes, the fee bound, duplicate inputs and
+    /// nullifiers; nothing here verifies a proof.
+    pub(crate) fn check_structure(&self, joinsplit: &JoinSplit) -> Result<()> {
+        joinsplit.check_well_formed(&self.parameters)?;
+        joinsplit.check_distinct_inputs()?;
+        self.check_sizes(joinsplit)?;
+        if joinsplit.fee > self.parameters.max_coin_value {
+            return Err(LelantusError::BalanceMismatch);
+        }
+        joinsplit.check_nullifiers()?;
+        for check in RangeCheck::of(joinsplit) {
+            match check {
+                RangeCheck::Output(proof) => proof.check_encoding()?,
+                RangeCheck::Aggregated(proof, _, asset_proof) => {
+                    proof.check_encoding()?;
+                    if let Some(asset_proof) = asset_proof {
+                        asset_proof.check_encoding()?;
+                    }
+                }
+            }
+        }
+        joinsplit.proof.zk_proof.check_encoding()
+    }
+
+    /// Check that commitment values have the configured size and padding
+    /// stays within the largest transaction size
+    ///
+    /// Randomness is not published, so only the values are checked.
+    fn check_sizes(&self, joinsplit: &JoinSplit) -> Result<()> {
+        let commitments = joinsplit.inputs.iter().chain(&joinsplit.outputs);
+        if commitments
+            .into_iter()
+            .any(|c| c.value.len() != self.value_len)
+        {
+            return Err(LelantusError::InvalidCommitment);
+        }
+        if joinsplit.padding.len() > self.max_padding {
+            return Err(LelantusError::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Verify a single range proof
+    ///
+    /// An aggregated proof with an asset surjection proof is checked
+    /// against its tags once they are shown to blind assets of its ring.
+    pub(crate) fn verify_range_proof(&self, check: RangeCheck) -> Result<bool> {
+        match check {
+            RangeCheck::Output(proof) => proof.verify_with(&self.scheme),
+            RangeCheck::Aggregated(proof, outputs, None) => {
+                proof.verify(outputs, &[], &self.scheme)
+            }
+            RangeCheck::Aggregated(proof, outputs, Some(asset_proof)) => Ok(asset_proof
+                .verify(&self.scheme, outputs)?
+                && proof.verify(outputs, &asset_proof.tags, &self.scheme)?),
+        }
+    }
+
+    /// Verify the membership proofs and the zero-knowledge proof of a
+    /// JoinSplit
+    ///
+    /// Each input's membership is checked against the input plus its
+    /// revealed serial, so a nullifier other than the spent coin's fails.
+    /// `sets` holds the anonymity set of each input, as returned by
+    /// [`anonymity_sets`] for `accumulator_head`. An input whose set is
+    /// empty cannot be a member, so the JoinSplit is invalid.
+    pub(crate) fn verify_zk_proof(
+        &self,
+        joinsplit: &JoinSplit,
+        accumulator_head: &[u8],
+        sets: &[Vec<Vec<u8>>],
+    ) -> Result<bool> {
+        if sets.len() != joinsplit.inputs.len() || sets.iter().any(Vec::is_empty) {
+            return Ok(false);
+        }
+        for (((proof, set), input), nullifier) in joinsplit
+            .proof
+            .membership_proofs
+            .iter()
+            .zip(sets)
+            .zip(&joinsplit.inputs)
+            .zip(&joinsplit.nullifiers)
+        {
+            let offset = self.scheme.spend_offset(input, nullifier.as_bytes())?;
+            if !proof.verify(
+                &self.scheme,
+                set,
+                &offset.value,
+                self.parameters.privacy_level,
+            )? {
+                return Ok(false);
+            }
+        }
+
+        let statement = ZkStatement {
+            inputs: &joinsplit.inputs,
+            nullifiers: &joinsplit.nullifiers,
+            outputs: &joinsplit.outputs,
+            fee: joinsplit.fee,
+            accumulator_value: accumulator_head,
+            binding_key: &joinsplit.binding_key,
+        };
+        let generators = self.scheme.pedersen_generators()?;
+        joinsplit
+            .proof
+            .zk_proof
+            .verify_with(&statement, &self.parameters, generators)
+    }
+
+    /// Get the parameters this verifier was prepared for
+    pub fn parameters(&self) -> &LelantusParameters {
+        &self.parameters
+    }
+}
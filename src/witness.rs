@@ -1,13 +1,29 @@
 //! Witness for Lelantus proofs
 
-use crate::commitment::Commitment;
-use crate::errors::{LelantusError, Result};
+#[cfg(feature = "std")]
+use crate::accumulator::AccumulatorDiff;
+use crate::accumulator::{Accumulator, MembershipProof};
+use crate::commitment::{AccumulatorElement, Commitment};
+use crate::errors::{ErrorContext, LelantusError, Result};
+use crate::keys::ViewKey;
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::serialization::binary;
+use crate::serialization::LelantusSerializable;
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hmac::{Hmac, Mac};
+#[cfg(feature = "std")]
+use rand::RngCore;
+#[cfg(feature = "std")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use aes_gcm::KeyInit;
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 /// Constant-time comparison to prevent timing attacks
-fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
+pub(crate) fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
         return false;
     }
@@ -20,8 +36,231 @@ fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
     result == 0
 }
 
+/// Size of the AES-256 key taken from the expanded HMAC-SHA512 output
+const AES_KEY_SIZE: usize = 32;
+
+/// Size of the AES-GCM nonce in an encrypted amount
+const AMOUNT_NONCE_SIZE: usize = 12;
+
+/// Size of the AES-GCM ciphertext of an amount (8-byte amount + 16-byte tag)
+const AMOUNT_CIPHERTEXT_SIZE: usize = 24;
+
+/// Size of the truncated HMAC-SHA512 tag
+const AMOUNT_TAG_SIZE: usize = 32;
+
+/// Offset of the HMAC tag (and end of the sealed amount)
+const AMOUNT_TAG_OFFSET: usize = AMOUNT_NONCE_SIZE + AMOUNT_CIPHERTEXT_SIZE;
+
+/// Offset of the optional metadata (and size of a sealed amount without it)
+pub(crate) const AMOUNT_METADATA_OFFSET: usize = AMOUNT_TAG_OFFSET + AMOUNT_TAG_SIZE;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// Derive the AES-256 and HMAC keys protecting an encrypted amount
+///
+/// Uses HKDF-SHA512 style extract/expand keyed by `secret` and bound to the
+/// commitment, its index and any metadata. Intermediate key material is
+/// wiped before returning.
+fn derive_amount_keys(
+    secret: &[u8],
+    commitment: &Commitment,
+    index: usize,
+    metadata: &[u8],
+) -> Result<AmountDisclosure> {
+    // Step 1: Extract phase - derive PRK from commitment components
+    let mut prk_hmac = <HmacSha512 as KeyInit>::new_from_slice(secret)
+        .map_err(|_| LelantusError::invalid_witness("hkdf_secret"))?;
+    prk_hmac.update(&commitment.value);
+    let mut prk = prk_hmac.finalize().into_bytes();
+
+    // Step 2: Expand phase - derive encryption key using HKDF expansion
+    let mut expand_hmac = <HmacSha512 as KeyInit>::new_from_slice(&prk[..])
+        .map_err(|_| LelantusError::invalid_witness("hkdf_prk"))?;
+    expand_hmac.update(b"lelantus_amount_decryption_key");
+    expand_hmac.update(&index.to_le_bytes());
+    expand_hmac.update(metadata);
+    let mut decryption_key = expand_hmac.finalize().into_bytes();
+
+    // Step 3: Derive HMAC verification key
+    let mut hmac_key_hmac = <HmacSha512 as KeyInit>::new_from_slice(&prk[..])
+        .map_err(|_| LelantusError::invalid_witness("hkdf_prk"))?;
+    hmac_key_hmac.update(b"lelantus_hmac_verification_key");
+    hmac_key_hmac.update(&index.to_le_bytes());
+    let mut hmac_key = hmac_key_hmac.finalize().into_bytes();
+
+    // AES-256 key is the first AES_KEY_SIZE bytes of the expanded key,
+    // independent of the hash configured for commitments
+    let keys = AmountDisclosure {
+        aes_key: decryption_key[..AES_KEY_SIZE].to_vec(),
+        hmac_key: hmac_key.to_vec(),
+    };
+
+    prk.as_mut_slice().zeroize();
+    decryption_key.as_mut_slice().zeroize();
+    hmac_key.as_mut_slice().zeroize();
+    Ok(keys)
+}
+
+/// Compute the truncated HMAC tag over a sealed amount
+fn amount_tag(hmac_key: &[u8], sealed: &[u8], index: usize, metadata: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <HmacSha512 as KeyInit>::new_from_slice(hmac_key)
+        .map_err(|_| LelantusError::invalid_witness("hmac_key"))?;
+    mac.update(sealed);
+    mac.update(&index.to_le_bytes());
+    mac.update(metadata);
+    Ok(mac.finalize().into_bytes()[..AMOUNT_TAG_SIZE].to_vec())
+}
+
+/// Encrypt an amount
+///
+/// Format: [12-byte nonce][24-byte AES-256-GCM ciphertext][32-byte HMAC-SHA512 tag][metadata]
+#[cfg(feature = "std")]
+fn seal_amount(
+    secret: &[u8],
+    commitment: &Commitment,
+    index: usize,
+    amount: u64,
+) -> Result<Vec<u8>> {
+    if amount == 0 {
+        return Err(LelantusError::invalid_witness("amount"));
+    }
+
+    let keys = derive_amount_keys(secret, commitment, index, &[])?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&keys.aes_key));
+
+    let mut nonce = [0u8; AMOUNT_NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), &amount.to_le_bytes()[..])
+        .map_err(|e| LelantusError::CryptoError(e.to_string()))?;
+
+    let mut sealed = Vec::with_capacity(AMOUNT_METADATA_OFFSET);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+
+    let tag = amount_tag(&keys.hmac_key, &sealed, index, &[])?;
+    sealed.extend_from_slice(&tag);
+    Ok(sealed)
+}
+
+/// Decrypt an amount produced by [`seal_amount`]
+pub(crate) fn open_amount(
+    secret: &[u8],
+    commitment: &Commitment,
+    index: usize,
+    encrypted_value: &[u8],
+) -> Result<u64> {
+    let keys = amount_keys_for(secret, commitment, index, encrypted_value)?;
+    open_amount_with_keys(&keys, index, encrypted_value)
+}
+
+/// Derive the keys protecting `encrypted_value`, whose metadata they bind
+fn amount_keys_for(
+    secret: &[u8],
+    commitment: &Commitment,
+    index: usize,
+    encrypted_value: &[u8],
+) -> Result<AmountDisclosure> {
+    // Validate encrypted value structure
+    if encrypted_value.len() < AMOUNT_METADATA_OFFSET {
+        return Err(LelantusError::invalid_witness("encrypted_value"));
+    }
+
+    let metadata = &encrypted_value[AMOUNT_METADATA_OFFSET..];
+    derive_amount_keys(secret, commitment, index, metadata)
+}
+
+/// Decrypt a sealed amount with its already derived keys
+fn open_amount_with_keys(
+    keys: &AmountDisclosure,
+    index: usize,
+    encrypted_value: &[u8],
+) -> Result<u64> {
+    if encrypted_value.len() < AMOUNT_METADATA_OFFSET || keys.aes_key.len() != AES_KEY_SIZE {
+        return Err(LelantusError::invalid_witness("encrypted_value"));
+    }
+
+    // Extract components
+    let sealed = &encrypted_value[..AMOUNT_TAG_OFFSET];
+    let tag = &encrypted_value[AMOUNT_TAG_OFFSET..AMOUNT_METADATA_OFFSET];
+    let metadata = &encrypted_value[AMOUNT_METADATA_OFFSET..];
+
+    // PRODUCTION: Verify HMAC tag with constant-time comparison
+    let computed_tag = amount_tag(&keys.hmac_key, sealed, index, metadata)?;
+    if !constant_time_compare(tag, &computed_tag) {
+        return Err(LelantusError::invalid_witness("amount_tag"));
+    }
+
+    // PRODUCTION: Decrypt amount using AES-256-GCM for authenticated encryption
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&keys.aes_key));
+    let nonce = Nonce::from_slice(&sealed[..AMOUNT_NONCE_SIZE]);
+    let decrypted_bytes = Zeroizing::new(
+        cipher
+            .decrypt(nonce, &sealed[AMOUNT_NONCE_SIZE..])
+            .map_err(|_| LelantusError::invalid_witness("encrypted_value"))?,
+    );
+
+    // Extract amount (first 8 bytes of decrypted data)
+    if decrypted_bytes.len() < 8 {
+        return Err(LelantusError::invalid_witness("amount"));
+    }
+
+    let mut amount_bytes = Zeroizing::new([0u8; 8]);
+    amount_bytes.copy_from_slice(&decrypted_bytes[..8]);
+    let amount = u64::from_le_bytes(*amount_bytes);
+
+    // PRODUCTION: Amount of 0 is invalid (no zero-value coins)
+    if amount == 0 {
+        return Err(LelantusError::invalid_witness("amount"));
+    }
+
+    Ok(amount)
+}
+
+/// Per-coin keys that let an auditor read one encrypted amount
+///
+/// Produced by the owner with [`Witness::disclose_amount`]. The keys are
+/// derived for this coin only, so they reveal neither the commitment
+/// randomness nor any other coin's amount.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    Zeroize,
+    ZeroizeOnDrop,
+)]
+pub struct AmountDisclosure {
+    /// AES-256-GCM key of the sealed amount
+    pub aes_key: Vec<u8>,
+
+    /// HMAC key authenticating the sealed amount
+    pub hmac_key: Vec<u8>,
+}
+
+impl LelantusSerializable for AmountDisclosure {}
+
+/// Format tag of witnesses holding a raw-bytes membership proof
+pub const WITNESS_VERSION_LEGACY: u8 = 0;
+
+/// Current witness format: the membership proof is an encoded [`MembershipProof`]
+///
+/// Format 1 held hash-chain paths from before the Merkle accumulator and
+/// format 2 proofs lacked the tree peaks; such witnesses must be rebuilt with
+/// `LelantusState::upgrade_witness`.
+pub const WITNESS_VERSION: u8 = 3;
+
 /// Witness for a coin in the accumulator
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Holds the coin's opening, so it is wiped when dropped.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize, Zeroize, ZeroizeOnDrop,
+)]
 pub struct Witness {
     /// The commitment being witnessed
     pub commitment: Commitment,
@@ -37,10 +276,24 @@ pub struct Witness {
 
     /// Coin value (encrypted)
     pub encrypted_value: Vec<u8>,
+
+    /// Format of `membership_proof`; absent in legacy encodings
+    #[serde(default)]
+    pub version: u8,
+
+    /// Coin group the witnessed coin belongs to
+    #[serde(default)]
+    pub group_id: u64,
 }
 
+impl LelantusSerializable for Witness {}
+
 impl Witness {
-    /// Create a new witness
+    /// Create a witness from a raw-bytes membership proof
+    ///
+    /// The proof is opaque, so the witness is tagged with the legacy format.
+    /// Use [`from_membership_proof`](Self::from_membership_proof) to build a
+    /// current-format witness.
     pub fn new(
         commitment: Commitment,
         index: usize,
@@ -54,29 +307,137 @@ impl Witness {
             accumulator_value,
             membership_proof,
             encrypted_value,
+            version: WITNESS_VERSION_LEGACY,
+            group_id: 0,
+        }
+    }
+
+    /// Create a current-format witness from a typed membership proof
+    pub fn from_membership_proof(
+        commitment: Commitment,
+        proof: &MembershipProof,
+        encrypted_value: Vec<u8>,
+    ) -> Result<Self> {
+        Ok(Self {
+            commitment,
+            index: proof.element_index,
+            accumulator_value: proof.accumulator_value.clone(),
+            membership_proof: proof.consensus_encode()?,
+            encrypted_value,
+            version: WITNESS_VERSION,
+            group_id: proof.group_id,
+        })
+    }
+
+    /// Decode the typed membership proof of a current-format witness
+    pub fn typed_membership_proof(&self) -> Result<MembershipProof> {
+        if self.version != WITNESS_VERSION {
+            return Err(LelantusError::InvalidWitness(
+                ErrorContext::new("version")
+                    .expected(WITNESS_VERSION)
+                    .actual(self.version),
+            ));
         }
+        MembershipProof::consensus_decode(&self.membership_proof)
     }
 
     /// Verify the witness is valid
     pub fn verify(&self) -> Result<bool> {
         // Check that commitment is valid
         if self.commitment.value.is_empty() {
-            return Err(LelantusError::InvalidWitness);
+            return Err(LelantusError::invalid_witness("commitment"));
         }
 
         // Check that accumulator value is valid
         if self.accumulator_value.is_empty() {
-            return Err(LelantusError::InvalidWitness);
+            return Err(LelantusError::invalid_witness("accumulator_value"));
         }
 
         // Check that membership proof is valid
         if self.membership_proof.is_empty() {
-            return Err(LelantusError::InvalidWitness);
+            return Err(LelantusError::invalid_witness("membership_proof"));
         }
 
         Ok(true)
     }
 
+    /// Check the witness against the current accumulator head
+    pub fn verify_against(&self, accumulator: &Accumulator) -> Result<bool> {
+        Ok(self.verify()?
+            && self.index < accumulator.element_count()
+            && constant_time_compare(&self.accumulator_value, accumulator.value()))
+    }
+
+    /// Advance the witness past one coin added to the accumulator
+    ///
+    /// Only current-format witnesses can be updated; others fail with
+    /// `InvalidWitness`.
+    pub fn update(&mut self, new_element: &AccumulatorElement) -> Result<()> {
+        self.advance(core::slice::from_ref(new_element))
+    }
+
+    /// Advance the witness past coins added to the accumulator, in order
+    pub(crate) fn advance(&mut self, elements: &[AccumulatorElement]) -> Result<()> {
+        let mut proof = self.typed_membership_proof()?;
+        proof.append(elements)?;
+        self.membership_proof = proof.consensus_encode()?;
+        self.accumulator_value = proof.accumulator_value;
+        Ok(())
+    }
+
+    /// Advance the witness through a stream of accumulator diffs
+    ///
+    /// Diffs must be given in order, each starting at the head the previous
+    /// one ended at. The membership path is advanced past their elements
+    /// (legacy raw proofs get the elements appended) and the accumulator
+    /// value moves to the final head. If any diff does not
+    /// extend the current head, or the stream is malformed, the witness is
+    /// left unchanged.
+    #[cfg(feature = "std")]
+    pub fn apply_diff_stream(&mut self, reader: &mut impl std::io::Read) -> Result<()> {
+        let mut accumulator_value = self.accumulator_value.clone();
+        let mut membership_proof = self.membership_proof.clone();
+        let mut typed_proof = match self.version {
+            WITNESS_VERSION => Some(self.typed_membership_proof()?),
+            WITNESS_VERSION_LEGACY => None,
+            _ => {
+                return Err(LelantusError::InvalidWitness(
+                    ErrorContext::new("version").actual(self.version),
+                ))
+            }
+        };
+        let mut next_count = None;
+
+        while let Some(diff) = AccumulatorDiff::read_from(reader)? {
+            if diff.previous_value != accumulator_value
+                || next_count.is_some_and(|count| count != diff.start_count)
+                || diff.start_count <= self.index
+            {
+                return Err(LelantusError::AccumulatorError(
+                    "diff does not extend the witness accumulator".to_string(),
+                ));
+            }
+
+            match typed_proof.as_mut() {
+                Some(proof) => proof.apply_diff(&diff)?,
+                None => {
+                    for element in &diff.elements {
+                        binary::put_bytes(&mut membership_proof, &element.value)?;
+                    }
+                }
+            }
+            accumulator_value = diff.resulting_value()?;
+            next_count = Some(diff.end_count());
+        }
+
+        if let Some(proof) = typed_proof {
+            membership_proof = proof.consensus_encode()?;
+        }
+        self.accumulator_value = accumulator_value;
+        self.membership_proof = membership_proof;
+        Ok(())
+    }
+
     /// Get the commitment
     pub fn commitment(&self) -> &Commitment {
         &self.commitment
@@ -92,129 +453,134 @@ impl Witness {
         &self.accumulator_value
     }
 
+    /// Stable identifier of the witnessed coin
+    ///
+    /// Derived only from the commitment and its accumulator index, so it does
+    /// not change as the witness is updated and needs no spend key. Unlike the
+    /// nullifier it is linkable to the coin, and is meant for watch-only
+    /// indexing before the coin is spent.
+    pub fn coin_id(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"lelantus_coin_id");
+        hasher.update((self.commitment.value.len() as u64).to_le_bytes());
+        hasher.update(&self.commitment.value);
+        hasher.update((self.index as u64).to_le_bytes());
+        hasher.finalize().into()
+    }
+
     /// Get the amount from encrypted value
     /// PRODUCTION IMPLEMENTATION: Full Lelantus witness decryption with proper key derivation
     /// Uses HKDF-SHA512 for key derivation and HMAC-SHA512 for authenticated encryption
     pub fn get_amount(&self) -> Result<u64> {
-        use hmac::{Hmac, Mac};
-        use sha2::Sha512;
+        open_amount(
+            &self.commitment.randomness,
+            &self.commitment,
+            self.index,
+            &self.encrypted_value,
+        )
+    }
 
-        // Validate encrypted value structure
-        // Format: [8 bytes encrypted amount][32 bytes HMAC-SHA512 tag][variable length metadata]
-        if self.encrypted_value.len() < 40 {
-            return Err(LelantusError::InvalidWitness);
-        }
+    /// Get the amount from encrypted value using a view key
+    pub fn get_amount_with_view_key(&self, view_key: &ViewKey) -> Result<u64> {
+        open_amount(
+            view_key.as_bytes(),
+            &self.commitment,
+            self.index,
+            &self.encrypted_value,
+        )
+    }
 
-        // Extract components
-        let encrypted_amount = &self.encrypted_value[0..8];
-        let tag = &self.encrypted_value[8..40];
-        let metadata = if self.encrypted_value.len() > 40 {
-            &self.encrypted_value[40..]
-        } else {
-            &[]
-        };
+    /// Disclose this coin's amount keys, for amounts sealed to the coin
+    pub fn disclose_amount(&self) -> Result<AmountDisclosure> {
+        amount_keys_for(
+            &self.commitment.randomness,
+            &self.commitment,
+            self.index,
+            &self.encrypted_value,
+        )
+    }
 
-        // PRODUCTION: Proper key derivation using HKDF-SHA512
-        // Step 1: Extract phase - derive PRK from commitment components
-        type HmacSha512 = Hmac<Sha512>;
-        let mut prk_hmac = <HmacSha512 as KeyInit>::new_from_slice(&self.commitment.randomness)
-            .map_err(|_| LelantusError::InvalidWitness)?;
-        prk_hmac.update(&self.commitment.value);
-        let prk = prk_hmac.finalize().into_bytes();
-
-        // Step 2: Expand phase - derive decryption key using HKDF expansion
-        let mut expand_hmac =
-            <HmacSha512 as KeyInit>::new_from_slice(&prk[..]).map_err(|_| LelantusError::InvalidWitness)?;
-        expand_hmac.update(b"lelantus_amount_decryption_key");
-        expand_hmac.update(&self.index.to_le_bytes());
-        expand_hmac.update(metadata);
-        let decryption_key = expand_hmac.finalize().into_bytes();
-
-        // Step 3: Derive HMAC verification key
-        let mut hmac_key_hmac =
-            <HmacSha512 as KeyInit>::new_from_slice(&prk[..]).map_err(|_| LelantusError::InvalidWitness)?;
-        hmac_key_hmac.update(b"lelantus_hmac_verification_key");
-        hmac_key_hmac.update(&self.index.to_le_bytes());
-        let hmac_key = hmac_key_hmac.finalize().into_bytes();
-
-        // PRODUCTION: Verify HMAC tag with constant-time comparison
-        let mut verify_mac =
-            <HmacSha512 as KeyInit>::new_from_slice(&hmac_key[..]).map_err(|_| LelantusError::InvalidWitness)?;
-        verify_mac.update(encrypted_amount);
-        verify_mac.update(&self.index.to_le_bytes());
-        verify_mac.update(metadata);
-        let computed_tag = verify_mac.finalize().into_bytes();
-
-        // Constant-time comparison to prevent timing attacks
-        if !constant_time_compare(tag, &computed_tag[..]) {
-            return Err(LelantusError::InvalidWitness);
-        }
+    /// Disclose this coin's amount keys, for amounts sealed to a view key
+    pub fn disclose_amount_with_view_key(&self, view_key: &ViewKey) -> Result<AmountDisclosure> {
+        amount_keys_for(
+            view_key.as_bytes(),
+            &self.commitment,
+            self.index,
+            &self.encrypted_value,
+        )
+    }
 
-        // PRODUCTION: Decrypt amount using AES-256-GCM for authenticated encryption
-        // Real production-grade authenticated encryption with proper nonce handling
-        use aes_gcm::{Aes256Gcm, Key, Nonce};
-        use aes_gcm::aead::Aead;
-        
-        // Derive AES-256 key from decryption key (first 32 bytes)
-        let aes_key_bytes: [u8; 32] = if decryption_key.len() >= 32 {
-            let mut key = [0u8; 32];
-            key.copy_from_slice(&decryption_key[..32]);
-            key
-        } else {
-            // If key is shorter, pad with zeros (should not happen in production)
-            let mut padded = [0u8; 32];
-            padded[..decryption_key.len()].copy_from_slice(&decryption_key);
-            padded
-        };
-        
-        let aes_key = Key::<Aes256Gcm>::from_slice(&aes_key_bytes);
-        let cipher = Aes256Gcm::new(aes_key);
-        
-        // Extract nonce from encrypted_amount (first 12 bytes are nonce, rest is ciphertext)
-        if encrypted_amount.len() < 12 + 8 {
-            return Err(LelantusError::InvalidWitness);
-        }
-        
-        let nonce = Nonce::from_slice(&encrypted_amount[..12]);
-        let ciphertext = &encrypted_amount[12..];
-        
-        // Decrypt with authenticated encryption
-        let decrypted_bytes = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|_| LelantusError::InvalidWitness)?;
-        
-        // Extract amount (first 8 bytes of decrypted data)
-        if decrypted_bytes.len() < 8 {
-            return Err(LelantusError::InvalidWitness);
-        }
-        
-        let mut amount_bytes = [0u8; 8];
-        amount_bytes.copy_from_slice(&decrypted_bytes[..8]);
-        let amount = u64::from_le_bytes(amount_bytes);
-
-        // PRODUCTION: Validate amount is within valid range (0 to 2^64-1)
-        // Amount of 0 is invalid (no zero-value coins)
-        if amount == 0 {
-            return Err(LelantusError::InvalidWitness);
+    /// Check that the encrypted amount equals `claimed`, given the owner's
+    /// disclosure
+    ///
+    /// Needs no spend or view key. Returns false if the disclosure does not
+    /// open the encrypted amount or opens it to another value.
+    pub fn verify_amount_disclosure(
+        &self,
+        claimed: u64,
+        disclosure: &AmountDisclosure,
+    ) -> Result<bool> {
+        if disclosure.aes_key.len() != AES_KEY_SIZE {
+            return Err(LelantusError::InvalidParameter(
+                ErrorContext::new("aes_key")
+                    .expected(AES_KEY_SIZE)
+                    .actual(disclosure.aes_key.len()),
+            ));
         }
 
-        Ok(amount)
+        Ok(
+            open_amount_with_keys(disclosure, self.index, &self.encrypted_value).is_ok_and(
+                |amount| constant_time_compare(&amount.to_le_bytes(), &claimed.to_le_bytes()),
+            ),
+        )
+    }
+
+    /// Decrypt the amounts of many witnesses in parallel
+    ///
+    /// Each witness gets its own result, so a corrupt witness does not abort
+    /// the rest of the batch.
+    #[cfg(feature = "std")]
+    pub fn decrypt_amounts_batch(witnesses: &[Witness], view_key: &ViewKey) -> Vec<Result<u64>> {
+        witnesses
+            .par_iter()
+            .map(|witness| witness.get_amount_with_view_key(view_key))
+            .collect()
+    }
+
+    /// Encrypt an amount so that it can be recovered with [`Witness::get_amount`]
+    #[cfg(feature = "std")]
+    pub fn encrypt_amount(commitment: &Commitment, index: usize, amount: u64) -> Result<Vec<u8>> {
+        seal_amount(&commitment.randomness, commitment, index, amount)
+    }
+
+    /// Encrypt an amount so that it can be recovered with a view key
+    #[cfg(feature = "std")]
+    pub fn encrypt_amount_for_view_key(
+        commitment: &Commitment,
+        index: usize,
+        amount: u64,
+        view_key: &ViewKey,
+    ) -> Result<Vec<u8>> {
+        seal_amount(view_key.as_bytes(), commitment, index, amount)
     }
 
     /// Serialize the witness
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec(self).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        LelantusSerializable::to_bytes(self)
     }
 
     /// Deserialize the witness
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        serde_json::from_slice(data).map_err(|e| LelantusError::SerializationError(e.to_string()))
+        <Self as LelantusSerializable>::from_bytes(data)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commitment::AccumulatorElement;
+    use crate::parameters::LelantusParameters;
+    use crate::pedersen::POINT_SIZE;
 
     #[test]
     fn test_witness_creation() -> Result<()> {
@@ -245,4 +611,277 @@ mod tests {
         assert_eq!(witness.index(), deserialized.index());
         Ok(())
     }
+
+    #[test]
+    fn test_amount_encryption_roundtrip() -> Result<()> {
+        let commitment = Commitment {
+            value: vec![1; 32],
+            randomness: vec![2; 32],
+        };
+
+        let encrypted = Witness::encrypt_amount(&commitment, 3, 1000)?;
+        let witness = Witness::new(commitment, 3, vec![3; 32], vec![4; 32], encrypted);
+        assert_eq!(witness.get_amount()?, 1000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_witness_zeroize() -> Result<()> {
+        let commitment = Commitment {
+            value: vec![1; 32],
+            randomness: vec![2; 32],
+        };
+        let encrypted = Witness::encrypt_amount(&commitment, 3, 1000)?;
+        let mut witness = Witness::new(commitment, 3, vec![3; 32], vec![4; 32], encrypted);
+        let mut disclosure = witness.disclose_amount()?;
+
+        witness.zeroize();
+        assert!(witness.commitment.randomness.is_empty());
+        assert!(witness.encrypted_value.is_empty());
+        assert!(witness.get_amount().is_err());
+        disclosure.zeroize();
+        assert!(disclosure.aes_key.is_empty() && disclosure.hmac_key.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_amount_disclosure() -> Result<()> {
+        let commitment = Commitment {
+            value: vec![1; 32],
+            randomness: vec![2; 32],
+        };
+        let encrypted = Witness::encrypt_amount(&commitment, 3, 4200)?;
+        let witness = Witness::new(commitment.clone(), 3, vec![3; 32], vec![4; 32], encrypted);
+
+        let disclosure = witness.disclose_amount()?;
+        assert!(witness.verify_amount_disclosure(4200, &disclosure)?);
+        assert!(!witness.verify_amount_disclosure(4201, &disclosure)?);
+
+        // Keys of another coin do not open this one
+        let other = Witness::new(
+            commitment.clone(),
+            4,
+            vec![3; 32],
+            vec![4; 32],
+            Witness::encrypt_amount(&commitment, 4, 4200)?,
+        );
+        assert!(!witness.verify_amount_disclosure(4200, &other.disclose_amount()?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_witness_under_blake3() -> Result<()> {
+        let params = LelantusParameters {
+            hash_function: "blake3".to_string(),
+            ..Default::default()
+        };
+        let scheme = crate::commitment::CommitmentScheme::new(&params)?;
+        let commitment = scheme.commit(1000)?;
+        assert_eq!(commitment.value.len(), 2 * params.hash_output_len());
+
+        let mut accumulator = Accumulator::new(&params)?;
+        accumulator.add_element(commitment.to_element()?)?;
+
+        let encrypted = Witness::encrypt_amount(&commitment, 0, 1000)?;
+        let witness = Witness::new(
+            commitment,
+            0,
+            accumulator.value().to_vec(),
+            vec![4; 32],
+            encrypted,
+        );
+        assert!(witness.verify_against(&accumulator)?);
+        assert_eq!(witness.get_amount()?, 1000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_amounts_batch() -> Result<()> {
+        let view_key = ViewKey::generate();
+        let mut witnesses = Vec::new();
+
+        for i in 0..8u64 {
+            let commitment = Commitment {
+                value: vec![i as u8; 32],
+                randomness: vec![2; 32],
+            };
+            let mut encrypted =
+                Witness::encrypt_amount_for_view_key(&commitment, i as usize, 100 + i, &view_key)?;
+
+            // Corrupt every third witness
+            if i % 3 == 0 {
+                encrypted[0] ^= 0xff;
+            }
+
+            witnesses.push(Witness::new(
+                commitment,
+                i as usize,
+                vec![3; 32],
+                vec![4; 32],
+                encrypted,
+            ));
+        }
+
+        let results = Witness::decrypt_amounts_batch(&witnesses, &view_key);
+        assert_eq!(results.len(), witnesses.len());
+
+        for (i, result) in results.iter().enumerate() {
+            if i % 3 == 0 {
+                assert!(matches!(result, Err(LelantusError::InvalidWitness(_))));
+            } else {
+                assert_eq!(result.as_ref().ok(), Some(&(100 + i as u64)));
+            }
+        }
+
+        // A different view key cannot decrypt anything
+        let other_key = ViewKey::generate();
+        assert!(Witness::decrypt_amounts_batch(&witnesses, &other_key)
+            .iter()
+            .all(|r| r.is_err()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_coin_id() {
+        let commitment = Commitment {
+            value: vec![1; 32],
+            randomness: vec![2; 32],
+        };
+
+        let mut witness =
+            Witness::new(commitment.clone(), 0, vec![3; 32], vec![4; 32], vec![5; 32]);
+        let coin_id = witness.coin_id();
+
+        // Updating the witness keeps the coin identity
+        witness.accumulator_value = vec![6; 32];
+        witness.membership_proof = vec![7; 64];
+        assert_eq!(witness.coin_id(), coin_id);
+
+        // A different index or commitment is a different coin
+        let moved = Witness::new(commitment, 1, vec![3; 32], vec![4; 32], vec![5; 32]);
+        assert_ne!(moved.coin_id(), coin_id);
+
+        let other = Commitment {
+            value: vec![9; 32],
+            randomness: vec![2; 32],
+        };
+        let other = Witness::new(other, 0, vec![3; 32], vec![4; 32], vec![5; 32]);
+        assert_ne!(other.coin_id(), coin_id);
+    }
+
+    fn coin_in_accumulator() -> Result<(Accumulator, Witness)> {
+        let mut accumulator = Accumulator::new(&LelantusParameters::default())?;
+        let commitment = Commitment {
+            value: vec![1; 2 * POINT_SIZE],
+            randomness: vec![2; 64],
+        };
+        accumulator.add_element(commitment.to_element()?)?;
+
+        let witness = Witness::new(
+            commitment,
+            0,
+            accumulator.value().to_vec(),
+            vec![4; 32],
+            vec![5; 32],
+        );
+        Ok((accumulator, witness))
+    }
+
+    fn elements(values: &[u8]) -> Vec<AccumulatorElement> {
+        values
+            .iter()
+            .map(|&v| AccumulatorElement {
+                value: vec![v; 2 * POINT_SIZE],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_diff_stream_typed_proof() -> Result<()> {
+        let (mut accumulator, witness) = coin_in_accumulator()?;
+        let proof = accumulator.create_membership_proof(0)?;
+        let mut witness = Witness::from_membership_proof(
+            witness.commitment.clone(),
+            &proof,
+            witness.encrypted_value.clone(),
+        )?;
+
+        let mut stream = Vec::new();
+        accumulator
+            .add_elements(elements(&[10, 11]))?
+            .write_to(&mut stream)?;
+        witness.apply_diff_stream(&mut stream.as_slice())?;
+
+        assert!(witness.verify_against(&accumulator)?);
+        let updated = witness.typed_membership_proof()?;
+        // The base and three coins make a four-leaf tree
+        assert_eq!(updated.path.len(), 2);
+        assert!(accumulator.verify_membership_proof(&updated)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_witness() -> Result<()> {
+        let (mut accumulator, witness) = coin_in_accumulator()?;
+        let proof = accumulator.create_membership_proof(0)?;
+        let mut witness = Witness::from_membership_proof(
+            witness.commitment.clone(),
+            &proof,
+            witness.encrypted_value.clone(),
+        )?;
+
+        for element in elements(&[10, 11, 12]) {
+            accumulator.add_element(element.clone())?;
+            witness.update(&element)?;
+            assert!(witness.verify_against(&accumulator)?);
+        }
+        let fresh = accumulator.create_membership_proof(0)?;
+        assert_eq!(witness.membership_proof, fresh.consensus_encode()?);
+
+        // Legacy witnesses have no typed proof to advance
+        let (_, mut legacy) = coin_in_accumulator()?;
+        assert!(matches!(
+            legacy.update(&elements(&[10])[0]),
+            Err(LelantusError::InvalidWitness(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_diff_stream() -> Result<()> {
+        let (mut accumulator, mut witness) = coin_in_accumulator()?;
+        assert!(witness.verify_against(&accumulator)?);
+
+        let mut stream = Vec::new();
+        accumulator
+            .add_elements(elements(&[10, 11]))?
+            .write_to(&mut stream)?;
+        accumulator
+            .add_elements(elements(&[12]))?
+            .write_to(&mut stream)?;
+        assert!(!witness.verify_against(&accumulator)?);
+
+        witness.apply_diff_stream(&mut stream.as_slice())?;
+        assert!(witness.verify_against(&accumulator)?);
+        assert!(witness.membership_proof.len() > 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_diff_stream_rejects_gap() -> Result<()> {
+        let (mut accumulator, mut witness) = coin_in_accumulator()?;
+        let original = witness.clone();
+
+        // The witness never sees the first diff
+        accumulator.add_elements(elements(&[10, 11]))?;
+        let mut stream = Vec::new();
+        accumulator
+            .add_elements(elements(&[12]))?
+            .write_to(&mut stream)?;
+
+        assert!(witness.apply_diff_stream(&mut stream.as_slice()).is_err());
+        assert_eq!(witness.accumulator_value, original.accumulator_value);
+        assert_eq!(witness.membership_proof, original.membership_proof);
+        Ok(())
+    }
 }
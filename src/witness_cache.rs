@@ -0,0 +1,254 @@
+//! Witness cache with configurable capacity, expiry and pinning
+//!
+//! [`WitnessCache`] keeps recently used witnesses in an LRU of
+//! [`CacheConfig::capacity`] entries, optionally expiring them after
+//! [`CacheConfig::ttl`]. Pinned witnesses live outside the LRU, so they are
+//! never evicted or expired and do not count against the capacity.
+
+use crate::errors::{LelantusError, Result};
+use crate::witness::Witness;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+/// Witness cache settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Most unpinned witnesses held at once
+    pub capacity: usize,
+
+    /// Age after which an unpinned witness is dropped, if any
+    pub ttl: Option<Duration>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            ttl: None,
+        }
+    }
+}
+
+/// Counters of a [`WitnessCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found a witness
+    pub hits: u64,
+
+    /// Lookups that found nothing, including expired witnesses
+    pub misses: u64,
+
+    /// Witnesses pushed out to make room for others
+    pub evictions: u64,
+
+    /// Witnesses dropped for outliving the TTL
+    pub expirations: u64,
+
+    /// Witnesses currently held, pinned or not
+    pub entries: usize,
+
+    /// Witnesses currently pinned
+    pub pinned: usize,
+}
+
+/// Cached witness and when it was inserted
+#[derive(Debug)]
+struct Entry {
+    witness: Witness,
+    inserted: Instant,
+}
+
+/// LRU witness cache keyed by caller-chosen keys
+#[derive(Debug)]
+pub struct WitnessCache {
+    entries: lru::LruCache<Vec<u8>, Entry>,
+    pinned: HashMap<Vec<u8>, Witness>,
+    ttl: Option<Duration>,
+    stats: CacheStats,
+}
+
+impl WitnessCache {
+    /// Create an empty cache, failing with `InvalidParameter` for a zero capacity
+    pub fn new(config: CacheConfig) -> Result<Self> {
+        let capacity = NonZeroUsize::new(config.capacity)
+            .ok_or(LelantusError::invalid_parameter("capacity"))?;
+        Ok(Self {
+            entries: lru::LruCache::new(capacity),
+            pinned: HashMap::new(),
+            ttl: config.ttl,
+            stats: CacheStats::default(),
+        })
+    }
+
+    /// Cache `witness` under `key`, evicting the least recently used if full
+    ///
+    /// A pinned witness under `key` is replaced and stays pinned.
+    pub fn put(&mut self, key: Vec<u8>, witness: Witness) {
+        if let Some(pinned) = self.pinned.get_mut(&key) {
+            *pinned = witness;
+            return;
+        }
+
+        let entry = Entry {
+            witness,
+            inserted: Instant::now(),
+        };
+        if let Some((evicted, _)) = self.entries.push(key.clone(), entry) {
+            if evicted != key {
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    /// Look up the witness under `key`, dropping it if it has expired
+    pub fn get(&mut self, key: &[u8]) -> Option<Witness> {
+        if let Some(witness) = self.pinned.get(key) {
+            self.stats.hits += 1;
+            return Some(witness.clone());
+        }
+
+        let expired = match (self.entries.peek(key), self.ttl) {
+            (Some(entry), Some(ttl)) => entry.inserted.elapsed() >= ttl,
+            _ => false,
+        };
+        if expired {
+            self.entries.pop(key);
+            self.stats.expirations += 1;
+        }
+
+        match self.entries.get(key) {
+            Some(entry) => {
+                self.stats.hits += 1;
+                Some(entry.witness.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Pin `witness` under `key` so it is never evicted or expired
+    pub fn pin(&mut self, key: Vec<u8>, witness: Witness) {
+        self.entries.pop(&key);
+        self.pinned.insert(key, witness);
+    }
+
+    /// Unpin the witness under `key`, returning it to the LRU
+    ///
+    /// Returns false if nothing was pinned under `key`.
+    pub fn unpin(&mut self, key: &[u8]) -> bool {
+        match self.pinned.remove(key) {
+            Some(witness) => {
+                self.put(key.to_vec(), witness);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop every witness, pinned or not
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.pinned.clear();
+    }
+
+    /// Most unpinned witnesses held at once
+    pub fn capacity(&self) -> usize {
+        self.entries.cap().get()
+    }
+
+    /// Number of unpinned witnesses held
+    pub fn unpinned_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Every cached witness with its key, pinned first, without touching
+    /// recency or counters
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Witness)> {
+        self.pinned.iter().chain(
+            self.entries
+                .iter()
+                .map(|(key, entry)| (key, &entry.witness)),
+        )
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len() + self.pinned.len(),
+            pinned: self.pinned.len(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::Commitment;
+
+    fn witness(index: usize) -> Witness {
+        let commitment = Commitment {
+            value: vec![1; 32],
+            randomness: vec![2; 32],
+        };
+        Witness::new(commitment, index, Vec::new(), Vec::new(), Vec::new())
+    }
+
+    #[test]
+    fn test_eviction_and_pinning() -> Result<()> {
+        let mut cache = WitnessCache::new(CacheConfig {
+            capacity: 2,
+            ttl: None,
+        })?;
+        cache.pin(b"pinned".to_vec(), witness(0));
+        for index in 1..=3 {
+            cache.put(vec![index as u8], witness(index));
+        }
+
+        // The oldest unpinned witness made room; the pinned one stayed
+        assert!(cache.get(&[1]).is_none());
+        assert_eq!(cache.get(&[3]).map(|w| w.index), Some(3));
+        assert_eq!(cache.get(b"pinned").map(|w| w.index), Some(0));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 1,
+                evictions: 1,
+                expirations: 0,
+                entries: 3,
+                pinned: 1,
+            }
+        );
+
+        assert!(cache.unpin(b"pinned"));
+        assert!(!cache.unpin(b"pinned"));
+        assert_eq!(cache.stats().evictions, 2);
+        assert!(WitnessCache::new(CacheConfig {
+            capacity: 0,
+            ttl: None,
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ttl_expires_unpinned_witnesses() -> Result<()> {
+        let mut cache = WitnessCache::new(CacheConfig {
+            capacity: 4,
+            ttl: Some(Duration::ZERO),
+        })?;
+        cache.put(b"coin".to_vec(), witness(1));
+        cache.pin(b"pinned".to_vec(), witness(2));
+
+        assert!(cache.get(b"coin").is_none());
+        assert!(cache.get(b"pinned").is_some());
+        let stats = cache.stats();
+        assert_eq!((stats.expirations, stats.misses, stats.hits), (1, 1, 1));
+        assert_eq!(stats.entries, 1);
+        Ok(())
+    }
+}